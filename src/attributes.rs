@@ -0,0 +1,194 @@
+//! An implementation of gitattributes, which attach settings like `diff` and `text` to paths.
+//!
+//! See: <https://git-scm.com/docs/gitattributes>
+
+use std::collections::HashMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::ignore::Pattern;
+
+/// The state of an attribute for a path, when a line mentions it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrState {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+    /// `!attr`, which returns the attribute to the state of having never been mentioned
+    Unspecified,
+}
+
+/// The attributes set by the built-in `binary` macro.
+const BINARY_MACRO: [(&str, AttrState); 3] = [
+    ("diff", AttrState::Unset),
+    ("merge", AttrState::Unset),
+    ("text", AttrState::Unset),
+];
+
+/// A single line of an attributes file: a pattern, and the attributes it assigns.
+#[derive(Debug, Clone)]
+struct AttrLine {
+    pattern: Pattern,
+    attributes: Vec<(String, AttrState)>,
+}
+
+impl AttrLine {
+    /// Parse a line of an attributes file found in the directory `base`. Returns `None` for
+    /// blank lines and comments, and for negative patterns, which git forbids.
+    fn parse(line: &str, base: &Utf8Path) -> Option<Self> {
+        let line = line.trim();
+        if line.starts_with('!') {
+            return None;
+        }
+
+        let mut words = line.split_ascii_whitespace();
+        let pattern = Pattern::parse(words.next()?, base)?;
+
+        let mut attributes = Vec::new();
+        for word in words {
+            let (name, state) = if let Some(name) = word.strip_prefix('-') {
+                (name, AttrState::Unset)
+            } else if let Some(name) = word.strip_prefix('!') {
+                (name, AttrState::Unspecified)
+            } else if let Some((name, value)) = word.split_once('=') {
+                (name, AttrState::Value(value.to_owned()))
+            } else {
+                (word, AttrState::Set)
+            };
+
+            if name == "binary" && state == AttrState::Set {
+                attributes.extend(BINARY_MACRO.map(|(name, state)| (name.to_owned(), state)));
+            }
+            attributes.push((name.to_owned(), state));
+        }
+
+        Some(Self {
+            pattern,
+            attributes,
+        })
+    }
+}
+
+/// The lines of a single attributes file. Later lines take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct AttrList {
+    lines: Vec<AttrLine>,
+}
+
+impl AttrList {
+    pub fn parse(contents: &str, base: &Utf8Path) -> Self {
+        Self {
+            lines: contents
+                .lines()
+                .filter_map(|line| AttrLine::parse(line, base))
+                .collect(),
+        }
+    }
+
+    /// Read the attributes file at `path`, which applies to paths under `base`. A missing file
+    /// is treated as empty.
+    pub fn from_file(path: &Utf8Path, base: &Utf8Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents, base),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// The state of `name` for `path` according to the last line that matches `path` and
+    /// mentions `name`, if there is one.
+    pub fn decide(&self, path: &Utf8Path, name: &str) -> Option<&AttrState> {
+        self.lines
+            .iter()
+            .rev()
+            .filter(|line| line.pattern.matches(path, false))
+            .find_map(|line| {
+                line.attributes
+                    .iter()
+                    .rev()
+                    .find(|(attr, _)| attr == name)
+                    .map(|(_, state)| state)
+            })
+    }
+}
+
+/// All of the attributes files that apply to a workspace.
+///
+/// Sources are consulted in git's order of precedence: `.git/info/attributes`, then
+/// `.gitattributes` files from the deepest directory up to the root of the workspace, then
+/// `core.attributesFile`. The first source that mentions an attribute decides its state.
+pub struct Attributes {
+    workspace: Utf8PathBuf,
+    per_directory: HashMap<Utf8PathBuf, AttrList>,
+    info: AttrList,
+    global: AttrList,
+}
+
+impl Attributes {
+    pub fn new(workspace: &Utf8Path, info: AttrList, global: AttrList) -> Self {
+        Self {
+            workspace: workspace.to_owned(),
+            per_directory: HashMap::new(),
+            info,
+            global,
+        }
+    }
+
+    /// The state of the attribute `name` for `path`, relative to the root of the workspace.
+    /// Returns `None` if it is unspecified.
+    pub fn get(&mut self, path: &Utf8Path, name: &str) -> Option<AttrState> {
+        let mut state = self.info.decide(path, name).cloned();
+        for dir in path.ancestors().skip(1) {
+            if state.is_none() {
+                state = self.directory_list(dir).decide(path, name).cloned();
+            }
+        }
+        state
+            .or_else(|| self.global.decide(path, name).cloned())
+            .filter(|state| *state != AttrState::Unspecified)
+    }
+
+    /// The `.gitattributes` in `dir`, relative to the root of the workspace.
+    fn directory_list(&mut self, dir: &Utf8Path) -> &AttrList {
+        let workspace = &self.workspace;
+        self.per_directory.entry(dir.to_owned()).or_insert_with(|| {
+            AttrList::from_file(&workspace.join(dir).join(".gitattributes"), dir)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let root = Utf8Path::new("");
+        let list = AttrList::parse(
+            "# images\n*.png binary\n*.txt diff=text eol=lf\nlogo.png !diff\n!*.c -diff\n",
+            root,
+        );
+
+        let get = |path: &str, name: &str| list.decide(Utf8Path::new(path), name).cloned();
+        assert_eq!(get("a/b.png", "diff"), Some(AttrState::Unset));
+        assert_eq!(get("a/b.png", "text"), Some(AttrState::Unset));
+        assert_eq!(get("a/b.png", "binary"), Some(AttrState::Set));
+        assert_eq!(get("logo.png", "diff"), Some(AttrState::Unspecified));
+        assert_eq!(get("logo.png", "merge"), Some(AttrState::Unset));
+        assert_eq!(
+            get("notes.txt", "diff"),
+            Some(AttrState::Value("text".to_owned()))
+        );
+        assert_eq!(
+            get("notes.txt", "eol"),
+            Some(AttrState::Value("lf".to_owned()))
+        );
+        assert_eq!(get("notes.md", "diff"), None);
+        // Negative patterns are ignored
+        assert_eq!(get("main.c", "diff"), None);
+    }
+}
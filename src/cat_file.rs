@@ -5,6 +5,7 @@ use tracing::warn;
 use crate::interface::CatFile;
 use crate::repo::database::LoadedItem;
 use crate::repo::Repo;
+use crate::revision::Rev;
 use crate::Result;
 
 pub fn handle(repo: &mut Repo, args: &CatFile) -> Result<()> {
@@ -30,7 +31,11 @@ pub fn handle(repo: &mut Repo, args: &CatFile) -> Result<()> {
         }
         CatFile::PrettyPrint { object_ref } => {
             let object = {
-                let loaded = repo.read_ref(object_ref)?;
+                // Plain refs and oids are used as-is, so `cat-file` can show tags themselves
+                let loaded = match repo.read_ref(object_ref)? {
+                    Some(oid) => Some(oid),
+                    None => Rev::parse(object_ref)?.resolve(repo)?,
+                };
                 match loaded {
                     None => {
                         eprintln!("Invalid object ref: {}", object_ref);
@@ -53,6 +58,7 @@ pub fn handle(repo: &mut Repo, args: &CatFile) -> Result<()> {
                 LoadedItem::Blob(blob) => blob.pretty_print()?,
                 LoadedItem::Commit(commit) => commit.pretty_print()?,
                 LoadedItem::Tree(tree) => tree.pretty_print()?,
+                LoadedItem::Tag(tag) => tag.pretty_print()?,
             };
 
             Ok(())
@@ -0,0 +1,142 @@
+//! Tidying up commit messages before they are recorded, as `git commit --cleanup` does.
+//!
+//! See: <https://git-scm.com/docs/git-commit#Documentation/git-commit.txt---cleanupltmodegt>
+
+/// The line below which everything is ignored with `--cleanup=scissors`, after the comment
+/// character.
+pub const SCISSORS: &str = " ------------------------ >8 ------------------------";
+
+/// How a commit message is cleaned up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Cleanup {
+    /// `strip` if the message is edited, otherwise `whitespace`
+    #[default]
+    Default,
+    /// Strip leading and trailing empty lines, trailing whitespace and comments, and collapse
+    /// consecutive empty lines
+    Strip,
+    /// Like `strip`, but keep comments
+    Whitespace,
+    /// Don't change the message at all
+    Verbatim,
+    /// Like `whitespace`, but if the message is edited, drop everything from the scissors line
+    /// down
+    Scissors,
+}
+
+impl Cleanup {
+    /// Resolve [`Cleanup::Default`], given whether the message was edited.
+    pub fn resolve(self, edited: bool) -> Self {
+        match self {
+            Cleanup::Default if edited => Cleanup::Strip,
+            Cleanup::Default => Cleanup::Whitespace,
+            mode => mode,
+        }
+    }
+}
+
+/// Clean up `message` according to `mode`, where comments start with `comment_char`.
+///
+/// Unless the mode is `verbatim`, a non-empty result always ends with exactly one newline.
+pub fn cleanup_message(message: &str, mode: Cleanup, comment_char: char, edited: bool) -> String {
+    let mut message = message;
+    let strip_comments = match mode.resolve(edited) {
+        Cleanup::Verbatim => return message.to_owned(),
+        Cleanup::Strip => true,
+        Cleanup::Scissors if edited => {
+            let scissors = format!("{comment_char}{SCISSORS}");
+            if let Some(i) = find_line(message, &scissors) {
+                message = &message[..i];
+            }
+            false
+        }
+        _ => false,
+    };
+
+    let mut out = String::with_capacity(message.len());
+    let mut pending_blank = false;
+    for line in message.lines() {
+        if strip_comments && line.starts_with(comment_char) {
+            continue;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            pending_blank = true;
+            continue;
+        }
+
+        // Runs of empty lines become one, and leading ones are dropped
+        if pending_blank && !out.is_empty() {
+            out.push('\n');
+        }
+        pending_blank = false;
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// The byte offset of the first line of `message` that is exactly `line`.
+fn find_line(message: &str, line: &str) -> Option<usize> {
+    let mut offset = 0;
+    for candidate in message.split_inclusive('\n') {
+        if candidate.strip_suffix('\n').unwrap_or(candidate) == line {
+            return Some(offset);
+        }
+        offset += candidate.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_cleanup_message() {
+        let message = "\n\n  \nSubject  \n\n\n\nBody\t\n# a comment\n\n";
+
+        let cleanup = |mode, edited| cleanup_message(message, mode, '#', edited);
+        assert_eq!(cleanup(Cleanup::Strip, false), "Subject\n\nBody\n");
+        assert_eq!(
+            cleanup(Cleanup::Whitespace, false),
+            "Subject\n\nBody\n# a comment\n"
+        );
+        assert_eq!(cleanup(Cleanup::Verbatim, false), message);
+        assert_eq!(cleanup(Cleanup::Default, true), "Subject\n\nBody\n");
+        assert_eq!(
+            cleanup(Cleanup::Default, false),
+            "Subject\n\nBody\n# a comment\n"
+        );
+
+        assert_eq!(
+            cleanup_message("; not a comment\n", Cleanup::Strip, '#', true),
+            "; not a comment\n"
+        );
+        assert_eq!(
+            cleanup_message("; a comment\nx", Cleanup::Strip, ';', true),
+            "x\n"
+        );
+        assert_eq!(
+            cleanup_message("# only comments\n", Cleanup::Strip, '#', true),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_scissors() {
+        let message = format!("Subject\n# kept\n#{SCISSORS}\nDropped\n");
+        assert_eq!(
+            cleanup_message(&message, Cleanup::Scissors, '#', true),
+            "Subject\n# kept\n"
+        );
+        // Without an editor, the scissors line is just a comment
+        assert_eq!(
+            cleanup_message(&message, Cleanup::Scissors, '#', false),
+            message
+        );
+    }
+}
@@ -1,18 +1,69 @@
+pub mod cleanup;
 mod parse;
+mod walk;
 mod write;
 
+use std::borrow::Cow;
+
+use bstr::ByteSlice;
+
 use crate::digest::Digest;
 use crate::repo::Repo;
 use crate::timestamp::Timestamp;
 use crate::Result;
 
-struct GpgSig;
+pub use self::walk::{topo_sort, CommitWalk};
+
+/// The header holding a commit's signature.
+const GPGSIG: &str = "gpgsig";
 
 #[derive(Debug, Clone)]
-struct Signature {
+pub struct Signature {
     name: String,
     email: String,
     when: Timestamp,
+    /// The bytes a parsed signature was read from, which are written back unchanged, so names
+    /// that aren't UTF-8, odd spacing and dates that can't be parsed survive a round trip
+    raw: Option<Vec<u8>>,
+}
+
+impl Signature {
+    pub fn new(name: String, email: String, when: Timestamp) -> Self {
+        Self {
+            name,
+            email,
+            when,
+            raw: None,
+        }
+    }
+
+    /// The signature as it is stored: `Name <email> 1658312219 +0100`.
+    pub fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match &self.raw {
+            Some(raw) => Cow::Borrowed(raw),
+            None => {
+                Cow::Owned(format!("{} <{}> {}", self.name, self.email, self.when).into_bytes())
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn when(&self) -> &Timestamp {
+        &self.when
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
+    }
 }
 
 pub struct Commit {
@@ -20,32 +71,28 @@ pub struct Commit {
     parents: Vec<Digest>,
     author: Signature,
     committer: Signature,
-    gpgsig: Option<GpgSig>,
-    message: String,
+    /// Headers after `committer`, such as `gpgsig`, `mergetag` and `encoding`, in order. The
+    /// continuation lines of multi-line values are joined with `\n`.
+    headers: Vec<(String, Vec<u8>)>,
+    /// The message exactly as stored, in the commit's `encoding`
+    message: Vec<u8>,
 }
 
 impl Commit {
     pub fn new(
-        parent_commit: Option<Digest>,
+        parents: Vec<Digest>,
         tree_id: Digest,
-        name: String,
-        email: String,
+        author: Signature,
+        committer: Signature,
         message: String,
     ) -> Self {
-        let author = Signature {
-            name,
-            email,
-            when: Timestamp::now(),
-        };
-        let committer = author.clone();
-
         Commit {
             tree_id,
-            parents: parent_commit.into_iter().collect::<Vec<_>>(),
+            parents,
             author,
             committer,
-            gpgsig: None,
-            message,
+            headers: Vec::new(),
+            message: message.into_bytes(),
         }
     }
 
@@ -71,35 +118,99 @@ impl Commit {
         Ok(Some(parent))
     }
 
-    pub fn message(&self) -> &str {
-        self.message.as_ref()
+    /// The message, without leading and trailing whitespace, decoded from the commit's
+    /// [encoding](Self::encoding). Anything that can't be decoded is replaced with `�`.
+    pub fn message(&self) -> Cow<'_, str> {
+        decode(self.message.trim(), self.encoding())
     }
 
-    pub fn pretty_print(&self) -> std::io::Result<()> {
-        println!("tree {:x}", self.tree_id);
-        for parent in &self.parents {
-            println!("parent {parent:x}");
-        }
-        println!(
-            "author {} <{}> {}",
-            self.author.name, self.author.email, self.author.when
-        );
-        println!(
-            "committer {} <{}> {}",
-            self.committer.name, self.committer.email, self.committer.when
-        );
-
-        if let Some(_gpgsig) = &self.gpgsig {
-            println!("gpgsig [not yet implemented]");
+    /// The message exactly as stored.
+    pub fn raw_message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// The encoding of the message, from the `encoding` header. Without one, it is UTF-8.
+    pub fn encoding(&self) -> Option<&str> {
+        self.header("encoding").and_then(|x| x.to_str().ok())
+    }
+
+    /// The subject of the commit message: its first paragraph, joined into a single line.
+    pub fn subject(&self) -> String {
+        self.message()
+            .split("\n\n")
+            .next()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The body of the commit message: everything after the first paragraph.
+    pub fn body(&self) -> String {
+        match self.message().split_once("\n\n") {
+            Some((_, body)) => body.trim_start_matches('\n').to_owned(),
+            None => String::new(),
         }
-        println!();
+    }
+
+    pub fn author(&self) -> &Signature {
+        &self.author
+    }
+
+    pub fn committer(&self) -> &Signature {
+        &self.committer
+    }
+
+    /// The headers after `committer`, as `(name, value)` pairs.
+    pub fn headers(&self) -> &[(String, Vec<u8>)] {
+        &self.headers
+    }
+
+    /// The value of the first header called `name`, if there is one.
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// The signature in the `gpgsig` header, which may be an OpenPGP, X.509 or SSH signature.
+    pub fn signature(&self) -> Option<&str> {
+        self.header(GPGSIG).and_then(|x| x.to_str().ok())
+    }
 
-        println!("{}", self.message);
+    /// Sign the commit with `signature`, which must cover [`Commit::signed_payload`].
+    pub fn set_signature(&mut self, signature: &str) {
+        self.headers.retain(|(key, _)| key != GPGSIG);
+        let signature = signature.strip_suffix('\n').unwrap_or(signature);
+        self.headers
+            .push((GPGSIG.to_owned(), signature.as_bytes().to_vec()));
+    }
 
-        Ok(())
+    /// Print the commit exactly as it is stored.
+    pub fn pretty_print(&self) -> std::io::Result<()> {
+        use std::io::Write;
+        std::io::stdout().write_all(&self.data(true))
     }
 
     pub(crate) fn commit_date(&self) -> chrono::NaiveDate {
         self.committer.when.0.date_naive()
     }
 }
+
+/// Decode `bytes` from `encoding`. Only UTF-8 and ISO-8859-1 are understood, and anything else
+/// is treated as UTF-8.
+fn decode<'a>(bytes: &'a [u8], encoding: Option<&str>) -> Cow<'a, str> {
+    let latin1 = encoding.is_some_and(|encoding| {
+        matches!(
+            encoding.to_ascii_lowercase().as_str(),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1"
+        )
+    });
+    match latin1 {
+        // Every byte is the code point of the same value
+        true => Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect()),
+        false => String::from_utf8_lossy(bytes),
+    }
+}
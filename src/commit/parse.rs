@@ -9,13 +9,13 @@ mod nom {
 
     use bstr::ByteSlice;
     use nom::{
-        bytes::complete::{tag, take, take_till, take_until, take_while},
+        bytes::complete::{tag, take, take_till},
         Parser,
     };
     use nom_supreme::ParserExt;
 
     use crate::{
-        commit::{Commit, GpgSig, Signature, Timestamp},
+        commit::{Commit, Signature, Timestamp},
         digest::Digest,
     };
 
@@ -28,7 +28,7 @@ mod nom {
         let (i, parents) = parse_parents.context("Parents").parse(i)?;
         let (i, author) = parse_author.context("Author").parse(i)?;
         let (i, committer) = parse_committer.context("Committer").parse(i)?;
-        let (i, gpgsig) = parse_gpgsig.context("GpgSig").parse(i)?;
+        let (i, headers) = parse_headers.context("Headers").parse(i)?;
         let (i, message) = parse_message.context("Message").parse(i)?;
 
         Ok((
@@ -38,51 +38,73 @@ mod nom {
                 parents,
                 author,
                 committer,
-                gpgsig,
-                message: message.to_str().unwrap().to_owned(),
+                headers,
+                message: message.to_vec(),
             },
         ))
     }
 
     fn parse_message(i: Input) -> Result<&[u8]> {
-        let (i, _) = take_while(|x: u8| x.is_ascii_whitespace())(i)?;
-        Ok((b"", i.trim()))
+        // The message follows an empty line, which is missing if there is no message at all
+        let (i, _) = tag(b"\n").opt().parse(i)?;
+        Ok((b"", i))
     }
 
-    fn parse_gpgsig(i: Input) -> Result<Option<GpgSig>> {
-        let (i, header) = tag(b"gpgsig ").opt().parse(i)?;
-        if header.is_none() {
-            return Ok((i, None));
+    /// Lines of `name value`, where each line after the first of a multi-line value starts with
+    /// a space.
+    fn parse_headers(mut i: Input) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut headers = Vec::new();
+
+        while !i.is_empty() && i[0] != b'\n' {
+            let (rest, name) = take_till(|b| b == b' ' || b == b'\n')(i)?;
+            let (rest, _) = tag(b" ")(rest)?;
+            let (rest, line) = take_till(|b| b == b'\n')(rest)?;
+            let (mut rest, _) = tag(b"\n")(rest)?;
+
+            let mut value = line.to_vec();
+            while let Some(continuation) = rest.strip_prefix(b" ") {
+                let (continuation, line) = take_till(|b| b == b'\n')(continuation)?;
+                let (continuation, _) = tag(b"\n")(continuation)?;
+                value.push(b'\n');
+                value.extend_from_slice(line);
+                rest = continuation;
+            }
+
+            headers.push((String::from_utf8_lossy(name).into_owned(), value));
+            i = rest;
         }
 
-        let (i, _) = take_until("END PGP SIGNATURE")(i)?;
-        let (i, _) = take_till(|x| x == b'\n')(i)?;
-        let (i, _) = tag(b"\n")(i)?;
-
-        Ok((i, Some(GpgSig)))
+        Ok((i, headers))
     }
 
-    fn parse_signature(i: Input) -> Result<Signature> {
+    pub(super) fn parse_signature(i: Input) -> Result<Signature> {
+        let start = i;
         let (i, name) = take_till(|b| b == b'<').context("name").parse(i)?;
-        let name = name.trim().to_str().unwrap();
+        let name = String::from_utf8_lossy(name.trim()).into_owned();
         let (i, _) = tag(b"<")(i)?;
         let (i, email) = take_till(|b| b == b'>').context("email").parse(i)?;
-        let email = email.trim().to_str().unwrap();
+        let email = String::from_utf8_lossy(email.trim()).into_owned();
         let (i, _) = tag(b"> ")(i)?;
 
-        // the rest of the string, up to \n, is the unix timestamp, and the offset "%s %z"
+        // the rest of the string, up to \n, is the unix timestamp, and the offset "%s %z". Like
+        // git, show dates that can't be parsed as the epoch
         let (i, time) = take_till(|b| b == b'\n')
             .context("timestamp/offset")
             .parse(i)?;
-        let time = time.trim().to_str().unwrap();
-        let time = Timestamp::from_git(time).unwrap();
+        let time = time
+            .trim()
+            .to_str()
+            .ok()
+            .and_then(|time| Timestamp::from_git(time).ok())
+            .unwrap_or_else(|| Timestamp::from_git("0 +0000").unwrap());
 
         Ok((
             i,
             Signature {
-                name: name.to_owned(),
-                email: email.to_owned(),
+                name,
+                email,
                 when: time,
+                raw: Some(start[..start.len() - i.len()].to_vec()),
             },
         ))
     }
@@ -129,6 +151,17 @@ mod nom {
     }
 }
 
+impl Signature {
+    /// Parse a signature of the form `Name <email> 1658312219 +0100`, as found after `author`,
+    /// `committer` and `tagger` in objects.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        match nom::parse_signature(bytes) {
+            Ok((_, signature)) => Ok(signature),
+            Err(e) => Err(eyre!("Failed to parse signature: {e:?}")),
+        }
+    }
+}
+
 impl Commit {
     /// Parse a decompressed commit.
     ///
@@ -169,6 +202,7 @@ mod tests {
     use chrono::{DateTime, FixedOffset, NaiveDate};
 
     use super::*;
+    use crate::storable::Storable;
 
     #[test]
     fn test_parse_signature() {
@@ -198,7 +232,7 @@ parent 14a9d8464caef987f3b5c3cf26f56db825459abd
 author Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
 committer Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
 gpgsig -----BEGIN PGP SIGNATURE-----
-
+ 
  iQEzBAABCAAdFiEEMLv/P6sLuz4ENfg8jo/2biro2XAFAmLX1h0ACgkQjo/2biro
  2XC8yQf/eVwDZC0hZxMuPcHOsiDLa+f65tNvMA4k8edoQRp90+Z/o+ENewFnnKD5
  64p0Rk6V7KTt9SGE5VJUnYzsNW0RU8js3fkHt+sE2qk4w7DcMLlROb/OLGYknRAq
@@ -229,10 +263,89 @@ Merge remote-tracking branch 'origin/renovate/clap-3.x' into develop
         assert_eq!(commit.author.email, "jamie@quigley.xyz");
         assert_eq!(commit.committer.name, "Jamie Quigley");
         assert_eq!(commit.committer.email, "jamie@quigley.xyz");
-        assert!(commit.gpgsig.is_some());
+        let signature = commit.signature().unwrap();
+        assert!(signature.starts_with("-----BEGIN PGP SIGNATURE-----\n\niQEzBAAB"));
+        assert!(signature.ends_with("\n=JO5C\n-----END PGP SIGNATURE-----"));
         assert_eq!(
-            commit.message,
+            commit.message(),
             "Merge remote-tracking branch 'origin/renovate/clap-3.x' into develop"
         );
+
+        // Writing the commit out again gives exactly what was read, so the oid is unchanged
+        let formatted = commit.format();
+        assert_eq!(
+            formatted,
+            [
+                format!("commit {}\0", input.len()).as_bytes(),
+                input.as_bytes()
+            ]
+            .concat()
+        );
+
+        // The signature covers everything but itself
+        let payload = String::from_utf8(commit.signed_payload()).unwrap();
+        assert!(payload.starts_with("tree 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5\n"));
+        assert!(payload.contains("+0100\n\nMerge remote-tracking"));
+        assert!(!payload.contains("gpgsig"));
+    }
+
+    #[test]
+    fn test_parse_extra_headers() {
+        let input = "tree 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5
+author Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
+committer Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
+encoding ISO-8859-1
+mergetag object e6a49274aa0893ce2e2928589100387aee220c5b
+ type commit
+ tag v1.0
+ 
+ Version 1.0
+
+Subject
+";
+        let commit = Commit::parse(input.as_bytes()).unwrap();
+        assert_eq!(
+            commit.headers(),
+            [
+                ("encoding".to_owned(), b"ISO-8859-1".to_vec()),
+                (
+                    "mergetag".to_owned(),
+                    b"object e6a49274aa0893ce2e2928589100387aee220c5b\ntype commit\ntag v1.0\n\nVersion 1.0"
+                        .to_vec()
+                ),
+            ]
+        );
+        assert_eq!(commit.encoding(), Some("ISO-8859-1"));
+        assert_eq!(commit.signature(), None);
+        assert_eq!(String::from_utf8(commit.signed_payload()).unwrap(), input);
+    }
+
+    #[test]
+    fn test_parse_non_utf8() {
+        let mut input = b"tree 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5
+author J\xf6rg  <jorg@example.com>  1658312219 -0000
+committer Jamie Quigley <jamie@quigley.xyz> not a date
+encoding ISO-8859-1
+x-unknown caf\xe9
+
+"
+        .to_vec();
+        input.extend_from_slice(b"  Gr\xfc\xdfe\n\nCaf\xe9\n");
+
+        let commit = Commit::parse(&input).unwrap();
+        assert_eq!(commit.author.name(), "J\u{fffd}rg");
+        assert_eq!(commit.author.email(), "jorg@example.com");
+        assert_eq!(commit.committer.when().0.timestamp(), 0);
+        assert_eq!(commit.header("x-unknown"), Some(&b"caf\xe9"[..]));
+        assert_eq!(commit.raw_message(), b"  Gr\xfc\xdfe\n\nCaf\xe9\n");
+        assert_eq!(commit.message(), "Gr\u{fc}\u{df}e\n\nCaf\u{e9}");
+        assert_eq!(commit.subject(), "Gr\u{fc}\u{df}e");
+        assert_eq!(commit.body(), "Caf\u{e9}");
+
+        // Nothing is lost, so the oid doesn't change
+        assert_eq!(
+            commit.format(),
+            [format!("commit {}\0", input.len()).as_bytes(), &input].concat()
+        );
     }
 }
@@ -0,0 +1,213 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::digest::Digest;
+use crate::repo::database::Database;
+use crate::timestamp::Timestamp;
+use crate::Result;
+
+use super::Commit;
+
+/// An iterator over the history reachable from a set of commits, newest first.
+///
+/// Commits are yielded in order of decreasing commit date, and each commit is yielded exactly once,
+/// no matter how many paths lead to it. Commits with the same date are yielded in the order they
+/// were discovered.
+pub struct CommitWalk<'d> {
+    database: &'d Database,
+    queue: BinaryHeap<(Timestamp, Reverse<usize>, Digest)>,
+    pending: HashMap<Digest, Commit>,
+    seen: HashSet<Digest>,
+    /// Commits that are neither yielded nor walked through. See [`CommitWalk::hide`]
+    hidden: HashSet<Digest>,
+    first_parent: bool,
+}
+
+impl<'d> CommitWalk<'d> {
+    pub fn new(database: &'d Database, starts: impl IntoIterator<Item = Digest>) -> Result<Self> {
+        let mut walk = Self {
+            database,
+            queue: BinaryHeap::new(),
+            pending: HashMap::new(),
+            seen: HashSet::new(),
+            hidden: HashSet::new(),
+            first_parent: false,
+        };
+
+        for oid in starts {
+            walk.push(oid)?;
+        }
+
+        Ok(walk)
+    }
+
+    /// Only follow the first parent of each commit, treating merges as ordinary commits.
+    pub fn first_parent(mut self) -> Self {
+        self.first_parent = true;
+        self
+    }
+
+    /// Hide every commit reachable from `oids`, so the walk yields only the commits reachable from
+    /// its starting points but not from these. This is the `^<rev>` of `rev-list`.
+    pub fn hide(mut self, oids: impl IntoIterator<Item = Digest>) -> Result<Self> {
+        let mut queue = oids.into_iter().collect::<Vec<_>>();
+        while let Some(oid) = queue.pop() {
+            if self.hidden.insert(oid.clone()) {
+                queue.extend(self.database.load_commit(&oid)?.parents().iter().cloned());
+            }
+        }
+        Ok(self)
+    }
+
+    /// Returns `true` if `oid` is reachable from a commit passed to [`CommitWalk::hide`].
+    pub fn is_hidden(&self, oid: &Digest) -> bool {
+        self.hidden.contains(oid)
+    }
+
+    fn push(&mut self, oid: Digest) -> Result<()> {
+        if self.hidden.contains(&oid) || !self.seen.insert(oid.clone()) {
+            return Ok(());
+        }
+
+        let commit = self.database.load_commit(&oid)?;
+        let seq = Reverse(self.seen.len());
+        self.queue
+            .push((commit.committer.when.clone(), seq, oid.clone()));
+        self.pending.insert(oid, commit);
+        Ok(())
+    }
+
+    fn next_commit(&mut self) -> Result<Option<(Digest, Commit)>> {
+        let (oid, commit) = loop {
+            let oid = match self.queue.pop() {
+                Some((_, _, oid)) => oid,
+                None => return Ok(None),
+            };
+
+            let commit = self
+                .pending
+                .remove(&oid)
+                .expect("queued commits should be pending");
+
+            // Starting points may have been hidden after they were queued
+            if !self.hidden.contains(&oid) {
+                break (oid, commit);
+            }
+        };
+
+        for parent in followed_parents(&commit, self.first_parent) {
+            self.push(parent.clone())?;
+        }
+
+        Ok(Some((oid, commit)))
+    }
+}
+
+/// Reorder `commits`, as yielded by a [`CommitWalk`], so that no commit comes before any of its
+/// children, and each line of history is shown in one piece rather than interleaved with others
+/// by date. This is `rev-list --topo-order`.
+///
+/// With `first_parent`, only the edges to first parents are considered.
+pub fn topo_sort(commits: Vec<(Digest, Commit)>, first_parent: bool) -> Vec<(Digest, Commit)> {
+    let positions = commits
+        .iter()
+        .enumerate()
+        .map(|(i, (oid, _))| (oid.clone(), i))
+        .collect::<HashMap<_, _>>();
+
+    let mut children = vec![0; commits.len()];
+    for (_, commit) in &commits {
+        for parent in followed_parents(commit, first_parent) {
+            if let Some(&i) = positions.get(parent) {
+                children[i] += 1;
+            }
+        }
+    }
+
+    // A stack, with the newest tip on top, so each line is followed as far as it can go before
+    // moving on to another. As in git, the last parent is pushed last, so a merged branch is
+    // shown before the branch it was merged into
+    let mut stack = (0..commits.len())
+        .rev()
+        .filter(|&i| children[i] == 0)
+        .collect::<Vec<_>>();
+    let mut order = Vec::with_capacity(commits.len());
+
+    while let Some(i) = stack.pop() {
+        order.push(i);
+        for parent in followed_parents(&commits[i].1, first_parent) {
+            if let Some(&j) = positions.get(parent) {
+                children[j] -= 1;
+                if children[j] == 0 {
+                    stack.push(j);
+                }
+            }
+        }
+    }
+
+    let mut commits = commits.into_iter().map(Some).collect::<Vec<_>>();
+    order
+        .into_iter()
+        .map(|i| commits[i].take().expect("each commit is ordered once"))
+        .collect()
+}
+
+impl Iterator for CommitWalk<'_> {
+    type Item = Result<(Digest, Commit)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_commit().transpose()
+    }
+}
+
+/// The parents of `commit` that a walk should follow.
+fn followed_parents(commit: &Commit, first_parent: bool) -> &[Digest] {
+    match commit.parents() {
+        [first, ..] if first_parent => std::slice::from_ref(first),
+        parents => parents,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+    use tempdir::TempDir;
+
+    use crate::repo::Repo;
+    use crate::test::{COMMIT_EMAIL, COMMIT_NAME};
+
+    use super::*;
+
+    #[test]
+    /// Make a linear history, and check that walking it from HEAD visits every commit, newest
+    /// first.
+    fn test_walk_linear() -> Result<()> {
+        std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+        std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+        let dir = TempDir::new("")?;
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+        Repo::init_default(dir)?;
+        let mut repo = Repo::open(dir.to_owned())?;
+
+        crate::create_test_files!(dir, ["file0"]);
+        repo.add_all()?;
+        repo.commit("commit 0")?;
+        crate::create_test_files!(dir, ["file1"]);
+        repo.add_all()?;
+        repo.commit("commit 1")?;
+        crate::create_test_files!(dir, ["file2"]);
+        repo.add_all()?;
+        repo.commit("commit 2")?;
+
+        let head = repo.read_head()?.unwrap();
+        let messages = CommitWalk::new(&repo.database, [head])?
+            .map(|x| x.map(|(_, commit)| commit.message().into_owned()))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(messages, ["commit 2", "commit 1", "commit 0"]);
+
+        Ok(())
+    }
+}
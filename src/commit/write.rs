@@ -1,37 +1,51 @@
+use bstr::ByteSlice;
+
 use crate::storable::Storable;
 
-use tracing::warn;
+use super::GPGSIG;
+
+impl super::Commit {
+    /// The commit as stored, without the `commit <len>\0` prefix, and optionally without its
+    /// signature.
+    pub(super) fn data(&self, with_signature: bool) -> Vec<u8> {
+        let mut data = format!("tree {}\n", self.tree_id.to_hex()).into_bytes();
+        for parent in &self.parents {
+            data.extend_from_slice(format!("parent {parent:x}\n").as_bytes());
+        }
+        for (name, signature) in [("author", &self.author), ("committer", &self.committer)] {
+            data.extend_from_slice(name.as_bytes());
+            data.push(b' ');
+            data.extend_from_slice(&signature.to_bytes());
+            data.push(b'\n');
+        }
+        for (name, value) in &self.headers {
+            if with_signature || name != GPGSIG {
+                data.extend_from_slice(name.as_bytes());
+                data.push(b' ');
+                // Continuation lines of multi-line values start with a space
+                data.extend_from_slice(&value.replace("\n", "\n "));
+                data.push(b'\n');
+            }
+        }
+        data.push(b'\n');
+        data.extend_from_slice(&self.message);
+        data
+    }
+
+    /// What the signature in the `gpgsig` header signs: the commit without that header.
+    pub fn signed_payload(&self) -> Vec<u8> {
+        self.data(false)
+    }
+}
 
 impl Storable for super::Commit {
     fn format(&self) -> Vec<u8> {
-        let data = format!(
-            "\
-            tree {}\n\
-            {}\
-            author {} <{}> {}\n\
-            committer {} <{}> {}\n\
-            \n\
-            {}",
-            self.tree_id.to_hex(),
-            if let Some(parent) = self.parents.first() {
-                warn!("Only writing first parent, NYI");
-                format!("parent {parent:x}\n")
-            } else {
-                String::new()
-            },
-            self.author.name,
-            self.author.email,
-            self.author.when,
-            self.committer.name,
-            self.committer.email,
-            self.committer.when,
-            self.message
-        );
+        let data = self.data(true);
         let mut formatted = Vec::new();
         formatted.extend_from_slice(b"commit ");
         formatted.extend_from_slice(format!("{}", data.len()).as_bytes());
         formatted.push(b'\0');
-        formatted.extend_from_slice(data.as_bytes());
+        formatted.extend_from_slice(&data);
         formatted
     }
 }
@@ -0,0 +1,126 @@
+use camino::Utf8Path;
+use color_eyre::eyre::eyre;
+
+use crate::interface::{ConfigAction, ConfigArgs};
+use crate::Result;
+
+use super::{Config, ConfigEntry, ConfigFile, ConfigKey, ConfigScope};
+
+/// The exit status used by git when a variable to get or unset is not set.
+const EXIT_NOT_FOUND: i32 = 1;
+const EXIT_NOTHING_TO_UNSET: i32 = 5;
+
+/// Handle `rit config`. `git_dir` is `None` when run outside of a repository.
+pub fn handle(args: &ConfigArgs, git_dir: Option<&Utf8Path>) -> Result<()> {
+    let scope = if args.global {
+        Some(ConfigScope::Global)
+    } else if args.system {
+        Some(ConfigScope::System)
+    } else if args.local {
+        Some(ConfigScope::Local)
+    } else {
+        None
+    };
+
+    let action = match &args.action {
+        Some(_) if args.list => return Err(eyre!("--list cannot be used with an action")),
+        Some(action) => action,
+        None if args.list => {
+            for entry in load(args, scope, git_dir)?.entries() {
+                print_entry(args, entry, true);
+            }
+            return Ok(());
+        }
+        None => return Err(eyre!("no action given; try 'rit config --help'")),
+    };
+
+    match action {
+        ConfigAction::Get { key, all } => {
+            let key = key.parse::<ConfigKey>()?;
+            let config = load(args, scope, git_dir)?;
+            let entries = config
+                .entries()
+                .iter()
+                .filter(|entry| entry.key == key)
+                .collect::<Vec<_>>();
+
+            let shown = match (entries.last(), *all) {
+                (None, _) => std::process::exit(EXIT_NOT_FOUND),
+                (Some(last), false) => vec![*last],
+                (Some(_), true) => entries,
+            };
+            for entry in shown {
+                print_entry(args, entry, false);
+            }
+        }
+
+        ConfigAction::Set { key, value, append } => {
+            let key = key.parse::<ConfigKey>()?;
+            let mut file = open_for_writing(args, scope, git_dir)?;
+            if *append {
+                file.add(&key, value)?;
+            } else {
+                file.set(&key, value)?;
+            }
+            file.save()?;
+        }
+
+        ConfigAction::Unset { key, all } => {
+            let key = key.parse::<ConfigKey>()?;
+            let mut file = open_for_writing(args, scope, git_dir)?;
+            let removed = if *all {
+                file.unset_all(&key) > 0
+            } else {
+                file.unset(&key)?
+            };
+            if !removed {
+                std::process::exit(EXIT_NOTHING_TO_UNSET);
+            }
+            file.save()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn load(
+    args: &ConfigArgs,
+    scope: Option<ConfigScope>,
+    git_dir: Option<&Utf8Path>,
+) -> Result<Config> {
+    match (&args.file, scope) {
+        (Some(path), _) => Config::load_file_only(path, ConfigScope::Local, git_dir),
+        (None, Some(scope)) => Config::load_scope(scope, git_dir),
+        (None, None) => Config::load(git_dir),
+    }
+}
+
+/// Open the file that changes should be written to. This is the repository's config file unless
+/// told otherwise.
+fn open_for_writing(
+    args: &ConfigArgs,
+    scope: Option<ConfigScope>,
+    git_dir: Option<&Utf8Path>,
+) -> Result<ConfigFile> {
+    let path = match &args.file {
+        Some(path) => path.clone(),
+        None => scope.unwrap_or(ConfigScope::Local).path(git_dir)?,
+    };
+    ConfigFile::open(&path)
+}
+
+fn print_entry(args: &ConfigArgs, entry: &ConfigEntry, with_key: bool) {
+    let mut line = String::new();
+    if args.show_scope {
+        line.push_str(&format!("{}\t", entry.scope));
+    }
+    if args.show_origin {
+        line.push_str(&format!("file:{}\t", entry.origin));
+    }
+    match (with_key, &entry.value) {
+        (true, Some(value)) => line.push_str(&format!("{}={}", entry.key, value)),
+        (true, None) => line.push_str(&entry.key.to_string()),
+        (false, value) => line.push_str(value.as_deref().unwrap_or("")),
+    }
+    println!("{line}");
+}
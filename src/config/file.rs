@@ -0,0 +1,667 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::trace;
+
+use crate::lockfile::Lockfile;
+use crate::Result;
+
+use super::ConfigKey;
+
+/// A single logical line of a config file, along with the exact text it was parsed from.
+///
+/// The `raw` text includes the line terminator, and any continuation lines, so concatenating the
+/// raw text of every line reproduces the file exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// A section header, such as `[remote "origin"]`
+    Section {
+        name: String,
+        subsection: Option<String>,
+        raw: String,
+    },
+    /// A variable. The value is `None` for a bare `name` with no `=`, which means `true`.
+    Entry {
+        name: String,
+        value: Option<String>,
+        raw: String,
+    },
+    /// Blank lines and comments
+    Other(String),
+}
+
+impl Line {
+    fn raw(&self) -> &str {
+        match self {
+            Line::Section { raw, .. } | Line::Entry { raw, .. } | Line::Other(raw) => raw,
+        }
+    }
+
+    fn raw_mut(&mut self) -> &mut String {
+        match self {
+            Line::Section { raw, .. } | Line::Entry { raw, .. } | Line::Other(raw) => raw,
+        }
+    }
+}
+
+/// A variable read from a config file, with its section and subsection resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub key: ConfigKey,
+    pub value: Option<String>,
+}
+
+/// A single git config file.
+///
+/// Edits only touch the lines they need to, so comments, formatting and everything else in the
+/// file survive being rewritten.
+///
+/// See: <https://git-scm.com/docs/git-config#_configuration_file>
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    path: Utf8PathBuf,
+    lines: Vec<Line>,
+}
+
+impl ConfigFile {
+    /// Read and parse the config file at `path`. A missing file is treated as empty.
+    pub fn open(path: &Utf8Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("Failed to read config file: {path}"))
+            }
+        };
+
+        let lines = parse(&contents).wrap_err_with(|| format!("Bad config file: {path}"))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            lines,
+        })
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Every variable in the file, in order.
+    pub fn entries(&self) -> Vec<FileEntry> {
+        let mut entries = Vec::new();
+        let mut section: Option<(&str, Option<&str>)> = None;
+
+        for line in &self.lines {
+            match line {
+                Line::Section {
+                    name, subsection, ..
+                } => section = Some((name, subsection.as_deref())),
+                Line::Entry { name, value, .. } => {
+                    // The parser rejects entries before the first section header
+                    let (section, subsection) = section.expect("entry outside of a section");
+                    entries.push(FileEntry {
+                        key: ConfigKey::new(section, subsection, name),
+                        value: value.clone(),
+                    });
+                }
+                Line::Other(_) => {}
+            }
+        }
+
+        entries
+    }
+
+    /// Set `key` to `value`, replacing its current value. Fails if `key` has multiple values.
+    pub fn set(&mut self, key: &ConfigKey, value: &str) -> Result<()> {
+        match self.find(key)[..] {
+            [] => self.add(key, value),
+            [i] => {
+                self.lines[i] = entry_line(key, value);
+                Ok(())
+            }
+            _ => Err(eyre!(
+                "cannot overwrite multiple values with a single value: {key}"
+            )),
+        }
+    }
+
+    /// Add a new value for `key`, keeping any existing values.
+    pub fn add(&mut self, key: &ConfigKey, value: &str) -> Result<()> {
+        let position = match self.last_section(key) {
+            Some(section) => {
+                // After the last entry of the section, or its header if it has none
+                let mut position = section + 1;
+                for (i, line) in self.lines.iter().enumerate().skip(section + 1) {
+                    match line {
+                        Line::Section { .. } => break,
+                        Line::Entry { .. } => position = i + 1,
+                        Line::Other(_) => {}
+                    }
+                }
+                position
+            }
+            None => {
+                self.terminate_last_line();
+                self.lines.push(section_line(key));
+                self.lines.len()
+            }
+        };
+
+        // An entry may follow a section header on the same line, with no newline in between
+        if let Some(previous) = self.lines.get_mut(position - 1) {
+            if matches!(previous, Line::Section { .. }) && !previous.raw().ends_with('\n') {
+                previous.raw_mut().push('\n');
+            }
+        }
+        if position == self.lines.len() {
+            self.terminate_last_line();
+        }
+
+        self.lines.insert(position, entry_line(key, value));
+        Ok(())
+    }
+
+    /// Remove the value of `key`. Returns whether there was a value to remove. Fails if `key` has
+    /// multiple values.
+    pub fn unset(&mut self, key: &ConfigKey) -> Result<bool> {
+        match self.find(key)[..] {
+            [] => Ok(false),
+            [i] => {
+                self.remove_line(i);
+                Ok(true)
+            }
+            _ => Err(eyre!("{key} has multiple values")),
+        }
+    }
+
+    /// Remove every value of `key`. Returns the number of values removed.
+    pub fn unset_all(&mut self, key: &ConfigKey) -> usize {
+        let found = self.find(key);
+        for &i in found.iter().rev() {
+            self.remove_line(i);
+        }
+        found.len()
+    }
+
+    /// Write the file back to disk, going via a lockfile so that readers never see a partially
+    /// written file.
+    pub fn save(&self) -> Result<()> {
+        trace!(path=%self.path, "Writing config file");
+        let mut lock = Lockfile::acquire(&self.path)?;
+        lock.write_all(self.to_string().as_bytes())?;
+        lock.commit()
+    }
+
+    /// The indices of the lines that set `key`.
+    fn find(&self, key: &ConfigKey) -> Vec<usize> {
+        let mut in_section = false;
+        let mut found = Vec::new();
+
+        for (i, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Section {
+                    name, subsection, ..
+                } => in_section = key.in_section(name, subsection.as_deref()),
+                Line::Entry { name, .. } if in_section && key.name_matches(name) => found.push(i),
+                _ => {}
+            }
+        }
+
+        found
+    }
+
+    /// The index of the last header of the section `key` belongs to.
+    fn last_section(&self, key: &ConfigKey) -> Option<usize> {
+        self.lines.iter().rposition(|line| match line {
+            Line::Section {
+                name, subsection, ..
+            } => key.in_section(name, subsection.as_deref()),
+            _ => false,
+        })
+    }
+
+    fn remove_line(&mut self, i: usize) {
+        let removed = self.lines.remove(i);
+        // Keep a section header that shared its line with the entry terminated
+        if removed.raw().ends_with('\n') {
+            if let Some(previous @ Line::Section { .. }) =
+                i.checked_sub(1).map(|i| &mut self.lines[i])
+            {
+                if !previous.raw().ends_with('\n') {
+                    previous.raw_mut().push('\n');
+                }
+            }
+        }
+    }
+
+    fn terminate_last_line(&mut self) {
+        if let Some(last) = self.lines.last_mut() {
+            if !last.raw().ends_with('\n') {
+                last.raw_mut().push('\n');
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            f.write_str(line.raw())?;
+        }
+        Ok(())
+    }
+}
+
+fn section_line(key: &ConfigKey) -> Line {
+    let raw = match key.subsection() {
+        Some(subsection) => {
+            let escaped = subsection.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("[{} \"{}\"]\n", key.section(), escaped)
+        }
+        None => format!("[{}]\n", key.section()),
+    };
+
+    Line::Section {
+        name: key.section().to_owned(),
+        subsection: key.subsection().map(str::to_owned),
+        raw,
+    }
+}
+
+fn entry_line(key: &ConfigKey, value: &str) -> Line {
+    Line::Entry {
+        name: key.name().to_owned(),
+        value: Some(value.to_owned()),
+        raw: format!("\t{} = {}\n", key.name(), quote_value(value)),
+    }
+}
+
+/// Format a value so that it parses back to exactly `value`.
+fn quote_value(value: &str) -> String {
+    let needs_quotes = value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['#', ';']);
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    if needs_quotes {
+        quoted.push('"');
+    }
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\x08' => quoted.push_str("\\b"),
+            c => quoted.push(c),
+        }
+    }
+    if needs_quotes {
+        quoted.push('"');
+    }
+    quoted
+}
+
+/// Parse the contents of a config file into lines.
+fn parse(contents: &str) -> Result<Vec<Line>> {
+    let mut parser = Parser {
+        input: contents,
+        pos: 0,
+        line_number: 1,
+    };
+    let mut lines = Vec::new();
+    let mut seen_section = false;
+
+    while parser.pos < contents.len() {
+        let start = parser.pos;
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None | Some('\n' | '#' | ';') => {
+                parser.skip_comment();
+                lines.push(Line::Other(contents[start..parser.pos].to_owned()));
+            }
+            Some('[') => {
+                let (name, subsection) = parser.section_header()?;
+                seen_section = true;
+
+                // Anything after the header on the same line is a separate entry
+                let header_end = parser.pos;
+                parser.skip_whitespace();
+                if matches!(parser.peek(), None | Some('\n' | '#' | ';')) {
+                    parser.skip_comment();
+                    lines.push(Line::Section {
+                        name,
+                        subsection,
+                        raw: contents[start..parser.pos].to_owned(),
+                    });
+                } else {
+                    lines.push(Line::Section {
+                        name,
+                        subsection,
+                        raw: contents[start..header_end].to_owned(),
+                    });
+                    parser.pos = header_end;
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                if !seen_section {
+                    return Err(parser.error("variable outside of a section"));
+                }
+                let (name, value) = parser.entry()?;
+                lines.push(Line::Entry {
+                    name,
+                    value,
+                    raw: contents[start..parser.pos].to_owned(),
+                });
+            }
+            Some(c) => return Err(parser.error(&format!("unexpected character '{c}'"))),
+        }
+    }
+
+    Ok(lines)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    line_number: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line_number += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> color_eyre::Report {
+        eyre!("{message} on line {}", self.line_number)
+    }
+
+    /// Skip spaces and tabs, but not newlines.
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace() && c != '\n') {
+            self.next();
+        }
+    }
+
+    /// Skip to just after the end of the current line.
+    fn skip_comment(&mut self) {
+        while let Some(c) = self.next() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// Parse `[section]`, `[section "subsection"]`, or the deprecated `[section.subsection]`.
+    fn section_header(&mut self) -> Result<(String, Option<String>)> {
+        self.next();
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                name.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        let subsection = match self.next() {
+            Some(']') => {
+                if let Some((section, subsection)) = name.split_once('.') {
+                    // The deprecated syntax is case-insensitive
+                    let subsection = subsection.to_ascii_lowercase();
+                    name = section.to_owned();
+                    Some(subsection)
+                } else {
+                    None
+                }
+            }
+            Some(c) if c.is_whitespace() && c != '\n' && !name.contains('.') => {
+                self.skip_whitespace();
+                if self.next() != Some('"') {
+                    return Err(self.error("expected '\"' in section header"));
+                }
+                let mut subsection = String::new();
+                loop {
+                    match self.next() {
+                        Some('"') => break,
+                        Some('\\') => match self.next() {
+                            Some('\n') | None => return Err(self.error("bad section header")),
+                            Some(c) => subsection.push(c),
+                        },
+                        Some('\n') | None => return Err(self.error("bad section header")),
+                        Some(c) => subsection.push(c),
+                    }
+                }
+                if self.next() != Some(']') {
+                    return Err(self.error("expected ']' in section header"));
+                }
+                Some(subsection)
+            }
+            _ => return Err(self.error("bad section header")),
+        };
+
+        if name.is_empty() {
+            return Err(self.error("empty section name"));
+        }
+
+        Ok((name.to_ascii_lowercase(), subsection))
+    }
+
+    /// Parse `name = value`, or a bare `name`.
+    fn entry(&mut self) -> Result<(String, Option<String>)> {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                name.push(c);
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('\n' | '#' | ';') => {
+                self.skip_comment();
+                Ok((name.to_ascii_lowercase(), None))
+            }
+            Some('=') => {
+                self.next();
+                let value = self.value()?;
+                Ok((name.to_ascii_lowercase(), Some(value)))
+            }
+            Some(c) => Err(self.error(&format!("unexpected character '{c}' in variable name"))),
+        }
+    }
+
+    /// Parse a value, up to and including the end of its line.
+    ///
+    /// Whitespace outside of quotes is trimmed from either end, and each run of it inside the
+    /// value is kept as that many spaces. A backslash at the end of a line continues the value on
+    /// the next line.
+    fn value(&mut self) -> Result<String> {
+        let mut value = String::new();
+        let mut in_quotes = false;
+        let mut pending_spaces = 0;
+
+        loop {
+            let c = match self.next() {
+                None => break,
+                Some('\n') if in_quotes => return Err(self.error("unterminated quoted value")),
+                Some('\n') => break,
+                Some(c) => c,
+            };
+
+            if c.is_whitespace() && !in_quotes {
+                if !value.is_empty() {
+                    pending_spaces += 1;
+                }
+                continue;
+            }
+            if !in_quotes && (c == '#' || c == ';') {
+                self.skip_comment();
+                break;
+            }
+
+            for _ in 0..pending_spaces {
+                value.push(' ');
+            }
+            pending_spaces = 0;
+
+            match c {
+                '"' => in_quotes = !in_quotes,
+                '\\' => match self.next() {
+                    Some('\n') => {}
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('b') => value.push('\x08'),
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    _ => return Err(self.error("bad escape sequence in value")),
+                },
+                c => value.push(c),
+            }
+        }
+
+        if in_quotes {
+            return Err(self.error("unterminated quoted value"));
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const SAMPLE: &str = r#"# A comment
+[core]
+	bare = false ; inline comment
+	filemode
+[remote "origin"]
+	url = "https://example.com/  repo.git"
+	fetch = +refs/heads/*:refs/remotes/origin/*
+	fetch = +refs/tags/*:refs/tags/*
+[Branch.Main] remote = origin
+[alias]
+	lg = log \
+	  --oneline
+	say = "echo \"hi\"\t# not a comment"
+"#;
+
+    fn file(contents: &str) -> ConfigFile {
+        ConfigFile {
+            path: Utf8PathBuf::new(),
+            lines: parse(contents).unwrap(),
+        }
+    }
+
+    fn key(key: &str) -> ConfigKey {
+        ConfigKey::from_str(key).unwrap()
+    }
+
+    fn values(file: &ConfigFile) -> Vec<(String, Option<String>)> {
+        file.entries()
+            .into_iter()
+            .map(|e| (e.key.to_string(), e.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let file = file(SAMPLE);
+        assert_eq!(file.to_string(), SAMPLE);
+
+        let some = |x: &str| Some(x.to_owned());
+        assert_eq!(
+            values(&file),
+            [
+                ("core.bare".to_owned(), some("false")),
+                ("core.filemode".to_owned(), None),
+                (
+                    "remote.origin.url".to_owned(),
+                    some("https://example.com/  repo.git")
+                ),
+                (
+                    "remote.origin.fetch".to_owned(),
+                    some("+refs/heads/*:refs/remotes/origin/*")
+                ),
+                (
+                    "remote.origin.fetch".to_owned(),
+                    some("+refs/tags/*:refs/tags/*")
+                ),
+                ("branch.main.remote".to_owned(), some("origin")),
+                ("alias.lg".to_owned(), some("log    --oneline")),
+                ("alias.say".to_owned(), some("echo \"hi\"\t# not a comment")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "x = 1\n",
+            "[core\n",
+            "[core]\n\tx = \"open\n",
+            "[core]\n\t1x = 2\n",
+        ] {
+            assert!(parse(bad).is_err(), "{bad:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_edit() -> Result<()> {
+        let mut file = file(SAMPLE);
+
+        file.set(&key("core.bare"), "true")?;
+        file.set(&key("core.editor"), "vim -f")?;
+        file.set(&key("user.name"), " spaced ")?;
+        file.add(&key("remote.origin.fetch"), "+refs/notes/*:refs/notes/*")?;
+        file.set(&key("branch.main.merge"), "refs/heads/main")?;
+        assert!(file.set(&key("remote.origin.fetch"), "x").is_err());
+        assert!(file.unset(&key("remote.origin.fetch")).is_err());
+        assert_eq!(file.unset_all(&key("remote.origin.fetch")), 3);
+        assert!(file.unset(&key("alias.lg"))?);
+        assert!(!file.unset(&key("alias.nope"))?);
+
+        let expected = r#"# A comment
+[core]
+	bare = true
+	filemode
+	editor = vim -f
+[remote "origin"]
+	url = "https://example.com/  repo.git"
+[Branch.Main] remote = origin
+	merge = refs/heads/main
+[alias]
+	say = "echo \"hi\"\t# not a comment"
+[user]
+	name = " spaced "
+"#;
+        assert_eq!(file.to_string(), expected);
+
+        // The result should read back the same
+        let reparsed = self::file(expected);
+        assert_eq!(values(&reparsed), values(&file));
+        assert_eq!(
+            reparsed.entries().last().unwrap().value.as_deref(),
+            Some(" spaced ")
+        );
+
+        Ok(())
+    }
+}
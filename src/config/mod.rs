@@ -0,0 +1,557 @@
+//! Reading and writing git config files.
+//!
+//! See: <https://git-scm.com/docs/git-config>
+
+pub mod command;
+mod file;
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::{debug, trace};
+
+use crate::ignore::wildmatch;
+use crate::Result;
+
+pub use self::file::ConfigFile;
+
+/// How deeply `include.path` directives may be nested before giving up, to catch cycles.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// The name of a config variable, such as `core.bare` or `remote.origin.url`.
+///
+/// Section and variable names are case-insensitive, and are stored lowercased. Subsection names
+/// are case-sensitive.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ConfigKey {
+    section: String,
+    subsection: Option<String>,
+    name: String,
+}
+
+impl ConfigKey {
+    pub fn new(section: &str, subsection: Option<&str>, name: &str) -> Self {
+        Self {
+            section: section.to_ascii_lowercase(),
+            subsection: subsection.map(str::to_owned),
+            name: name.to_ascii_lowercase(),
+        }
+    }
+
+    pub fn section(&self) -> &str {
+        &self.section
+    }
+
+    pub fn subsection(&self) -> Option<&str> {
+        self.subsection.as_deref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn in_section(&self, section: &str, subsection: Option<&str>) -> bool {
+        self.section.eq_ignore_ascii_case(section) && self.subsection.as_deref() == subsection
+    }
+
+    fn name_matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+impl FromStr for ConfigKey {
+    type Err = color_eyre::Report;
+
+    /// Parse a key of the form `section.name` or `section.subsection.name`. The subsection may
+    /// itself contain dots.
+    fn from_str(s: &str) -> Result<Self> {
+        let (section, rest) = s
+            .split_once('.')
+            .ok_or_else(|| eyre!("key does not contain a section: {s}"))?;
+        let (subsection, name) = match rest.rsplit_once('.') {
+            Some((subsection, name)) => (Some(subsection), name),
+            None => (None, rest),
+        };
+
+        let valid_section = !section.is_empty()
+            && section
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+        if !valid_section || !valid_name {
+            return Err(eyre!("invalid key: {s}"));
+        }
+
+        Ok(Self::new(section, subsection, name))
+    }
+}
+
+impl Display for ConfigKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.subsection {
+            Some(subsection) => write!(f, "{}.{}.{}", self.section, subsection, self.name),
+            None => write!(f, "{}.{}", self.section, self.name),
+        }
+    }
+}
+
+/// Where a config file sits in the hierarchy. Later scopes override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigScope {
+    /// `/etc/gitconfig`
+    System,
+    /// `~/.gitconfig`, and `$XDG_CONFIG_HOME/git/config`
+    Global,
+    /// `.git/config`
+    Local,
+}
+
+impl ConfigScope {
+    /// The file that writes to this scope should go to.
+    pub fn path(self, git_dir: Option<&Utf8Path>) -> Result<Utf8PathBuf> {
+        match self {
+            ConfigScope::System => Ok(system_config_path()),
+            ConfigScope::Global => {
+                if let Some(path) = env_path("GIT_CONFIG_GLOBAL") {
+                    return Ok(path);
+                }
+                let home = home_dir().ok_or_else(|| eyre!("$HOME is not set"))?;
+                Ok(home.join(".gitconfig"))
+            }
+            ConfigScope::Local => git_dir
+                .map(|git_dir| git_dir.join("config"))
+                .ok_or_else(|| eyre!("not in a git directory")),
+        }
+    }
+
+    /// Every file that is read for this scope, lowest precedence first.
+    fn read_paths(self, git_dir: Option<&Utf8Path>) -> Vec<Utf8PathBuf> {
+        match self {
+            ConfigScope::System => {
+                if env_bool("GIT_CONFIG_NOSYSTEM") {
+                    vec![]
+                } else {
+                    vec![system_config_path()]
+                }
+            }
+            ConfigScope::Global => {
+                if let Some(path) = env_path("GIT_CONFIG_GLOBAL") {
+                    return vec![path];
+                }
+                let xdg = match env_path("XDG_CONFIG_HOME") {
+                    Some(xdg) => Some(xdg.join("git/config")),
+                    None => home_dir().map(|home| home.join(".config/git/config")),
+                };
+                let home = home_dir().map(|home| home.join(".gitconfig"));
+                xdg.into_iter().chain(home).collect()
+            }
+            ConfigScope::Local => git_dir
+                .map(|git_dir| git_dir.join("config"))
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl Display for ConfigScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigScope::System => write!(f, "system"),
+            ConfigScope::Global => write!(f, "global"),
+            ConfigScope::Local => write!(f, "local"),
+        }
+    }
+}
+
+/// A single value, along with where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEntry {
+    pub key: ConfigKey,
+    /// `None` for a variable with no `=`, which is shorthand for `true`
+    pub value: Option<String>,
+    pub scope: ConfigScope,
+    /// The file the value was read from, which may have been included from a file in `scope`
+    pub origin: Utf8PathBuf,
+}
+
+/// The combined configuration from every scope, with includes expanded.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    git_dir: Option<Utf8PathBuf>,
+    entries: Vec<ConfigEntry>,
+}
+
+impl Config {
+    /// Load the configuration for the repository at `git_dir`, or just the system and global
+    /// configuration if there is no repository.
+    pub fn load(git_dir: Option<&Utf8Path>) -> Result<Self> {
+        let mut config = Self {
+            git_dir: git_dir.map(Utf8Path::to_owned),
+            entries: Vec::new(),
+        };
+
+        for scope in [ConfigScope::System, ConfigScope::Global, ConfigScope::Local] {
+            for path in scope.read_paths(git_dir) {
+                config.load_file(&path, scope, 0)?;
+            }
+        }
+
+        debug!(entries = config.entries.len(), "Loaded config");
+        Ok(config)
+    }
+
+    /// Load only the files for a single scope, as for `rit config --global`.
+    pub fn load_scope(scope: ConfigScope, git_dir: Option<&Utf8Path>) -> Result<Self> {
+        let mut config = Self {
+            git_dir: git_dir.map(Utf8Path::to_owned),
+            entries: Vec::new(),
+        };
+        for path in scope.read_paths(git_dir) {
+            config.load_file(&path, scope, 0)?;
+        }
+        Ok(config)
+    }
+
+    /// Load only the single file at `path`, as for `rit config --file`. Its values are treated as
+    /// being in `scope`.
+    pub fn load_file_only(
+        path: &Utf8Path,
+        scope: ConfigScope,
+        git_dir: Option<&Utf8Path>,
+    ) -> Result<Self> {
+        let mut config = Self {
+            git_dir: git_dir.map(Utf8Path::to_owned),
+            entries: Vec::new(),
+        };
+        config.load_file(path, scope, 0)?;
+        Ok(config)
+    }
+
+    fn load_file(&mut self, path: &Utf8Path, scope: ConfigScope, depth: usize) -> Result<()> {
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(eyre!(
+                "exceeded maximum include depth ({MAX_INCLUDE_DEPTH}) while including {path}"
+            ));
+        }
+        if !path.is_file() {
+            return Ok(());
+        }
+
+        trace!(%path, %scope, "Reading config file");
+        let file = ConfigFile::open(path)?;
+
+        for entry in file.entries() {
+            let include = self.include_path(&entry.key, entry.value.as_deref(), path)?;
+
+            self.entries.push(ConfigEntry {
+                key: entry.key,
+                value: entry.value,
+                scope,
+                origin: path.to_owned(),
+            });
+
+            if let Some(include) = include {
+                self.load_file(&include, scope, depth + 1)
+                    .wrap_err_with(|| format!("While including from {path}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// If `key` is an `include.path`, or an `includeIf.<condition>.path` whose condition holds,
+    /// returns the path of the file to include.
+    fn include_path(
+        &self,
+        key: &ConfigKey,
+        value: Option<&str>,
+        including: &Utf8Path,
+    ) -> Result<Option<Utf8PathBuf>> {
+        if key.name() != "path" {
+            return Ok(None);
+        }
+
+        let applies = match (key.section(), key.subsection()) {
+            ("include", None) => true,
+            ("includeif", Some(condition)) => self.include_condition_holds(condition, including)?,
+            _ => false,
+        };
+        if !applies {
+            return Ok(None);
+        }
+
+        let value = value.ok_or_else(|| eyre!("missing value for '{key}'"))?;
+        let path = expand_path(value)?;
+        if path.is_absolute() {
+            Ok(Some(path))
+        } else {
+            // Relative includes are relative to the including file
+            let dir = including.parent().unwrap_or_else(|| Utf8Path::new(""));
+            Ok(Some(dir.join(path)))
+        }
+    }
+
+    /// Evaluate the condition of an `includeIf` section, such as `gitdir:~/work/` or
+    /// `onbranch:feature/`.
+    fn include_condition_holds(&self, condition: &str, including: &Utf8Path) -> Result<bool> {
+        let git_dir = match &self.git_dir {
+            Some(git_dir) => git_dir,
+            None => return Ok(false),
+        };
+
+        let (kind, pattern) = match condition.split_once(':') {
+            Some(x) => x,
+            None => return Ok(false),
+        };
+
+        match kind {
+            "gitdir" | "gitdir/i" => {
+                let mut pattern = if let Some(rest) = pattern.strip_prefix("./") {
+                    let dir = including.parent().unwrap_or_else(|| Utf8Path::new(""));
+                    dir.join(rest).into_string()
+                } else {
+                    expand_path(pattern)?.into_string()
+                };
+                if !pattern.starts_with('/') {
+                    pattern.insert_str(0, "**/");
+                }
+                if pattern.ends_with('/') {
+                    pattern.push_str("**");
+                }
+
+                let git_dir = git_dir
+                    .canonicalize_utf8()
+                    .unwrap_or_else(|_| git_dir.clone())
+                    .into_string();
+
+                Ok(if kind == "gitdir/i" {
+                    glob_matches(&pattern.to_lowercase(), &git_dir.to_lowercase())
+                } else {
+                    glob_matches(&pattern, &git_dir)
+                })
+            }
+            "onbranch" => {
+                let head = std::fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+                let branch = match head.trim().strip_prefix("ref: refs/heads/") {
+                    Some(branch) => branch,
+                    None => return Ok(false),
+                };
+                let mut pattern = pattern.to_owned();
+                if pattern.ends_with('/') {
+                    pattern.push_str("**");
+                }
+                Ok(glob_matches(&pattern, branch))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Every value, in the order they were read.
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// The entries for `key`, in the order they were read.
+    fn entries_for(&self, key: &str) -> impl DoubleEndedIterator<Item = &ConfigEntry> {
+        let key = ConfigKey::from_str(key).ok();
+        self.entries
+            .iter()
+            .filter(move |entry| Some(&entry.key) == key.as_ref())
+    }
+
+    /// The value of `key`. If it is set multiple times, the last value wins. A variable with no
+    /// `=` has the value `""`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries_for(key)
+            .next_back()
+            .map(|entry| entry.value.as_deref().unwrap_or(""))
+    }
+
+    /// Every value of `key`, in the order they were read.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.entries_for(key)
+            .map(|entry| entry.value.as_deref().unwrap_or(""))
+            .collect()
+    }
+
+    /// The value of `key`, interpreted as a boolean.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        match self.entries_for(key).next_back() {
+            None => Ok(None),
+            Some(ConfigEntry { value: None, .. }) => Ok(Some(true)),
+            Some(ConfigEntry {
+                value: Some(value), ..
+            }) => parse_bool(value)
+                .map(Some)
+                .ok_or_else(|| eyre!("bad boolean config value '{value}' for '{key}'")),
+        }
+    }
+
+    /// The value of `key`, interpreted as an integer with an optional `k`, `m` or `g` suffix.
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(value) => parse_int(value)
+                .map(Some)
+                .ok_or_else(|| eyre!("bad numeric config value '{value}' for '{key}'")),
+        }
+    }
+
+    /// The value of `key`, interpreted as a path, with a leading `~/` expanded.
+    pub fn get_path(&self, key: &str) -> Result<Option<Utf8PathBuf>> {
+        self.get(key).map(expand_path).transpose()
+    }
+}
+
+/// Interpret a config value as a boolean, the way git does.
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" | "" => Some(false),
+        other => other.parse::<i64>().ok().map(|x| x != 0),
+    }
+}
+
+/// Interpret a config value as an integer, the way git does.
+pub fn parse_int(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (digits, factor) = match value.chars().last()?.to_ascii_lowercase() {
+        'k' => (&value[..value.len() - 1], 1 << 10),
+        'm' => (&value[..value.len() - 1], 1 << 20),
+        'g' => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    digits.parse::<i64>().ok()?.checked_mul(factor)
+}
+
+/// Expand a leading `~/` to the user's home directory.
+fn expand_path(path: &str) -> Result<Utf8PathBuf> {
+    match path.strip_prefix("~/") {
+        Some(rest) => {
+            let home = home_dir().ok_or_else(|| eyre!("$HOME is not set, cannot expand {path}"))?;
+            Ok(home.join(rest))
+        }
+        None => Ok(Utf8PathBuf::from(path)),
+    }
+}
+
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    wildmatch(
+        &pattern.chars().collect::<Vec<_>>(),
+        &text.chars().collect::<Vec<_>>(),
+    )
+}
+
+fn home_dir() -> Option<Utf8PathBuf> {
+    env_path("HOME")
+}
+
+fn env_path(var: &str) -> Option<Utf8PathBuf> {
+    std::env::var(var)
+        .ok()
+        .filter(|x| !x.is_empty())
+        .map(Utf8PathBuf::from)
+}
+
+fn env_bool(var: &str) -> bool {
+    std::env::var(var)
+        .ok()
+        .and_then(|x| parse_bool(&x))
+        .unwrap_or(false)
+}
+
+fn system_config_path() -> Utf8PathBuf {
+    env_path("GIT_CONFIG_SYSTEM").unwrap_or_else(|| Utf8PathBuf::from("/etc/gitconfig"))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_key() -> Result<()> {
+        let key = ConfigKey::from_str("Remote.Origin.URL")?;
+        assert_eq!(key.section(), "remote");
+        assert_eq!(key.subsection(), Some("Origin"));
+        assert_eq!(key.name(), "url");
+        assert_eq!(key.to_string(), "remote.Origin.url");
+
+        let key = ConfigKey::from_str("url.https://example.com/.insteadOf")?;
+        assert_eq!(key.subsection(), Some("https://example.com/"));
+
+        for bad in ["core", "core.", ".name", "core.1name", "co re.name"] {
+            assert!(ConfigKey::from_str(bad).is_err(), "{bad} should be invalid");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(parse_bool("Yes"), Some(true));
+        assert_eq!(parse_bool("0"), Some(false));
+        assert_eq!(parse_bool(""), Some(false));
+        assert_eq!(parse_bool("maybe"), None);
+        assert_eq!(parse_int("12"), Some(12));
+        assert_eq!(parse_int("2k"), Some(2048));
+        assert_eq!(parse_int("1G"), Some(1 << 30));
+        assert_eq!(parse_int("x"), None);
+    }
+
+    #[test]
+    /// Check that included files are expanded in place, and that conditional includes only apply
+    /// when their condition holds.
+    fn test_includes() -> Result<()> {
+        let dir = TempDir::new("")?;
+        let dir = Utf8Path::from_path(dir.path())
+            .unwrap()
+            .canonicalize_utf8()?;
+        let git_dir = dir.join("repo/.git");
+        std::fs::create_dir_all(&git_dir)?;
+        std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/x\n")?;
+
+        std::fs::write(
+            git_dir.join("config"),
+            format!(
+                "[user]\n\tname = Before\n[include]\n\tpath = ../../extra.inc\n\
+                 [includeIf \"gitdir:{dir}/repo/\"]\n\tpath = {dir}/gitdir.inc\n\
+                 [includeIf \"gitdir:/elsewhere/\"]\n\tpath = {dir}/never.inc\n\
+                 [includeIf \"onbranch:feature/\"]\n\tpath = {dir}/branch.inc\n"
+            ),
+        )?;
+        std::fs::write(dir.join("extra.inc"), "[user]\n\tname = Included\n")?;
+        std::fs::write(
+            dir.join("gitdir.inc"),
+            "[user]\n\temail = gitdir@example.com\n",
+        )?;
+        std::fs::write(
+            dir.join("never.inc"),
+            "[user]\n\temail = never@example.com\n",
+        )?;
+        std::fs::write(dir.join("branch.inc"), "[core]\n\tabbrev = 12\n")?;
+
+        let config =
+            Config::load_file_only(&git_dir.join("config"), ConfigScope::Local, Some(&git_dir))?;
+        assert_eq!(config.get("user.name"), Some("Included"));
+        assert_eq!(config.get_all("user.name"), ["Before", "Included"]);
+        assert_eq!(config.get("user.email"), Some("gitdir@example.com"));
+        assert_eq!(config.get_int("core.abbrev")?, Some(12));
+
+        std::fs::write(dir.join("extra.inc"), "[include]\n\tpath = extra.inc\n")?;
+        assert!(Config::load_file_only(
+            &git_dir.join("config"),
+            ConfigScope::Local,
+            Some(&git_dir)
+        )
+        .is_err());
+
+        Ok(())
+    }
+}
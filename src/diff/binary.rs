@@ -0,0 +1,132 @@
+//! Binary files: deciding whether a file is binary, and git's `GIT binary patch` format, which
+//! `diff --binary` uses to show changes to them in a form `git apply` understands.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::pack::delta::create_delta;
+use crate::Result;
+
+/// How much of a file is searched for a NUL byte to decide whether it is binary.
+const FIRST_FEW_BYTES: usize = 8000;
+
+/// The most bytes encoded on one line of a binary patch.
+const BYTES_PER_LINE: usize = 52;
+
+const BASE85: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// Whether `data` looks binary: as in git, whether there is a NUL byte near the start.
+pub fn is_binary(data: &[u8]) -> bool {
+    memchr::memchr(0, &data[..data.len().min(FIRST_FEW_BYTES)]).is_some()
+}
+
+/// Encode `data` in git's base85: each group of 4 bytes, read as a big-endian number, becomes 5
+/// characters. The last group is padded with zeros.
+pub fn encode_base85(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(4) * 5);
+    for group in data.chunks(4) {
+        let mut bytes = [0; 4];
+        bytes[..group.len()].copy_from_slice(group);
+        let mut value = u32::from_be_bytes(bytes);
+
+        let mut chars = [0; 5];
+        for char in chars.iter_mut().rev() {
+            *char = BASE85[(value % 85) as usize];
+            value /= 85;
+        }
+        out.extend(chars.map(char::from));
+    }
+    out
+}
+
+/// A binary patch turning `old` into `new`, starting with `GIT binary patch`. It has a hunk for
+/// each direction, so that it can also be applied in reverse.
+pub fn binary_patch(old: &[u8], new: &[u8]) -> Result<String> {
+    let mut out = String::from("GIT binary patch\n");
+    write_hunk(&mut out, old, new)?;
+    write_hunk(&mut out, new, old)?;
+    Ok(out)
+}
+
+/// Write a hunk producing `to`: a delta against `from` if that is smaller once compressed, or
+/// else `to` itself.
+///
+/// The data is compressed and split into lines, each starting with its length (`A`-`Z` for 1-26
+/// bytes, `a`-`z` for 27-52) followed by the bytes in base85.
+fn write_hunk(out: &mut String, from: &[u8], to: &[u8]) -> Result<()> {
+    let literal = compress(to)?;
+    let delta = match from.is_empty() || to.is_empty() {
+        true => None,
+        false => Some(create_delta(from, to)),
+    };
+
+    let (data, header) = match delta {
+        Some(delta) => match compress(&delta)? {
+            compressed if compressed.len() < literal.len() => {
+                (compressed, format!("delta {}", delta.len()))
+            }
+            _ => (literal, format!("literal {}", to.len())),
+        },
+        None => (literal, format!("literal {}", to.len())),
+    };
+
+    writeln!(out, "{header}")?;
+    for line in data.chunks(BYTES_PER_LINE) {
+        let len = line.len() as u8;
+        out.push(match len {
+            1..=26 => char::from(b'A' + len - 1),
+            _ => char::from(b'a' + len - 27),
+        });
+        out.push_str(&encode_base85(line));
+        out.push('\n');
+    }
+    out.push('\n');
+    Ok(())
+}
+
+/// Compress `data` as git does for binary patches, at zlib's fastest level.
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut e = ZlibEncoder::new(Vec::with_capacity(data.len() / 2), Compression::fast());
+    e.write_all(data)?;
+    Ok(e.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(!is_binary("caf\u{e9}".as_bytes()));
+        assert!(!is_binary(b"Latin-1 caf\xe9"));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        // Only the start of the file is checked
+        let mut late = vec![b'a'; FIRST_FEW_BYTES];
+        late.push(0);
+        assert!(!is_binary(&late));
+    }
+
+    #[test]
+    fn test_encode_base85() {
+        assert_eq!(encode_base85(b"Hello, world!"), "NM&qnZ!92pZ*pv8ApigX");
+        assert_eq!(encode_base85(b"\xff\xff\xff\xff"), "|NsC0");
+        assert_eq!(encode_base85(b""), "");
+    }
+
+    #[test]
+    fn test_binary_patch() {
+        // Deleting a file, as git writes it
+        let patch = binary_patch(b"\0", b"").unwrap();
+        assert_eq!(
+            patch,
+            "GIT binary patch\nliteral 0\nHcmV?d00001\n\nliteral 1\nIcmZPo000310RR91\n\n"
+        );
+    }
+}
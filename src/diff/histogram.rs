@@ -0,0 +1,145 @@
+//! Histogram diff, a version of patience diff that copes with files with few unique lines. It
+//! matches up the longest run of lines around the line that is least common in the old file, and
+//! then diffs either side of that run in the same way.
+//!
+//! See: <https://github.com/git/git/blob/master/xdiff/xhistogram.c>
+
+use std::collections::HashMap;
+
+use super::{common_prefix, common_suffix, push_equal, push_unmatched};
+use super::{Algorithm, Edit, Line, Myers};
+
+/// Lines that appear more often than this in the old file aren't used to match up the files.
+/// If those are the only lines in common, Myers' algorithm is used instead.
+const MAX_CHAIN: usize = 64;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Histogram;
+
+impl Algorithm for Histogram {
+    fn diff<'a>(&self, a: &[Line<'a>], b: &[Line<'a>]) -> Vec<Edit<'a>> {
+        let mut edits = Vec::new();
+        histogram(a, b, &mut edits);
+        edits
+    }
+}
+
+/// A run of lines that are the same in both files.
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    a_start: usize,
+    b_start: usize,
+    len: usize,
+    /// How often the least common line in the run appears in the old file
+    count: usize,
+}
+
+fn histogram<'a>(mut a: &[Line<'a>], mut b: &[Line<'a>], edits: &mut Vec<Edit<'a>>) {
+    let mut suffixes = Vec::new();
+
+    // The part after each run is handled by going round again, rather than recursing
+    loop {
+        let prefix = common_prefix(a, b);
+        let suffix = common_suffix(&a[prefix..], &b[prefix..]);
+        push_equal(edits, &a[..prefix], &b[..prefix]);
+        suffixes.push((&a[a.len() - suffix..], &b[b.len() - suffix..]));
+        (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+        if a.is_empty() || b.is_empty() {
+            push_unmatched(edits, a, b);
+            break;
+        }
+
+        match longest_rare_region(a, b) {
+            Ok(Some(region)) => {
+                let (a_end, b_end) = (region.a_start + region.len, region.b_start + region.len);
+                histogram(&a[..region.a_start], &b[..region.b_start], edits);
+                push_equal(edits, &a[region.a_start..a_end], &b[region.b_start..b_end]);
+                (a, b) = (&a[a_end..], &b[b_end..]);
+            }
+            Ok(None) => {
+                push_unmatched(edits, a, b);
+                break;
+            }
+            Err(TooCommon) => {
+                edits.extend(Myers::default().diff(a, b));
+                break;
+            }
+        }
+    }
+
+    for (a, b) in suffixes.into_iter().rev() {
+        push_equal(edits, a, b);
+    }
+}
+
+/// The lines in common were all too common to use.
+struct TooCommon;
+
+/// Find the run of lines in common whose least common line is rarest, preferring the longest of
+/// those, and then the first. Returns `None` if there are no lines in common at all.
+fn longest_rare_region(a: &[Line], b: &[Line]) -> Result<Option<Region>, TooCommon> {
+    let mut occurrences = HashMap::<&str, Vec<usize>>::new();
+    for (i, line) in a.iter().enumerate() {
+        occurrences.entry(line.line).or_default().push(i);
+    }
+
+    let mut best: Option<Region> = None;
+    let mut too_common = false;
+    let mut j = 0;
+    while j < b.len() {
+        let mut next = j + 1;
+        let positions = match occurrences.get(b[j].line) {
+            Some(positions) if positions.len() > MAX_CHAIN => {
+                too_common = true;
+                j = next;
+                continue;
+            }
+            Some(positions) => positions,
+            None => {
+                j = next;
+                continue;
+            }
+        };
+
+        for &i in positions {
+            let (mut a_start, mut b_start) = (i, j);
+            while a_start > 0 && b_start > 0 && a[a_start - 1] == b[b_start - 1] {
+                a_start -= 1;
+                b_start -= 1;
+            }
+            let mut len = i + 1 - a_start;
+            while a_start + len < a.len() && b_start + len < b.len() {
+                if a[a_start + len] != b[b_start + len] {
+                    break;
+                }
+                len += 1;
+            }
+
+            let count = a[a_start..a_start + len]
+                .iter()
+                .map(|line| occurrences[line.line].len())
+                .min()
+                .unwrap();
+            let better = match best {
+                Some(best) => count < best.count || (count == best.count && len > best.len),
+                None => true,
+            };
+            if better {
+                best = Some(Region {
+                    a_start,
+                    b_start,
+                    len,
+                    count,
+                });
+            }
+            next = next.max(b_start + len);
+        }
+        j = next;
+    }
+
+    match best {
+        None if too_common => Err(TooCommon),
+        best => Ok(best),
+    }
+}
@@ -0,0 +1,443 @@
+pub mod binary;
+mod histogram;
+mod myers;
+mod patience;
+pub mod rename;
+
+use std::fmt::Display;
+
+pub use self::histogram::Histogram;
+pub use self::myers::Myers;
+pub use self::patience::Patience;
+
+/// Compute the difference between two slices of strings, using the Myers diff algorithm
+pub fn diff<'a>(b: &[&'a str], a: &[&'a str]) -> Vec<Edit<'a>> {
+    DiffAlgorithm::default().diff(b, a)
+}
+
+/// A way of finding the edits that turn one file into another. Every way finds correct edits,
+/// but they differ in how readable the edits are, and how long they take to find.
+pub trait Algorithm {
+    /// Find the edits that turn the lines `a` into the lines `b`. Each line of both is in
+    /// exactly one edit, in order.
+    fn diff<'a>(&self, a: &[Line<'a>], b: &[Line<'a>]) -> Vec<Edit<'a>>;
+}
+
+/// The algorithms `diff` can use, as named by `--diff-algorithm` and `diff.algorithm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DiffAlgorithm {
+    /// Myers' algorithm, settling for more edits than needed if finding the fewest takes long
+    #[default]
+    #[value(alias = "default")]
+    Myers,
+    /// Myers' algorithm, always finding the fewest edits
+    Minimal,
+    /// Match up lines that appear once in each file first, so moved blocks stay whole
+    Patience,
+    /// Like patience, but also matching up lines that are merely rare
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    /// Compute the difference between two slices of strings, the new version first.
+    pub fn diff<'a>(self, b: &[&'a str], a: &[&'a str]) -> Vec<Edit<'a>> {
+        let a = Line::number(a);
+        let b = Line::number(b);
+        match self {
+            DiffAlgorithm::Myers => Myers { minimal: false }.diff(&a, &b),
+            DiffAlgorithm::Minimal => Myers { minimal: true }.diff(&a, &b),
+            DiffAlgorithm::Patience => Patience.diff(&a, &b),
+            DiffAlgorithm::Histogram => Histogram.diff(&a, &b),
+        }
+    }
+}
+
+/// How many lines at the start of `a` and `b` are the same.
+fn common_prefix(a: &[Line], b: &[Line]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// How many lines at the end of `a` and `b` are the same.
+fn common_suffix(a: &[Line], b: &[Line]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// Add the lines `a` and `b`, which are the same, as unchanged.
+fn push_equal<'a>(edits: &mut Vec<Edit<'a>>, a: &[Line<'a>], b: &[Line<'a>]) {
+    debug_assert_eq!(a.len(), b.len());
+    edits.extend(
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| Edit::new(EditKind::Equal, Some(*a), Some(*b))),
+    );
+}
+
+/// Add the lines `a` as deleted and the lines `b` as inserted, where they have nothing in common.
+fn push_unmatched<'a>(edits: &mut Vec<Edit<'a>>, a: &[Line<'a>], b: &[Line<'a>]) {
+    edits.extend(
+        a.iter()
+            .map(|a| Edit::new(EditKind::Delete, Some(*a), None)),
+    );
+    edits.extend(
+        b.iter()
+            .map(|b| Edit::new(EditKind::Insert, None, Some(*b))),
+    );
+}
+
+/// Collect a slice of edits into a [`Vec`] of [`Hunk`]s.
+pub fn hunks<'a>(edits: &[Edit<'a>]) -> Vec<Hunk<'a>> {
+    let mut hunks = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        while edits.get(offset).map(|x| x.kind) == Some(EditKind::Equal) {
+            offset += 1;
+        }
+
+        if offset >= edits.len() {
+            return hunks;
+        }
+
+        // Start just before the context leading up to the change, which may be before the
+        // first edit
+        let start = offset as isize - (HUNK_CONTEXT as isize + 1);
+
+        let (a_start, b_start) = match usize::try_from(start) {
+            Ok(start) => (
+                edits[start].a_line.map(|x| x.index),
+                edits[start].b_line.map(|x| x.index),
+            ),
+            Err(_) => (None, None),
+        };
+        hunks.push(Hunk {
+            a_start,
+            b_start,
+            edits: Vec::new(),
+        });
+
+        offset = Hunk::build(hunks.last_mut().unwrap(), edits, start);
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EditKind {
+    Insert,
+    Delete,
+    Equal,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LineKind {
+    A,
+    B,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Edit<'a> {
+    kind: EditKind,
+    a_line: Option<Line<'a>>,
+    b_line: Option<Line<'a>>,
+}
+
+impl<'a> Edit<'a> {
+    fn new(kind: EditKind, a_line: Option<Line<'a>>, b_line: Option<Line<'a>>) -> Self {
+        Edit {
+            kind,
+            a_line,
+            b_line,
+        }
+    }
+
+    pub fn kind(&self) -> EditKind {
+        self.kind
+    }
+
+    /// The index of this edit's line in the old file, if it has one.
+    pub fn a_index(&self) -> Option<usize> {
+        self.a_line.map(|x| x.index)
+    }
+
+    /// The index of this edit's line in the new file, if it has one.
+    pub fn b_index(&self) -> Option<usize> {
+        self.b_line.map(|x| x.index)
+    }
+}
+
+/// A line of a file being diffed, and where it is in the file.
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    line: &'a str,
+    index: usize,
+}
+
+impl<'a> Line<'a> {
+    fn number(lines: &[&'a str]) -> Vec<Self> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| Line { line, index })
+            .collect()
+    }
+}
+
+impl PartialEq for Line<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.line == other.line
+    }
+}
+
+impl Eq for Line<'_> {}
+
+impl Display for Edit<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let char = match self.kind {
+            EditKind::Insert => '+',
+            EditKind::Delete => '-',
+            EditKind::Equal => ' ',
+        };
+        let line = self.a_line.unwrap_or_else(|| self.b_line.unwrap());
+        write!(f, "{}{}", char, line.line)
+    }
+}
+
+pub struct Hunk<'a> {
+    a_start: Option<usize>,
+    b_start: Option<usize>,
+    edits: Vec<Edit<'a>>,
+}
+
+/// The amount of context given to each hunk displayed
+const HUNK_CONTEXT: usize = 3;
+
+impl<'a> Hunk<'a> {
+    fn build(hunk: &mut Self, edits: &[Edit<'a>], mut offset: isize) -> usize {
+        let mut counter = -1;
+
+        while counter != 0 {
+            if offset >= 0 && counter > 0 {
+                hunk.edits.push(edits[offset as usize].clone())
+            }
+
+            offset += 1;
+            if offset >= edits.len() as isize {
+                break;
+            }
+
+            let next = (offset + HUNK_CONTEXT as isize) as usize;
+            match edits.get(next).map(|x| x.kind) {
+                Some(EditKind::Insert | EditKind::Delete) => {
+                    counter = 2 * (HUNK_CONTEXT as isize) + 1
+                }
+                Some(EditKind::Equal) | None => counter -= 1,
+            }
+        }
+
+        offset as usize
+    }
+
+    /// The hunk's header, giving the lines it covers in each file as `<start>,<count>`. Lines
+    /// are numbered from 1, a count of 1 is left out, and an empty range starts at the line
+    /// before it, as in git.
+    pub fn header(&self) -> String {
+        let (a_start, a_len) = self.offsets_for(LineKind::A, self.a_start);
+        let (b_start, b_len) = self.offsets_for(LineKind::B, self.b_start);
+
+        format!(
+            "@@ -{} +{} @@",
+            format_range(a_start, a_len),
+            format_range(b_start, b_len)
+        )
+    }
+
+    fn offsets_for(&self, mode: LineKind, default: Option<usize>) -> (usize, usize) {
+        let mut lines = self
+            .edits
+            .iter()
+            .filter_map(|e| match mode {
+                LineKind::A => e.a_line,
+                LineKind::B => e.b_line,
+            })
+            .peekable();
+
+        let start = match lines.peek() {
+            Some(line) => line.index + 1,
+            None => default.unwrap_or_default(),
+        };
+
+        let lines = lines.count();
+
+        (start, lines)
+    }
+
+    pub fn edits(&self) -> &[Edit] {
+        self.edits.as_ref()
+    }
+}
+
+fn format_range(start: usize, len: usize) -> String {
+    match len {
+        1 => start.to_string(),
+        _ => format!("{start},{len}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_works() {
+        let b = ["a", "b", "b", "a"];
+        let a = ["a", "b", "b", "a", "c"];
+        let diff = diff(&a, &b);
+        println!("{:?}", diff);
+
+        assert_eq!(diff.len(), 5);
+        assert_eq!(
+            diff.last().unwrap(),
+            &Edit {
+                kind: EditKind::Insert,
+                a_line: None,
+                b_line: Some(Line {
+                    line: "c",
+                    index: 4
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn hunk_headers() {
+        let old = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
+        let mut new = old.to_vec();
+        new[5] = "six";
+        new.push("11");
+        let edits = diff(&new, &old);
+        let headers = hunks(&edits).iter().map(Hunk::header).collect::<Vec<_>>();
+        assert_eq!(headers, ["@@ -3,8 +3,9 @@"]);
+
+        let edits = diff(&["only"], &[]);
+        let headers = hunks(&edits).iter().map(Hunk::header).collect::<Vec<_>>();
+        assert_eq!(headers, ["@@ -0,0 +1 @@"]);
+
+        let long = (0..20).map(|i| i.to_string()).collect::<Vec<_>>();
+        let old = long.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut new = old.clone();
+        new.remove(1);
+        new.remove(15);
+        let edits = diff(&new, &old);
+        let headers = hunks(&edits).iter().map(Hunk::header).collect::<Vec<_>>();
+        assert_eq!(headers, ["@@ -1,5 +1,4 @@", "@@ -14,7 +13,6 @@"]);
+    }
+
+    const ALGORITHMS: [DiffAlgorithm; 4] = [
+        DiffAlgorithm::Myers,
+        DiffAlgorithm::Minimal,
+        DiffAlgorithm::Patience,
+        DiffAlgorithm::Histogram,
+    ];
+
+    /// Check that `edits` turn `old` into `new`, and return how many lines they change.
+    fn check_edits(edits: &[Edit], new: &[&str], old: &[&str]) -> usize {
+        let kept_old = edits
+            .iter()
+            .filter(|x| x.kind != EditKind::Insert)
+            .map(|x| (x.a_index().unwrap(), x.a_line.unwrap().line))
+            .collect::<Vec<_>>();
+        let kept_new = edits
+            .iter()
+            .filter(|x| x.kind != EditKind::Delete)
+            .map(|x| (x.b_index().unwrap(), x.b_line.unwrap().line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept_old,
+            old.iter().copied().enumerate().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            kept_new,
+            new.iter().copied().enumerate().collect::<Vec<_>>()
+        );
+        for edit in edits.iter().filter(|x| x.kind == EditKind::Equal) {
+            assert_eq!(edit.a_line, edit.b_line);
+        }
+        edits.iter().filter(|x| x.kind != EditKind::Equal).count()
+    }
+
+    #[test]
+    fn algorithms_find_correct_edits() {
+        use rand::prelude::*;
+
+        let mut rng = StdRng::seed_from_u64(24);
+        let words = ["a", "b", "c", "{", "}", "", "fn x()", "fn y()"];
+        for len in [0, 1, 5, 50, 2000] {
+            let old = (0..len)
+                .map(|_| *words.choose(&mut rng).unwrap())
+                .collect::<Vec<_>>();
+            let mut new = old.clone();
+            for _ in 0..len / 2 {
+                let i = rng.gen_range(0..=new.len());
+                match rng.gen_range(0..3) {
+                    0 if i < new.len() => drop(new.remove(i)),
+                    _ => new.insert(i, words.choose(&mut rng).unwrap()),
+                }
+            }
+
+            let changed =
+                ALGORITHMS.map(|algorithm| check_edits(&algorithm.diff(&new, &old), &new, &old));
+            // The minimal diff is no bigger than any other
+            assert!(changed.iter().all(|x| *x >= changed[1]), "{changed:?}");
+        }
+    }
+
+    #[test]
+    fn patience_keeps_moved_blocks() {
+        let old = [
+            "fn alpha() {",
+            "    one();",
+            "}",
+            "",
+            "fn beta() {",
+            "    two();",
+            "}",
+        ];
+        let new = [
+            "fn beta() {",
+            "    two();",
+            "}",
+            "",
+            "fn alpha() {",
+            "    one();",
+            "}",
+        ];
+        let show = |edits: Vec<Edit>| edits.iter().map(Edit::to_string).collect::<Vec<_>>();
+
+        // Myers keeps the braces and blank line, splitting both functions up
+        let myers = show(DiffAlgorithm::Myers.diff(&new, &old));
+        assert_eq!(myers.iter().filter(|x| x.starts_with(' ')).count(), 3);
+
+        // Patience and histogram keep beta whole, and move alpha after it. The closing braces
+        // at the ends match first, so the moved braces are the ones in the middle
+        for algorithm in [DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+            assert_eq!(
+                show(algorithm.diff(&new, &old)),
+                [
+                    "-fn alpha() {",
+                    "-    one();",
+                    "-}",
+                    "-",
+                    " fn beta() {",
+                    "     two();",
+                    "+}",
+                    "+",
+                    "+fn alpha() {",
+                    "+    one();",
+                    " }",
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,673 @@
+//! Myers' algorithm, which finds the fewest edits between two files by searching outwards from
+//! the start of both, one more edit at a time.
+//!
+//! This is the linear space version: rather than keeping every step of the search to trace the
+//! path back along, it searches from both ends at once until the two searches meet, and then
+//! diffs the parts either side of where they met in the same way. Like git, it stops searching
+//! when that is taking too long, and settles for a good enough place to split.
+//!
+//! See: <http://www.xmailserver.org/diff2.pdf>, section 4b, and git's `xdiff/xdiffi.c`.
+
+use std::collections::HashMap;
+
+use super::{Algorithm, Edit, EditKind, Line};
+
+/// However long the files, searches are allowed at least this many edits before giving up.
+const MIN_COST: isize = 256;
+
+/// Runs of unchanged lines at least this long are good places to split a search.
+const SNAKE_COUNT: isize = 20;
+
+/// Searches that have taken at least this many edits may split at a good run of unchanged lines.
+const HEURISTIC_MIN_COST: isize = 256;
+
+/// How much further along a good run of unchanged lines must be than the number of edits the
+/// search has taken, for it to be worth splitting at.
+const HEURISTIC_FACTOR: isize = 4;
+
+/// Myers' algorithm. Unless `minimal`, searches that take more edits than about the square root
+/// of the files' length give up and split at the point that got furthest, and long runs of
+/// unchanged lines are split at as soon as they are found.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Myers {
+    /// Always find the fewest edits, however long it takes
+    pub minimal: bool,
+}
+
+impl Algorithm for Myers {
+    fn diff<'a>(&self, a: &[Line<'a>], b: &[Line<'a>]) -> Vec<Edit<'a>> {
+        let mut search = Search::new(a, b);
+
+        // The areas left to compare are kept here rather than on the call stack, as there can be
+        // very many of them
+        let whole = Area {
+            a_start: 0,
+            a_end: a.len() as isize,
+            b_start: 0,
+            b_end: b.len() as isize,
+        };
+        let mut areas = vec![(whole, self.minimal)];
+        while let Some((area, minimal)) = areas.pop() {
+            if let Some(split) = search.compare(area, minimal) {
+                let low = Area {
+                    a_end: split.a,
+                    b_end: split.b,
+                    ..area
+                };
+                let high = Area {
+                    a_start: split.a,
+                    b_start: split.b,
+                    ..area
+                };
+                areas.push((high, split.min_high));
+                areas.push((low, split.min_low));
+            }
+        }
+
+        search.edits()
+    }
+}
+
+/// A part of the edit graph: lines `a_start..a_end` of the old file, and `b_start..b_end` of the
+/// new one.
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    a_start: isize,
+    a_end: isize,
+    b_start: isize,
+    b_end: isize,
+}
+
+/// Where an area is split in two, and whether the fewest edits must be found on each side.
+#[derive(Debug, Clone, Copy)]
+struct Split {
+    a: isize,
+    b: isize,
+    min_low: bool,
+    min_high: bool,
+}
+
+struct Search<'a, 'b> {
+    a: &'b [Line<'a>],
+    b: &'b [Line<'a>],
+    /// Each line as a number, which is the same for lines that are the same, as these are
+    /// quicker to compare
+    a_ids: Vec<usize>,
+    b_ids: Vec<usize>,
+    /// The furthest line of `a` reached on each diagonal `x - y`, searching forwards from the
+    /// start of an area and backwards from its end. Diagonal `k` is at `k + offset`
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    offset: isize,
+    max_cost: isize,
+    /// Which lines of each file are deleted or inserted
+    a_changed: Vec<bool>,
+    b_changed: Vec<bool>,
+}
+
+impl<'a, 'b> Search<'a, 'b> {
+    fn new(a: &'b [Line<'a>], b: &'b [Line<'a>]) -> Self {
+        let mut ids = HashMap::new();
+        let mut id = |line: &Line<'a>| {
+            let next = ids.len();
+            *ids.entry(line.line).or_insert(next)
+        };
+        let a_ids = a.iter().map(&mut id).collect();
+        let b_ids = b.iter().map(&mut id).collect();
+
+        // There is a diagonal for every point on the edges, and one more beyond each end
+        let diagonals = a.len() + b.len() + 3;
+        Self {
+            a,
+            b,
+            a_ids,
+            b_ids,
+            forward: vec![0; diagonals],
+            backward: vec![0; diagonals],
+            offset: b.len() as isize + 1,
+            max_cost: (diagonals.isqrt() as isize).max(MIN_COST),
+            a_changed: vec![false; a.len()],
+            b_changed: vec![false; b.len()],
+        }
+    }
+
+    /// Compare the lines in `area`. Lines that are the same at its start and end are kept. Then
+    /// if either side is empty, the other is all changes, and otherwise the area is split in
+    /// two to compare each half.
+    fn compare(&mut self, mut area: Area, minimal: bool) -> Option<Split> {
+        while area.a_start < area.a_end
+            && area.b_start < area.b_end
+            && self.a_ids[area.a_start as usize] == self.b_ids[area.b_start as usize]
+        {
+            area.a_start += 1;
+            area.b_start += 1;
+        }
+        while area.a_start < area.a_end
+            && area.b_start < area.b_end
+            && self.a_ids[area.a_end as usize - 1] == self.b_ids[area.b_end as usize - 1]
+        {
+            area.a_end -= 1;
+            area.b_end -= 1;
+        }
+
+        if area.a_start == area.a_end {
+            self.b_changed[area.b_start as usize..area.b_end as usize].fill(true);
+            None
+        } else if area.b_start == area.b_end {
+            self.a_changed[area.a_start as usize..area.a_end as usize].fill(true);
+            None
+        } else {
+            Some(self.split(area, minimal))
+        }
+    }
+
+    /// Find where the fewest edits through `area` cross the middle, by searching forwards from
+    /// its start and backwards from its end, one more edit at a time, until the searches meet.
+    ///
+    /// Unless `minimal`, a search that finds a long run of unchanged lines well ahead of the
+    /// others splits there, and a search that takes too long splits at the point either way
+    /// got furthest.
+    fn split(&mut self, area: Area, minimal: bool) -> Split {
+        let Area {
+            a_start,
+            a_end,
+            b_start,
+            b_end,
+        } = area;
+        let o = self.offset;
+        let (min_diagonal, max_diagonal) = (a_start - b_end, a_end - b_start);
+        let (forward_mid, backward_mid) = (a_start - b_start, a_end - b_end);
+        // Whether the searches can meet after the forward one has taken a step, or only after
+        // the backward one has
+        let odd = (forward_mid - backward_mid) & 1 != 0;
+        let (mut forward_min, mut forward_max) = (forward_mid, forward_mid);
+        let (mut backward_min, mut backward_max) = (backward_mid, backward_mid);
+
+        self.forward[(forward_mid + o) as usize] = a_start;
+        self.backward[(backward_mid + o) as usize] = a_end;
+
+        for cost in 1.. {
+            let mut got_snake = false;
+
+            // Take in one more diagonal on each side, or one fewer if that would leave the area.
+            // The diagonals just beyond are set so that they are never followed
+            if forward_min > min_diagonal {
+                forward_min -= 1;
+                self.forward[(forward_min - 1 + o) as usize] = -1;
+            } else {
+                forward_min += 1;
+            }
+            if forward_max < max_diagonal {
+                forward_max += 1;
+                self.forward[(forward_max + 1 + o) as usize] = -1;
+            } else {
+                forward_max -= 1;
+            }
+
+            for k in (forward_min..=forward_max).rev().step_by(2) {
+                let i = (k + o) as usize;
+                let mut x = if self.forward[i - 1] >= self.forward[i + 1] {
+                    self.forward[i - 1] + 1
+                } else {
+                    self.forward[i + 1]
+                };
+                let start = x;
+                let mut y = x - k;
+                while x < a_end && y < b_end && self.same(x, y) {
+                    x += 1;
+                    y += 1;
+                }
+                got_snake |= x - start > SNAKE_COUNT;
+                self.forward[i] = x;
+
+                if odd && (backward_min..=backward_max).contains(&k) && self.backward[i] <= x {
+                    return Split {
+                        a: x,
+                        b: y,
+                        min_low: true,
+                        min_high: true,
+                    };
+                }
+            }
+
+            if backward_min > min_diagonal {
+                backward_min -= 1;
+                self.backward[(backward_min - 1 + o) as usize] = isize::MAX;
+            } else {
+                backward_min += 1;
+            }
+            if backward_max < max_diagonal {
+                backward_max += 1;
+                self.backward[(backward_max + 1 + o) as usize] = isize::MAX;
+            } else {
+                backward_max -= 1;
+            }
+
+            for k in (backward_min..=backward_max).rev().step_by(2) {
+                let i = (k + o) as usize;
+                let mut x = if self.backward[i - 1] < self.backward[i + 1] {
+                    self.backward[i - 1]
+                } else {
+                    self.backward[i + 1] - 1
+                };
+                let start = x;
+                let mut y = x - k;
+                while x > a_start && y > b_start && self.same(x - 1, y - 1) {
+                    x -= 1;
+                    y -= 1;
+                }
+                got_snake |= start - x > SNAKE_COUNT;
+                self.backward[i] = x;
+
+                if !odd && (forward_min..=forward_max).contains(&k) && x <= self.forward[i] {
+                    return Split {
+                        a: x,
+                        b: y,
+                        min_low: true,
+                        min_high: true,
+                    };
+                }
+            }
+
+            if minimal {
+                continue;
+            }
+
+            if got_snake && cost > HEURISTIC_MIN_COST {
+                if let Some(split) = self.forward_snake(area, forward_min, forward_max, cost) {
+                    return split;
+                }
+                if let Some(split) = self.backward_snake(area, backward_min, backward_max, cost) {
+                    return split;
+                }
+            }
+
+            if cost >= self.max_cost {
+                return self.furthest(
+                    area,
+                    (forward_min, forward_max),
+                    (backward_min, backward_max),
+                );
+            }
+        }
+        unreachable!()
+    }
+
+    /// The point on the forward search that is furthest along, for how far it is from the middle
+    /// diagonal, if it is well ahead of `cost` and at the end of a long run of unchanged lines.
+    fn forward_snake(&self, area: Area, min: isize, max: isize, cost: isize) -> Option<Split> {
+        let mid = area.a_start - area.b_start;
+        let mut best = 0;
+        let mut split = None;
+        for k in (min..=max).rev().step_by(2) {
+            let x = self.forward[(k + self.offset) as usize];
+            let y = x - k;
+            let progress = (x - area.a_start) + (y - area.b_start) - (k - mid).abs();
+
+            if progress > HEURISTIC_FACTOR * cost
+                && progress > best
+                && (area.a_start + SNAKE_COUNT..area.a_end).contains(&x)
+                && (area.b_start + SNAKE_COUNT..area.b_end).contains(&y)
+                && (1..=SNAKE_COUNT).all(|i| self.same(x - i, y - i))
+            {
+                best = progress;
+                split = Some(Split {
+                    a: x,
+                    b: y,
+                    min_low: true,
+                    min_high: false,
+                });
+            }
+        }
+        split
+    }
+
+    /// Like [`Search::forward_snake`], for the backward search.
+    fn backward_snake(&self, area: Area, min: isize, max: isize, cost: isize) -> Option<Split> {
+        let mid = area.a_end - area.b_end;
+        let mut best = 0;
+        let mut split = None;
+        for k in (min..=max).rev().step_by(2) {
+            let x = self.backward[(k + self.offset) as usize];
+            let y = x - k;
+            let progress = (area.a_end - x) + (area.b_end - y) - (k - mid).abs();
+
+            if progress > HEURISTIC_FACTOR * cost
+                && progress > best
+                && x > area.a_start
+                && x <= area.a_end - SNAKE_COUNT
+                && y > area.b_start
+                && y <= area.b_end - SNAKE_COUNT
+                && (0..SNAKE_COUNT).all(|i| self.same(x + i, y + i))
+            {
+                best = progress;
+                split = Some(Split {
+                    a: x,
+                    b: y,
+                    min_low: false,
+                    min_high: true,
+                });
+            }
+        }
+        split
+    }
+
+    /// The point either search got furthest from where it started, kept within the area.
+    fn furthest(&self, area: Area, forward: (isize, isize), backward: (isize, isize)) -> Split {
+        let (mut forward_best, mut forward_x) = (-1, -1);
+        for k in (forward.0..=forward.1).rev().step_by(2) {
+            let mut x = self.forward[(k + self.offset) as usize].min(area.a_end);
+            let mut y = x - k;
+            if area.b_end < y {
+                (x, y) = (area.b_end + k, area.b_end);
+            }
+            if forward_best < x + y {
+                (forward_best, forward_x) = (x + y, x);
+            }
+        }
+
+        let (mut backward_best, mut backward_x) = (isize::MAX, isize::MAX);
+        for k in (backward.0..=backward.1).rev().step_by(2) {
+            let mut x = self.backward[(k + self.offset) as usize].max(area.a_start);
+            let mut y = x - k;
+            if y < area.b_start {
+                (x, y) = (area.b_start + k, area.b_start);
+            }
+            if x + y < backward_best {
+                (backward_best, backward_x) = (x + y, x);
+            }
+        }
+
+        if (area.a_end + area.b_end) - backward_best < forward_best - (area.a_start + area.b_start)
+        {
+            Split {
+                a: forward_x,
+                b: forward_best - forward_x,
+                min_low: true,
+                min_high: false,
+            }
+        } else {
+            Split {
+                a: backward_x,
+                b: backward_best - backward_x,
+                min_low: false,
+                min_high: true,
+            }
+        }
+    }
+
+    fn same(&self, x: isize, y: isize) -> bool {
+        self.a_ids[x as usize] == self.b_ids[y as usize]
+    }
+
+    /// The edits found, with the deletions in each run of changes before the insertions.
+    fn edits(&self) -> Vec<Edit<'a>> {
+        let mut edits = Vec::with_capacity(self.a.len().max(self.b.len()));
+        let (mut x, mut y) = (0, 0);
+        while x < self.a.len() || y < self.b.len() {
+            if x < self.a.len() && self.a_changed[x] {
+                edits.push(Edit::new(EditKind::Delete, Some(self.a[x]), None));
+                x += 1;
+            } else if y < self.b.len() && self.b_changed[y] {
+                edits.push(Edit::new(EditKind::Insert, None, Some(self.b[y])));
+                y += 1;
+            } else {
+                edits.push(Edit::new(EditKind::Equal, Some(self.a[x]), Some(self.b[y])));
+                x += 1;
+                y += 1;
+            }
+        }
+        edits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rand::prelude::*;
+
+    use self::trace::TraceMyers;
+    use super::*;
+
+    /// The quadratic space version of Myers' algorithm that this replaced, which keeps a copy of
+    /// the search for every edit, and traces back through them to find the edits.
+    mod trace {
+        use std::collections::HashMap;
+
+        use super::super::MIN_COST;
+        use crate::diff::{Algorithm, Edit, EditKind, Line};
+
+        /// Unless `minimal`, a search that needs more edits than about the square root of the
+        /// files' length gives up, keeps the edits that got furthest, and starts again from there.
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct TraceMyers {
+            pub minimal: bool,
+        }
+
+        impl Algorithm for TraceMyers {
+            fn diff<'a>(&self, a: &[Line<'a>], b: &[Line<'a>]) -> Vec<Edit<'a>> {
+                let max_cost = match self.minimal {
+                    true => i64::MAX,
+                    false => (a.len() + b.len() + 3).isqrt().max(MIN_COST as usize) as i64,
+                };
+
+                let mut edits = Vec::new();
+                let (mut x, mut y) = (0, 0);
+                while x < a.len() || y < b.len() {
+                    let search = Search {
+                        a: &a[x..],
+                        b: &b[y..],
+                    };
+                    let (trace, end_x, end_y) = search.shortest_edit(max_cost);
+                    edits.extend(search.edits(&trace, end_x, end_y));
+                    x += end_x as usize;
+                    y += end_y as usize;
+                }
+                edits
+            }
+        }
+
+        struct Search<'a, 'b> {
+            a: &'b [Line<'a>],
+            b: &'b [Line<'a>],
+        }
+
+        impl<'a> Search<'a, '_> {
+            /// The edits leading to `(x, y)` in `trace`.
+            fn edits(&self, trace: &[HashMap<i64, i64>], x: i64, y: i64) -> Vec<Edit<'a>> {
+                let mut diff = Vec::new();
+                self.backtrack(trace, x, y, |prev_x, prev_y, x, y| {
+                    let a_line = self.a.get(prev_x as usize);
+                    let b_line = self.b.get(prev_y as usize);
+
+                    if x == prev_x {
+                        diff.push(Edit::new(EditKind::Insert, None, Some(*b_line.unwrap())));
+                    } else if y == prev_y {
+                        diff.push(Edit::new(EditKind::Delete, Some(*a_line.unwrap()), None));
+                    } else {
+                        diff.push(Edit::new(
+                            EditKind::Equal,
+                            Some(*a_line.unwrap()),
+                            Some(*b_line.unwrap()),
+                        ));
+                    }
+                });
+
+                diff.reverse();
+                diff
+            }
+
+            fn backtrack<F>(
+                &self,
+                trace: &[HashMap<i64, i64>],
+                mut x: i64,
+                mut y: i64,
+                mut callback: F,
+            ) where
+                F: FnMut(i64, i64, i64, i64),
+            {
+                for (d, v) in trace.iter().enumerate().rev() {
+                    let d = d as i64;
+                    let k = x - y;
+                    let prev_k = if (k == -d) || ((k != d) && v[&(k - 1)] < v[&(k + 1)]) {
+                        k + 1
+                    } else {
+                        k - 1
+                    };
+                    let prev_x = v[&prev_k];
+                    let prev_y = prev_x - prev_k;
+                    while x > prev_x && y > prev_y {
+                        callback(x - 1, y - 1, x, y);
+                        x -= 1;
+                        y -= 1;
+                    }
+                    if d.is_positive() {
+                        callback(prev_x, prev_y, x, y);
+                    }
+                    x = prev_x;
+                    y = prev_y;
+                }
+            }
+
+            /// Search for the fewest edits from the start of both files to the end. If that takes
+            /// more than `max_cost` edits, stop at the point after that many edits that is
+            /// furthest from the start. Returns the trace of the search, and where it stopped.
+            fn shortest_edit(&self, max_cost: i64) -> (Vec<HashMap<i64, i64>>, i64, i64) {
+                let n = self.a.len() as i64;
+                let m = self.b.len() as i64;
+                let mut v = HashMap::new();
+                v.insert(1, 0);
+                let mut trace = Vec::new();
+                for d in 0..=n + m {
+                    trace.push(v.clone());
+
+                    for k in (-d..=d).step_by(2) {
+                        let mut x = if (k == -d) || ((k != d) && (v[&(k - 1)] < v[&(k + 1)])) {
+                            v[&(k + 1)]
+                        } else {
+                            v[&(k - 1)] + 1
+                        };
+                        let mut y = x - k;
+
+                        while x < n && y < m && self.a[x as usize] == self.b[y as usize] {
+                            x += 1;
+                            y += 1;
+                        }
+
+                        v.insert(k, x);
+
+                        if x >= n && y >= m {
+                            return (trace, x, y);
+                        }
+                    }
+
+                    if d >= max_cost {
+                        let (x, k) = (-d..=d)
+                            .step_by(2)
+                            .map(|k| (v[&k], k))
+                            .filter(|&(x, k)| x <= n && (0..=m).contains(&(x - k)))
+                            .max_by_key(|&(x, k)| 2 * x - k)
+                            .unwrap();
+                        return (trace, x, x - k);
+                    }
+                }
+                unreachable!();
+            }
+        }
+    }
+
+    fn edit_count(edits: &[Edit]) -> usize {
+        edits.iter().filter(|x| x.kind != EditKind::Equal).count()
+    }
+
+    /// A file like a lockfile, with `len` lines, some of which repeat, and a copy of it with one
+    /// in `every` lines changed, removed, or added to.
+    fn lockfile(len: usize, every: usize, rng: &mut StdRng) -> (Vec<String>, Vec<String>) {
+        let old = (0..len)
+            .map(|i| match i % 4 {
+                0 => format!("[[package]] # {i}"),
+                1 => format!("name = \"crate-{}\"", i / 4),
+                2 => format!("version = \"0.{}.{}\"", i % 7, i % 13),
+                _ => String::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut new = Vec::with_capacity(len);
+        for line in &old {
+            match rng.gen_range(0..every * 3) {
+                0 => new.push(format!("{line} changed")),
+                1 => {}
+                2 => new.extend([line.clone(), "added".to_owned()]),
+                _ => new.push(line.clone()),
+            }
+        }
+        (old, new)
+    }
+
+    #[test]
+    fn finds_fewest_edits() {
+        let mut rng = StdRng::seed_from_u64(25);
+        for len in [0, 1, 2, 10, 100, 500] {
+            let (old, new) = lockfile(len, 5, &mut rng);
+            let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+            let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+            let (a, b) = (Line::number(&old), Line::number(&new));
+
+            let edits = Myers { minimal: true }.diff(&a, &b);
+            let expected = TraceMyers { minimal: true }.diff(&a, &b);
+            assert_eq!(edit_count(&edits), edit_count(&expected), "{len} lines");
+        }
+    }
+
+    #[test]
+    fn diffs_large_files() {
+        let mut rng = StdRng::seed_from_u64(25);
+        let (old, new) = lockfile(20_000, 10, &mut rng);
+        let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+        let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let edits = Myers::default().diff(&Line::number(&old), &Line::number(&new));
+        let a_kept = edits.iter().filter(|x| x.kind != EditKind::Insert);
+        let b_kept = edits.iter().filter(|x| x.kind != EditKind::Delete);
+        assert!(a_kept.map(Edit::a_index).eq((0..old.len()).map(Some)));
+        assert!(b_kept.map(Edit::b_index).eq((0..new.len()).map(Some)));
+        assert!(edits
+            .iter()
+            .filter(|x| x.kind == EditKind::Equal)
+            .all(|x| x.a_line == x.b_line));
+    }
+
+    #[test]
+    #[ignore = "benchmark: run with `cargo test --release bench_myers -- --ignored --nocapture`"]
+    /// Time this implementation against the one it replaced, on lockfiles with many changes.
+    /// The old one only finds the fewest edits for smaller files, as that needs too much memory
+    /// for large ones.
+    fn bench_myers() {
+        fn time(mut f: impl FnMut() -> usize) -> (Duration, usize) {
+            let start = Instant::now();
+            let count = f();
+            (start.elapsed(), count)
+        }
+
+        let mut rng = StdRng::seed_from_u64(25);
+        for len in [1_000, 5_000, 20_000, 100_000] {
+            let (old, new) = lockfile(len, 20, &mut rng);
+            let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+            let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+            let (a, b) = (Line::number(&old), Line::number(&new));
+
+            let (elapsed, count) = time(|| edit_count(&Myers::default().diff(&a, &b)));
+            println!("{len:>7} lines: linear space     {elapsed:>10.2?}, {count} edits");
+            let (elapsed, count) = time(|| edit_count(&Myers { minimal: true }.diff(&a, &b)));
+            println!("{len:>7} lines: linear, minimal  {elapsed:>10.2?}, {count} edits");
+            let (elapsed, count) = time(|| edit_count(&TraceMyers::default().diff(&a, &b)));
+            println!("{len:>7} lines: quadratic space  {elapsed:>10.2?}, {count} edits");
+            if len <= 20_000 {
+                let (elapsed, count) =
+                    time(|| edit_count(&TraceMyers { minimal: true }.diff(&a, &b)));
+                println!("{len:>7} lines: quadratic, minimal {elapsed:>8.2?}, {count} edits");
+            }
+        }
+    }
+}
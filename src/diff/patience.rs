@@ -0,0 +1,115 @@
+//! Patience diff, which matches up the lines that appear exactly once in each file before
+//! anything else. Those are usually the distinctive ones, like function signatures, rather than
+//! braces and blank lines, so moved blocks of code come out as whole blocks.
+//!
+//! See: <https://bramcohen.livejournal.com/73318.html>
+
+use std::collections::HashMap;
+
+use super::{common_prefix, common_suffix, push_equal, push_unmatched};
+use super::{Algorithm, Edit, Line, Myers};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Patience;
+
+impl Algorithm for Patience {
+    fn diff<'a>(&self, a: &[Line<'a>], b: &[Line<'a>]) -> Vec<Edit<'a>> {
+        let mut edits = Vec::new();
+        patience(a, b, &mut edits);
+        edits
+    }
+}
+
+fn patience<'a>(a: &[Line<'a>], b: &[Line<'a>], edits: &mut Vec<Edit<'a>>) {
+    let prefix = common_prefix(a, b);
+    let suffix = common_suffix(&a[prefix..], &b[prefix..]);
+    push_equal(edits, &a[..prefix], &b[..prefix]);
+
+    let (a_rest, b_rest) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    if a_rest.is_empty() || b_rest.is_empty() {
+        push_unmatched(edits, a_rest, b_rest);
+    } else {
+        let anchors = unique_matches(a_rest, b_rest);
+        if anchors.is_empty() {
+            edits.extend(Myers::default().diff(a_rest, b_rest));
+        } else {
+            let (mut x, mut y) = (0, 0);
+            for (i, j) in anchors {
+                patience(&a_rest[x..i], &b_rest[y..j], edits);
+                push_equal(edits, &a_rest[i..=i], &b_rest[j..=j]);
+                (x, y) = (i + 1, j + 1);
+            }
+            patience(&a_rest[x..], &b_rest[y..], edits);
+        }
+    }
+
+    push_equal(edits, &a[a.len() - suffix..], &b[b.len() - suffix..]);
+}
+
+/// The longest run of lines that appear once in each of `a` and `b`, in the same order in both,
+/// as pairs of their indices.
+fn unique_matches(a: &[Line], b: &[Line]) -> Vec<(usize, usize)> {
+    // How often each line appears in each file, and where it last appeared
+    let mut counts = HashMap::<&str, (usize, usize, usize, usize)>::new();
+    for (i, line) in a.iter().enumerate() {
+        let count = counts.entry(line.line).or_default();
+        count.0 += 1;
+        count.1 = i;
+    }
+    for (j, line) in b.iter().enumerate() {
+        if let Some(count) = counts.get_mut(line.line) {
+            count.2 += 1;
+            count.3 = j;
+        }
+    }
+
+    let mut unique = counts
+        .into_values()
+        .filter(|&(a_count, _, b_count, _)| a_count == 1 && b_count == 1)
+        .map(|(_, i, _, j)| (i, j))
+        .collect::<Vec<_>>();
+    unique.sort_unstable();
+
+    longest_increasing(&unique)
+}
+
+/// The longest subsequence of `pairs`, which are in order of their first elements, that is also
+/// in order of their second elements. Found by patience sorting, where each pile's top is the
+/// smallest end of a subsequence of its length, and each pair remembers the top of the pile
+/// before it when it was placed.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut tops = Vec::<usize>::new();
+    let mut previous = vec![None; pairs.len()];
+
+    for (index, &(_, j)) in pairs.iter().enumerate() {
+        let pile = tops.partition_point(|&top| pairs[top].1 < j);
+        if pile > 0 {
+            previous[index] = Some(tops[pile - 1]);
+        }
+        match tops.get_mut(pile) {
+            Some(top) => *top = index,
+            None => tops.push(index),
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(tops.len());
+    let mut next = tops.last().copied();
+    while let Some(index) = next {
+        sequence.push(pairs[index]);
+        next = previous[index];
+    }
+    sequence.reverse();
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_increasing() {
+        let pairs = [(0, 3), (1, 0), (2, 4), (3, 1), (4, 2), (5, 5)];
+        assert_eq!(longest_increasing(&pairs), [(1, 0), (3, 1), (4, 2), (5, 5)]);
+        assert_eq!(longest_increasing(&[]), []);
+    }
+}
@@ -0,0 +1,347 @@
+//! Finding files that were renamed or copied, by pairing files that went away (or, for copies,
+//! that changed) with files that appeared and have similar contents.
+//!
+//! Similarity is scored as in git: each file is cut into lines, or 64 byte pieces of long lines,
+//! and a score is the number of bytes of the new file found in the old one, as a fraction of the
+//! larger file's size.
+//!
+//! See: <https://git-scm.com/docs/git-diff#Documentation/git-diff.txt--Mltngt>
+
+use std::collections::HashMap;
+
+use camino::Utf8Path;
+use color_eyre::eyre::eyre;
+
+use crate::digest::Digest;
+use crate::Result;
+
+use super::binary::is_binary;
+
+/// The score of identical files. Scores are fractions of this.
+pub const MAX_SCORE: u32 = 60000;
+
+/// How similar files must be to be paired up by default: half the same.
+pub const DEFAULT_SCORE: u32 = MAX_SCORE / 2;
+
+/// Pieces of a file are hashed into this many buckets, so that they can be counted.
+const HASHBASE: u32 = 107927;
+
+/// The longest piece of a line that is hashed on its own.
+const MAX_SPAN: usize = 64;
+
+/// How renames and copies are looked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    /// How similar a file must be to the one it came from, out of [`MAX_SCORE`]
+    pub min_score: u32,
+    /// Look for copies of files that changed, as well as renames of files that went away
+    pub copies: bool,
+    /// Inexact renames are only looked for if there are at most this many sources and
+    /// destinations, or rather, if comparing each with each takes at most this many squared
+    pub limit: usize,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        Self {
+            min_score: DEFAULT_SCORE,
+            copies: false,
+            limit: 1000,
+        }
+    }
+}
+
+/// Parse a similarity as given to `-M` and `-C`: a percentage like `75%`, or otherwise the
+/// digits after a decimal point, so that `75` and `7` both mean 70% and more.
+pub fn parse_score(score: &str) -> Result<u32> {
+    let invalid = || eyre!("invalid similarity '{score}'");
+
+    let (digits, percent) = match score.strip_suffix('%') {
+        Some(digits) => (digits, true),
+        None => (score, false),
+    };
+    if digits.is_empty() || !digits.bytes().all(|x| x.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let score = if percent {
+        let percent = digits.parse::<u64>().map_err(|_| invalid())?;
+        percent * MAX_SCORE as u64 / 100
+    } else {
+        // Too many digits to matter can be ignored
+        let digits = &digits[..digits.len().min(9)];
+        let scale = 10u64.pow(digits.len() as u32);
+        digits.parse::<u64>().map_err(|_| invalid())? * MAX_SCORE as u64 / scale
+    };
+    Ok(score.min(MAX_SCORE as u64) as u32)
+}
+
+/// Turn a score into the percentage shown in `similarity index` lines.
+pub fn similarity_index(score: u32) -> u32 {
+    score * 100 / MAX_SCORE
+}
+
+/// A file that may have been renamed, or may be a rename.
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub path: &'a Utf8Path,
+    pub oid: &'a Digest,
+    pub data: &'a [u8],
+}
+
+/// A destination found to come from a source: their indices in the slices given to
+/// [`find_renames`], and how similar they are, out of [`MAX_SCORE`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rename {
+    pub source: usize,
+    pub destination: usize,
+    pub score: u32,
+}
+
+/// Find which of `destinations` came from which of `sources`.
+///
+/// Files with identical contents are paired first, preferring a source with the same file name.
+/// Then the remaining destinations are paired with the most similar source at least as similar
+/// as `options.min_score`. Unless looking for copies, each source is used at most once.
+pub fn find_renames(
+    sources: &[Candidate],
+    destinations: &[Candidate],
+    options: &RenameOptions,
+) -> Vec<Rename> {
+    let mut renames = Vec::new();
+    let mut found = vec![false; destinations.len()];
+    let mut used = vec![false; sources.len()];
+
+    for (d, destination) in destinations.iter().enumerate() {
+        let best = sources
+            .iter()
+            .enumerate()
+            .filter(|(s, source)| source.oid == destination.oid && (options.copies || !used[*s]))
+            .max_by_key(|(s, source)| {
+                // The first of the best, rather than the last
+                let preference = usize::from(!used[*s])
+                    + usize::from(source.path.file_name() == destination.path.file_name());
+                (preference, std::cmp::Reverse(*s))
+            });
+
+        if let Some((s, _)) = best {
+            found[d] = true;
+            used[s] = true;
+            renames.push(Rename {
+                source: s,
+                destination: d,
+                score: MAX_SCORE,
+            });
+        }
+    }
+
+    let remaining = found.iter().filter(|x| !**x).count();
+    if remaining == 0 || sources.len() * remaining > options.limit.saturating_mul(options.limit) {
+        return renames;
+    }
+
+    let sizes = sources.iter().map(|x| x.data.len()).collect::<Vec<_>>();
+    let spans = sources
+        .iter()
+        .map(|source| Spans::new(source.data))
+        .collect::<Vec<_>>();
+
+    let mut scores = Vec::new();
+    for (d, destination) in destinations.iter().enumerate() {
+        if found[d] {
+            continue;
+        }
+        let destination_spans = Spans::new(destination.data);
+        for (s, source_spans) in spans.iter().enumerate() {
+            let score = similarity(
+                sizes[s],
+                source_spans,
+                destination.data.len(),
+                &destination_spans,
+                options.min_score,
+            );
+            if score >= options.min_score {
+                scores.push(Rename {
+                    source: s,
+                    destination: d,
+                    score,
+                });
+            }
+        }
+    }
+
+    // The best matches are taken first, and otherwise the first destinations and sources
+    scores.sort_by_key(|x| (std::cmp::Reverse(x.score), x.destination, x.source));
+
+    for candidate in scores {
+        if found[candidate.destination] || (!options.copies && used[candidate.source]) {
+            continue;
+        }
+        found[candidate.destination] = true;
+        used[candidate.source] = true;
+        renames.push(candidate);
+    }
+
+    renames.sort_by_key(|x| x.destination);
+    renames
+}
+
+/// How many bytes of a file are in pieces with each hash.
+struct Spans {
+    counts: HashMap<u32, u64>,
+}
+
+impl Spans {
+    /// Cut `data` into lines, or pieces of at most [`MAX_SPAN`] bytes, and count them by hash.
+    /// In text, the `\r` of a `\r\n` is ignored.
+    fn new(data: &[u8]) -> Self {
+        let text = !is_binary(data);
+        let mut counts = HashMap::new();
+
+        let mut add = |accum1: u32, accum2: u32, len: usize| {
+            let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % HASHBASE;
+            *counts.entry(hash).or_default() += len as u64;
+        };
+
+        let (mut accum1, mut accum2, mut len) = (0u32, 0u32, 0);
+        for (i, &c) in data.iter().enumerate() {
+            if text && c == b'\r' && data.get(i + 1) == Some(&b'\n') {
+                continue;
+            }
+
+            let old = accum1;
+            accum1 = (accum1 << 7) ^ (accum2 >> 25);
+            accum2 = (accum2 << 7) ^ (old >> 25);
+            accum1 = accum1.wrapping_add(c as u32);
+            len += 1;
+
+            if len == MAX_SPAN || c == b'\n' {
+                add(accum1, accum2, len);
+                (accum1, accum2, len) = (0, 0, 0);
+            }
+        }
+        if len > 0 {
+            add(accum1, accum2, len);
+        }
+
+        Self { counts }
+    }
+
+    /// How many bytes of `other` are also in these pieces.
+    fn copied_into(&self, other: &Self) -> u64 {
+        other
+            .counts
+            .iter()
+            .map(|(hash, &count)| count.min(self.counts.get(hash).copied().unwrap_or(0)))
+            .sum()
+    }
+}
+
+/// Score how much of the destination came from the source, out of [`MAX_SCORE`]. Files whose
+/// sizes are too different to reach `min_score` aren't compared, and score 0.
+fn similarity(
+    source_size: usize,
+    source: &Spans,
+    destination_size: usize,
+    destination: &Spans,
+    min_score: u32,
+) -> u32 {
+    let max_size = source_size.max(destination_size) as u64;
+    let delta_size = max_size - source_size.min(destination_size) as u64;
+    if max_size == 0 || max_size * ((MAX_SCORE - min_score) as u64) < delta_size * MAX_SCORE as u64
+    {
+        return 0;
+    }
+
+    (source.copied_into(destination) * MAX_SCORE as u64 / max_size) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_score() {
+        assert_eq!(parse_score("50%").unwrap(), MAX_SCORE / 2);
+        assert_eq!(parse_score("5").unwrap(), MAX_SCORE / 2);
+        assert_eq!(parse_score("75").unwrap(), MAX_SCORE / 4 * 3);
+        assert_eq!(parse_score("100%").unwrap(), MAX_SCORE);
+        assert_eq!(parse_score("07").unwrap(), MAX_SCORE / 100 * 7);
+        assert!(parse_score("").is_err());
+        assert!(parse_score("x%").is_err());
+        assert_eq!(similarity_index(59999), 99);
+    }
+
+    #[test]
+    fn test_find_renames() {
+        let lines = |range: std::ops::Range<u32>| {
+            range
+                .map(|i| format!("line {i}\n"))
+                .collect::<String>()
+                .into_bytes()
+        };
+        let oids = [1, 2, 3, 4, 5].map(|x| Digest([x; 20]));
+        let (a, b, c) = (lines(0..20), lines(1..20), lines(0..3));
+
+        let candidate = |path, oid, data| Candidate {
+            path: Utf8Path::new(path),
+            oid,
+            data,
+        };
+        let sources = [
+            candidate("a", &oids[0], &a),
+            candidate("dir/same", &oids[1], &c),
+            candidate("other", &oids[1], &c),
+        ];
+        let destinations = [
+            candidate("b", &oids[2], &b),
+            candidate("same", &oids[1], &c),
+            candidate("unrelated", &oids[3], b"something else\n"),
+            candidate("copy", &oids[1], &c),
+        ];
+
+        let options = RenameOptions::default();
+        let renames = find_renames(&sources, &destinations, &options);
+        // 19 of 20 lines, with the size of the larger file
+        let score = MAX_SCORE * (b.len() as u32) / (a.len() as u32);
+        assert_eq!(
+            renames,
+            [
+                Rename {
+                    source: 0,
+                    destination: 0,
+                    score
+                },
+                Rename {
+                    source: 1,
+                    destination: 1,
+                    score: MAX_SCORE
+                },
+                Rename {
+                    source: 2,
+                    destination: 3,
+                    score: MAX_SCORE
+                },
+            ]
+        );
+
+        // Copies can reuse sources, but the sources that haven't been used are preferred
+        let options = RenameOptions {
+            copies: true,
+            ..options
+        };
+        let renames = find_renames(&sources[..2], &destinations, &options);
+        assert_eq!(
+            renames.iter().map(|x| x.source).collect::<Vec<_>>(),
+            [0, 1, 1]
+        );
+
+        let options = RenameOptions {
+            min_score: parse_score("99%").unwrap(),
+            ..options
+        };
+        assert_eq!(find_renames(&sources, &destinations[..1], &options), []);
+    }
+}
@@ -8,10 +8,16 @@ use hex::FromHexError;
 use sha1::{Digest as _, Sha1};
 use tap::Tap;
 
+/// The shortest abbreviated oid that is recognised as one, rather than as a ref name.
+pub const MINIMUM_ABBREV: usize = 4;
+
+/// The length oids are abbreviated to for display, before being lengthened to be unique.
+pub const DEFAULT_ABBREV: usize = 7;
+
 /// A SHA1 digest.
 ///
 /// Use as object IDs or as the hash of a file.
-#[derive(Clone, Default, PartialEq, Eq)]
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub struct Digest(pub [u8; 20]);
 
@@ -47,9 +53,10 @@ impl Digest {
 
     /// Shorten a Digest, usually for display purposes.
     ///
-    /// Note: This doesn't check for collisions.
+    /// Note: This doesn't check for collisions. Use [`Repo::abbreviate`](crate::repo::Repo::abbreviate)
+    /// for an abbreviation that is unique in a repository.
     pub fn short(&self) -> String {
-        self.to_hex().tap_mut(|x| x.truncate(7))
+        self.to_hex().tap_mut(|x| x.truncate(DEFAULT_ABBREV))
     }
 }
 
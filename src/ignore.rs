@@ -0,0 +1,424 @@
+//! An implementation of gitignore pattern matching.
+//!
+//! See: <https://git-scm.com/docs/gitignore>
+
+use std::collections::HashMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::trace;
+
+/// A single line of an ignore file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    /// The glob, with any leading `!`, leading `/` and trailing `/` removed
+    glob: Vec<char>,
+    /// The pattern started with `!`, so re-includes paths excluded by an earlier pattern
+    negated: bool,
+    /// The pattern ended with `/`, so only matches directories
+    dir_only: bool,
+    /// The pattern contained a `/` other than at the end, so is matched against the whole path
+    /// relative to `base` rather than just the file name
+    anchored: bool,
+    /// The directory containing the file this pattern came from, relative to the root of the
+    /// workspace
+    base: Utf8PathBuf,
+}
+
+impl Pattern {
+    /// Parse a line of an ignore file found in the directory `base`. Returns `None` for blank
+    /// lines and comments.
+    pub fn parse(line: &str, base: &Utf8Path) -> Option<Self> {
+        let line = trim_trailing_spaces(line);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            glob: line.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+            base: base.to_owned(),
+        })
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Check whether this pattern matches `path`, which is relative to the root of the workspace.
+    pub fn matches(&self, path: &Utf8Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let path = match path.strip_prefix(&self.base) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+
+        let text = if self.anchored {
+            path.as_str()
+        } else {
+            match path.file_name() {
+                Some(name) => name,
+                None => return false,
+            }
+        };
+
+        wildmatch(&self.glob, &text.chars().collect::<Vec<_>>())
+    }
+}
+
+/// Remove trailing spaces, unless they are escaped with a backslash.
+fn trim_trailing_spaces(line: &str) -> &str {
+    let mut end = line.len();
+    while line[..end].ends_with(' ') {
+        let before = &line[..end - 1];
+        let backslashes = before.len() - before.trim_end_matches('\\').len();
+        if backslashes % 2 == 1 {
+            break;
+        }
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Match `text` against the glob `pattern`, in the style of git's `wildmatch` with
+/// `WM_PATHNAME`: `*`, `?` and bracket expressions never match `/`, but `**` as a whole path
+/// component matches any number of directories.
+pub fn wildmatch(pattern: &[char], text: &[char]) -> bool {
+    wildmatch_from(pattern, 0, text, 0, true)
+}
+
+/// Match `text` against the glob `pattern` without `WM_PATHNAME`, as pathspecs are: wildcards
+/// match `/` like any other character.
+pub fn fnmatch(pattern: &[char], text: &[char]) -> bool {
+    wildmatch_from(pattern, 0, text, 0, false)
+}
+
+fn wildmatch_from(p: &[char], mut pi: usize, t: &[char], mut ti: usize, pathname: bool) -> bool {
+    while pi < p.len() {
+        match p[pi] {
+            '*' => {
+                let double = pathname
+                    && p.get(pi + 1) == Some(&'*')
+                    && (pi == 0 || p[pi - 1] == '/')
+                    && (pi + 2 == p.len() || p[pi + 2] == '/');
+
+                if double {
+                    if pi + 2 == p.len() {
+                        return true;
+                    }
+                    // `**/` matches zero or more whole directories
+                    let rest = pi + 3;
+                    return (ti..=t.len())
+                        .filter(|&k| k == ti || t[k - 1] == '/')
+                        .any(|k| wildmatch_from(p, rest, t, k, pathname));
+                }
+
+                // Collapse runs of `*` that are not a valid `**`
+                while p.get(pi) == Some(&'*') {
+                    pi += 1;
+                }
+                let mut k = ti;
+                loop {
+                    if wildmatch_from(p, pi, t, k, pathname) {
+                        return true;
+                    }
+                    if k == t.len() || (pathname && t[k] == '/') {
+                        return false;
+                    }
+                    k += 1;
+                }
+            }
+            '?' => {
+                if ti == t.len() || (pathname && t[ti] == '/') {
+                    return false;
+                }
+                pi += 1;
+                ti += 1;
+            }
+            '[' => match match_class(p, pi, t.get(ti).copied(), pathname) {
+                Some((true, next)) => {
+                    pi = next;
+                    ti += 1;
+                }
+                Some((false, _)) => return false,
+                // An unterminated bracket is matched literally
+                None => {
+                    if t.get(ti) != Some(&'[') {
+                        return false;
+                    }
+                    pi += 1;
+                    ti += 1;
+                }
+            },
+            '\\' if pi + 1 < p.len() => {
+                if t.get(ti) != Some(&p[pi + 1]) {
+                    return false;
+                }
+                pi += 2;
+                ti += 1;
+            }
+            c => {
+                if t.get(ti) != Some(&c) {
+                    return false;
+                }
+                pi += 1;
+                ti += 1;
+            }
+        }
+    }
+
+    ti == t.len()
+}
+
+/// Match `c` against the bracket expression starting at `p[start]`. Returns whether it matched,
+/// and the index just past the closing `]`, or `None` if the bracket is unterminated.
+fn match_class(p: &[char], start: usize, c: Option<char>, pathname: bool) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(p.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let lo = match p.get(i) {
+            None => return None,
+            Some(']') if !first => break,
+            Some('\\') => {
+                i += 1;
+                *p.get(i)?
+            }
+            Some(&lo) => lo,
+        };
+        first = false;
+        i += 1;
+
+        let hi = if p.get(i) == Some(&'-') && !matches!(p.get(i + 1), None | Some(']')) {
+            i += 1;
+            let hi = match p[i] {
+                '\\' => {
+                    i += 1;
+                    *p.get(i)?
+                }
+                hi => hi,
+            };
+            i += 1;
+            hi
+        } else {
+            lo
+        };
+
+        if let Some(c) = c {
+            if lo <= c && c <= hi {
+                matched = true;
+            }
+        }
+    }
+
+    match c {
+        Some(c) if !pathname || c != '/' => Some((matched != negated, i + 1)),
+        _ => Some((false, i + 1)),
+    }
+}
+
+/// An ordered list of patterns from a single source. Later patterns take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct PatternList {
+    patterns: Vec<Pattern>,
+}
+
+impl PatternList {
+    pub fn parse(contents: &str, base: &Utf8Path) -> Self {
+        Self {
+            patterns: contents
+                .lines()
+                .filter_map(|line| Pattern::parse(line, base))
+                .collect(),
+        }
+    }
+
+    /// Read the ignore file at `path`, which applies to paths under `base`. A missing file is
+    /// treated as empty.
+    pub fn from_file(path: &Utf8Path, base: &Utf8Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents, base),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Returns `Some(true)` if `path` is ignored by this list, `Some(false)` if it is explicitly
+    /// re-included, or `None` if no pattern matches.
+    pub fn decide(&self, path: &Utf8Path, is_dir: bool) -> Option<bool> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(path, is_dir))
+            .map(|pattern| !pattern.is_negated())
+    }
+}
+
+/// All of the ignore rules that apply to a workspace.
+///
+/// Sources are consulted in git's order of precedence: `.gitignore` files from the deepest
+/// directory up to the root of the workspace, then `.git/info/exclude`, then `core.excludesFile`.
+/// The first source with a matching pattern decides.
+pub struct Ignores {
+    workspace: Utf8PathBuf,
+    per_directory: HashMap<Utf8PathBuf, PatternList>,
+    exclude: PatternList,
+    global: PatternList,
+}
+
+impl Ignores {
+    pub fn new(workspace: &Utf8Path, exclude: PatternList, global: PatternList) -> Self {
+        Self {
+            workspace: workspace.to_owned(),
+            per_directory: HashMap::new(),
+            exclude,
+            global,
+        }
+    }
+
+    /// Check whether `path`, relative to the root of the workspace, is ignored. A path is also
+    /// ignored if any of its parent directories are, as git never looks inside them.
+    pub fn is_ignored(&mut self, path: &Utf8Path, is_dir: bool) -> bool {
+        let mut ancestors = path.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+
+        // The first ancestor is "", which is the root of the workspace itself
+        for (i, ancestor) in ancestors.iter().enumerate().skip(1) {
+            let is_last = i == ancestors.len() - 1;
+            if self.is_ignored_here(ancestor, if is_last { is_dir } else { true }) {
+                trace!(%path, %ancestor, "Path is ignored");
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Check `path` against the rules, without considering whether its parents are ignored.
+    fn is_ignored_here(&mut self, path: &Utf8Path, is_dir: bool) -> bool {
+        if path.file_name() == Some(".git") {
+            return true;
+        }
+
+        for dir in path.ancestors().skip(1) {
+            if let Some(ignored) = self.directory_list(dir).decide(path, is_dir) {
+                return ignored;
+            }
+        }
+
+        self.exclude
+            .decide(path, is_dir)
+            .or_else(|| self.global.decide(path, is_dir))
+            .unwrap_or(false)
+    }
+
+    /// The patterns from the `.gitignore` in `dir`, loading them if needed.
+    fn directory_list(&mut self, dir: &Utf8Path) -> &PatternList {
+        let workspace = &self.workspace;
+        self.per_directory
+            .entry(dir.to_owned())
+            .or_insert_with(|| PatternList::from_file(&workspace.join(dir).join(".gitignore"), dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, text: &str) -> bool {
+        wildmatch(
+            &pattern.chars().collect::<Vec<_>>(),
+            &text.chars().collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_wildmatch() {
+        assert!(glob("foo", "foo"));
+        assert!(!glob("foo", "foobar"));
+        assert!(glob("*.o", "main.o"));
+        assert!(!glob("*.o", "src/main.o"));
+        assert!(glob("?.txt", "a.txt"));
+        assert!(!glob("?", "/"));
+        assert!(glob("[a-c]x", "bx"));
+        assert!(!glob("[!a-c]x", "bx"));
+        assert!(glob("[]]", "]"));
+        assert!(glob("\\*", "*"));
+        assert!(!glob("\\*", "a"));
+        assert!(glob("**/foo", "foo"));
+        assert!(glob("**/foo", "a/b/foo"));
+        assert!(glob("a/**", "a/b/c"));
+        assert!(!glob("a/**", "a"));
+        assert!(glob("a/**/b", "a/b"));
+        assert!(glob("a/**/b", "a/x/y/b"));
+        assert!(!glob("a/**/b", "a/xb"));
+        assert!(glob("a**b", "axxb"));
+        assert!(!glob("a**b", "a/b"));
+
+        let fnmatch = |pattern: &str, text: &str| {
+            fnmatch(
+                &pattern.chars().collect::<Vec<_>>(),
+                &text.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert!(fnmatch("*.o", "src/main.o"));
+        assert!(fnmatch("src/?/x", "src///x"));
+        assert!(fnmatch("a[/]b", "a/b"));
+        assert!(fnmatch("a/**/b", "a/x/y/b"));
+        assert!(!fnmatch("a/**/b", "a/b"));
+    }
+
+    #[test]
+    fn test_pattern_semantics() {
+        let root = Utf8Path::new("");
+        let list = PatternList::parse(
+            "# comment\n\n*.log\n!important.log\nbuild/\n/top\ndoc/*.txt\ntrailing\\ \n",
+            root,
+        );
+
+        let check = |path: &str, is_dir: bool| list.decide(Utf8Path::new(path), is_dir);
+
+        assert_eq!(check("a.log", false), Some(true));
+        assert_eq!(check("deep/dir/a.log", false), Some(true));
+        assert_eq!(check("important.log", false), Some(false));
+        assert_eq!(check("build", true), Some(true));
+        assert_eq!(check("build", false), None);
+        assert_eq!(check("top", false), Some(true));
+        assert_eq!(check("sub/top", false), None);
+        assert_eq!(check("doc/a.txt", false), Some(true));
+        assert_eq!(check("doc/sub/a.txt", false), None);
+        assert_eq!(check("trailing ", false), Some(true));
+        assert_eq!(check("a", false), None);
+
+        let nested = PatternList::parse("/only-here\n", Utf8Path::new("sub"));
+        assert_eq!(
+            nested.decide(Utf8Path::new("sub/only-here"), false),
+            Some(true)
+        );
+        assert_eq!(nested.decide(Utf8Path::new("only-here"), false), None);
+    }
+}
@@ -79,16 +79,24 @@ impl IndexEntry {
         v.pop();
         v
     }
+
+    /// The merge stage of this entry. Stage 0 is a normal, merged entry; stages 1, 2 and 3 hold
+    /// the base, "ours" and "theirs" versions of a conflicted path.
+    pub fn stage(&self) -> u16 {
+        (self.flags >> 12) & 0b11
+    }
 }
 
 impl Ord for IndexEntry {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.name.cmp(&other.name)
+        self.name
+            .cmp(&other.name)
+            .then_with(|| self.stage().cmp(&other.stage()))
     }
 }
 impl PartialOrd for IndexEntry {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.name.partial_cmp(&other.name)
+        Some(self.cmp(other))
     }
 }
 
@@ -123,13 +131,44 @@ impl IndexEntry {
         })
     }
 
+    /// Create an entry for a conflicted path at the given merge stage. Such entries don't
+    /// correspond to a file in the workspace, so carry no stat information.
+    pub fn new_conflict(path: &Utf8Path, oid: &Digest, mode: FileMode, stage: u16) -> Self {
+        debug_assert!((1..=3).contains(&stage));
+        let len = path
+            .as_os_str()
+            .len()
+            .try_into()
+            .unwrap_or(Self::MAX_PATH_SIZE)
+            .min(Self::MAX_PATH_SIZE);
+
+        Self {
+            ctime_s: 0,
+            ctime_n: 0,
+            mtime_s: 0,
+            mtime_n: 0,
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            siz: 0,
+            oid: oid.clone(),
+            flags: len | (stage << 12),
+            name: path.as_str().to_owned(),
+        }
+    }
+
     pub fn oid(&self) -> &Digest {
         &self.oid
     }
 
-    pub fn stat_matches(&self, stat: &libc::stat) -> bool {
+    /// Check whether the size and mode in `stat` match this entry. The mode is only compared if
+    /// `trust_mode` is set, see `core.filemode`.
+    pub fn stat_matches(&self, stat: &libc::stat, trust_mode: bool) -> bool {
         // Fine to cast unconditionally, u32 will always fit in i64
-        (self.siz == 0 || self.siz as i64 == stat.st_size) && (FileMode::from(stat) == self.mode)
+        (self.siz == 0 || self.siz as i64 == stat.st_size)
+            && (!trust_mode || FileMode::from(stat) == self.mode)
     }
 
     pub fn times_match(&self, stat: &libc::stat) -> bool {
@@ -194,6 +233,7 @@ impl IndexWrapper {
         let entry = IndexEntry::new(path, oid, stat).unwrap();
 
         self.discard_conflicts(&entry);
+        self.entries.retain(|e| e.path() != entry.path());
         self.entries.push(entry);
 
         self.entries.sort_unstable();
@@ -243,6 +283,51 @@ impl IndexWrapper {
     pub fn get_entry_by_path(&self, path: &Utf8Path) -> Option<&IndexEntry> {
         self.entries.iter().find(|e| e.path() == path)
     }
+
+    /// Remove every entry for `path`, including any conflict stages.
+    pub fn remove(&mut self, path: &Utf8Path) {
+        trace!(?path, "Removing entry from index");
+        self.entries.retain(|e| e.path() != path);
+    }
+
+    /// Record a conflict at `path`, replacing any existing entries for it. Each of `base`, `ours`
+    /// and `theirs` is stored at stage 1, 2 and 3 respectively, if present.
+    pub fn add_conflict(
+        &mut self,
+        path: &Utf8Path,
+        base: Option<(&Digest, FileMode)>,
+        ours: Option<(&Digest, FileMode)>,
+        theirs: Option<(&Digest, FileMode)>,
+    ) {
+        trace!(?path, "Adding conflict to index");
+        self.remove(path);
+
+        for (stage, version) in [(1, base), (2, ours), (3, theirs)] {
+            if let Some((oid, mode)) = version {
+                self.entries
+                    .push(IndexEntry::new_conflict(path, oid, mode, stage));
+            }
+        }
+
+        self.entries.sort_unstable();
+    }
+
+    /// Returns `true` if any path in the index is conflicted.
+    pub fn has_conflicts(&self) -> bool {
+        self.entries.iter().any(|e| e.stage() != 0)
+    }
+
+    /// Returns the paths of all conflicted entries, without duplicates.
+    pub fn conflicted_paths(&self) -> Vec<&Utf8Path> {
+        let mut paths = self
+            .entries
+            .iter()
+            .filter(|e| e.stage() != 0)
+            .map(|e| e.path())
+            .collect::<Vec<_>>();
+        paths.dedup();
+        paths
+    }
 }
 
 #[cfg(test)]
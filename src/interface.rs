@@ -1,9 +1,13 @@
 use camino::Utf8PathBuf;
 use clap::ArgAction;
+use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
 
+use crate::commit::cleanup::Cleanup;
+use crate::diff::DiffAlgorithm;
 use crate::digest::Digest;
+use crate::timestamp::DateFormat;
 
 #[derive(Clone, Debug, Subcommand)]
 pub enum Command {
@@ -15,8 +19,64 @@ pub enum Command {
 
     /// Record changes to the repository
     Commit {
-        #[clap(short, long, env = "RIT_COMMIT_MESSAGE")]
+        /// Use the given message, rather than opening an editor
+        #[clap(short, long, env = "RIT_COMMIT_MESSAGE", conflicts_with = "file")]
         message: Option<String>,
+
+        /// Take the message from the given file, or standard input for `-`
+        #[clap(short = 'F', long)]
+        file: Option<Utf8PathBuf>,
+
+        /// Edit the message given with `-m` or `-F` before committing
+        #[clap(short, long)]
+        edit: bool,
+
+        /// How to clean up the message. Defaults to `commit.cleanup`, or `default`
+        #[clap(long, value_enum)]
+        cleanup: Option<Cleanup>,
+
+        /// Allow committing with an empty message
+        #[clap(long)]
+        allow_empty_message: bool,
+
+        /// Skip the `pre-commit` and `commit-msg` hooks
+        #[clap(short, long)]
+        no_verify: bool,
+
+        /// Replace the tip of the current branch, keeping its parents, author and message
+        #[clap(long)]
+        amend: bool,
+
+        /// Use the selected message without opening an editor
+        #[clap(long, conflicts_with = "edit")]
+        no_edit: bool,
+
+        /// Allow recording a commit that changes nothing
+        #[clap(long)]
+        allow_empty: bool,
+
+        /// Override the author, as `Name <email>`, or a pattern matching an existing author
+        #[clap(long)]
+        author: Option<String>,
+
+        /// Override the author date
+        #[clap(long)]
+        date: Option<String>,
+
+        /// Sign the commit, with the given key or `user.signingKey`
+        #[clap(
+            short = 'S',
+            long,
+            value_name = "KEYID",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        gpg_sign: Option<String>,
+
+        /// Don't sign the commit, even if `commit.gpgSign` is set
+        #[clap(long, conflicts_with = "gpg_sign")]
+        no_gpg_sign: bool,
     },
 
     /// Add file contents to the index
@@ -42,8 +102,59 @@ pub enum Command {
 
     /// Show changes between commits, commit and working tree, etc
     Diff {
+        /// Compare the index with HEAD, or with the given revision, rather than the workspace
+        /// with the index
         #[clap(long)]
         cached: bool,
+
+        /// Output a binary patch that `git apply` can use, rather than just saying binary files
+        /// differ
+        #[clap(long)]
+        binary: bool,
+
+        /// Show files renamed from others that are at least this similar, as a percentage like
+        /// `-M=75%`, or the digits after a decimal point. Renames are found by default, at 50%
+        #[clap(
+            short = 'M',
+            long,
+            value_name = "n",
+            num_args(0..=1),
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        find_renames: Option<String>,
+
+        /// Show files copied from files that changed, as well as renames, at least this similar
+        #[clap(
+            short = 'C',
+            long,
+            value_name = "n",
+            num_args(0..=1),
+            require_equals = true,
+            default_missing_value = ""
+        )]
+        find_copies: Option<String>,
+
+        /// Don't look for renames, even if `diff.renames` says to
+        #[clap(long, conflicts_with_all = ["find_renames", "find_copies"])]
+        no_renames: bool,
+
+        /// Which algorithm to find the differences with. Defaults to `diff.algorithm`, or `myers`
+        #[clap(long, value_enum)]
+        diff_algorithm: Option<DiffAlgorithm>,
+
+        /// Take as long as needed to find the smallest diff, as `--diff-algorithm=minimal`
+        #[clap(long)]
+        minimal: bool,
+
+        /// Compare the workspace (or with `--cached`, the index) with this revision, or with two,
+        /// compare the first with the second. Arguments after the revisions are paths, which
+        /// must exist unless they come after `--`
+        args: Vec<String>,
+
+        /// Only show changes to these paths
+        #[clap(last = true)]
+        paths: Vec<String>,
     },
 
     /// Equivalent to `jit/show_head.rb`
@@ -51,15 +162,356 @@ pub enum Command {
 
     /// List, create, or delete branches
     Branch {
-        #[clap(num_args(1..))]
-        patterns: Vec<String>,
+        /// The name of the branch to create and its start point (defaulting to HEAD), the
+        /// branches to delete with `-d`, the old and new names with `-m`, or patterns to match
+        /// with `-l`
+        #[clap(num_args(0..))]
+        args: Vec<String>,
+
+        /// Delete branches, which must be fully merged into HEAD unless `--force` is given
         #[clap(short = 'd', long)]
         delete: bool,
+
+        /// Shortcut for `--delete --force`
+        #[clap(short = 'D')]
+        delete_force: bool,
+
+        /// Rename a branch, along with its reflog. Renames the current branch if only the new
+        /// name is given
+        #[clap(short = 'm', long = "move")]
+        rename: bool,
+
+        /// Shortcut for `--move --force`
+        #[clap(short = 'M')]
+        rename_force: bool,
+
+        /// List branches, optionally only those matching the given patterns
+        #[clap(short, long)]
+        list: bool,
+
+        /// Allow deleting unmerged branches, or renaming over an existing branch
         #[clap(short, long)]
+        force: bool,
+
+        /// Show the oid and subject of each branch's tip when listing
+        #[clap(short, long)]
+        verbose: bool,
+    },
+
+    /// Create, list, or delete tags
+    Tag {
+        /// The name of the tag to create and the object it should point to (defaulting to HEAD),
+        /// the tags to delete with `-d`, or patterns to match with `-l`
+        #[clap(num_args(0..))]
+        args: Vec<String>,
+
+        /// Create an annotated tag object
+        #[clap(short, long)]
+        annotate: bool,
+
+        /// The message for an annotated tag. Implies `-a`
+        #[clap(short, long)]
+        message: Option<String>,
+
+        /// Delete the named tags
+        #[clap(short, long, conflicts_with_all = ["annotate", "message", "list"])]
+        delete: bool,
+
+        /// List tags, optionally only those matching the given patterns
+        #[clap(short, long, conflicts_with_all = ["annotate", "message"])]
         list: bool,
+
+        /// Replace an existing tag with the same name
         #[clap(short, long)]
         force: bool,
     },
+
+    /// Switch branches or restore working tree files
+    Checkout {
+        /// The branch or commit to check out
+        rev: String,
+
+        /// Create a new branch starting at `rev`, and check it out
+        #[clap(short = 'b')]
+        new_branch: Option<String>,
+
+        /// Detach HEAD at `rev`, even if it names a branch
+        #[clap(long)]
+        detach: bool,
+    },
+
+    /// Switch branches
+    Switch {
+        /// The branch to switch to, or the start point of a new branch with `-c`
+        branch: String,
+
+        /// Create a new branch starting at `branch`, and switch to it
+        #[clap(short, long)]
+        create: Option<String>,
+
+        /// Switch to a commit, detaching HEAD
+        #[clap(long, conflicts_with = "create")]
+        detach: bool,
+    },
+
+    /// Show commit logs
+    Log {
+        /// The revision to start from. Defaults to HEAD
+        rev: Option<String>,
+
+        /// Show each commit as its abbreviated oid and subject, on a single line
+        #[clap(long, conflicts_with = "format")]
+        oneline: bool,
+
+        /// Limit the number of commits to output
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+
+        /// Pretty-print commits in a named format (oneline, short, medium, full or fuller), or
+        /// using `format:<string>` or `tformat:<string>`. Format strings support the placeholders
+        /// %H, %h, %an, %ae, %ad, %s, %b, %n, and %%
+        #[clap(long, alias = "pretty")]
+        format: Option<String>,
+
+        /// The format to display dates in
+        #[clap(long, value_enum, default_value_t)]
+        date: DateFormat,
+
+        /// Check the signatures of signed commits, and show what was found
+        #[clap(long)]
+        show_signature: bool,
+    },
+
+    /// Join two development histories together
+    Merge {
+        /// The commit to merge into the current branch
+        #[clap(required_unless_present = "continue_merge")]
+        rev: Option<String>,
+
+        /// The message to use for the merge commit
+        #[clap(short, long)]
+        message: Option<String>,
+
+        /// Conclude a merge that stopped due to conflicts, once they have been resolved
+        #[clap(long = "continue", conflicts_with_all = ["rev", "message"])]
+        continue_merge: bool,
+    },
+
+    /// Pack heads and tags for efficient repository access
+    PackRefs {
+        /// Pack all refs, rather than just tags
+        #[clap(long)]
+        all: bool,
+
+        /// Keep the loose ref files after packing them
+        #[clap(long)]
+        no_prune: bool,
+    },
+
+    /// Pack unpacked objects in a repository
+    Repack {
+        /// After packing, remove loose objects and packs that are made redundant by the new pack
+        #[clap(short = 'd')]
+        delete: bool,
+    },
+
+    /// Cleanup unnecessary files and optimize the local repository
+    Gc,
+
+    /// Get and set repository or global options
+    Config(ConfigArgs),
+
+    /// Update the object name stored in a ref safely
+    UpdateRef(UpdateRefArgs),
+
+    /// Manage reflog information
+    Reflog(ReflogArgs),
+
+    /// List commits reachable from some revisions but not from others
+    RevList(RevListArgs),
+
+    /// Check the signatures of commits
+    VerifyCommit {
+        /// Print the contents of each commit
+        #[clap(short, long)]
+        verbose: bool,
+
+        #[clap(required = true)]
+        commits: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ConfigArgs {
+    #[clap(subcommand)]
+    pub action: Option<ConfigAction>,
+
+    /// List all variables set in the config files, along with their values
+    #[clap(short, long)]
+    pub list: bool,
+
+    /// Use the per-user config file, `~/.gitconfig`
+    #[clap(long, global = true, group = "location")]
+    pub global: bool,
+
+    /// Use the system-wide config file, `/etc/gitconfig`
+    #[clap(long, global = true, group = "location")]
+    pub system: bool,
+
+    /// Use the repository config file, `.git/config`
+    #[clap(long, global = true, group = "location")]
+    pub local: bool,
+
+    /// Use the given config file
+    #[clap(short, long, global = true, group = "location")]
+    pub file: Option<Utf8PathBuf>,
+
+    /// Show the file each value was read from
+    #[clap(long, global = true)]
+    pub show_origin: bool,
+
+    /// Show the scope each value was read from
+    #[clap(long, global = true)]
+    pub show_scope: bool,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Print the value of a variable. Exits with status 1 if it is not set
+    Get {
+        key: String,
+
+        /// Print every value of a multi-valued variable
+        #[clap(long)]
+        all: bool,
+    },
+
+    /// Set the value of a variable
+    Set {
+        key: String,
+        value: String,
+
+        /// Add a new value, rather than replacing the existing one
+        #[clap(long)]
+        append: bool,
+    },
+
+    /// Remove a variable. Exits with status 5 if it is not set
+    Unset {
+        key: String,
+
+        /// Remove every value of a multi-valued variable
+        #[clap(long)]
+        all: bool,
+    },
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ReflogArgs {
+    /// What to do. Defaults to showing the log of HEAD
+    #[clap(subcommand)]
+    pub action: Option<ReflogAction>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ReflogAction {
+    /// Show the log of a ref, newest entry first
+    Show {
+        /// The ref to show the log of. Defaults to HEAD
+        ref_name: Option<String>,
+
+        /// Limit the number of entries to output
+        #[clap(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+    },
+
+    /// Remove old entries from reflogs
+    Expire {
+        /// Remove entries older than this, such as `90.days.ago`, `now` or `never`. Defaults to
+        /// `gc.reflogExpire`, or 90 days
+        #[clap(long)]
+        expire: Option<String>,
+
+        /// Remove entries older than this that are not reachable from the current tip of the
+        /// ref. Defaults to `gc.reflogExpireUnreachable`, or 30 days
+        #[clap(long)]
+        expire_unreachable: Option<String>,
+
+        /// Process the reflogs of every ref
+        #[clap(long, conflicts_with = "refs")]
+        all: bool,
+
+        /// The refs whose reflogs should be expired
+        refs: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct RevListArgs {
+    /// The commits to list the history of. `^<rev>` excludes the history of `<rev>`, `<a>..<b>`
+    /// lists commits reachable from `<b>` but not `<a>`, and `<a>...<b>` lists commits reachable
+    /// from either but not both
+    #[clap(required_unless_present = "not")]
+    pub revs: Vec<String>,
+
+    /// Revisions whose meaning is flipped: `<rev>` excludes the history of `<rev>`, and `^<rev>`
+    /// includes it
+    #[clap(long, num_args(1..), value_name = "REVS")]
+    pub not: Vec<String>,
+
+    /// Print the number of commits that would have been listed
+    #[clap(long)]
+    pub count: bool,
+
+    /// Limit the number of commits to output
+    #[clap(short = 'n', long)]
+    pub max_count: Option<usize>,
+
+    /// List commits oldest first. Applied after `--max-count`
+    #[clap(long)]
+    pub reverse: bool,
+
+    /// Only follow the first parent of merge commits
+    #[clap(long)]
+    pub first_parent: bool,
+
+    /// After the commits, list the trees and blobs they refer to that aren't reachable from the
+    /// excluded commits, along with their paths
+    #[clap(long)]
+    pub objects: bool,
+
+    /// Never show a commit before its children, and don't interleave lines of history
+    #[clap(long, conflicts_with = "date_order")]
+    pub topo_order: bool,
+
+    /// Show commits newest first by commit date. This is the default
+    #[clap(long)]
+    pub date_order: bool,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct UpdateRefArgs {
+    /// `<ref> <new-value> [<old-value>]`, or `<ref> [<old-value>]` with `-d`. If `<old-value>`
+    /// is given, the ref is only changed if it currently points there
+    #[clap(num_args(0..=3))]
+    pub args: Vec<String>,
+
+    /// Delete the ref
+    #[clap(short)]
+    pub delete: bool,
+
+    /// Read `update`, `create`, `delete` and `verify` commands from stdin, one per line, and
+    /// apply them all in a single transaction
+    #[clap(long, conflicts_with_all = ["args", "delete"])]
+    pub stdin: bool,
+
+    /// Update symbolic refs themselves, rather than the refs they point to
+    #[clap(long)]
+    pub no_deref: bool,
+
+    /// The reason for the update, recorded in the reflog
+    #[clap(short, default_value = "")]
+    pub message: String,
 }
 
 // FIXME: This is exposing the full names of the subcommands.
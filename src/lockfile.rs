@@ -0,0 +1,170 @@
+//! Git-compatible lock files.
+//!
+//! To change a file such as a ref, `packed-refs`, or `config`, git first creates `<file>.lock`
+//! with `O_EXCL`, writes the new contents there, then renames it over the original. Creating the
+//! lock fails if another process already holds it, and readers only ever see the old or the new
+//! contents, never a partial write.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::trace;
+
+use crate::Result;
+
+/// A held lock on `path`. Dropping the lock without calling [`Lockfile::commit`] releases it and
+/// leaves `path` untouched, removing any directories that were created for it.
+#[derive(Debug)]
+pub struct Lockfile {
+    path: Utf8PathBuf,
+    lock_path: Utf8PathBuf,
+    file: Option<File>,
+    /// The parent directories created to hold the lock, outermost first
+    created_dirs: Vec<Utf8PathBuf>,
+}
+
+impl Lockfile {
+    /// Take the lock for `path`, creating any missing parent directories.
+    pub fn acquire(path: &Utf8Path) -> Result<Self> {
+        let lock_path = Utf8PathBuf::from(format!("{path}.lock"));
+        let mut created_dirs = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_str().is_empty() && !dir.exists())
+            .map(Utf8Path::to_owned)
+            .collect::<Vec<_>>();
+        created_dirs.reverse();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("Unable to create directory for '{lock_path}'"))?;
+        }
+
+        let file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(eyre!(
+                    "Unable to create '{lock_path}': File exists.\n\n\
+                     Another git process seems to be running in this repository. If no other \
+                     process is running, remove the file manually to continue."
+                ))
+            }
+            Err(e) => {
+                remove_dirs(&created_dirs);
+                return Err(e).wrap_err_with(|| format!("Unable to create '{lock_path}'"));
+            }
+        };
+
+        trace!(%lock_path, "Acquired lock");
+        Ok(Self {
+            path: path.to_owned(),
+            lock_path,
+            file: Some(file),
+            created_dirs,
+        })
+    }
+
+    /// The path of the file being locked, not the lock itself.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Append `bytes` to the new contents of the file.
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<()> {
+        let file = self.file.as_mut().expect("lock is held until commit");
+        file.write_all(bytes)
+            .wrap_err_with(|| format!("Failed to write '{}'", self.lock_path))
+    }
+
+    /// Replace the locked file with what has been written, releasing the lock.
+    pub fn commit(mut self) -> Result<()> {
+        let file = self.file.take().expect("lock is held until commit");
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(&self.lock_path, &self.path)
+            .wrap_err_with(|| format!("Unable to rename '{}' into place", self.lock_path))?;
+        trace!(path=%self.path, "Committed lock");
+        Ok(())
+    }
+
+    /// Delete the locked file, releasing the lock. The file is removed while the lock is still
+    /// held, so no other process can recreate it in between.
+    pub fn delete(mut self) -> Result<()> {
+        drop(self.file.take());
+
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).wrap_err_with(|| format!("Unable to delete '{}'", self.path)),
+        }
+        std::fs::remove_file(&self.lock_path)?;
+        Ok(())
+    }
+}
+
+impl Drop for Lockfile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            trace!(lock_path=%self.lock_path, "Rolling back lock");
+            let _ = std::fs::remove_file(&self.lock_path);
+            remove_dirs(&self.created_dirs);
+        }
+    }
+}
+
+/// Remove `dirs`, innermost first, stopping at the first that something else has since been put
+/// in.
+fn remove_dirs(dirs: &[Utf8PathBuf]) {
+    for dir in dirs.iter().rev() {
+        if std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_lockfile() -> Result<()> {
+        let dir = TempDir::new("")?;
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+        let path = dir.join("sub/file");
+
+        let mut lock = Lockfile::acquire(&path)?;
+        assert!(Lockfile::acquire(&path).is_err());
+        lock.write_all(b"one")?;
+        lock.commit()?;
+        assert_eq!(std::fs::read_to_string(&path)?, "one");
+        assert!(!dir.join("sub/file.lock").exists());
+
+        // Dropping the lock leaves the file as it was
+        let mut lock = Lockfile::acquire(&path)?;
+        lock.write_all(b"two")?;
+        drop(lock);
+        assert_eq!(std::fs::read_to_string(&path)?, "one");
+        assert!(!dir.join("sub/file.lock").exists());
+
+        Lockfile::acquire(&path)?.delete()?;
+        assert!(!path.exists());
+
+        // Directories created for a lock that is dropped are removed, but not ones in use
+        let lock = Lockfile::acquire(&dir.join("a/b/c/file"))?;
+        std::fs::write(dir.join("a/other"), "")?;
+        drop(lock);
+        assert!(!dir.join("a/b").exists());
+        assert!(dir.join("a").is_dir());
+        assert!(dir.join("sub").is_dir());
+
+        Ok(())
+    }
+}
@@ -3,25 +3,43 @@
 #[cfg(test)]
 mod test;
 
+mod attributes;
 mod blob;
 mod cat_file;
 mod commit;
+mod config;
 mod diff;
 mod digest;
 mod filemode;
+mod ignore;
 mod index;
 mod interface;
+mod lockfile;
+mod merge;
+mod pack;
+mod packed_refs;
+mod pathspec;
+mod reflog;
 mod repo;
+mod rev_list;
 mod revision;
 mod storable;
+mod tag;
 mod timestamp;
 mod tree;
+mod update_ref;
 mod util;
+mod verify_commit;
 
 use camino::Utf8PathBuf;
 use color_eyre::eyre::{eyre, Context};
 pub use color_eyre::Result;
-use repo::diff::DiffMode;
+use pathspec::Pathspec;
+use repo::checkout::CheckoutOutcome;
+use repo::commit::CommitOptions;
+use repo::diff::DiffOptions;
+use repo::log::{LogFormat, LogOptions};
+use repo::merge::MergeOutcome;
 use repo::status::StatusOutputMode;
 use revision::Rev;
 use tracing::{info, Level};
@@ -71,17 +89,48 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if let Command::Config(args) = &ARGS.command {
+        let git_dir = path.join(".git");
+        let git_dir = git_dir.is_dir().then_some(git_dir.as_path());
+        return config::command::handle(args, git_dir);
+    }
+
     let mut repo = Repo::open(path)?;
 
     match &ARGS.command {
         Command::Init { .. } => unreachable!("Init command is handled above"),
+        Command::Config(_) => unreachable!("Config command is handled above"),
 
-        Command::Commit { message } => {
-            let commit_id = repo.commit(
-                message
-                    .as_deref()
-                    .expect("Using an editor for commit message is currently unimplemented"),
-            )?;
+        Command::Commit {
+            message,
+            file,
+            edit,
+            cleanup,
+            allow_empty_message,
+            no_verify,
+            amend,
+            no_edit,
+            allow_empty,
+            author,
+            date,
+            gpg_sign,
+            no_gpg_sign,
+        } => {
+            let commit_id = repo.commit_with(&CommitOptions {
+                message: message.clone(),
+                file: file.clone(),
+                edit: *edit,
+                cleanup: *cleanup,
+                allow_empty_message: *allow_empty_message,
+                no_verify: *no_verify,
+                amend: *amend,
+                no_edit: *no_edit,
+                allow_empty: *allow_empty,
+                author: author.clone(),
+                date: date.clone(),
+                gpg_sign: gpg_sign.clone(),
+                no_gpg_sign: *no_gpg_sign,
+            })?;
             println!("Created commit {}", commit_id.to_hex())
         }
 
@@ -95,6 +144,16 @@ fn main() -> Result<()> {
 
         Command::CatFile(args) => cat_file::handle(&mut repo, args)?,
 
+        Command::UpdateRef(args) => update_ref::handle(&mut repo, args)?,
+
+        Command::Reflog(args) => reflog::command::handle(&mut repo, args)?,
+
+        Command::RevList(args) => rev_list::handle(&repo, args)?,
+
+        Command::VerifyCommit { verbose, commits } => {
+            verify_commit::handle(&repo, commits, *verbose)?
+        }
+
         Command::Status { porcelain, long } => {
             let mode = if !porcelain || *long {
                 StatusOutputMode::Long
@@ -104,26 +163,76 @@ fn main() -> Result<()> {
             repo.status(mode)?
         }
 
-        Command::Diff { cached } => {
-            let mode = if *cached {
-                DiffMode::IndexHead
-            } else {
-                DiffMode::WorktreeIndex
+        Command::Diff {
+            cached,
+            binary,
+            find_renames,
+            find_copies,
+            no_renames,
+            diff_algorithm,
+            minimal,
+            args,
+            paths,
+        } => {
+            let (revisions, mut pathspecs) = repo.split_diff_args(args)?;
+            pathspecs.extend(paths.iter().cloned());
+            let mode = repo.diff_mode(*cached, &revisions)?;
+            let options = DiffOptions {
+                binary: *binary,
+                pathspec: Pathspec::new(&pathspecs),
+                renames: repo.diff_renames(
+                    find_renames.as_deref(),
+                    find_copies.as_deref(),
+                    *no_renames,
+                )?,
+                algorithm: repo.diff_algorithm(*diff_algorithm, *minimal)?,
             };
-            repo.diff(mode)?
+            repo.diff(mode, &options)?
         }
 
         Command::ShowHead { oid } => repo.show_head(oid.clone())?,
 
-        #[allow(unused_variables)]
         Command::Branch {
-            patterns,
+            args,
             delete,
+            delete_force,
+            rename,
+            rename_force,
             list,
             force,
+            verbose,
         } => {
-            match patterns.as_slice() {
-                [] => todo!("List branches"),
+            if *delete || *delete_force {
+                if args.is_empty() {
+                    return Err(eyre!("branch name required"));
+                }
+                for name in args {
+                    let oid = repo.delete_branch(name, *force || *delete_force)?;
+                    println!("Deleted branch {name} (was {}).", repo.abbreviate(&oid)?);
+                }
+                return Ok(());
+            }
+
+            if *rename || *rename_force {
+                let force = *force || *rename_force;
+                return match args.as_slice() {
+                    [new] => {
+                        let current = repo.current_branch()?.ok_or_else(|| {
+                            eyre!("cannot rename the current branch while not on any")
+                        })?;
+                        repo.rename_branch(&current, new, force)
+                    }
+                    [old, new] => repo.rename_branch(old, new, force),
+                    _ => Err(eyre!("too many arguments for a rename operation")),
+                };
+            }
+
+            if *list {
+                return repo.print_branches(args, *verbose);
+            }
+
+            match args.as_slice() {
+                [] => repo.print_branches(&[], *verbose)?,
 
                 [name] => {
                     // FIXME: don't explode on a just-inited repo
@@ -131,18 +240,161 @@ fn main() -> Result<()> {
                         .read_head()?
                         .ok_or_else(|| eyre!("Repo does not have a HEAD"))?;
 
-                    repo.create_branch(name, &head)?
+                    repo.create_branch(name, &head, "HEAD")?
                 }
                 [name, rev] => {
-                    let rev = Rev::parse(rev)?
+                    let oid = Rev::parse(rev)?
                         .resolve(&repo)?
                         .ok_or_else(|| eyre!("Provided revision does not exist: '{}'", rev))?;
-                    repo.create_branch(name, &rev)?
+                    repo.create_branch(name, &oid, rev)?
+                }
+                _ => return Err(eyre!("too many arguments")),
+            }
+        }
+
+        Command::Tag {
+            args,
+            annotate,
+            message,
+            delete,
+            list,
+            force,
+        } => {
+            if *delete {
+                for name in args {
+                    let oid = repo.delete_tag(name)?;
+                    println!("Deleted tag '{name}' (was {})", repo.abbreviate(&oid)?);
+                }
+            } else if *list || args.is_empty() {
+                for name in repo.list_tags(args)? {
+                    println!("{name}");
+                }
+            } else {
+                let (name, target) = match args.as_slice() {
+                    [name] => (name, "HEAD"),
+                    [name, target] => (name, target.as_str()),
+                    _ => return Err(eyre!("too many arguments")),
+                };
+                let message = match message {
+                    Some(message) => Some(message.clone()),
+                    None if *annotate => Some(repo.edit_tag_message(name)?),
+                    None => None,
+                };
+                repo.create_tag(name, target, message.as_deref(), *force)?;
+            }
+        }
+
+        Command::Checkout {
+            rev,
+            new_branch,
+            detach,
+        } => checkout(&mut repo, rev, new_branch.as_deref(), *detach)?,
+
+        Command::Switch {
+            branch,
+            create,
+            detach,
+        } => {
+            if create.is_none() && !*detach && !repo.branch_exists(branch)? {
+                return Err(eyre!("a branch is expected, got '{branch}'"));
+            }
+            checkout(&mut repo, branch, create.as_deref(), *detach)?
+        }
+
+        Command::Log {
+            rev,
+            oneline,
+            max_count,
+            format,
+            date,
+            show_signature,
+        } => {
+            let format = match format {
+                Some(format) => LogFormat::parse(format)?,
+                None if *oneline => LogFormat::Oneline,
+                None => LogFormat::Medium,
+            };
+            repo.log(LogOptions {
+                start: rev.as_deref(),
+                max_count: *max_count,
+                format,
+                abbrev_commit: *oneline,
+                date_format: *date,
+                show_signature: *show_signature,
+            })?
+        }
+
+        Command::Merge {
+            rev,
+            message,
+            continue_merge,
+        } => {
+            if *continue_merge {
+                let commit_id = repo.merge_continue()?;
+                println!("Created commit {}", commit_id.to_hex());
+                return Ok(());
+            }
+
+            let rev = rev
+                .as_deref()
+                .ok_or_else(|| eyre!("No revision given to merge"))?;
+            match repo.merge(rev, message.as_deref())? {
+                MergeOutcome::UpToDate => println!("Already up to date."),
+                MergeOutcome::FastForward(oid) => {
+                    println!("Fast-forward to {}", repo.abbreviate(&oid)?)
+                }
+                MergeOutcome::Merged(oid) => println!("Created merge commit {}", oid.to_hex()),
+                MergeOutcome::Conflicted(paths) => {
+                    for path in paths {
+                        println!("CONFLICT: Merge conflict in {path}");
+                    }
+                    println!("Automatic merge failed; fix conflicts and then commit the result.");
+                    std::process::exit(1);
                 }
-                _ => todo!("catch this with clap?"),
             }
         }
+
+        Command::PackRefs { all, no_prune } => repo.pack_refs(*all, !*no_prune)?,
+
+        Command::Repack { delete } => {
+            if let Some(idx_path) = repo.repack(*delete)? {
+                println!("Wrote pack {}", idx_path.with_extension("pack"))
+            }
+        }
+
+        Command::Gc => {
+            repo.pack_refs(true, true)?;
+            repo.repack(true)?;
+        }
     };
 
     Ok(())
 }
+
+/// Check out `rev`, first creating `new_branch` at it if given.
+fn checkout(repo: &mut Repo, rev: &str, new_branch: Option<&str>, detach: bool) -> Result<()> {
+    let rev = match new_branch {
+        Some(name) => {
+            let oid = Rev::parse(rev)?
+                .resolve(repo)?
+                .ok_or_else(|| eyre!("Provided revision does not exist: '{}'", rev))?;
+            repo.create_branch(name, &oid, rev)?;
+            name
+        }
+        None => rev,
+    };
+
+    match repo.checkout(rev, detach)? {
+        CheckoutOutcome::Branch(name) => eprintln!("Switched to branch '{name}'"),
+        CheckoutOutcome::Detached(oid) => {
+            let commit = repo.database.load_commit(&oid)?;
+            eprintln!(
+                "HEAD is now at {} {}",
+                repo.abbreviate(&oid)?,
+                commit.subject()
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! A line-based three-way merge.
+//!
+//! Both sides are diffed against the base, and the lines that are unchanged in both diffs are used
+//! to split the three files into alternating stable and unstable chunks. An unstable chunk is
+//! clean if only one side changed it (or both changed it identically), and a conflict otherwise.
+//!
+//! See: <https://www.cis.upenn.edu/~bcpierce/papers/diff3-short.pdf>
+
+use std::collections::HashMap;
+
+use crate::diff::{self, EditKind};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Chunk<'a> {
+    Clean(Vec<&'a str>),
+    Conflict {
+        base: Vec<&'a str>,
+        ours: Vec<&'a str>,
+        theirs: Vec<&'a str>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Merge3<'a> {
+    chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> Merge3<'a> {
+    pub fn is_clean(&self) -> bool {
+        self.chunks.iter().all(|x| matches!(x, Chunk::Clean(_)))
+    }
+
+    pub fn chunks(&self) -> &[Chunk<'a>] {
+        &self.chunks
+    }
+
+    /// Render the result of the merge, with git-style conflict markers around any conflicts.
+    pub fn to_text(&self, ours_name: &str, theirs_name: &str) -> String {
+        fn push_lines(out: &mut String, lines: &[&str]) {
+            for line in lines {
+                out.push_str(line);
+            }
+            // Markers must start on their own line, even if a file has no trailing newline
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+
+        let mut out = String::new();
+        for chunk in &self.chunks {
+            match chunk {
+                Chunk::Clean(lines) => lines.iter().for_each(|x| out.push_str(x)),
+                Chunk::Conflict { ours, theirs, .. } => {
+                    if !out.is_empty() && !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    out.push_str(&format!("<<<<<<< {ours_name}\n"));
+                    push_lines(&mut out, ours);
+                    out.push_str("=======\n");
+                    push_lines(&mut out, theirs);
+                    out.push_str(&format!(">>>>>>> {theirs_name}\n"));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// For each line of `base` that survives unchanged into `other`, map its (1-based) line number to
+/// its line number in `other`.
+fn matches(base: &[&str], other: &[&str]) -> HashMap<usize, usize> {
+    diff::diff(other, base)
+        .iter()
+        .filter(|edit| edit.kind() == EditKind::Equal)
+        .map(|edit| (edit.a_index().unwrap() + 1, edit.b_index().unwrap() + 1))
+        .collect()
+}
+
+struct Diff3<'a, 'b> {
+    base: &'b [&'a str],
+    ours: &'b [&'a str],
+    theirs: &'b [&'a str],
+    match_ours: HashMap<usize, usize>,
+    match_theirs: HashMap<usize, usize>,
+    line_base: usize,
+    line_ours: usize,
+    line_theirs: usize,
+    chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> Diff3<'a, '_> {
+    fn generate_chunks(mut self) -> Vec<Chunk<'a>> {
+        loop {
+            match self.find_next_mismatch() {
+                Some(1) => match self.find_next_match() {
+                    (o, Some(a), Some(b)) => self.emit_chunk(o, a, b),
+                    _ => {
+                        self.emit_final_chunk();
+                        return self.chunks;
+                    }
+                },
+                Some(i) => {
+                    self.emit_chunk(self.line_base + i, self.line_ours + i, self.line_theirs + i);
+                }
+                None => {
+                    self.emit_final_chunk();
+                    return self.chunks;
+                }
+            }
+        }
+    }
+
+    fn in_bounds(&self, i: usize) -> bool {
+        self.line_base + i <= self.base.len()
+            || self.line_ours + i <= self.ours.len()
+            || self.line_theirs + i <= self.theirs.len()
+    }
+
+    /// Returns how many lines from the current position all three files agree for, plus one.
+    fn find_next_mismatch(&self) -> Option<usize> {
+        let mut i = 1;
+        while self.in_bounds(i)
+            && self.match_ours.get(&(self.line_base + i)) == Some(&(self.line_ours + i))
+            && self.match_theirs.get(&(self.line_base + i)) == Some(&(self.line_theirs + i))
+        {
+            i += 1;
+        }
+        self.in_bounds(i).then_some(i)
+    }
+
+    /// Find the next base line that is unchanged in both sides.
+    fn find_next_match(&self) -> (usize, Option<usize>, Option<usize>) {
+        let mut o = self.line_base + 1;
+        while o <= self.base.len()
+            && !(self.match_ours.contains_key(&o) && self.match_theirs.contains_key(&o))
+        {
+            o += 1;
+        }
+        (
+            o,
+            self.match_ours.get(&o).copied(),
+            self.match_theirs.get(&o).copied(),
+        )
+    }
+
+    fn emit_chunk(&mut self, o: usize, a: usize, b: usize) {
+        self.write_chunk(
+            self.base[self.line_base..o - 1].to_vec(),
+            self.ours[self.line_ours..a - 1].to_vec(),
+            self.theirs[self.line_theirs..b - 1].to_vec(),
+        );
+        self.line_base = o - 1;
+        self.line_ours = a - 1;
+        self.line_theirs = b - 1;
+    }
+
+    fn emit_final_chunk(&mut self) {
+        self.write_chunk(
+            self.base[self.line_base..].to_vec(),
+            self.ours[self.line_ours..].to_vec(),
+            self.theirs[self.line_theirs..].to_vec(),
+        );
+    }
+
+    fn write_chunk(&mut self, base: Vec<&'a str>, ours: Vec<&'a str>, theirs: Vec<&'a str>) {
+        let chunk = if ours == base || ours == theirs {
+            Chunk::Clean(theirs)
+        } else if theirs == base {
+            Chunk::Clean(ours)
+        } else {
+            Chunk::Conflict { base, ours, theirs }
+        };
+
+        // Merge adjacent clean chunks, to keep the output tidy
+        match (self.chunks.last_mut(), chunk) {
+            (Some(Chunk::Clean(prev)), Chunk::Clean(lines)) => prev.extend(lines),
+            (_, chunk) => self.chunks.push(chunk),
+        }
+    }
+}
+
+/// Merge the changes made to `base` in `ours` and `theirs`.
+pub fn merge<'a>(base: &'a str, ours: &'a str, theirs: &'a str) -> Merge3<'a> {
+    // Keep line terminators, so that the merged text can be reassembled exactly
+    let base = base.split_inclusive('\n').collect::<Vec<_>>();
+    let ours = ours.split_inclusive('\n').collect::<Vec<_>>();
+    let theirs = theirs.split_inclusive('\n').collect::<Vec<_>>();
+
+    let diff3 = Diff3 {
+        match_ours: matches(&base, &ours),
+        match_theirs: matches(&base, &theirs),
+        base: &base,
+        ours: &ours,
+        theirs: &theirs,
+        line_base: 0,
+        line_ours: 0,
+        line_theirs: 0,
+        chunks: Vec::new(),
+    };
+
+    Merge3 {
+        chunks: diff3.generate_chunks(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_merge() {
+        let base = "celery\ngarlic\nonions\nsalmon\ntomatoes\nwine\n";
+        let ours = "celery\nsalmon\ntomatoes\ngarlic\nonions\nwine\n";
+        let theirs = "celery\ngarlic\nsalmon\ntomatoes\nonions\nwine\n";
+
+        let merged = merge(base, ours, theirs);
+        assert!(!merged.is_clean());
+
+        let base = "a\nb\nc\nd\ne\n";
+        let ours = "A\nb\nc\nd\ne\n";
+        let theirs = "a\nb\nc\nd\nE\n";
+        let merged = merge(base, ours, theirs);
+        assert!(merged.is_clean());
+        assert_eq!(merged.to_text("ours", "theirs"), "A\nb\nc\nd\nE\n");
+    }
+
+    #[test]
+    fn test_conflict_markers() {
+        let base = "a\nb\nc\n";
+        let ours = "a\nours\nc\n";
+        let theirs = "a\ntheirs\nc\n";
+
+        let merged = merge(base, ours, theirs);
+        assert!(!merged.is_clean());
+        assert_eq!(
+            merged.to_text("HEAD", "topic"),
+            "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\nc\n"
+        );
+    }
+
+    #[test]
+    fn test_identical_changes() {
+        let base = "a\nb\nc\n";
+        let both = "a\nB\nc\nd\n";
+
+        let merged = merge(base, both, both);
+        assert!(merged.is_clean());
+        assert_eq!(merged.to_text("ours", "theirs"), both);
+    }
+}
@@ -0,0 +1,241 @@
+//! Finding common ancestors, and three-way merging of trees and files.
+
+pub mod diff3;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::blob::Blob;
+use crate::digest::Digest;
+use crate::repo::database::Database;
+use crate::storable::DatabaseObject;
+use crate::tree::{RawTreeEntry, Tree};
+use crate::Result;
+
+/// Every commit reachable from `oid`, including itself.
+fn ancestors(database: &Database, oid: &Digest) -> Result<HashSet<Digest>> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([oid.clone()]);
+
+    while let Some(oid) = queue.pop_front() {
+        if !seen.insert(oid.clone()) {
+            continue;
+        }
+        let commit = database.load_commit(&oid)?;
+        queue.extend(commit.parents().iter().cloned());
+    }
+
+    Ok(seen)
+}
+
+/// Returns `true` if `ancestor` is reachable from `descendant`, including when they are the same
+/// commit.
+pub fn is_ancestor(database: &Database, ancestor: &Digest, descendant: &Digest) -> Result<bool> {
+    Ok(ancestors(database, descendant)?.contains(ancestor))
+}
+
+/// Find the best common ancestors of `one` and `two`: the common ancestors that aren't reachable
+/// from any other common ancestor. There may be more than one in histories with criss-cross
+/// merges.
+pub fn merge_bases(database: &Database, one: &Digest, two: &Digest) -> Result<Vec<Digest>> {
+    let reachable_from_one = ancestors(database, one)?;
+
+    // Walk back from `two`, stopping at the first common ancestors found along each path
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([two.clone()]);
+    while let Some(oid) = queue.pop_front() {
+        if !seen.insert(oid.clone()) {
+            continue;
+        }
+        if reachable_from_one.contains(&oid) {
+            candidates.push(oid);
+            continue;
+        }
+        let commit = database.load_commit(&oid)?;
+        queue.extend(commit.parents().iter().cloned());
+    }
+
+    // Discard any candidate that is an ancestor of another
+    let mut redundant = HashSet::new();
+    for candidate in &candidates {
+        if redundant.contains(candidate) {
+            continue;
+        }
+        let commit = database.load_commit(candidate)?;
+        for parent in commit.parents() {
+            redundant.extend(ancestors(database, parent)?);
+        }
+    }
+
+    Ok(candidates
+        .into_iter()
+        .filter(|x| !redundant.contains(x))
+        .collect())
+}
+
+/// Find the best common ancestor of `one` and `two`, if they have any history in common.
+pub fn merge_base(database: &Database, one: &Digest, two: &Digest) -> Result<Option<Digest>> {
+    Ok(merge_bases(database, one, two)?.into_iter().next())
+}
+
+/// The result of merging a single path.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergedEntry {
+    /// The path merged cleanly, and should contain this entry. If the content needed a line-level
+    /// merge, the entry's oid is that of the merged blob, which has already been written to the
+    /// database.
+    Clean(RawTreeEntry),
+    /// The path merged cleanly, and should not exist.
+    Deleted,
+    /// Both sides changed the path in incompatible ways. `merged` is the content to leave in the
+    /// workspace, with conflict markers where appropriate.
+    Conflict {
+        base: Option<RawTreeEntry>,
+        ours: Option<RawTreeEntry>,
+        theirs: Option<RawTreeEntry>,
+        merged: Vec<u8>,
+    },
+}
+
+/// Merge the trees `ours` and `theirs`, with `base` as their common ancestor. `base` is `None` if
+/// the histories are unrelated.
+///
+/// Only paths that differ between `ours` and the result are returned. Line-merged blobs are stored
+/// in the database as a side effect.
+pub fn merge_trees(
+    database: &Database,
+    base: Option<&Digest>,
+    ours: &Digest,
+    theirs: &Digest,
+    ours_name: &str,
+    theirs_name: &str,
+) -> Result<BTreeMap<Utf8PathBuf, MergedEntry>> {
+    let base = match base {
+        Some(base) => Tree::flatten(database, base)?,
+        None => BTreeMap::new(),
+    };
+    let ours = Tree::flatten(database, ours)?;
+    let theirs = Tree::flatten(database, theirs)?;
+
+    let paths = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut result = BTreeMap::new();
+
+    for path in paths {
+        let b = base.get(path);
+        let o = ours.get(path);
+        let t = theirs.get(path);
+
+        let merged = if o == t || t == b {
+            // Unchanged on their side, or changed identically on both
+            continue;
+        } else if o == b {
+            // Only changed on their side
+            match t {
+                Some(t) => MergedEntry::Clean(t.clone()),
+                None => MergedEntry::Deleted,
+            }
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) => {
+                    merge_contents(database, path, b, o, t, ours_name, theirs_name)?
+                }
+                // Modified on one side, deleted on the other
+                (o, t) => {
+                    let survivor = o.or(t).expect("at least one side should exist");
+                    let (_, data) = database.read_raw(&survivor.oid)?;
+                    MergedEntry::Conflict {
+                        base: b.cloned(),
+                        ours: o.cloned(),
+                        theirs: t.cloned(),
+                        merged: data,
+                    }
+                }
+            }
+        };
+
+        if matches!(&merged, MergedEntry::Clean(entry) if Some(entry) == o) {
+            continue;
+        }
+        result.insert(path.clone(), merged);
+    }
+
+    Ok(result)
+}
+
+/// Merge two versions of a file that both exist, and were both changed relative to `base`.
+fn merge_contents(
+    database: &Database,
+    path: &Utf8Path,
+    base: Option<&RawTreeEntry>,
+    ours: &RawTreeEntry,
+    theirs: &RawTreeEntry,
+    ours_name: &str,
+    theirs_name: &str,
+) -> Result<MergedEntry> {
+    let base_mode = base.map(|x| x.mode);
+    let mode = if Some(ours.mode) == base_mode {
+        Some(theirs.mode)
+    } else if Some(theirs.mode) == base_mode || ours.mode == theirs.mode {
+        Some(ours.mode)
+    } else {
+        None
+    };
+
+    let (oid, merged, clean) = if ours.oid == theirs.oid {
+        let (_, data) = database.read_raw(&ours.oid)?;
+        (ours.oid.clone(), data, true)
+    } else if base.map(|x| &x.oid) == Some(&ours.oid) {
+        let (_, data) = database.read_raw(&theirs.oid)?;
+        (theirs.oid.clone(), data, true)
+    } else if base.map(|x| &x.oid) == Some(&theirs.oid) {
+        let (_, data) = database.read_raw(&ours.oid)?;
+        (ours.oid.clone(), data, true)
+    } else {
+        let base_data = match base {
+            Some(base) => database.read_raw(&base.oid)?.1,
+            None => Vec::new(),
+        };
+        let ours_data = database.read_raw(&ours.oid)?.1;
+        let theirs_data = database.read_raw(&theirs.oid)?.1;
+
+        match (
+            std::str::from_utf8(&base_data),
+            std::str::from_utf8(&ours_data),
+            std::str::from_utf8(&theirs_data),
+        ) {
+            (Ok(b), Ok(o), Ok(t)) => {
+                let merge = diff3::merge(b, o, t);
+                let blob = Blob::new(merge.to_text(ours_name, theirs_name).into_bytes());
+                let oid = {
+                    let blob = DatabaseObject::new(&blob);
+                    database.store(&blob)?;
+                    blob.into_oid()
+                };
+                (oid, blob.into_data(), merge.is_clean())
+            }
+            // Binary files can't be line-merged; leave ours in place
+            _ => (ours.oid.clone(), ours_data, false),
+        }
+    };
+
+    match mode {
+        Some(mode) if clean => Ok(MergedEntry::Clean(RawTreeEntry {
+            mode,
+            name: path.file_name().unwrap_or_default().to_owned(),
+            oid,
+        })),
+        _ => Ok(MergedEntry::Conflict {
+            base: base.cloned(),
+            ours: Some(ours.clone()),
+            theirs: Some(theirs.clone()),
+            merged,
+        }),
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use color_eyre::eyre::eyre;
+
+use super::MAX_PREALLOCATION;
+use crate::Result;
+
+/// Read a little-endian base-128 varint, as used for the sizes at the start of a delta.
+fn read_size(delta: &[u8], pos: &mut usize) -> Result<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        if shift >= usize::BITS {
+            return Err(eyre!("size varint too long"));
+        }
+        let byte = *delta.get(*pos).ok_or_else(|| eyre!("delta is truncated"))?;
+        *pos += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Apply a delta to `base`, producing the target object.
+///
+/// A delta consists of the size of the base, the size of the target, and then a series of
+/// instructions. An instruction with its MSB set copies a range of bytes out of the base; the low
+/// 7 bits say which offset and size bytes follow. Otherwise, the instruction byte is the number of
+/// literal bytes that follow, which are inserted as-is.
+///
+/// See: <https://git-scm.com/docs/pack-format#_deltified_representation>
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let base_size = read_size(delta, &mut pos)?;
+    if base_size != base.len() {
+        return Err(eyre!(
+            "delta base size mismatch: expected {base_size}, got {}",
+            base.len()
+        ));
+    }
+    let target_size = read_size(delta, &mut pos)?;
+
+    let mut out = Vec::with_capacity(target_size.min(MAX_PREALLOCATION));
+
+    while pos < delta.len() {
+        let instruction = delta[pos];
+        pos += 1;
+
+        if instruction & 0x80 != 0 {
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for i in 0..4 {
+                if instruction & (1 << i) != 0 {
+                    let byte = *delta.get(pos).ok_or_else(|| eyre!("delta is truncated"))?;
+                    pos += 1;
+                    offset |= (byte as usize) << (i * 8);
+                }
+            }
+            for i in 0..3 {
+                if instruction & (1 << (i + 4)) != 0 {
+                    let byte = *delta.get(pos).ok_or_else(|| eyre!("delta is truncated"))?;
+                    pos += 1;
+                    size |= (byte as usize) << (i * 8);
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let chunk = base
+                .get(offset..offset + size)
+                .ok_or_else(|| eyre!("delta copy out of range of base"))?;
+            out.extend_from_slice(chunk);
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            let chunk = delta
+                .get(pos..pos + size)
+                .ok_or_else(|| eyre!("delta is truncated"))?;
+            pos += size;
+            out.extend_from_slice(chunk);
+        } else {
+            return Err(eyre!("delta contains reserved instruction 0"));
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(eyre!(
+            "delta target size mismatch: expected {target_size}, got {}",
+            out.len()
+        ));
+    }
+
+    Ok(out)
+}
+
+/// The size of the blocks of the base that are indexed when searching for copies.
+const BLOCK_SIZE: usize = 16;
+
+/// The largest copy that can be expressed in a single instruction.
+const MAX_COPY: usize = 0xff_ffff;
+
+/// The largest insert that can be expressed in a single instruction.
+const MAX_INSERT: usize = 0x7f;
+
+fn write_size(out: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_insert(out: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT) {
+        out.push(chunk.len() as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+fn write_copy(out: &mut Vec<u8>, mut offset: usize, mut size: usize) {
+    while size > 0 {
+        let len = size.min(MAX_COPY);
+        let instruction_idx = out.len();
+        let mut instruction = 0x80;
+        out.push(0);
+        for i in 0..4 {
+            let byte = (offset >> (i * 8)) as u8;
+            if byte != 0 {
+                instruction |= 1 << i;
+                out.push(byte);
+            }
+        }
+        for i in 0..3 {
+            let byte = (len >> (i * 8)) as u8;
+            if byte != 0 {
+                instruction |= 1 << (i + 4);
+                out.push(byte);
+            }
+        }
+        out[instruction_idx] = instruction;
+        offset += len;
+        size -= len;
+    }
+}
+
+/// Create a delta that transforms `base` into `target`, in the format understood by
+/// [`apply_delta`].
+///
+/// Block-aligned chunks of the base are indexed, and the target is scanned for matching chunks,
+/// which are then extended as far as possible in both directions. Anything that can't be copied
+/// out of the base is inserted literally.
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_size(&mut out, base.len());
+    write_size(&mut out, target.len());
+
+    let mut blocks = HashMap::new();
+    for (i, block) in base.chunks_exact(BLOCK_SIZE).enumerate() {
+        blocks.entry(block).or_insert(i * BLOCK_SIZE);
+    }
+
+    let mut insert_start = 0;
+    let mut i = 0;
+    while i + BLOCK_SIZE <= target.len() {
+        let mut offset = match blocks.get(&target[i..i + BLOCK_SIZE]) {
+            Some(&x) => x,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut len = BLOCK_SIZE;
+        while offset + len < base.len()
+            && i + len < target.len()
+            && base[offset + len] == target[i + len]
+        {
+            len += 1;
+        }
+        while i > insert_start && offset > 0 && base[offset - 1] == target[i - 1] {
+            offset -= 1;
+            i -= 1;
+            len += 1;
+        }
+
+        write_insert(&mut out, &target[insert_start..i]);
+        write_copy(&mut out, offset, len);
+        i += len;
+        insert_start = i;
+    }
+    write_insert(&mut out, &target[insert_start..]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Deltas created by `create_delta` should reproduce their target when applied to their base,
+    /// and should be much smaller than the target when the two are similar.
+    fn delta_roundtrip() {
+        let base = (0..2000)
+            .map(|i| format!("line {i}\n"))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut target = base.clone();
+        target.splice(100..120, b"something different".iter().cloned());
+        target.extend_from_slice(b"a new line at the end\n");
+        target.drain(5000..5500);
+
+        let delta = create_delta(&base, &target);
+        assert!(delta.len() < target.len() / 10);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+
+        let unrelated = b"nothing in common".to_vec();
+        let delta = create_delta(&base, &unrelated);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), unrelated);
+
+        let delta = create_delta(&[], &target);
+        assert_eq!(apply_delta(&[], &delta).unwrap(), target);
+    }
+
+    #[test]
+    fn corrupt_sizes() {
+        assert!(apply_delta(&[], &[0x80]).is_err());
+        assert!(apply_delta(&[], &[0xff; 20]).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use camino::Utf8Path;
+use color_eyre::eyre::eyre;
+
+use crate::digest::Digest;
+use crate::Result;
+
+/// A parsed version 2 pack index (`.idx`) file.
+///
+/// The on-disk layout is:
+/// - a 4-byte magic number (`\377tOc`) and a 4-byte version number (`2`)
+/// - a 256-entry fanout table, where entry `n` is the number of objects whose first byte is `<= n`
+/// - a sorted table of 20-byte object names
+/// - a table of 4-byte CRC32s of the packed object data
+/// - a table of 4-byte offsets into the packfile. If the MSB is set, the remaining bits index into
+///   the large offset table
+/// - a table of 8-byte offsets, for packfiles larger than 2GiB
+/// - the checksum of the packfile, and the checksum of the index itself
+///
+/// See: <https://git-scm.com/docs/pack-format#_version_2_pack_idx_files_support_packs_larger_than_4_gib_and>
+pub struct PackIndex {
+    fanout: [u32; 256],
+    oids: Vec<Digest>,
+    crcs: Vec<u32>,
+    offsets: Vec<u64>,
+    pack_checksum: Digest,
+}
+
+impl PackIndex {
+    pub const MAGIC: &'static [u8; 4] = b"\xfftOc";
+    pub const VERSION: u32 = 2;
+
+    pub fn open(path: &Utf8Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::parse(&bytes).map_err(|e| e.wrap_err(format!("Failed to parse pack index: {path}")))
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 8 + 256 * 4 + 40 {
+            return Err(eyre!("pack index is truncated"));
+        }
+        if &bytes[..4] != Self::MAGIC {
+            return Err(eyre!("pack index has invalid magic"));
+        }
+        let version = read_u32(bytes, 4);
+        if version != Self::VERSION {
+            return Err(eyre!("unsupported pack index version: {version}"));
+        }
+
+        let trailer = &bytes[bytes.len() - 40..];
+        let expected = Digest(trailer[20..].try_into().unwrap());
+        let actual = Digest::new(&bytes[..bytes.len() - 20]);
+        if expected != actual {
+            return Err(eyre!(
+                "pack index checksum mismatch: expected {expected:x}, got {actual:x}"
+            ));
+        }
+        let pack_checksum = Digest(trailer[..20].try_into().unwrap());
+
+        let mut fanout = [0; 256];
+        for (i, x) in fanout.iter_mut().enumerate() {
+            *x = read_u32(bytes, 8 + i * 4);
+        }
+        let count = fanout[255] as usize;
+
+        let oids_start = 8 + 256 * 4;
+        let crcs_start = oids_start + count * 20;
+        let offsets_start = crcs_start + count * 4;
+        let large_offsets_start = offsets_start + count * 4;
+
+        if large_offsets_start > bytes.len() - 40 {
+            return Err(eyre!("pack index is truncated"));
+        }
+
+        let oids = (0..count)
+            .map(|i| {
+                let start = oids_start + i * 20;
+                Digest(bytes[start..start + 20].try_into().unwrap())
+            })
+            .collect();
+
+        let crcs = (0..count)
+            .map(|i| read_u32(bytes, crcs_start + i * 4))
+            .collect();
+
+        let offsets = (0..count)
+            .map(|i| {
+                let offset = read_u32(bytes, offsets_start + i * 4);
+                if offset & 0x8000_0000 == 0 {
+                    return Ok(offset as u64);
+                }
+                let idx = (offset & 0x7fff_ffff) as usize;
+                let start = large_offsets_start + idx * 8;
+                if start + 8 > bytes.len() - 40 {
+                    return Err(eyre!("pack index large offset out of range"));
+                }
+                Ok(u64::from_be_bytes(
+                    bytes[start..start + 8].try_into().unwrap(),
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            fanout,
+            oids,
+            crcs,
+            offsets,
+            pack_checksum,
+        })
+    }
+
+    /// The range of positions in the sorted oid table whose first byte is `byte`.
+    fn bucket(&self, byte: u8) -> std::ops::Range<usize> {
+        let end = self.fanout[byte as usize] as usize;
+        let start = match byte {
+            0 => 0,
+            byte => self.fanout[byte as usize - 1] as usize,
+        };
+        start..end
+    }
+
+    fn position(&self, oid: &Digest) -> Option<usize> {
+        let bucket = self.bucket(oid[0]);
+        let start = bucket.start;
+        self.oids[bucket].binary_search(oid).ok().map(|x| x + start)
+    }
+
+    /// Returns the offset into the packfile of the object `oid`, if this index contains it.
+    pub fn offset_of(&self, oid: &Digest) -> Option<u64> {
+        self.position(oid).map(|x| self.offsets[x])
+    }
+
+    pub fn contains(&self, oid: &Digest) -> bool {
+        self.position(oid).is_some()
+    }
+
+    /// Returns the oid of the object stored at `offset` in the packfile.
+    pub fn oid_at_offset(&self, offset: u64) -> Option<&Digest> {
+        self.offsets
+            .iter()
+            .position(|&x| x == offset)
+            .map(|x| &self.oids[x])
+    }
+
+    /// Returns every oid in this index whose hex representation starts with `prefix`.
+    pub fn prefix_match(&self, prefix: &str) -> Vec<Digest> {
+        let first_byte = match prefix.get(..2).map(|x| u8::from_str_radix(x, 16)) {
+            Some(Ok(x)) => x,
+            _ => return Vec::new(),
+        };
+
+        self.oids[self.bucket(first_byte)]
+            .iter()
+            .filter(|oid| oid.to_hex().starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    pub fn oids(&self) -> &[Digest] {
+        &self.oids
+    }
+
+    pub fn crcs(&self) -> &[u32] {
+        &self.crcs
+    }
+
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    pub fn pack_checksum(&self) -> &Digest {
+        &self.pack_checksum
+    }
+
+    pub fn len(&self) -> usize {
+        self.oids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.oids.is_empty()
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+}
@@ -0,0 +1,282 @@
+//! Reading and writing git packfiles.
+//!
+//! A pack is a pair of files in `objects/pack`: `pack-<checksum>.pack`, which holds the
+//! (compressed, and possibly deltified) objects, and `pack-<checksum>.idx`, which maps oids to
+//! offsets into the packfile.
+//!
+//! See: <https://git-scm.com/docs/pack-format>
+
+pub mod delta;
+mod index;
+mod write;
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::eyre;
+use flate2::bufread::ZlibDecoder;
+use tracing::trace;
+
+use crate::digest::Digest;
+use crate::Result;
+
+pub use self::delta::apply_delta;
+pub use self::index::PackIndex;
+pub use self::write::{name_hash, write_pack, PackObject};
+
+/// The type of an object, as stored in a pack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl ObjectKind {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            ObjectKind::Commit => "commit",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tag => "tag",
+        }
+    }
+
+    /// The 3-bit type number used in pack entry headers.
+    pub const fn type_number(&self) -> u8 {
+        match self {
+            ObjectKind::Commit => 1,
+            ObjectKind::Tree => 2,
+            ObjectKind::Blob => 3,
+            ObjectKind::Tag => 4,
+        }
+    }
+}
+
+impl std::str::FromStr for ObjectKind {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "commit" => Ok(ObjectKind::Commit),
+            "tree" => Ok(ObjectKind::Tree),
+            "blob" => Ok(ObjectKind::Blob),
+            "tag" => Ok(ObjectKind::Tag),
+            _ => Err(eyre!("Unexpected object type: {s}")),
+        }
+    }
+}
+
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+
+/// git never writes delta chains longer than this, so longer ones are corrupt, or loop.
+const MAX_DELTA_DEPTH: usize = 4095;
+
+/// Sizes read from a pack are only trusted this far when allocating, so that a corrupt size
+/// can't exhaust memory before the data is found not to match it.
+const MAX_PREALLOCATION: usize = 1 << 20;
+
+/// How an entry in a packfile is stored.
+#[derive(Debug)]
+enum EntryKind {
+    Whole(ObjectKind),
+    /// A delta against the object stored at this (absolute) offset in the same pack.
+    OfsDelta(u64),
+    /// A delta against the object with this oid, which may live anywhere in the database.
+    RefDelta(Digest),
+}
+
+/// An opened packfile, along with its index.
+pub struct Pack {
+    pack_path: Utf8PathBuf,
+    index: PackIndex,
+}
+
+impl Pack {
+    pub const MAGIC: &'static [u8; 4] = b"PACK";
+    pub const VERSION: u32 = 2;
+
+    /// Open a pack given the path to its `.idx` file.
+    pub fn open(idx_path: &Utf8Path) -> Result<Self> {
+        trace!(path=%idx_path, "Opening pack");
+        let index = PackIndex::open(idx_path)?;
+        let pack_path = idx_path.with_extension("pack");
+
+        if !pack_path.exists() {
+            return Err(eyre!(
+                "pack index has no corresponding packfile: {idx_path}"
+            ));
+        }
+
+        Ok(Self { pack_path, index })
+    }
+
+    /// Open every pack in `pack_dir`. Returns an empty `Vec` if the directory does not exist.
+    pub fn open_all(pack_dir: &Utf8Path) -> Result<Vec<Self>> {
+        if !pack_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut packs = Vec::new();
+        for entry in pack_dir.read_dir_utf8()? {
+            let entry = entry?;
+            if entry.path().extension() == Some("idx") {
+                packs.push(Self::open(entry.path())?);
+            }
+        }
+
+        Ok(packs)
+    }
+
+    pub fn index(&self) -> &PackIndex {
+        &self.index
+    }
+
+    pub fn pack_path(&self) -> &Utf8Path {
+        &self.pack_path
+    }
+
+    pub fn contains(&self, oid: &Digest) -> bool {
+        self.index.contains(oid)
+    }
+
+    /// Read the object `oid` from this pack, resolving any delta chains.
+    ///
+    /// `resolve_ref` is called to look up the bases of `REF_DELTA` entries, which are not
+    /// guaranteed to be in this pack.
+    pub fn read_object<F>(
+        &self,
+        oid: &Digest,
+        resolve_ref: F,
+    ) -> Result<Option<(ObjectKind, Vec<u8>)>>
+    where
+        F: Fn(&Digest) -> Result<(ObjectKind, Vec<u8>)>,
+    {
+        let offset = match self.index.offset_of(oid) {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        trace!(?oid, pack=%self.pack_path, offset, "Reading object from pack");
+
+        let mut reader = BufReader::new(File::open(&self.pack_path)?);
+        self.read_at(&mut reader, offset, &resolve_ref).map(Some)
+    }
+
+    /// Read the entry at `offset`, following any chain of deltas down to a whole object, and
+    /// then applying them back up.
+    fn read_at<F>(
+        &self,
+        reader: &mut BufReader<File>,
+        offset: u64,
+        resolve_ref: &F,
+    ) -> Result<(ObjectKind, Vec<u8>)>
+    where
+        F: Fn(&Digest) -> Result<(ObjectKind, Vec<u8>)>,
+    {
+        let mut deltas = Vec::new();
+        let mut entry_offset = offset;
+        let (kind, mut data) = loop {
+            if deltas.len() > MAX_DELTA_DEPTH {
+                return Err(eyre!("delta chain at offset {offset} is too long"));
+            }
+
+            let (kind, data) = read_entry(reader, entry_offset)?;
+            match kind {
+                EntryKind::Whole(kind) => break (kind, data),
+                EntryKind::OfsDelta(base_offset) => {
+                    deltas.push(data);
+                    entry_offset = base_offset;
+                }
+                EntryKind::RefDelta(base_oid) => {
+                    deltas.push(data);
+                    match self.index.offset_of(&base_oid) {
+                        Some(base_offset) => entry_offset = base_offset,
+                        None => break resolve_ref(&base_oid)?,
+                    }
+                }
+            }
+        };
+
+        for delta in deltas.iter().rev() {
+            data = apply_delta(&data, delta)?;
+        }
+        Ok((kind, data))
+    }
+}
+
+fn read_byte(reader: &mut impl Read) -> Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Read and decompress the entry at `offset`. Deltas are returned undecoded.
+fn read_entry(reader: &mut BufReader<File>, offset: u64) -> Result<(EntryKind, Vec<u8>)> {
+    reader.seek(SeekFrom::Start(offset))?;
+
+    // The entry header is a varint, where the first byte also holds the 3-bit type
+    let mut byte = read_byte(reader)?;
+    let type_number = (byte >> 4) & 0b111;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        if shift >= usize::BITS {
+            return Err(eyre!("size varint too long"));
+        }
+        byte = read_byte(reader)?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+
+    let kind = match type_number {
+        1 => EntryKind::Whole(ObjectKind::Commit),
+        2 => EntryKind::Whole(ObjectKind::Tree),
+        3 => EntryKind::Whole(ObjectKind::Blob),
+        4 => EntryKind::Whole(ObjectKind::Tag),
+        OBJ_OFS_DELTA => {
+            // Offsets are encoded big-endian, adding one for each continuation byte so that
+            // every offset has exactly one encoding
+            let mut byte = read_byte(reader)?;
+            let mut distance = (byte & 0x7f) as u64;
+            while byte & 0x80 != 0 {
+                if distance.leading_zeros() < 8 {
+                    return Err(eyre!("offset varint too long"));
+                }
+                byte = read_byte(reader)?;
+                distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+            }
+            if distance == 0 {
+                return Err(eyre!("OFS_DELTA at offset {offset} is its own base"));
+            }
+            let base_offset = offset
+                .checked_sub(distance)
+                .ok_or_else(|| eyre!("OFS_DELTA base is before the start of the pack"))?;
+            EntryKind::OfsDelta(base_offset)
+        }
+        OBJ_REF_DELTA => {
+            let mut oid = Digest::default();
+            reader.read_exact(&mut oid.0)?;
+            EntryKind::RefDelta(oid)
+        }
+        x => return Err(eyre!("invalid pack entry type: {x}")),
+    };
+
+    // Reading one byte more than expected is enough to tell that the size is wrong
+    let mut data = Vec::with_capacity(size.min(MAX_PREALLOCATION));
+    ZlibDecoder::new(reader)
+        .take(size as u64 + 1)
+        .read_to_end(&mut data)?;
+
+    if data.len() != size {
+        return Err(eyre!(
+            "pack entry size mismatch at offset {offset}: expected {size}, got {}",
+            data.len()
+        ));
+    }
+
+    Ok((kind, data))
+}
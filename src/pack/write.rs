@@ -0,0 +1,248 @@
+use std::io::Write;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use tracing::{debug, trace};
+
+use crate::digest::Digest;
+use crate::util;
+use crate::Result;
+
+use super::delta::create_delta;
+use super::{ObjectKind, Pack, PackIndex, OBJ_OFS_DELTA};
+
+/// The number of preceding objects that are tried as a delta base for each object.
+const WINDOW: usize = 10;
+
+/// The maximum length of a delta chain. Longer chains make reading objects slower.
+const MAX_DEPTH: usize = 50;
+
+/// An object to be written into a pack.
+pub struct PackObject {
+    pub oid: Digest,
+    pub kind: ObjectKind,
+    pub data: Vec<u8>,
+    /// A hash of the path this object was found at, used to put likely delta candidates near each
+    /// other. See [`name_hash`].
+    pub name_hash: u32,
+}
+
+/// Hash a path such that files with the same name (and, to a lesser extent, the same extension)
+/// hash to similar values. Only the last 16 non-whitespace characters contribute.
+///
+/// See: <https://github.com/git/git/blob/v2.39.0/pack-objects.h#L199-L214>
+pub fn name_hash(path: &str) -> u32 {
+    path.bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .fold(0u32, |hash, c| (hash >> 2).wrapping_add((c as u32) << 24))
+}
+
+/// How an object is represented in the pack being written.
+enum Representation {
+    Whole,
+    /// A delta against the object at this position in the write order.
+    Delta {
+        base: usize,
+        delta: Vec<u8>,
+    },
+}
+
+/// Choose a representation for each object. `objects` must already be in write order.
+fn find_deltas(objects: &[PackObject]) -> Vec<Representation> {
+    let mut representations = Vec::with_capacity(objects.len());
+    let mut depths = vec![0; objects.len()];
+
+    for (i, object) in objects.iter().enumerate() {
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        // Only bother with deltas that are considerably smaller than the object itself
+        let mut max_size = object.data.len() / 2;
+
+        for j in (i.saturating_sub(WINDOW)..i).rev() {
+            let base = &objects[j];
+            if base.kind != object.kind || depths[j] >= MAX_DEPTH || base.data.is_empty() {
+                continue;
+            }
+            let delta = create_delta(&base.data, &object.data);
+            if delta.len() < max_size {
+                max_size = delta.len();
+                best = Some((j, delta));
+            }
+        }
+
+        representations.push(match best {
+            Some((base, delta)) => {
+                depths[i] = depths[base] + 1;
+                Representation::Delta { base, delta }
+            }
+            None => Representation::Whole,
+        });
+    }
+
+    representations
+}
+
+fn write_entry_header(out: &mut Vec<u8>, type_number: u8, mut size: usize) {
+    let mut byte = (type_number << 4) | (size & 0x0f) as u8;
+    size >>= 4;
+    while size != 0 {
+        out.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    out.push(byte);
+}
+
+fn write_ofs_delta_offset(out: &mut Vec<u8>, mut distance: u64) {
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+    while distance != 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut e = ZlibEncoder::new(Vec::with_capacity(data.len() / 2), Compression::default());
+    e.write_all(data)?;
+    Ok(e.finish()?)
+}
+
+/// The information about a written object that goes into the pack index.
+struct WrittenEntry {
+    oid: Digest,
+    /// The CRC32 of the entry's header and compressed data, as it appears in the pack
+    crc: u32,
+    offset: u64,
+}
+
+/// Serialise `objects` as a version 2 packfile. Returns the pack, and the index entries for each
+/// object.
+fn build_pack(objects: &[PackObject]) -> Result<(Vec<u8>, Vec<WrittenEntry>)> {
+    let representations = find_deltas(objects);
+
+    let mut pack = Vec::new();
+    pack.extend_from_slice(Pack::MAGIC);
+    pack.extend_from_slice(&Pack::VERSION.to_be_bytes());
+    pack.extend_from_slice(&u32::try_from(objects.len())?.to_be_bytes());
+
+    let mut entries: Vec<WrittenEntry> = Vec::with_capacity(objects.len());
+    let mut deltas = 0;
+
+    for (object, representation) in objects.iter().zip(representations) {
+        let offset = pack.len() as u64;
+        let mut entry = Vec::new();
+        match representation {
+            Representation::Whole => {
+                write_entry_header(&mut entry, object.kind.type_number(), object.data.len());
+                entry.extend_from_slice(&compress(&object.data)?);
+            }
+            Representation::Delta { base, delta } => {
+                deltas += 1;
+                write_entry_header(&mut entry, OBJ_OFS_DELTA, delta.len());
+                write_ofs_delta_offset(&mut entry, offset - entries[base].offset);
+                entry.extend_from_slice(&compress(&delta)?);
+            }
+        }
+
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        pack.extend_from_slice(&entry);
+        entries.push(WrittenEntry {
+            oid: object.oid.clone(),
+            crc: crc.sum(),
+            offset,
+        });
+    }
+
+    let checksum = Digest::new(&pack);
+    pack.extend_from_slice(&checksum.0);
+
+    debug!(objects = objects.len(), deltas, "Built pack");
+
+    Ok((pack, entries))
+}
+
+/// Serialise a version 2 pack index for the given entries.
+fn build_index(mut entries: Vec<WrittenEntry>, pack_checksum: &Digest) -> Vec<u8> {
+    entries.sort_unstable_by(|a, b| a.oid.cmp(&b.oid));
+
+    let mut idx = Vec::new();
+    idx.extend_from_slice(PackIndex::MAGIC);
+    idx.extend_from_slice(&PackIndex::VERSION.to_be_bytes());
+
+    let mut fanout = [0u32; 256];
+    for entry in &entries {
+        fanout[entry.oid[0] as usize] += 1;
+    }
+    let mut total = 0;
+    for count in fanout.iter_mut() {
+        total += *count;
+        *count = total;
+        idx.extend_from_slice(&count.to_be_bytes());
+    }
+
+    for entry in &entries {
+        idx.extend_from_slice(&entry.oid.0);
+    }
+    for entry in &entries {
+        idx.extend_from_slice(&entry.crc.to_be_bytes());
+    }
+
+    let mut large_offsets = Vec::new();
+    for entry in &entries {
+        if entry.offset < 0x8000_0000 {
+            idx.extend_from_slice(&(entry.offset as u32).to_be_bytes());
+        } else {
+            let large_idx = large_offsets.len() as u32;
+            idx.extend_from_slice(&(large_idx | 0x8000_0000).to_be_bytes());
+            large_offsets.push(entry.offset);
+        }
+    }
+    for offset in large_offsets {
+        idx.extend_from_slice(&offset.to_be_bytes());
+    }
+
+    idx.extend_from_slice(&pack_checksum.0);
+    let checksum = Digest::new(&idx);
+    idx.extend_from_slice(&checksum.0);
+
+    idx
+}
+
+/// Write `objects` to a new pack (and index) in `pack_dir`, using delta compression where it is
+/// worthwhile. Returns the path of the new `.idx` file.
+///
+/// The pack is named after its checksum, as git does: `pack-<checksum>.pack`.
+pub fn write_pack(pack_dir: &Utf8Path, mut objects: Vec<PackObject>) -> Result<Utf8PathBuf> {
+    // Group objects that are likely to delta well against each other. Within a group, larger
+    // objects come first, as deleting data makes for smaller deltas than adding it.
+    objects.sort_by(|a, b| {
+        (a.kind.type_number(), a.name_hash, b.data.len()).cmp(&(
+            b.kind.type_number(),
+            b.name_hash,
+            a.data.len(),
+        ))
+    });
+
+    let (pack, entries) = build_pack(&objects)?;
+    let checksum = Digest(pack[pack.len() - 20..].try_into().unwrap());
+    let idx = build_index(entries, &checksum);
+
+    std::fs::create_dir_all(pack_dir)?;
+    let pack_path = pack_dir.join(format!("pack-{checksum:x}.pack"));
+    let idx_path = pack_path.with_extension("idx");
+
+    // Write the packfile before the index; readers discover packs through their index
+    for (path, data) in [(&pack_path, &pack), (&idx_path, &idx)] {
+        trace!(%path, "Writing pack file");
+        let temp_path = pack_dir.join(util::tmp_file_name());
+        std::fs::write(&temp_path, data)?;
+        std::fs::rename(temp_path, path)?;
+    }
+
+    Ok(idx_path)
+}
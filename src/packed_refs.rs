@@ -0,0 +1,288 @@
+//! Reading and writing `.git/packed-refs`.
+//!
+//! Rather than storing every ref in its own file under `refs/`, git can collect them into a
+//! single file, one ref per line. A loose ref file always takes precedence over a packed ref of
+//! the same name.
+//!
+//! ```text
+//! # pack-refs with: peeled fully-peeled sorted
+//! 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5 refs/heads/master
+//! 5bf6b30f18e6272846198abf178422543d618f24 refs/tags/v1.0.0
+//! ^090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5
+//! ```
+//!
+//! A line starting with `^` gives the object the annotated tag on the line before it ultimately
+//! points to.
+//!
+//! See: <https://git-scm.com/docs/git-pack-refs>
+
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::trace;
+
+use crate::digest::Digest;
+use crate::lockfile::Lockfile;
+use crate::Result;
+
+/// The header rit writes. `fully-peeled` promises that every ref that can be peeled has a `^`
+/// line, so readers don't need to look up refs without one.
+const HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+
+/// The header written back when the file read didn't promise that every ref was peeled.
+const PARTLY_PEELED_HEADER: &str = "# pack-refs with: sorted \n";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRef {
+    pub oid: Digest,
+    /// For an annotated tag, the non-tag object it ultimately points to
+    pub peeled: Option<Digest>,
+}
+
+#[derive(Debug, Default)]
+pub struct PackedRefs {
+    path: Utf8PathBuf,
+    refs: BTreeMap<String, PackedRef>,
+    /// Whether the file had the `fully-peeled` trait, so that a ref without a `^` line is known not
+    /// to be an annotated tag
+    fully_peeled: bool,
+    /// Held if the refs were read with [`PackedRefs::lock`], so can be written back
+    lock: Option<Lockfile>,
+}
+
+impl PackedRefs {
+    /// Read the `packed-refs` file in `git_dir`. A missing file is treated as empty.
+    pub fn load(git_dir: &Utf8Path) -> Result<Self> {
+        let path = git_dir.join("packed-refs");
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {path}")),
+        };
+
+        let (refs, fully_peeled) =
+            parse(&contents).wrap_err_with(|| format!("Bad packed-refs file: {path}"))?;
+        Ok(Self {
+            path,
+            refs,
+            fully_peeled,
+            lock: None,
+        })
+    }
+
+    /// Lock the `packed-refs` file in `git_dir`, then read it. The lock is held until the refs
+    /// are committed or dropped.
+    pub fn lock(git_dir: &Utf8Path) -> Result<Self> {
+        let lock = Lockfile::acquire(&git_dir.join("packed-refs"))?;
+        Ok(Self {
+            lock: Some(lock),
+            ..Self::load(git_dir)?
+        })
+    }
+
+    /// Look up a ref by its full name, such as `refs/heads/master`.
+    pub fn get(&self, name: &str) -> Option<&PackedRef> {
+        self.refs.get(name)
+    }
+
+    /// The object the ref `name` peels to, if the file records it: the `^` line for an annotated
+    /// tag, or the ref's own oid if the file is fully peeled. Otherwise the ref must be peeled by
+    /// reading it from the database.
+    pub fn peeled(&self, name: &str) -> Option<&Digest> {
+        let r = self.refs.get(name)?;
+        match &r.peeled {
+            Some(peeled) => Some(peeled),
+            None if self.fully_peeled => Some(&r.oid),
+            None => None,
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.refs.contains_key(name)
+    }
+
+    pub fn insert(&mut self, name: String, oid: Digest, peeled: Option<Digest>) {
+        self.refs.insert(name, PackedRef { oid, peeled });
+    }
+
+    /// Remove the ref `name`, returning it if it was present.
+    pub fn remove(&mut self, name: &str) -> Option<PackedRef> {
+        self.refs.remove(name)
+    }
+
+    /// Iterate over every packed ref, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &PackedRef)> {
+        self.refs.iter().map(|(name, r)| (name.as_str(), r))
+    }
+
+    /// Write the refs back to disk, releasing the lock.
+    pub fn commit(mut self) -> Result<()> {
+        let mut lock = self
+            .lock
+            .take()
+            .ok_or_else(|| eyre!("packed-refs must be locked before writing"))?;
+        trace!(path=%self.path, refs = self.refs.len(), "Writing packed-refs");
+        lock.write_all(self.to_string().as_bytes())?;
+        lock.commit()
+    }
+}
+
+/// Enough of the `packed-refs` file's metadata to tell whether it has changed since it was read.
+/// The file is only ever replaced by renaming a lockfile over it, which changes its inode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    inode: u64,
+    len: u64,
+    modified: SystemTime,
+}
+
+impl Stamp {
+    /// Returns `None` if the file does not exist.
+    fn of(path: &Utf8Path) -> Result<Option<Self>> {
+        match std::fs::metadata(path) {
+            Ok(metadata) => Ok(Some(Self {
+                inode: metadata.ino(),
+                len: metadata.len(),
+                modified: metadata.modified()?,
+            })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).wrap_err_with(|| format!("Failed to read {path}")),
+        }
+    }
+}
+
+/// The `packed-refs` file as last read, so that looking up many refs doesn't read it each time.
+/// It's read again whenever it has changed on disk.
+#[derive(Debug, Default)]
+pub struct PackedRefsCache {
+    cached: Mutex<Option<(Option<Stamp>, Arc<PackedRefs>)>>,
+}
+
+impl PackedRefsCache {
+    /// The current contents of the `packed-refs` file in `git_dir`.
+    pub fn get(&self, git_dir: &Utf8Path) -> Result<Arc<PackedRefs>> {
+        // The file is stamped before it's read, so a change in between is picked up next time
+        let stamp = Stamp::of(&git_dir.join("packed-refs"))?;
+        let mut cached = self
+            .cached
+            .lock()
+            .map_err(|_| eyre!("packed-refs cache poisoned"))?;
+        match &*cached {
+            Some((cached_stamp, packed)) if *cached_stamp == stamp => Ok(Arc::clone(packed)),
+            _ => {
+                let packed = Arc::new(PackedRefs::load(git_dir)?);
+                *cached = Some((stamp, Arc::clone(&packed)));
+                Ok(packed)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PackedRefs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Refs kept from a file that wasn't fully peeled may be missing their `^` lines
+        f.write_str(if self.fully_peeled {
+            HEADER
+        } else {
+            PARTLY_PEELED_HEADER
+        })?;
+        for (name, r) in &self.refs {
+            writeln!(f, "{:x} {name}", r.oid)?;
+            if let Some(peeled) = &r.peeled {
+                writeln!(f, "^{peeled:x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse the contents of a `packed-refs` file, also returning whether its header has the
+/// `fully-peeled` trait. An empty file has no refs to peel, so counts as fully peeled.
+fn parse(contents: &str) -> Result<(BTreeMap<String, PackedRef>, bool)> {
+    let mut refs = BTreeMap::new();
+    let mut last: Option<String> = None;
+    let mut fully_peeled = contents.is_empty();
+
+    for line in contents.lines() {
+        if let Some(traits) = line.strip_prefix("# pack-refs with:") {
+            fully_peeled = traits.split_whitespace().any(|x| x == "fully-peeled");
+            continue;
+        }
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+
+        if let Some(peeled) = line.strip_prefix('^') {
+            let r: &mut PackedRef = last
+                .as_ref()
+                .and_then(|name| refs.get_mut(name))
+                .ok_or_else(|| eyre!("peeled line without a ref: {line}"))?;
+            r.peeled = Some(Digest::from_str(peeled)?);
+            continue;
+        }
+
+        let (oid, name) = line
+            .split_once(' ')
+            .ok_or_else(|| eyre!("unexpected line: {line}"))?;
+        let oid = Digest::from_str(oid)?;
+        refs.insert(name.to_owned(), PackedRef { oid, peeled: None });
+        last = Some(name.to_owned());
+    }
+
+    Ok((refs, fully_peeled))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let contents = "# pack-refs with: peeled fully-peeled sorted \n\
+                        090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5 refs/heads/master\n\
+                        5bf6b30f18e6272846198abf178422543d618f24 refs/tags/v1.0.0\n\
+                        ^090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5\n";
+
+        let (refs, fully_peeled) = parse(contents)?;
+        let refs = PackedRefs {
+            refs,
+            fully_peeled,
+            ..Default::default()
+        };
+
+        let master = Digest::from_str("090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5")?;
+        assert_eq!(refs.get("refs/heads/master").unwrap().oid, master);
+        assert_eq!(refs.get("refs/heads/master").unwrap().peeled, None);
+        assert_eq!(
+            refs.get("refs/tags/v1.0.0").unwrap().peeled,
+            Some(master.clone())
+        );
+        assert_eq!(refs.peeled("refs/heads/master"), Some(&master));
+        assert_eq!(refs.peeled("refs/tags/v1.0.0"), Some(&master));
+        assert_eq!(refs.to_string(), contents);
+
+        // Without `fully-peeled`, a ref with no `^` line may still be an annotated tag
+        let contents = "# pack-refs with: sorted \n\
+                        5bf6b30f18e6272846198abf178422543d618f24 refs/tags/v1.0.0\n";
+        let (refs, fully_peeled) = parse(contents)?;
+        let refs = PackedRefs {
+            refs,
+            fully_peeled,
+            ..Default::default()
+        };
+        assert_eq!(refs.peeled("refs/tags/v1.0.0"), None);
+        assert_eq!(refs.to_string(), contents);
+
+        assert!(parse("^090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5\n").is_err());
+        assert!(parse("not a ref\n").is_err());
+
+        Ok(())
+    }
+}
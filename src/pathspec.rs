@@ -0,0 +1,148 @@
+//! Pathspecs, which limit a command to some of the paths in the workspace.
+//!
+//! A pathspec is either a path, which matches that file and everything under that directory, or
+//! a glob, which is matched against whole paths with wildcards that also match `/`.
+//!
+//! See: <https://git-scm.com/docs/gitglossary#Documentation/gitglossary.txt-aiddefpathspecapathspec>
+
+use camino::Utf8Path;
+
+use crate::ignore::fnmatch;
+
+/// A single pathspec.
+#[derive(Debug, Clone)]
+struct Item {
+    /// The pathspec, without any leading `./` or trailing `/`. Empty for the whole workspace
+    path: String,
+    /// The pathspec as a glob, if it has any wildcards
+    glob: Option<Vec<char>>,
+    /// The length of the part before the first wildcard, which every match starts with
+    prefix_len: usize,
+}
+
+impl Item {
+    fn parse(spec: &str) -> Self {
+        let mut path = spec;
+        while let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        }
+        let path = match path.trim_end_matches('/') {
+            "." => "",
+            path => path,
+        };
+
+        let prefix_len = path.find(['*', '?', '[', '\\']).unwrap_or(path.len());
+        let glob = (prefix_len < path.len()).then(|| path.chars().collect());
+
+        Self {
+            path: path.to_owned(),
+            glob,
+            prefix_len,
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        if is_within(path, &self.path) {
+            return true;
+        }
+        match &self.glob {
+            Some(glob) => fnmatch(glob, &path.chars().collect::<Vec<_>>()),
+            None => false,
+        }
+    }
+
+    fn may_match_under(&self, dir: &str) -> bool {
+        let dir = format!("{dir}/");
+        let prefix = &self.path[..self.prefix_len];
+        if prefix.starts_with(&dir) {
+            return true;
+        }
+        match &self.glob {
+            // The wildcards may match the rest of the directory's name, and what is inside it
+            Some(_) => dir.starts_with(prefix),
+            None => is_within(&dir[..dir.len() - 1], &self.path),
+        }
+    }
+}
+
+/// Whether `path` is `dir`, or is inside it.
+fn is_within(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// A list of pathspecs, relative to the root of the workspace. A path is included if it matches
+/// any of them, or if there are none.
+#[derive(Debug, Clone, Default)]
+pub struct Pathspec {
+    items: Vec<Item>,
+}
+
+impl Pathspec {
+    pub fn new(specs: &[impl AsRef<str>]) -> Self {
+        Self {
+            items: specs
+                .iter()
+                .map(|spec| Item::parse(spec.as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Whether the file at `path` is included.
+    pub fn matches(&self, path: &Utf8Path) -> bool {
+        self.items.is_empty() || self.items.iter().any(|item| item.matches(path.as_str()))
+    }
+
+    /// Whether anything inside the directory `dir` could be included, and so whether it is worth
+    /// looking inside.
+    pub fn may_match_under(&self, dir: &Utf8Path) -> bool {
+        self.items.is_empty()
+            || self
+                .items
+                .iter()
+                .any(|item| item.may_match_under(dir.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pathspec() {
+        let spec = Pathspec::new(&["./src/", "docs/*.md", "README"]);
+        let matches = |path: &str| spec.matches(Utf8Path::new(path));
+        let under = |dir: &str| spec.may_match_under(Utf8Path::new(dir));
+
+        assert!(matches("src/main.rs"));
+        assert!(matches("src/repo/diff.rs"));
+        assert!(!matches("srcs/main.rs"));
+        assert!(matches("docs/intro.md"));
+        assert!(matches("docs/guide/advanced.md"));
+        assert!(!matches("docs/intro.txt"));
+        assert!(matches("README"));
+        assert!(!matches("README.md"));
+
+        assert!(under("src"));
+        assert!(under("src/repo"));
+        assert!(under("docs"));
+        assert!(under("docs/guide"));
+        assert!(!under("srcs"));
+        // A path may name a directory
+        assert!(under("README"));
+        assert!(!under("READ"));
+        assert!(!under("tests"));
+
+        let prefix = Pathspec::new(&["src/ma*"]);
+        assert!(prefix.may_match_under(Utf8Path::new("src")));
+        assert!(prefix.may_match_under(Utf8Path::new("src/main")));
+        assert!(!prefix.may_match_under(Utf8Path::new("src/repo")));
+
+        let everything = Pathspec::new(&["."]);
+        assert!(everything.matches(Utf8Path::new("any/path")));
+        assert!(everything.may_match_under(Utf8Path::new("any")));
+        assert!(Pathspec::default().matches(Utf8Path::new("any/path")));
+    }
+}
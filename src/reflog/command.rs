@@ -0,0 +1,142 @@
+use color_eyre::eyre::{eyre, Context};
+
+use crate::interface::{ReflogAction, ReflogArgs};
+use crate::repo::Repo;
+use crate::timestamp::Timestamp;
+use crate::Result;
+
+const DEFAULT_EXPIRE: &str = "90.days.ago";
+const DEFAULT_EXPIRE_UNREACHABLE: &str = "30.days.ago";
+
+pub fn handle(repo: &mut Repo, args: &ReflogArgs) -> Result<()> {
+    match &args.action {
+        None => show(repo, None, None),
+        Some(ReflogAction::Show {
+            ref_name,
+            max_count,
+        }) => show(repo, ref_name.as_deref(), *max_count),
+        Some(ReflogAction::Expire {
+            expire,
+            expire_unreachable,
+            all,
+            refs,
+        }) => {
+            let now = Timestamp::now().0.timestamp();
+            let expire = expire
+                .as_deref()
+                .or_else(|| repo.config.get("gc.reflogexpire"))
+                .unwrap_or(DEFAULT_EXPIRE);
+            let expire_unreachable = expire_unreachable
+                .as_deref()
+                .or_else(|| repo.config.get("gc.reflogexpireunreachable"))
+                .unwrap_or(DEFAULT_EXPIRE_UNREACHABLE);
+            let expire = parse_expiry(expire, now)?;
+            let expire_unreachable = parse_expiry(expire_unreachable, now)?;
+
+            let names = if *all {
+                repo.reflog_names()?
+            } else if refs.is_empty() {
+                return Err(eyre!(
+                    "no reflog specified; use --all to expire every reflog"
+                ));
+            } else {
+                refs.iter()
+                    .map(|name| full_name(repo, name))
+                    .collect::<Result<_>>()?
+            };
+
+            for name in names {
+                repo.expire_reflog(&name, expire, expire_unreachable)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn show(repo: &Repo, name: Option<&str>, max_count: Option<usize>) -> Result<()> {
+    let display_name = name.unwrap_or("HEAD");
+    let full_name = full_name(repo, display_name)?;
+
+    let entries = repo.read_reflog(&full_name)?;
+    let abbrev = repo.abbrev_len()?;
+    for (i, entry) in entries
+        .iter()
+        .rev()
+        .enumerate()
+        .take(max_count.unwrap_or(usize::MAX))
+    {
+        println!(
+            "{} {display_name}@{{{i}}}: {}",
+            repo.database.abbreviate(&entry.new, abbrev)?,
+            entry.message
+        );
+    }
+
+    Ok(())
+}
+
+/// The full name of the ref `name`, which must exist.
+fn full_name(repo: &Repo, name: &str) -> Result<String> {
+    repo.expand_ref_name(name)?
+        .ok_or_else(|| eyre!("ambiguous argument '{name}': unknown revision or path"))
+}
+
+/// Parse an expiry time for `reflog expire`, such as `90.days.ago`, `2 weeks ago`, `now`, or
+/// `never`, into a unix timestamp relative to `now`. Returns `None` for times that never expire
+/// anything.
+fn parse_expiry(value: &str, now: i64) -> Result<Option<i64>> {
+    match value {
+        "now" | "all" => return Ok(Some(i64::MAX)),
+        "never" | "false" => return Ok(None),
+        _ => {}
+    }
+
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(Some(timestamp));
+    }
+
+    let words = value
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+
+    let (count, unit) = match words.as_slice() {
+        [count, unit] | [count, unit, "ago"] => (count, unit),
+        _ => return Err(eyre!("invalid expiry time: '{value}'")),
+    };
+
+    let count = count
+        .parse::<i64>()
+        .wrap_err_with(|| format!("invalid expiry time: '{value}'"))?;
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        "month" => 30 * 24 * 60 * 60,
+        "year" => 365 * 24 * 60 * 60,
+        _ => return Err(eyre!("invalid expiry time: '{value}'")),
+    };
+
+    Ok(Some(now - count * seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expiry() -> Result<()> {
+        let now = 1_000_000_000;
+        assert_eq!(parse_expiry("90.days.ago", now)?, Some(now - 90 * 86400));
+        assert_eq!(parse_expiry("2 weeks ago", now)?, Some(now - 14 * 86400));
+        assert_eq!(parse_expiry("1.hour", now)?, Some(now - 3600));
+        assert_eq!(parse_expiry("now", now)?, Some(i64::MAX));
+        assert_eq!(parse_expiry("never", now)?, None);
+        assert_eq!(parse_expiry("12345", now)?, Some(12345));
+        assert!(parse_expiry("yesterday-ish", now).is_err());
+        assert!(parse_expiry("3.fortnights.ago", now).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! The format of reflog files, under `.git/logs`.
+//!
+//! Each line records one update to a ref, oldest first:
+//!
+//! ```text
+//! <old oid> SP <new oid> SP <name> SP <<email>> SP <timestamp> SP <tz> [TAB <message>] LF
+//! ```
+//!
+//! The old oid is all zeros when the ref was created.
+//!
+//! See: <https://git-scm.com/docs/git-reflog>
+
+pub mod command;
+
+use std::str::FromStr;
+
+use camino::Utf8Path;
+use color_eyre::eyre::{eyre, Context};
+
+use crate::commit::Signature;
+use crate::digest::Digest;
+use crate::Result;
+
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old: Digest,
+    pub new: Digest,
+    pub committer: Signature,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    pub fn new(old: Option<&Digest>, new: &Digest, committer: Signature, message: &str) -> Self {
+        Self {
+            old: old.cloned().unwrap_or(Digest::NULL),
+            new: new.clone(),
+            committer,
+            message: sanitize_message(message),
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        let (line, message) = line.split_once('\t').unwrap_or((line, ""));
+        let (old, rest) = line
+            .split_once(' ')
+            .ok_or_else(|| eyre!("bad reflog entry: {line}"))?;
+        let (new, committer) = rest
+            .split_once(' ')
+            .ok_or_else(|| eyre!("bad reflog entry: {line}"))?;
+
+        Ok(Self {
+            old: Digest::from_str(old)?,
+            new: Digest::from_str(new)?,
+            committer: Signature::parse(committer.as_bytes())?,
+            message: message.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for ReflogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:x} {:x} {}", self.old, self.new, self.committer)?;
+        if !self.message.is_empty() {
+            write!(f, "\t{}", self.message)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Read every entry of the reflog at `path`, oldest first. A missing reflog is empty.
+pub fn read(path: &Utf8Path) -> Result<Vec<ReflogEntry>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read reflog {path}")),
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ReflogEntry::parse)
+        .collect::<Result<_>>()
+        .wrap_err_with(|| format!("Bad reflog {path}"))
+}
+
+/// Reflog messages are a single line, so collapse any runs of whitespace (including newlines)
+/// into single spaces, as git does.
+fn sanitize_message(message: &str) -> String {
+    message.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let line = "0000000000000000000000000000000000000000 \
+                    090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5 \
+                    Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100\tcommit (initial): first\n";
+
+        let entry = ReflogEntry::parse(line.trim_end())?;
+        assert_eq!(entry.old, Digest::NULL);
+        assert_eq!(entry.committer.email(), "jamie@quigley.xyz");
+        assert_eq!(entry.message, "commit (initial): first");
+        assert_eq!(entry.to_string(), line);
+
+        // No message means no tab
+        let entry = ReflogEntry::new(None, &entry.new, entry.committer, "");
+        assert!(!entry.to_string().contains('\t'));
+
+        assert_eq!(
+            sanitize_message("merge\n\n  two\tlines "),
+            "merge two lines"
+        );
+        Ok(())
+    }
+}
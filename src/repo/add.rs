@@ -2,13 +2,14 @@ use camino::Utf8PathBuf;
 use color_eyre::eyre::{eyre, Context};
 use tracing::trace;
 
-use crate::{blob::Blob, storable::DatabaseObject, Result};
+use crate::{blob::Blob, filemode::FileMode, storable::DatabaseObject, Result};
 
 impl super::Repo {
     /// Add paths to the index.
     ///
     /// if `paths` is empty, do nothing
     pub fn add(&mut self, paths: &[Utf8PathBuf]) -> Result<()> {
+        let trust_filemode = self.trust_filemode()?;
         for path in paths {
             trace!(?path, "Adding file to repo");
             if !self.dir.join(path).exists() {
@@ -27,7 +28,14 @@ impl super::Repo {
 
                 let data = std::fs::read(&abs_path)
                     .wrap_err(format!("Failed to read file: {}", abs_path))?;
-                let stat = Self::stat_file(&abs_path)?.unwrap();
+                let mut stat = Self::stat_file(&abs_path)?.unwrap();
+                if !trust_filemode {
+                    // Keep the mode that is already recorded, as the one on disk is meaningless
+                    stat.st_mode = match self.index.get_entry_by_path(path) {
+                        Some(entry) => entry.mode().inner(),
+                        None => FileMode::Regular.inner(),
+                    };
+                }
 
                 let blob = Blob::new(data);
                 let blob = DatabaseObject::new(&blob);
@@ -0,0 +1,33 @@
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::attributes::{AttrList, Attributes};
+
+impl super::Repo {
+    /// Load the attributes for this repository. `.gitattributes` files in the workspace are read
+    /// lazily, as paths in their directories are looked up.
+    pub fn attributes(&self) -> Attributes {
+        let root = Utf8Path::new("");
+        let info = AttrList::from_file(&self.git_dir.join("info/attributes"), root);
+        let global = match self.attributes_file() {
+            Some(path) => AttrList::from_file(&path, root),
+            None => AttrList::default(),
+        };
+
+        Attributes::new(&self.dir, info, global)
+    }
+
+    /// The path of the user's global attributes file. This is `core.attributesFile` if it is set,
+    /// falling back to `$XDG_CONFIG_HOME/git/attributes`.
+    fn attributes_file(&self) -> Option<Utf8PathBuf> {
+        // An unexpandable path is treated as unset
+        if let Ok(Some(path)) = self.config.get_path("core.attributesfile") {
+            return Some(path);
+        }
+
+        let home = std::env::var("HOME").ok().map(Utf8PathBuf::from);
+        match std::env::var("XDG_CONFIG_HOME") {
+            Ok(xdg) if !xdg.is_empty() => Some(Utf8PathBuf::from(xdg).join("git/attributes")),
+            _ => home.map(|home| home.join(".config/git/attributes")),
+        }
+    }
+}
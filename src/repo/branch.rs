@@ -1,10 +1,232 @@
+use std::io::Write;
+
+use color_eyre::eyre::eyre;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+use crate::digest::Digest;
+use crate::ignore::wildmatch;
+use crate::lockfile::Lockfile;
+use crate::merge;
+use crate::revision::is_valid_ref_name;
+use crate::Result;
+
+use super::transaction::{Expected, RefTransaction};
+
+/// Where a branch's reflog is kept while the branch is being renamed, as git does.
+const TMP_RENAMED_LOG: &str = "refs/.tmp-renamed-log";
+
 impl super::Repo {
-    // pub fn branch(&mut self, name: Option<&str>, _delete: bool) -> Result<()> {
-    //     if let Some(name) = name {
-    //         self.create_branch(name)?;
-    //     } else {
-    //         eprintln!("NYI");
-    //     }
-    //     Ok(())
-    // }
+    /// Returns `true` if the branch `name` exists, either as a loose ref or in `packed-refs`.
+    pub fn branch_exists(&self, name: &str) -> Result<bool> {
+        self.ref_exists(&format!("refs/heads/{name}"))
+    }
+
+    /// The branches whose names match any of `patterns`, or every branch if there are none,
+    /// sorted by name along with the commits they point to.
+    pub fn list_branches(&self, patterns: &[String]) -> Result<Vec<(String, Digest)>> {
+        let patterns = patterns
+            .iter()
+            .map(|x| x.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut branches = Vec::new();
+
+        for (name, oid) in self.list_refs()? {
+            let name = match name.strip_prefix("refs/heads/") {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let chars = name.chars().collect::<Vec<_>>();
+            if !patterns.is_empty() && !patterns.iter().any(|x| wildmatch(x, &chars)) {
+                continue;
+            }
+            branches.push((name.to_owned(), oid));
+        }
+
+        Ok(branches)
+    }
+
+    /// Print the branches matching `patterns` as `git branch` does, marking the current branch.
+    /// With `verbose`, each branch is followed by its tip's abbreviated oid and subject.
+    pub fn print_branches(&self, patterns: &[String], verbose: bool) -> Result<()> {
+        let current = self.current_branch()?;
+        let abbrev = self.abbrev_len()?;
+        let mut branches = self
+            .list_branches(patterns)?
+            .into_iter()
+            .map(|(name, oid)| {
+                let is_current = current.as_ref() == Some(&name);
+                (name, oid, is_current)
+            })
+            .collect::<Vec<_>>();
+
+        // A detached HEAD is listed first, in place of a branch
+        if current.is_none() && patterns.is_empty() {
+            if let Some(head) = self.read_head()? {
+                let name = format!(
+                    "(HEAD detached at {})",
+                    self.database.abbreviate(&head, abbrev)?
+                );
+                branches.insert(0, (name, head, true));
+            }
+        }
+
+        let width = branches
+            .iter()
+            .map(|(name, _, _)| name.chars().count())
+            .max()
+            .unwrap_or_default();
+
+        let mut writer = StandardStream::stdout(ColorChoice::Auto);
+        for (name, oid, is_current) in branches {
+            if is_current {
+                write!(&mut writer, "* ")?;
+                writer.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+            } else {
+                write!(&mut writer, "  ")?;
+            }
+
+            if verbose {
+                write!(&mut writer, "{name:width$}")?;
+            } else {
+                write!(&mut writer, "{name}")?;
+            }
+            if is_current {
+                writer.reset()?;
+            }
+
+            if verbose {
+                let subject = self.database.load_commit(&oid)?.subject();
+                write!(
+                    &mut writer,
+                    " {} {subject}",
+                    self.database.abbreviate(&oid, abbrev)?
+                )?;
+            }
+            writeln!(&mut writer)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Delete the branch `name`, returning the oid it pointed to. The branch HEAD is on cannot be
+    /// deleted. Unless `force` is set, neither can a branch whose commits aren't all reachable
+    /// from HEAD, as they could be lost.
+    pub fn delete_branch(&mut self, name: &str, force: bool) -> Result<Digest> {
+        let tip = self
+            .read_full_ref(&format!("refs/heads/{name}"))?
+            .ok_or_else(|| eyre!("branch '{name}' not found."))?;
+
+        if self.current_branch()?.as_deref() == Some(name) {
+            return Err(eyre!(
+                "Cannot delete branch '{name}' checked out at '{}'",
+                self.dir
+            ));
+        }
+
+        if !force {
+            let merged = match self.read_head()? {
+                Some(head) => merge::is_ancestor(&self.database, &tip, &head)?,
+                None => false,
+            };
+            if !merged {
+                return Err(eyre!(
+                    "The branch '{name}' is not fully merged.\n\
+                     If you are sure you want to delete it, run 'rit branch -D {name}'."
+                ));
+            }
+        }
+
+        self.delete_ref(&format!("refs/heads/{name}"))
+    }
+
+    /// Rename the branch `old` to `new`, keeping its reflog, and moving HEAD along with it if it
+    /// is on `old`. Unless `force` is set, `new` must not already exist.
+    pub fn rename_branch(&mut self, old: &str, new: &str, force: bool) -> Result<()> {
+        if !is_valid_ref_name(new) {
+            return Err(eyre!("'{new}' is not a valid branch name"));
+        }
+
+        let old_ref = format!("refs/heads/{old}");
+        let new_ref = format!("refs/heads/{new}");
+        let current = self.current_branch()?;
+        let on_old = current.as_deref() == Some(old);
+
+        if old != new && self.branch_exists(new)? {
+            if !force {
+                return Err(eyre!("a branch named '{new}' already exists"));
+            }
+            // The work tree would no longer match the branch it is on
+            if current.as_deref() == Some(new) {
+                return Err(eyre!(
+                    "cannot force update the branch '{new}' checked out at '{}'",
+                    self.dir
+                ));
+            }
+        }
+
+        match self.read_full_ref(&old_ref)? {
+            Some(_) if old == new => {}
+            Some(oid) => {
+                // The old branch and its reflog are moved out of the way first, as the old name
+                // may block the new one, such as when renaming `x` to `x/y`
+                let has_log = self.reflog_path(&old_ref).is_file();
+                if has_log {
+                    self.rename_reflog(&old_ref, TMP_RENAMED_LOG)?;
+                }
+
+                let mut transaction = RefTransaction::new();
+                transaction
+                    .no_deref()
+                    .delete(&old_ref, Expected::Value(oid.clone()));
+                if let Err(e) = self.commit_transaction(transaction) {
+                    if has_log {
+                        self.rename_reflog(TMP_RENAMED_LOG, &old_ref)?;
+                    }
+                    return Err(e);
+                }
+
+                let mut transaction = RefTransaction::new();
+                transaction
+                    .no_deref()
+                    .message(&format!("Branch: renamed {old_ref} to {new_ref}"))
+                    .update(&new_ref, &oid, Expected::Any);
+                if let Err(e) = self.commit_transaction(transaction) {
+                    // Put the old branch back as it was
+                    let mut transaction = RefTransaction::new();
+                    transaction.no_deref().create(&old_ref, &oid);
+                    self.commit_transaction(transaction)?;
+                    if has_log {
+                        self.rename_reflog(TMP_RENAMED_LOG, &old_ref)?;
+                    }
+                    return Err(e);
+                }
+
+                // The new branch takes over the old one's history, followed by the rename itself
+                if has_log {
+                    let log = std::fs::read(self.reflog_path(TMP_RENAMED_LOG))?;
+                    let renamed = self.read_reflog(&new_ref)?.pop();
+                    let mut lock = Lockfile::acquire(&self.reflog_path(&new_ref))?;
+                    lock.write_all(&log)?;
+                    if let Some(entry) = renamed {
+                        lock.write_all(entry.to_string().as_bytes())?;
+                    }
+                    lock.commit()?;
+                    self.delete_reflog(TMP_RENAMED_LOG)?;
+                }
+            }
+            // The current branch may not have any commits yet, in which case only HEAD changes
+            None if on_old => {}
+            None => return Err(eyre!("no branch named '{old}'")),
+        }
+
+        if on_old {
+            let mut lock = Lockfile::acquire(&self.head_path)?;
+            lock.write_all(format!("ref: {new_ref}\n").as_bytes())?;
+            lock.commit()?;
+        }
+
+        Ok(())
+    }
 }
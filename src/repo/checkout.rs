@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashSet};
+
+use camino::Utf8PathBuf;
+use color_eyre::eyre::eyre;
+use tracing::{debug, trace};
+
+use crate::digest::Digest;
+use crate::filemode::FileMode;
+use crate::revision::Rev;
+use crate::tree::{RawTreeEntry, Tree};
+use crate::Result;
+
+use super::status::{Change, Status};
+
+/// Where HEAD was left by a checkout.
+pub enum CheckoutOutcome {
+    /// HEAD is a symbolic ref to this branch
+    Branch(String),
+    /// HEAD points directly at this commit
+    Detached(Digest),
+}
+
+/// A single change to make to the workspace and index to move from one tree to another.
+#[derive(Debug)]
+enum Migration {
+    Write(RawTreeEntry),
+    Remove,
+}
+
+impl super::Repo {
+    /// Move HEAD to `rev`, updating the workspace and index to match its tree.
+    ///
+    /// If `rev` names a branch (and `detach` is not set), HEAD is left pointing at that branch.
+    /// Otherwise, HEAD is detached at the commit `rev` resolves to. Local changes to files that
+    /// are the same in HEAD and `rev` are carried over; if any other file has local changes, or
+    /// an untracked file is in the way, nothing is changed.
+    pub fn checkout(&mut self, rev: &str, detach: bool) -> Result<CheckoutOutcome> {
+        let target = Rev::parse(rev)?
+            .resolve(self)?
+            .ok_or_else(|| eyre!("pathspec '{rev}' did not match any revision known to rit"))?;
+
+        let branch = if !detach && self.branch_exists(rev)? {
+            Some(rev.to_owned())
+        } else {
+            None
+        };
+
+        let head_tree = match self.read_head()? {
+            Some(head) => {
+                Tree::flatten(&self.database, self.database.load_commit(&head)?.tree_id())?
+            }
+            None => BTreeMap::new(),
+        };
+        let target_tree = Tree::flatten(
+            &self.database,
+            self.database.load_commit(&target)?.tree_id(),
+        )?;
+
+        let migration = tree_migration(&head_tree, &target_tree);
+        debug!(?target, changes = migration.len(), "Checking out");
+
+        self.check_local_changes(migration.keys())?;
+        self.check_untracked_overwrites(
+            migration
+                .iter()
+                .filter(|(_, change)| matches!(change, Migration::Write(_)))
+                .map(|(path, _)| path),
+            "checkout",
+        )?;
+
+        let from = match self.current_branch()? {
+            Some(branch) => branch,
+            None => self.read_head()?.map(|x| x.to_hex()).unwrap_or_default(),
+        };
+        let message = format!("checkout: moving from {from} to {rev}");
+
+        self.apply_migration(&migration)?;
+
+        Ok(match branch {
+            Some(branch) => {
+                self.attach_head(&branch, &message)?;
+                CheckoutOutcome::Branch(branch)
+            }
+            None => {
+                self.detach_head(&target, &message)?;
+                CheckoutOutcome::Detached(target)
+            }
+        })
+    }
+
+    /// Refuse to check out if any of `paths` have uncommitted changes, staged or otherwise.
+    fn check_local_changes<'a>(&self, paths: impl Iterator<Item = &'a Utf8PathBuf>) -> Result<()> {
+        let status = match Status::new(self)? {
+            Some(status) => status,
+            None => return Ok(()),
+        };
+
+        let dirty = status
+            .get_statuses()?
+            .into_iter()
+            .filter(|(_, change)| *change != Change::Untracked)
+            .map(|(path, _)| path.to_owned())
+            .collect::<HashSet<_>>();
+
+        let clobbered = paths
+            .filter(|path| dirty.contains(path.as_path()))
+            .map(|path| path.to_string())
+            .collect::<Vec<_>>();
+
+        if clobbered.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "Your local changes to the following files would be overwritten by checkout:\n\t{}\nPlease commit your changes before you switch branches.",
+                clobbered.join("\n\t")
+            ))
+        }
+    }
+
+    /// Write a tree migration into the workspace and the index.
+    fn apply_migration(&mut self, migration: &BTreeMap<Utf8PathBuf, Migration>) -> Result<()> {
+        // Removals go first, so that a file being replaced by a directory (or vice versa) is out
+        // of the way by the time it is written
+        for (path, change) in migration {
+            if let Migration::Remove = change {
+                trace!(%path, "Removing file");
+                self.remove_workspace_file(path)?;
+                self.index.remove(path);
+            }
+        }
+
+        for (path, change) in migration {
+            if let Migration::Write(entry) = change {
+                trace!(%path, ?entry, "Writing file");
+                let (_, data) = self.database.read_raw(&entry.oid)?;
+                self.write_workspace_file(path, &data, FileMode::from(entry.mode))?;
+                self.index.remove(path);
+                self.add_to_index(path, &entry.oid)?;
+            }
+        }
+
+        self.index.flush()?;
+        Ok(())
+    }
+}
+
+/// Compute the changes needed to turn the flattened tree `from` into `to`. Paths that are the same
+/// in both are omitted.
+fn tree_migration(
+    from: &BTreeMap<Utf8PathBuf, RawTreeEntry>,
+    to: &BTreeMap<Utf8PathBuf, RawTreeEntry>,
+) -> BTreeMap<Utf8PathBuf, Migration> {
+    let mut migration = BTreeMap::new();
+
+    for (path, entry) in to {
+        if from.get(path) != Some(entry) {
+            migration.insert(path.clone(), Migration::Write(entry.clone()));
+        }
+    }
+
+    for path in from.keys() {
+        if !to.contains_key(path) {
+            migration.insert(path.clone(), Migration::Remove);
+        }
+    }
+
+    migration
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+
+    use super::*;
+
+    fn entry(mode: libc::mode_t, byte: u8) -> RawTreeEntry {
+        RawTreeEntry {
+            mode,
+            name: String::new(),
+            oid: Digest([byte; 20]),
+        }
+    }
+
+    #[test]
+    fn test_tree_migration() {
+        let from = BTreeMap::from([
+            (Utf8PathBuf::from("same"), entry(0o100644, 1)),
+            (Utf8PathBuf::from("changed"), entry(0o100644, 2)),
+            (Utf8PathBuf::from("chmod"), entry(0o100644, 3)),
+            (Utf8PathBuf::from("removed"), entry(0o100644, 4)),
+        ]);
+        let to = BTreeMap::from([
+            (Utf8PathBuf::from("same"), entry(0o100644, 1)),
+            (Utf8PathBuf::from("changed"), entry(0o100644, 5)),
+            (Utf8PathBuf::from("chmod"), entry(0o100755, 3)),
+            (Utf8PathBuf::from("added"), entry(0o100644, 6)),
+        ]);
+
+        let migration = tree_migration(&from, &to);
+        let summary = migration
+            .iter()
+            .map(|(path, change)| (path.as_str(), matches!(change, Migration::Write(_))))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            [
+                ("added", true),
+                ("changed", true),
+                ("chmod", true),
+                ("removed", false)
+            ]
+        );
+        assert!(!migration.contains_key(Utf8Path::new("same")));
+    }
+}
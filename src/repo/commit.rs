@@ -1,41 +1,531 @@
-use tracing::trace;
+use std::fmt::Write;
+use std::io::Read;
 
-use crate::commit::Commit;
+use camino::Utf8PathBuf;
+use color_eyre::eyre::{eyre, Context};
+use regex::Regex;
+use tracing::{debug, trace};
+
+use crate::commit::cleanup::{cleanup_message, Cleanup, SCISSORS};
+use crate::commit::{Commit, CommitWalk, Signature};
 use crate::digest::Digest;
+use crate::index::IndexWrapper;
+use crate::pack::ObjectKind;
 use crate::storable::DatabaseObject;
+use crate::timestamp::Timestamp;
 use crate::tree::Tree;
 use crate::Result;
 
+use super::status::{Change, Status};
+use super::transaction::Expected;
+
+/// How `rit commit` gets and checks its message. See [`Repo::commit_with`](super::Repo::commit_with).
+#[derive(Debug, Default)]
+pub struct CommitOptions {
+    /// The message, as given with `-m`
+    pub message: Option<String>,
+    /// A file to read the message from, with `-` meaning standard input
+    pub file: Option<Utf8PathBuf>,
+    /// Open the editor even though a message was given. Without one, it is always opened
+    pub edit: bool,
+    /// How to clean up the message. Defaults to `commit.cleanup`
+    pub cleanup: Option<Cleanup>,
+    pub allow_empty_message: bool,
+    /// Skip the `pre-commit` and `commit-msg` hooks
+    pub no_verify: bool,
+    /// Replace HEAD with the new commit, reusing its parents, author and (unless another is
+    /// given) message
+    pub amend: bool,
+    /// Use the message as it is, without opening the editor
+    pub no_edit: bool,
+    /// Record the commit even if its tree is the same as its parent's
+    pub allow_empty: bool,
+    /// The author, as `Name <email>`, or a pattern matching the author of an existing commit
+    pub author: Option<String>,
+    /// The author date, in any format [`Timestamp::parse`] accepts
+    pub date: Option<String>,
+    /// Sign the commit with this key, or the default key if it is empty. Defaults to
+    /// `commit.gpgSign`
+    pub gpg_sign: Option<String>,
+    /// Don't sign the commit, even if `commit.gpgSign` is set
+    pub no_gpg_sign: bool,
+}
+
+/// Where the message the user starts from came from, as passed to the `prepare-commit-msg` hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MessageSource {
+    /// `-m` or `-F`
+    Message,
+    /// `commit.template`
+    Template,
+    /// The message of the merge in progress
+    Merge,
+    /// The message of the commit being amended
+    Commit,
+}
+
+impl MessageSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            MessageSource::Message => "message",
+            MessageSource::Template => "template",
+            MessageSource::Merge => "merge",
+            MessageSource::Commit => "commit",
+        }
+    }
+}
+
 impl super::Repo {
-    pub fn commit(&mut self, message: &str) -> Result<Digest> {
-        trace!(path=?self.dir, %message, "Starting commit");
-        let entries = &self.index.entries();
-        let root = Tree::build(entries).unwrap();
+    /// Commit the index as `git commit` does: run the `pre-commit` hook, prepare a message in
+    /// `.git/COMMIT_EDITMSG` for the `prepare-commit-msg` hook and the editor, check it with the
+    /// `commit-msg` hook, then clean it up and record the commit.
+    pub fn commit_with(&mut self, options: &CommitOptions) -> Result<Digest> {
+        if options.message.is_some() && options.file.is_some() {
+            return Err(eyre!("Option -m cannot be combined with -F"));
+        }
+        self.check_unmerged()?;
+
+        if !options.no_verify && self.run_hook("pre-commit", &[])? {
+            // The hook may have staged changes
+            self.index = IndexWrapper::open(&self.git_dir);
+            self.check_unmerged()?;
+        }
+
+        let amended = match options.amend {
+            true => Some(self.amend_target()?),
+            false => None,
+        };
+        let tree = self.write_tree()?;
+        let parents = match &amended {
+            Some((_, commit)) => commit.parents().to_vec(),
+            None => self.next_parents()?,
+        };
+        self.check_not_empty(&tree, &parents, options)?;
+        let author = self.commit_author(options, amended.as_ref().map(|(_, x)| x.author()))?;
+
+        let (message, source) = self.initial_message(options, amended.as_ref().map(|(_, x)| x))?;
+        let edit = options.edit
+            || (options.message.is_none() && options.file.is_none() && !options.no_edit);
+        let comment_char = self.comment_char()?;
+        let cleanup = match options.cleanup {
+            Some(cleanup) => cleanup,
+            None => self.config_cleanup()?,
+        }
+        .resolve(edit);
+
+        let mut contents = message.clone();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        if edit {
+            contents.push_str(&self.edit_instructions(cleanup, comment_char)?);
+        }
+
+        let path = self.git_dir.join("COMMIT_EDITMSG");
+        std::fs::write(&path, &contents).wrap_err_with(|| format!("Unable to write {path}"))?;
+
+        let amended_oid = amended.as_ref().map(|(oid, _)| oid.to_hex());
+        let mut args = vec![path.as_str()];
+        args.extend(source.map(MessageSource::as_str));
+        args.extend(amended_oid.as_deref());
+        self.run_hook("prepare-commit-msg", &args)?;
+
+        if edit {
+            self.launch_editor(&path).map_err(|e| {
+                e.wrap_err("Please supply the message using either -m or -F option.")
+            })?;
+        }
+        if !options.no_verify {
+            self.run_hook("commit-msg", &[path.as_str()])?;
+        }
+
+        let contents = std::fs::read(&path).wrap_err_with(|| format!("Unable to read {path}"))?;
+        let contents = String::from_utf8(contents)
+            .map_err(|_| eyre!("The commit message in {path} is not valid UTF-8"))?;
+        let message_out = cleanup_message(&contents, cleanup, comment_char, edit);
+        debug!(?cleanup, edit, "Cleaned up commit message");
+
+        if message_out.is_empty() && !options.allow_empty_message {
+            return Err(eyre!("Aborting commit due to empty commit message."));
+        }
+        if source == Some(MessageSource::Template)
+            && !message_out.is_empty()
+            && message_out == cleanup_message(&message, cleanup, comment_char, edit)
+        {
+            return Err(eyre!("Aborting commit; you did not edit the message."));
+        }
+
+        let sign = self.signing_key(options)?;
+        self.record_commit(
+            tree,
+            parents,
+            author,
+            &message_out,
+            options.amend,
+            sign.as_deref(),
+        )
+    }
+
+    /// The key to sign the commit with, if it should be signed, from `-S` or `commit.gpgSign`.
+    /// An empty key means the default one.
+    fn signing_key(&self, options: &CommitOptions) -> Result<Option<String>> {
+        if options.no_gpg_sign {
+            return Ok(None);
+        }
+        Ok(match &options.gpg_sign {
+            Some(key) => Some(key.clone()),
+            None => self
+                .config
+                .get_bool("commit.gpgsign")?
+                .unwrap_or(false)
+                .then(String::new),
+        })
+    }
+
+    /// The message to start from: `-m`, `-F`, the message of the commit being amended or of a
+    /// merge in progress, or `commit.template`, in that order.
+    fn initial_message(
+        &self,
+        options: &CommitOptions,
+        amended: Option<&Commit>,
+    ) -> Result<(String, Option<MessageSource>)> {
+        if let Some(message) = &options.message {
+            return Ok((message.clone(), Some(MessageSource::Message)));
+        }
+
+        if let Some(file) = &options.file {
+            let message = if file == "-" {
+                let mut message = String::new();
+                std::io::stdin().read_to_string(&mut message)?;
+                message
+            } else {
+                std::fs::read_to_string(file)
+                    .wrap_err_with(|| format!("could not read log file '{file}'"))?
+            };
+            return Ok((message, Some(MessageSource::Message)));
+        }
+
+        if let Some(commit) = amended {
+            return Ok((commit.message().into_owned(), Some(MessageSource::Commit)));
+        }
+
+        if self.read_merge_head()?.is_some() {
+            let path = self.git_dir.join("MERGE_MSG");
+            if let Ok(message) = std::fs::read_to_string(path) {
+                return Ok((message, Some(MessageSource::Merge)));
+            }
+        }
+
+        if let Some(template) = self.config.get_path("commit.template")? {
+            let template = self.dir.join(template);
+            let message = std::fs::read_to_string(&template)
+                .wrap_err_with(|| format!("could not read '{template}'"))?;
+            return Ok((message, Some(MessageSource::Template)));
+        }
+
+        Ok((String::new(), None))
+    }
+
+    /// The character that starts comments in commit messages, from `core.commentChar`.
+    pub(super) fn comment_char(&self) -> Result<char> {
+        match self.config.get("core.commentchar") {
+            // Choosing a character that isn't used in the message isn't supported
+            None | Some("auto") => Ok('#'),
+            Some(value) => {
+                let mut chars = value.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if !c.is_whitespace() => Ok(c),
+                    _ => Err(eyre!("core.commentChar should only be one ASCII character")),
+                }
+            }
+        }
+    }
+
+    /// The cleanup mode from `commit.cleanup`.
+    fn config_cleanup(&self) -> Result<Cleanup> {
+        use clap::ValueEnum;
+
+        match self.config.get("commit.cleanup") {
+            None => Ok(Cleanup::Default),
+            Some(value) => {
+                Cleanup::from_str(value, true).map_err(|_| eyre!("Invalid cleanup mode {value}"))
+            }
+        }
+    }
+
+    /// The comments appended to the message in the editor: how it will be cleaned up, and which
+    /// changes are being committed.
+    fn edit_instructions(&self, cleanup: Cleanup, comment_char: char) -> Result<String> {
+        let c = comment_char;
+        let mut out = String::new();
+        writeln!(out)?;
+        if cleanup == Cleanup::Scissors {
+            writeln!(out, "{c}{SCISSORS}")?;
+            writeln!(out, "{c} Do not modify or remove the line above.")?;
+            writeln!(out, "{c} Everything below it will be ignored.")?;
+        }
+        writeln!(
+            out,
+            "{c} Please enter the commit message for your changes. Lines starting"
+        )?;
+        if cleanup == Cleanup::Strip {
+            writeln!(
+                out,
+                "{c} with '{c}' will be ignored, and an empty message aborts the commit."
+            )?;
+        } else {
+            writeln!(
+                out,
+                "{c} with '{c}' will be kept; you may remove them yourself if you want to."
+            )?;
+            writeln!(out, "{c} An empty message aborts the commit.")?;
+        }
+        writeln!(out, "{c}")?;
+
+        match (self.current_branch()?, self.read_head()?) {
+            (Some(branch), _) => writeln!(out, "{c} On branch {branch}")?,
+            (None, Some(head)) => {
+                writeln!(out, "{c} HEAD detached at {}", self.abbreviate(&head)?)?
+            }
+            (None, None) => {}
+        }
+
+        let mut sections: [(&str, Vec<String>); 3] = [
+            ("Changes to be committed:", Vec::new()),
+            ("Changes not staged for commit:", Vec::new()),
+            ("Untracked files:", Vec::new()),
+        ];
+        if self.read_head()?.is_none() {
+            writeln!(out, "{c}\n{c} Initial commit")?;
+            for entry in self.index.entries() {
+                sections[0].1.push(format!("new file:   {}", entry.path()));
+            }
+        } else if let Some(status) = Status::new(self)? {
+            let mut statuses = status.get_statuses()?;
+            let renames = status.detect_renames(&mut statuses)?;
+            statuses.sort_unstable_by_key(|(path, _)| *path);
+            for (path, change) in statuses {
+                let (section, line) = match change {
+                    Change::IndexAdded => (0, format!("new file:   {path}")),
+                    Change::IndexRenamed => (0, format!("renamed:    {} -> {path}", renames[path])),
+                    Change::IndexModified => (0, format!("modified:   {path}")),
+                    Change::IndexRemoved => (0, format!("deleted:    {path}")),
+                    Change::Modified => (1, format!("modified:   {path}")),
+                    Change::Removed => (1, format!("deleted:    {path}")),
+                    Change::Untracked => (2, path.to_string()),
+                };
+                sections[section].1.push(line);
+            }
+        }
+
+        for (title, lines) in sections {
+            if lines.is_empty() {
+                continue;
+            }
+            writeln!(out, "{c}\n{c} {title}")?;
+            for line in lines {
+                writeln!(out, "{c}\t{line}")?;
+            }
+        }
+        writeln!(out, "{c}")?;
+
+        Ok(out)
+    }
+
+    fn check_unmerged(&self) -> Result<()> {
+        if self.index.has_conflicts() {
+            return Err(eyre!(
+                "Committing is not possible because you have unmerged files"
+            ));
+        }
+        Ok(())
+    }
+
+    /// The commit `--amend` replaces: HEAD, which must exist, outside of a merge.
+    fn amend_target(&self) -> Result<(Digest, Commit)> {
+        if self.read_merge_head()?.is_some() {
+            return Err(eyre!("You are in the middle of a merge -- cannot amend."));
+        }
+        let head = self
+            .read_head()?
+            .ok_or_else(|| eyre!("You have nothing to amend."))?;
+        let commit = self.database.load_commit(&head)?;
+        Ok((head, commit))
+    }
+
+    /// The author of a new commit, if it isn't the default [author](Self::author): `base` (the author of the commit
+    /// being amended), with the name, email and date overridden by `--author` and `--date`.
+    fn commit_author(
+        &self,
+        options: &CommitOptions,
+        base: Option<&Signature>,
+    ) -> Result<Option<Signature>> {
+        if options.author.is_none() && options.date.is_none() {
+            return Ok(base.cloned());
+        }
+
+        let base = match base {
+            Some(base) => base.clone(),
+            None => self.author()?,
+        };
+        let (name, email) = match &options.author {
+            Some(author) => self.find_author(author)?,
+            None => (base.name().to_owned(), base.email().to_owned()),
+        };
+        let when = match &options.date {
+            Some(date) => Timestamp::parse(date)?,
+            None => base.when().clone(),
+        };
+        Ok(Some(Signature::new(name, email, when)))
+    }
+
+    /// Interpret `--author`: either `Name <email>`, or a regex matched against the authors of
+    /// existing commits, newest first.
+    fn find_author(&self, author: &str) -> Result<(String, String)> {
+        if let Some((name, email)) = author
+            .strip_suffix('>')
+            .and_then(|author| author.split_once('<'))
+        {
+            let name = name.trim();
+            if !name.is_empty() && !email.contains(['<', '>']) {
+                return Ok((name.to_owned(), email.trim().to_owned()));
+            }
+        }
+
+        let no_match =
+            || eyre!("--author '{author}' is not 'Name <email>' and matches no existing author");
+        let regex = Regex::new(author).map_err(|_| no_match())?;
+        let mut starts = Vec::new();
+        let head = match self.read_head()? {
+            Some(oid) => Some(self.database.peel(&oid)?),
+            None => None,
+        };
+        for oid in head
+            .into_iter()
+            .chain(self.list_peeled_refs()?.into_iter().map(|(_, oid)| oid))
+        {
+            if self.database.read_raw(&oid)?.0 == ObjectKind::Commit {
+                starts.push(oid);
+            }
+        }
+
+        for item in CommitWalk::new(&self.database, starts)? {
+            let (_, commit) = item?;
+            let author = commit.author();
+            if regex.is_match(&format!("{} <{}>", author.name(), author.email())) {
+                return Ok((author.name().to_owned(), author.email().to_owned()));
+            }
+        }
+        Err(no_match())
+    }
+
+    /// Write the index as a tree, returning its oid.
+    fn write_tree(&self) -> Result<Digest> {
+        let root = Tree::build(self.index.entries())?;
         trace!("Traversing root");
-        root.traverse(|tree| self.database.store(&DatabaseObject::new(&*tree)))
-            .unwrap();
+        root.traverse(|tree| self.database.store(&DatabaseObject::new(tree)))?;
 
         let root = DatabaseObject::new(&root);
+        self.database.store(&root)?;
+        Ok(root.into_oid())
+    }
+
+    /// The parents of a new commit: HEAD, and the commit being merged if a merge is in progress.
+    fn next_parents(&self) -> Result<Vec<Digest>> {
+        Ok(self
+            .read_head()?
+            .into_iter()
+            .chain(self.read_merge_head()?)
+            .collect())
+    }
+
+    /// Refuse to record a commit that changes nothing, unless `--allow-empty` was given. A merge
+    /// is never empty, as it records the merge itself.
+    fn check_not_empty(
+        &self,
+        tree: &Digest,
+        parents: &[Digest],
+        options: &CommitOptions,
+    ) -> Result<()> {
+        let empty = match parents {
+            _ if options.allow_empty => false,
+            [] => self.index.entries().is_empty(),
+            [parent] => self.database.load_commit(parent)?.tree_id() == tree,
+            _ => false,
+        };
+
+        match empty {
+            false => Ok(()),
+            true if options.amend => Err(eyre!(
+                "You asked to amend the most recent commit, but doing so would make it empty. You \
+                 can repeat your command with --allow-empty."
+            )),
+            true => Err(eyre!(
+                "nothing to commit (use \"rit add\" to stage changes, or --allow-empty to record \
+                 a commit anyway)"
+            )),
+        }
+    }
 
-        self.database.store(&root).unwrap();
+    /// Record the index as a commit with exactly `message`, on top of HEAD (and the commit being
+    /// merged, if a merge is in progress).
+    ///
+    /// Unlike [`commit_with`](Self::commit_with), this doesn't check that the commit changes
+    /// anything, as if `--allow-empty` was given.
+    pub fn commit(&mut self, message: &str) -> Result<Digest> {
+        trace!(path=?self.dir, %message, "Starting commit");
+        self.check_unmerged()?;
+
+        let tree = self.write_tree()?;
+        let parents = self.next_parents()?;
+        self.record_commit(tree, parents, None, message, false, None)
+    }
 
-        let parent_commit = self.read_head().unwrap();
+    /// Store a commit of `tree`, and move HEAD to it. The committer is the current
+    /// [committer](Self::committer), and the author the current [author](Self::author) unless
+    /// one is given. With `sign`, the commit is signed with that key, or the default key if it
+    /// is empty.
+    fn record_commit(
+        &mut self,
+        tree: Digest,
+        parents: Vec<Digest>,
+        author: Option<Signature>,
+        message: &str,
+        amend: bool,
+        sign: Option<&str>,
+    ) -> Result<Digest> {
+        let head = self.read_head()?;
+        let reflog_kind = match parents.len() {
+            _ if amend => "commit (amend)",
+            0 => "commit (initial)",
+            1 => "commit",
+            _ => "commit (merge)",
+        };
 
-        let name = std::env::var("RIT_AUTHOR_NAME").unwrap();
-        let email = std::env::var("RIT_AUTHOR_EMAIL").unwrap();
+        let committer = self.committer()?;
+        let author = match author {
+            Some(author) => author,
+            None => self.author()?,
+        };
 
-        let commit = Commit::new(
-            parent_commit,
-            root.into_oid(),
-            name,
-            email,
-            message.to_owned(),
-        );
+        let mut commit = Commit::new(parents, tree, author, committer, message.to_owned());
+        if let Some(key) = sign {
+            let signature = self.sign_buffer(&commit.signed_payload(), Some(key))?;
+            commit.set_signature(&signature);
+        }
+        let reflog_message = format!("{reflog_kind}: {}", commit.subject());
 
         let commit = DatabaseObject::new(&commit);
 
-        self.database.store(&commit).unwrap();
-        self.set_head(commit.oid()).unwrap();
+        self.database.store(&commit)?;
+        // Fails if HEAD was moved by someone else while we were committing
+        self.update_ref(
+            "HEAD",
+            commit.oid(),
+            Expected::from_current(head),
+            &reflog_message,
+        )?;
+        self.clear_merge_state()?;
 
         Ok(commit.into_oid())
     }
@@ -0,0 +1,150 @@
+use color_eyre::eyre::{eyre, Context};
+
+use crate::commit::Signature;
+use crate::diff::rename::RenameOptions;
+use crate::digest::{Digest, DEFAULT_ABBREV, MINIMUM_ABBREV};
+use crate::timestamp::Timestamp;
+use crate::Result;
+
+/// Who a [`Signature`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Author,
+    Committer,
+}
+
+impl super::Repo {
+    /// Whether the executable bit of files in the workspace can be trusted, from `core.filemode`.
+    pub fn trust_filemode(&self) -> Result<bool> {
+        Ok(self.config.get_bool("core.filemode")?.unwrap_or(true))
+    }
+
+    /// How many characters oids are abbreviated to before being lengthened to be unique, from
+    /// `core.abbrev`.
+    ///
+    /// This is an integer between 4 and 40, or `no` to never abbreviate. When it is `auto` or
+    /// unset, the length grows with the number of objects, so that abbreviations are likely to
+    /// stay unique as the repository grows.
+    pub fn abbrev_len(&self) -> Result<usize> {
+        let value = match self.config.get("core.abbrev") {
+            Some(value) if !value.eq_ignore_ascii_case("auto") => value,
+            _ => {
+                // With 2^bits objects, a collision is expected among the first bits / 2 bits of
+                // their oids, and each character holds 4 bits
+                let count = self.database.entries().len() as u64;
+                let bits = (u64::BITS - count.leading_zeros()) as usize;
+                return Ok(bits.div_ceil(2).max(DEFAULT_ABBREV));
+            }
+        };
+
+        // Only the words for false turn abbreviation off, `0` is just too short
+        if matches!(value.to_ascii_lowercase().as_str(), "false" | "no" | "off") {
+            return Ok(40);
+        }
+        match self.config.get_int("core.abbrev")? {
+            Some(len @ 4..=40) => Ok(len as usize),
+            _ => Err(eyre!(
+                "abbrev length out of range: {value} (must be between {MINIMUM_ABBREV} and 40)"
+            )),
+        }
+    }
+
+    /// The shortest abbreviation of `oid` that is unique in the repository, and at least as long
+    /// as `core.abbrev` asks for. See [`Repo::abbrev_len`].
+    pub fn abbreviate(&self, oid: &Digest) -> Result<String> {
+        self.database.abbreviate(oid, self.abbrev_len()?)
+    }
+
+    /// How diffs look for renames, from `key`, which is `diff.renames` or `status.renames`.
+    ///
+    /// It is a boolean, or `copies` to look for copies too, and renames are looked for when it
+    /// isn't set. `status.renames` falls back to `diff.renames`.
+    pub fn rename_options(&self, key: &str) -> Result<Option<RenameOptions>> {
+        let mut options = RenameOptions {
+            limit: self.rename_limit()?,
+            ..Default::default()
+        };
+
+        let value = match self.config.get(key) {
+            None if key != "diff.renames" => return self.rename_options("diff.renames"),
+            None => return Ok(Some(options)),
+            Some(value) => value,
+        };
+        if matches!(value.to_ascii_lowercase().as_str(), "copies" | "copy") {
+            options.copies = true;
+            return Ok(Some(options));
+        }
+        Ok(self
+            .config
+            .get_bool(key)?
+            .unwrap_or(true)
+            .then_some(options))
+    }
+
+    /// How many files inexact renames are looked for among, from `diff.renameLimit`. See
+    /// [`RenameOptions::limit`].
+    pub fn rename_limit(&self) -> Result<usize> {
+        Ok(match self.config.get_int("diff.renameLimit")? {
+            Some(limit) => usize::try_from(limit).unwrap_or(0),
+            None => RenameOptions::default().limit,
+        })
+    }
+
+    /// The author of new commits: `GIT_AUTHOR_NAME`, `GIT_AUTHOR_EMAIL` and `GIT_AUTHOR_DATE`,
+    /// falling back to the configured identity and the current time. See [`Repo::signature`].
+    pub fn author(&self) -> Result<Signature> {
+        self.signature(Role::Author, |var| std::env::var(var).ok())
+    }
+
+    /// The committer of new commits, and the tagger of new tags, from the `GIT_COMMITTER_*`
+    /// variables. See [`Repo::signature`].
+    pub fn committer(&self) -> Result<Signature> {
+        self.signature(Role::Committer, |var| std::env::var(var).ok())
+    }
+
+    /// The signature for `role`, with environment variables read through `env`.
+    ///
+    /// The name comes from the first of `GIT_{ROLE}_NAME`, `RIT_AUTHOR_NAME`, `{role}.name` and
+    /// `user.name` that is set, and likewise for the email. The date is `GIT_{ROLE}_DATE`, in any
+    /// format [`Timestamp::parse`] accepts, or now.
+    pub fn signature(&self, role: Role, env: impl Fn(&str) -> Option<String>) -> Result<Signature> {
+        let (prefix, section) = match role {
+            Role::Author => ("GIT_AUTHOR", "author"),
+            Role::Committer => ("GIT_COMMITTER", "committer"),
+        };
+        let lookup = |field: &str| {
+            env(&format!("{prefix}_{}", field.to_ascii_uppercase()))
+                .or_else(|| env(&format!("RIT_AUTHOR_{}", field.to_ascii_uppercase())))
+                .or_else(|| {
+                    self.config
+                        .get(&format!("{section}.{field}"))
+                        .map(str::to_owned)
+                })
+                .or_else(|| self.config.get(&format!("user.{field}")).map(str::to_owned))
+        };
+
+        let (name, email) = match (lookup("name"), lookup("email")) {
+            (Some(name), Some(email)) => (name, email),
+            _ => {
+                let role = match role {
+                    Role::Author => "Author",
+                    Role::Committer => "Committer",
+                };
+                return Err(eyre!(
+                    "{role} identity unknown\n\n*** Please tell me who you are.\n\nRun\n\n  rit config set --global user.email \"you@example.com\"\n  rit config set --global user.name \"Your Name\"\n\nto set your account's default identity."
+                ));
+            }
+        };
+        if name.trim().is_empty() {
+            return Err(eyre!("empty ident name (for <{email}>) not allowed"));
+        }
+
+        let when = match env(&format!("{prefix}_DATE")) {
+            Some(date) => {
+                Timestamp::parse(&date).wrap_err_with(|| format!("Invalid {prefix}_DATE"))?
+            }
+            None => Timestamp::now(),
+        };
+        Ok(Signature::new(name, email, when))
+    }
+}
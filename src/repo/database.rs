@@ -1,8 +1,11 @@
 use crate::blob::Blob;
 use crate::commit::Commit;
-use crate::digest::Digest;
+use crate::digest::{Digest, DEFAULT_ABBREV, MINIMUM_ABBREV};
+use crate::pack::{ObjectKind, Pack};
 use crate::storable::DatabaseObject;
 use crate::storable::Storable;
+use crate::tag::Tag;
+use crate::timestamp::DateFormat;
 use crate::tree::Tree;
 use crate::util;
 use crate::Result;
@@ -20,13 +23,27 @@ use tracing::*;
 
 pub struct Database {
     pub database_root: Utf8PathBuf,
+    packs: Vec<Pack>,
 }
 
 impl Database {
-    pub fn new(git_folder: impl AsRef<Utf8Path>) -> Self {
-        Self {
-            database_root: git_folder.as_ref().join("objects"),
-        }
+    pub fn new(git_folder: impl AsRef<Utf8Path>) -> Result<Self> {
+        let database_root = git_folder.as_ref().join("objects");
+        let packs = Pack::open_all(&database_root.join("pack"))?;
+        Ok(Self {
+            database_root,
+            packs,
+        })
+    }
+
+    /// Re-scan `objects/pack`. This must be called after packs are added or removed.
+    pub fn reload_packs(&mut self) -> Result<()> {
+        self.packs = Pack::open_all(&self.database_root.join("pack"))?;
+        Ok(())
+    }
+
+    pub fn packs(&self) -> &[Pack] {
+        &self.packs
     }
 
     pub fn store<T: Storable>(&self, obj: &DatabaseObject<T>) -> Result<()> {
@@ -64,7 +81,7 @@ impl Database {
         Ok(())
     }
 
-    fn object_path(&self, oid: &Digest) -> Utf8PathBuf {
+    pub fn object_path(&self, oid: &Digest) -> Utf8PathBuf {
         let mut x = self.database_root.to_owned();
         let oid = oid.to_hex();
         let (prefix, suffix) = oid.split_at(2);
@@ -86,38 +103,25 @@ impl Database {
     }
 
     pub fn exists(&self, oid: &Digest) -> bool {
-        self.object_path(oid).exists()
+        self.contains(oid)
     }
 
     pub fn contains(&self, oid: &Digest) -> bool {
-        let object_path = self.object_path(oid);
-        object_path.exists()
+        self.is_loose(oid) || self.packs.iter().any(|pack| pack.contains(oid))
+    }
+
+    /// Returns `true` if `oid` is stored as a loose object, i.e. under `objects/xx/yyyy`.
+    pub fn is_loose(&self, oid: &Digest) -> bool {
+        self.object_path(oid).exists()
     }
 
     pub fn any<F>(&self, key: F) -> Result<bool>
     where
         F: Fn(&LoadedItem) -> bool,
     {
-        for dir in self.database_root.read_dir().unwrap() {
-            let dir = dir.unwrap();
-            if !dir.file_type().unwrap().is_dir() {
-                continue;
-            }
-            for file in dir.path().read_dir().unwrap() {
-                let file = file.unwrap();
-                if !file.file_type().unwrap().is_file() {
-                    continue;
-                }
-                let item = self
-                    .load(&Digest::from_str(&format!(
-                        "{}{}",
-                        dir.file_name().to_str().unwrap(),
-                        file.file_name().to_str().unwrap(),
-                    ))?)
-                    .unwrap();
-                if key(&item) {
-                    return Ok(true);
-                }
+        for oid in self.entries() {
+            if key(&self.load(&oid)?) {
+                return Ok(true);
             }
         }
         Ok(false)
@@ -125,13 +129,18 @@ impl Database {
 
     /// Read an item from the database to a Vec<u8>. The returned Vec contains uncompressed but
     /// unparsed data.
+    ///
+    /// Objects stored in packs are given the same `<type> <len>\0` header as loose objects.
     pub fn read_uncompressed(&self, oid: &Digest) -> Result<Vec<u8>> {
         trace!(object=%oid.to_hex(), "reading object from database");
 
         let object_path = self.object_path(oid);
 
         if !object_path.exists() {
-            return Err(eyre!("object not found in database: {:x}", oid));
+            let (kind, data) = self.read_packed(oid)?;
+            let mut bytes = format!("{} {}\0", kind.as_str(), data.len()).into_bytes();
+            bytes.extend_from_slice(&data);
+            return Ok(bytes);
         }
 
         let compressed = std::fs::read(object_path)?;
@@ -145,6 +154,41 @@ impl Database {
         Ok(decompressed)
     }
 
+    /// Read an object from the database, returning its kind and its content without a header.
+    pub fn read_raw(&self, oid: &Digest) -> Result<(ObjectKind, Vec<u8>)> {
+        if !self.is_loose(oid) {
+            return self.read_packed(oid);
+        }
+
+        let mut bytes = self.read_uncompressed(oid)?;
+        let nul_idx = memchr::memchr(b'\0', &bytes)
+            .ok_or_else(|| eyre!("object has no header: {:x}", oid))?;
+        let kind = match DBHeader::from_bytes(&bytes[..nul_idx])?.type_string {
+            b"commit" => ObjectKind::Commit,
+            b"tree" => ObjectKind::Tree,
+            b"blob" => ObjectKind::Blob,
+            b"tag" => ObjectKind::Tag,
+            x => {
+                return Err(eyre!(
+                    "Unexpected object type: {}",
+                    String::from_utf8_lossy(x)
+                ))
+            }
+        };
+        bytes.drain(..nul_idx + 1);
+        Ok((kind, bytes))
+    }
+
+    /// Read an object out of whichever pack contains it.
+    fn read_packed(&self, oid: &Digest) -> Result<(ObjectKind, Vec<u8>)> {
+        for pack in &self.packs {
+            if let Some(object) = pack.read_object(oid, |base| self.read_raw(base))? {
+                return Ok(object);
+            }
+        }
+        Err(eyre!("object not found in database: {:x}", oid))
+    }
+
     /// Read an item from the database, and parse it into a `LoadedItem`.
     ///
     pub fn load(&self, oid: &Digest) -> Result<LoadedItem> {
@@ -154,8 +198,6 @@ impl Database {
         let header = &bytes[..nul_idx];
         let header = DBHeader::from_bytes(header)?;
 
-        debug_assert!(header.len > 0);
-
         let content_start = nul_idx + 1;
 
         match header.type_string {
@@ -172,11 +214,31 @@ impl Database {
                 let bytes = &bytes[content_start..];
                 Ok(LoadedItem::Commit(Commit::parse(bytes)?))
             }
-            type_string => unreachable!(
+            b"tag" => {
+                let bytes = &bytes[content_start..];
+                Ok(LoadedItem::Tag(Tag::parse(bytes)?))
+            }
+            type_string => Err(eyre!(
                 "Unexpected object type: {}",
                 String::from_utf8_lossy(type_string)
-            ),
+            )),
+        }
+    }
+
+    /// Follow annotated tags starting at `oid` until reaching an object that is not a tag.
+    pub fn peel(&self, oid: &Digest) -> Result<Digest> {
+        let mut oid = oid.clone();
+        while let (ObjectKind::Tag, data) = self.read_raw(&oid)? {
+            oid = Tag::parse(&data)?.object().clone();
         }
+        Ok(oid)
+    }
+
+    /// Load the object `oid`, which must be a commit.
+    pub fn load_commit(&self, oid: &Digest) -> Result<Commit> {
+        self.load(oid)?
+            .into_commit()
+            .ok_or_else(|| eyre!("object {:x} is not a commit", oid))
     }
 
     pub fn prefix_match(&self, prefix: &str) -> Result<Vec<Digest>> {
@@ -185,12 +247,18 @@ impl Database {
             None => return Ok(Vec::new()),
         };
 
+        let mut candidates = self
+            .packs
+            .iter()
+            .flat_map(|pack| pack.index().prefix_match(prefix))
+            .collect::<Vec<_>>();
+
         if !dirname.exists() {
-            return Ok(Vec::new());
+            candidates.sort_unstable();
+            candidates.dedup();
+            return Ok(candidates);
         }
 
-        let mut candidates = Vec::new();
-
         let prefix = &prefix[2..];
 
         let basename = dirname.file_name().unwrap();
@@ -208,23 +276,127 @@ impl Database {
             }
         }
 
+        candidates.sort_unstable();
+        candidates.dedup();
+
         Ok(candidates)
     }
 
-    /// Returns a vec of all objects in the database
+    /// Find the single object whose oid starts with the hex string `prefix`.
+    ///
+    /// When several objects match, only those that can be peeled to `kind` are considered, so an
+    /// abbreviated oid used where a commit is needed can't be ambiguous with a tree or blob. If
+    /// that still doesn't leave exactly one, an [`AmbiguousObject`] error listing every match is
+    /// returned.
+    pub fn resolve_prefix(&self, prefix: &str, kind: Option<ObjectKind>) -> Result<Option<Digest>> {
+        let candidates = self.prefix_match(prefix)?;
+        if let [] | [_] = &candidates[..] {
+            return Ok(candidates.into_iter().next());
+        }
+
+        if let Some(kind) = kind {
+            let mut matching = Vec::new();
+            for oid in &candidates {
+                if self.peels_to(oid, kind)? {
+                    matching.push(oid);
+                }
+            }
+            if let [oid] = &matching[..] {
+                trace!(%prefix, kind = kind.as_str(), "Disambiguated prefix by kind");
+                return Ok(Some((*oid).clone()));
+            }
+        }
+
+        let candidates = candidates
+            .into_iter()
+            .map(|oid| self.describe_candidate(oid))
+            .collect::<Result<Vec<_>>>()?;
+        Err(AmbiguousObject {
+            prefix: prefix.to_owned(),
+            candidates,
+        }
+        .into())
+    }
+
+    /// Whether `oid` is, or can be peeled to, an object of `kind`: tags are followed, and a
+    /// commit can be peeled to its tree.
+    fn peels_to(&self, oid: &Digest, kind: ObjectKind) -> Result<bool> {
+        let mut oid = oid.clone();
+        loop {
+            let (actual, data) = self.read_raw(&oid)?;
+            oid = match actual {
+                _ if actual == kind => return Ok(true),
+                ObjectKind::Tag => Tag::parse(&data)?.object().clone(),
+                ObjectKind::Commit => return Ok(kind == ObjectKind::Tree),
+                _ => return Ok(false),
+            };
+        }
+    }
+
+    fn describe_candidate(&self, oid: Digest) -> Result<Candidate> {
+        let (kind, summary) = match self.load(&oid)? {
+            LoadedItem::Commit(commit) => {
+                let date = commit.author().when().format(DateFormat::Short);
+                (
+                    ObjectKind::Commit,
+                    Some(format!("{date} - {}", commit.subject())),
+                )
+            }
+            LoadedItem::Tag(tag) => (ObjectKind::Tag, Some(tag.name().to_owned())),
+            LoadedItem::Tree(_) => (ObjectKind::Tree, None),
+            LoadedItem::Blob(_) => (ObjectKind::Blob, None),
+        };
+        Ok(Candidate { oid, kind, summary })
+    }
+
+    /// The shortest prefix of `oid`, at least `min_len` characters long, that no other object in
+    /// the database starts with.
+    pub fn abbreviate(&self, oid: &Digest, min_len: usize) -> Result<String> {
+        let hex = oid.to_hex();
+        let mut len = min_len.clamp(MINIMUM_ABBREV, hex.len());
+        while len < hex.len()
+            && self
+                .prefix_match(&hex[..len])?
+                .iter()
+                .any(|other| other != oid)
+        {
+            len += 1;
+        }
+        Ok(hex[..len].to_owned())
+    }
+
+    /// Returns a vec of all objects in the database, both loose and packed
     pub fn entries(&self) -> Vec<Digest> {
+        let mut entries = self.loose_entries();
+
+        for pack in &self.packs {
+            entries.extend_from_slice(pack.index().oids());
+        }
+
+        entries.sort_unstable();
+        entries.dedup();
+        entries
+    }
+
+    /// Returns a vec of all loose objects in the database
+    pub fn loose_entries(&self) -> Vec<Digest> {
         let mut entries = Vec::new();
 
         for dir in self.database_root.read_dir().unwrap() {
             let dir = dir.unwrap();
             let dir = Utf8PathBuf::from_path_buf(dir.path()).unwrap();
-            if dir.file_name().unwrap() == "pack" {
+            // Skip `pack` and `info`, as well as anything else that isn't a fanout directory
+            let dirname = dir.file_name().unwrap();
+            if dirname.len() != 2 || !dirname.chars().all(|c| c.is_ascii_hexdigit()) {
                 continue;
             }
             for file in dir.read_dir().unwrap() {
                 let file = file.unwrap();
                 let file = Utf8PathBuf::from_path_buf(file.path()).unwrap();
                 let name = file.file_name().unwrap();
+                if name.starts_with("tmp_obj_") {
+                    continue;
+                }
                 // XXX: Work out how to parse digests without allocating
                 let oid =
                     Digest::from_str(&format!("{}{}", dir.file_name().unwrap(), name)).unwrap();
@@ -236,6 +408,55 @@ impl Database {
     }
 }
 
+/// An abbreviated oid matched more than one object, and the context it was used in didn't single
+/// one out. Can be recovered from a [`color_eyre::Report`] with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousObject {
+    pub prefix: String,
+    /// Every object starting with `prefix`, sorted by oid
+    pub candidates: Vec<Candidate>,
+}
+
+/// One of the objects an ambiguous oid could refer to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub oid: Digest,
+    pub kind: ObjectKind,
+    /// The date and subject of a commit, or the name of a tag
+    pub summary: Option<String>,
+}
+
+impl std::fmt::Display for AmbiguousObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Every object with the prefix is a candidate, so the candidates only need to be long
+        // enough to tell them apart from each other
+        let len = self
+            .candidates
+            .windows(2)
+            .map(|pair| common_prefix_len(&pair[0].oid.to_hex(), &pair[1].oid.to_hex()) + 1)
+            .max()
+            .unwrap_or_default()
+            .max(self.prefix.len() + 1)
+            .clamp(DEFAULT_ABBREV, 40);
+
+        writeln!(f, "short object ID {} is ambiguous", self.prefix)?;
+        write!(f, "hint: The candidates are:")?;
+        for Candidate { oid, kind, summary } in &self.candidates {
+            write!(f, "\nhint:   {} {}", &oid.to_hex()[..len], kind.as_str())?;
+            if let Some(summary) = summary {
+                write!(f, " {summary}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AmbiguousObject {}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
 /// The header of database entry
 /// An item header consists of a type string, a space, the size of the object in bytes,
 /// terminated with a `b'0'`.
@@ -263,6 +484,7 @@ pub enum LoadedItem {
     Commit(Commit),
     Tree(Tree),
     Blob(Blob),
+    Tag(Tag),
 }
 
 impl LoadedItem {
@@ -290,16 +512,26 @@ impl LoadedItem {
         }
     }
 
+    pub fn into_tag(self) -> Option<Tag> {
+        if let Self::Tag(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
     /// Return a string representation of type of the object.
     pub const fn kind(&self) -> &'static str {
         const COMMIT: &str = "commit";
         const TREE: &str = "tree";
         const BLOB: &str = "blob";
+        const TAG: &str = "tag";
 
         match self {
             LoadedItem::Commit(_) => COMMIT,
             LoadedItem::Tree(_) => TREE,
             LoadedItem::Blob(_) => BLOB,
+            LoadedItem::Tag(_) => TAG,
         }
     }
 
@@ -327,6 +559,14 @@ impl LoadedItem {
         matches!(self, Self::Blob(..))
     }
 
+    /// Returns `true` if the loaded item is [`Tag`].
+    ///
+    /// [`Tag`]: LoadedItem::Tag
+    #[must_use]
+    pub fn is_tag(&self) -> bool {
+        matches!(self, Self::Tag(..))
+    }
+
     pub fn as_tree(&self) -> Option<&Tree> {
         if let Self::Tree(v) = self {
             Some(v)
@@ -350,6 +590,14 @@ impl LoadedItem {
             None
         }
     }
+
+    pub fn as_tag(&self) -> Option<&Tag> {
+        if let Self::Tag(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,140 +1,597 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
 use camino::{Utf8Path, Utf8PathBuf};
-use tap::Tap;
+use color_eyre::eyre::eyre;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 use crate::{
-    blob::Blob, diff::EditKind, digest::Digest, filemode::FileMode, index::IndexEntry,
-    storable::Storable, tree::Tree, Result,
+    attributes::{AttrState, Attributes},
+    blob::Blob,
+    diff::binary::{binary_patch, is_binary},
+    diff::rename::{self, parse_score, similarity_index, Candidate, RenameOptions},
+    diff::{DiffAlgorithm, EditKind},
+    digest::Digest,
+    filemode::FileMode,
+    pathspec::Pathspec,
+    revision::{Rev, RevisionError},
+    storable::Storable,
+    tree::{RawTreeEntry, Tree},
+    Result,
 };
 
-use super::{
-    status::{Change, Status},
-    Repo,
-};
+use super::Repo;
 
+/// What a diff compares, from the old side to the new one. Trees are given by oid, with `None`
+/// standing for the empty tree, as when there are no commits yet.
 pub enum DiffMode {
+    /// The index against the workspace: changes that haven't been staged
     WorktreeIndex,
-    IndexHead,
+    /// A tree against the index: changes staged since that tree
+    IndexTree(Option<Digest>),
+    /// A tree against the workspace
+    WorktreeTree(Option<Digest>),
+    /// One tree against another
+    TreeTree(Option<Digest>, Option<Digest>),
+}
+
+#[derive(Debug, Default)]
+pub struct DiffOptions {
+    /// Show changes to binary files as patches `git apply` understands, rather than just saying
+    /// that they differ
+    pub binary: bool,
+    /// Only show changes to these paths
+    pub pathspec: Pathspec,
+    /// Show files that were renamed or copied from others as such, rather than as new files
+    pub renames: Option<RenameOptions>,
+    /// How to find the differences between versions of a file
+    pub algorithm: DiffAlgorithm,
+}
+
+/// A version of a file being compared: its blob and mode, and where to read it from.
+#[derive(Debug, Clone)]
+struct Version {
+    oid: Digest,
+    mode: FileMode,
+    /// The file is read from the workspace, as it may not have been written to the database
+    in_workspace: bool,
+}
+
+impl Version {
+    fn from_entry(entry: RawTreeEntry) -> Self {
+        Self {
+            oid: entry.oid,
+            mode: FileMode::from(entry.mode),
+            in_workspace: false,
+        }
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        self.oid == other.oid && self.mode == other.mode
+    }
+}
+
+/// A path that differs between the two sides of a diff, with its old and new versions.
+type Change = (Utf8PathBuf, Option<Version>, Option<Version>);
+
+/// How a file came from another, and how similar they are, out of [`rename::MAX_SCORE`].
+#[derive(Debug, Clone, Copy)]
+enum Similarity {
+    Renamed(u32),
+    Copied(u32),
+}
+
+/// The old and new versions of a file to compare, and their paths, which differ if the file was
+/// renamed or copied.
+struct FilePair {
+    old_path: Utf8PathBuf,
+    a: DiffTarget,
+    new_path: Utf8PathBuf,
+    b: DiffTarget,
+    similarity: Option<Similarity>,
 }
 
 impl super::Repo {
-    pub fn diff(&self, mode: DiffMode) -> Result<()> {
-        let status = match Status::new(self)? {
-            Some(x) => x,
-            None => return Ok(()),
-        };
+    pub fn diff(&self, mode: DiffMode, options: &DiffOptions) -> Result<()> {
+        let mut writer = StandardStream::stdout(ColorChoice::Auto);
+        self.write_diff(&mut writer, mode, options)
+    }
 
-        let changes = status
-            .get_statuses()?
-            .tap_mut(|v| v.sort_unstable_by_key(|x| x.0));
-
-        match mode {
-            DiffMode::WorktreeIndex => {
-                for (path, change) in changes {
-                    match change {
-                        Change::Modified | Change::Removed => {
-                            let a = DiffTarget::from_file(path)?;
-                            let b = DiffTarget::from_index(path, self)?;
-                            self.diff_files(a, b)?
-                        }
-                        _ => {}
-                    }
-                }
+    /// Split the arguments `diff` was given before any `--` into revisions and paths, as git
+    /// does: arguments are revisions up to the first that doesn't name an object, and that one
+    /// and every one after it must be a path in the workspace or the index.
+    pub fn split_diff_args(&self, args: &[impl AsRef<str>]) -> Result<(Vec<String>, Vec<String>)> {
+        let mut revisions = Vec::new();
+        let mut paths = Vec::new();
+        for arg in args {
+            let arg = arg.as_ref();
+            if paths.is_empty() && self.is_revision(arg)? {
+                revisions.push(arg.to_owned());
+            } else if self.is_known_path(Utf8Path::new(arg)) {
+                paths.push(arg.to_owned());
+            } else {
+                return Err(eyre!(
+                    "ambiguous argument '{arg}': unknown revision or path not in the working \
+                     tree.\nUse '--' to separate paths from revisions"
+                ));
             }
-            DiffMode::IndexHead => {
-                let tree = status.tree();
-
-                for (path, change) in changes {
-                    match change {
-                        Change::IndexModified | Change::IndexAdded | Change::IndexRemoved => {
-                            let a = DiffTarget::from_index(path, self)?;
-                            let b = DiffTarget::from_head(path, self, tree)?;
-                            self.diff_files(a, b)?
-                        }
-                        _ => {}
+        }
+
+        Ok((revisions, paths))
+    }
+
+    /// Whether `arg` names an object. Malformed or missing revisions don't, but other errors,
+    /// such as an ambiguous abbreviated oid, are returned.
+    fn is_revision(&self, arg: &str) -> Result<bool> {
+        let rev = match Rev::parse(arg) {
+            Ok(rev) => rev,
+            Err(_) => return Ok(false),
+        };
+        match rev.resolve(self) {
+            Ok(oid) => Ok(oid.is_some()),
+            Err(e) if e.downcast_ref::<RevisionError>().is_some() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether `path` exists in the workspace, or is a file or directory in the index.
+    fn is_known_path(&self, path: &Utf8Path) -> bool {
+        self.dir.join(path).symlink_metadata().is_ok()
+            || self
+                .index
+                .entries()
+                .iter()
+                .any(|entry| entry.path().starts_with(path))
+    }
+
+    /// Decide what `diff` compares from its arguments, as git does:
+    ///
+    /// - with no revisions, the index against the workspace, or with `cached`, HEAD against the
+    ///   index
+    /// - with one, that revision against the workspace, or with `cached`, against the index
+    /// - with two, the first against the second
+    pub fn diff_mode(&self, cached: bool, revisions: &[impl AsRef<str>]) -> Result<DiffMode> {
+        let trees = revisions
+            .iter()
+            .map(|rev| {
+                let rev = rev.as_ref();
+                Rev::parse(rev)?.resolve_tree(self)?.ok_or_else(|| {
+                    RevisionError::UnknownRevision {
+                        rev: rev.to_owned(),
                     }
-                }
+                    .into()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        match (cached, &trees[..]) {
+            (false, []) => Ok(DiffMode::WorktreeIndex),
+            (true, []) => {
+                let head = match self.read_head()? {
+                    Some(head) => Some(self.database.load_commit(&head)?.tree_id().clone()),
+                    None => None,
+                };
+                Ok(DiffMode::IndexTree(head))
             }
+            (true, [tree]) => Ok(DiffMode::IndexTree(Some(tree.clone()))),
+            (false, [tree]) => Ok(DiffMode::WorktreeTree(Some(tree.clone()))),
+            (false, [old, new]) => Ok(DiffMode::TreeTree(Some(old.clone()), Some(new.clone()))),
+            (true, [_, _]) => Err(eyre!("--cached compares a single revision with the index")),
+            _ => Err(eyre!("too many revisions: diff compares at most two")),
+        }
+    }
+
+    /// Write the diff to `writer`, which is given colours if it supports them.
+    pub fn write_diff(
+        &self,
+        writer: &mut impl WriteColor,
+        mode: DiffMode,
+        options: &DiffOptions,
+    ) -> Result<()> {
+        let mut attributes = self.attributes();
+
+        let mut pairs = Vec::new();
+        for (path, old, new) in self.diff_changes(&mode, &options.pathspec)? {
+            pairs.push(FilePair {
+                a: DiffTarget::load(self, &path, old, "a")?,
+                b: DiffTarget::load(self, &path, new, "b")?,
+                old_path: path.clone(),
+                new_path: path,
+                similarity: None,
+            });
+        }
+        if let Some(renames) = &options.renames {
+            pairs = find_renames(pairs, renames);
+        }
+
+        for pair in pairs {
+            self.diff_files(writer, pair, options, &mut attributes)?;
         }
 
         Ok(())
     }
 
-    fn diff_files(&self, a: DiffTarget, b: DiffTarget) -> Result<()> {
-        println!("diff --git {} {}", a.path(), b.path());
+    /// How `diff` looks for renames, from its `-M` and `-C` options, which are `Some("")` when
+    /// given without a similarity. Without either, `diff.renames` decides.
+    pub fn diff_renames(
+        &self,
+        renames: Option<&str>,
+        copies: Option<&str>,
+        no_renames: bool,
+    ) -> Result<Option<RenameOptions>> {
+        let configured = self.rename_options("diff.renames")?;
+        if no_renames || (renames.is_none() && copies.is_none()) {
+            return Ok(configured.filter(|_| !no_renames));
+        }
 
-        self.print_diff_mode(&a, &b);
+        let mut options = match configured {
+            Some(options) => options,
+            None => RenameOptions {
+                limit: self.rename_limit()?,
+                ..Default::default()
+            },
+        };
 
-        self.print_diff_content(&a, &b);
+        options.copies = copies.is_some();
+        if let Some(score) = copies.or(renames).filter(|x| !x.is_empty()) {
+            options.min_score = parse_score(score)?;
+        }
+        Ok(Some(options))
+    }
 
-        Ok(())
+    /// Find the files within `pathspec` that differ between the two sides of `mode`, in order of
+    /// their paths.
+    fn diff_changes(&self, mode: &DiffMode, pathspec: &Pathspec) -> Result<Vec<Change>> {
+        let (old, new) = match mode {
+            DiffMode::TreeTree(old, new) => {
+                let changes = Tree::diff(&self.database, old.as_ref(), new.as_ref(), pathspec)?;
+                return Ok(changes
+                    .into_iter()
+                    .map(|(path, (old, new))| {
+                        (
+                            path,
+                            old.map(Version::from_entry),
+                            new.map(Version::from_entry),
+                        )
+                    })
+                    .collect());
+            }
+            DiffMode::WorktreeIndex => (
+                self.index_versions(pathspec),
+                self.workspace_versions(pathspec)?,
+            ),
+            DiffMode::IndexTree(tree) => (
+                self.tree_versions(tree.as_ref(), pathspec)?,
+                self.index_versions(pathspec),
+            ),
+            DiffMode::WorktreeTree(tree) => (
+                self.tree_versions(tree.as_ref(), pathspec)?,
+                self.workspace_versions(pathspec)?,
+            ),
+        };
+
+        let paths = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| match (old.get(path), new.get(path)) {
+                (Some(old), Some(new)) if old.same(new) => None,
+                (old, new) => Some((path.clone(), old.cloned(), new.cloned())),
+            })
+            .collect())
+    }
+
+    /// The files in the tree `tree`, without reading directories outside of `pathspec`.
+    fn tree_versions(
+        &self,
+        tree: Option<&Digest>,
+        pathspec: &Pathspec,
+    ) -> Result<BTreeMap<Utf8PathBuf, Version>> {
+        Ok(Tree::diff(&self.database, None, tree, pathspec)?
+            .into_iter()
+            .filter_map(|(path, (_, entry))| Some((path, Version::from_entry(entry?))))
+            .collect())
+    }
+
+    /// The files in the index. Conflicted files, which have several versions, are left out.
+    fn index_versions(&self, pathspec: &Pathspec) -> BTreeMap<Utf8PathBuf, Version> {
+        self.index
+            .entries()
+            .iter()
+            .filter(|entry| entry.stage() == 0 && pathspec.matches(entry.path()))
+            .map(|entry| {
+                let version = Version {
+                    oid: entry.oid().clone(),
+                    mode: entry.mode(),
+                    in_workspace: false,
+                };
+                (entry.path().to_owned(), version)
+            })
+            .collect()
+    }
+
+    /// The files in the workspace that are in the index. Files whose stat information matches
+    /// the index are assumed to have the same contents, rather than being read.
+    fn workspace_versions(&self, pathspec: &Pathspec) -> Result<BTreeMap<Utf8PathBuf, Version>> {
+        let trust_filemode = self.trust_filemode()?;
+        let mut versions = BTreeMap::new();
+
+        for entry in self.index.entries() {
+            if entry.stage() != 0 || !pathspec.matches(entry.path()) {
+                continue;
+            }
+
+            let full_path = self.dir.join(entry.path());
+            let stat = match Repo::stat_file(&full_path)? {
+                Some(stat) => stat,
+                None => continue,
+            };
+
+            let oid = if entry.stat_matches(&stat, trust_filemode) && entry.times_match(&stat) {
+                entry.oid().clone()
+            } else {
+                let blob = Blob::new(std::fs::read(&full_path)?);
+                blob.oid(&blob.format())
+            };
+            let mode = match trust_filemode {
+                true => FileMode::from(&stat),
+                false => entry.mode(),
+            };
+
+            let version = Version {
+                oid,
+                mode,
+                in_workspace: true,
+            };
+            versions.insert(entry.path().to_owned(), version);
+        }
+
+        Ok(versions)
     }
 
-    fn print_diff_mode(&self, a: &DiffTarget, b: &DiffTarget) {
+    /// The algorithm `diff` uses: `algorithm` if given, or else `diff.algorithm`. With
+    /// `minimal`, Myers' algorithm always finds the fewest edits.
+    pub fn diff_algorithm(
+        &self,
+        algorithm: Option<DiffAlgorithm>,
+        minimal: bool,
+    ) -> Result<DiffAlgorithm> {
+        use clap::ValueEnum;
+
+        let algorithm = match (algorithm, self.config.get("diff.algorithm")) {
+            (Some(algorithm), _) => algorithm,
+            (None, Some(value)) => DiffAlgorithm::from_str(value, true)
+                .map_err(|_| eyre!("unknown value for config 'diff.algorithm': {value}"))?,
+            (None, None) => DiffAlgorithm::default(),
+        };
+        Ok(match algorithm {
+            DiffAlgorithm::Myers if minimal => DiffAlgorithm::Minimal,
+            algorithm => algorithm,
+        })
+    }
+
+    /// Write the difference between the old version of a file and the new one.
+    fn diff_files(
+        &self,
+        writer: &mut impl WriteColor,
+        pair: FilePair,
+        options: &DiffOptions,
+        attributes: &mut Attributes,
+    ) -> Result<()> {
+        let FilePair {
+            old_path,
+            a,
+            new_path,
+            b,
+            similarity,
+        } = pair;
+        writeln!(
+            writer,
+            "diff --git {} {}",
+            Utf8Path::new("a").join(&old_path),
+            Utf8Path::new("b").join(&new_path)
+        )?;
+
+        self.print_diff_mode(writer, &a, &b)?;
+
+        if let Some(similarity) = similarity {
+            let (kind, score) = match similarity {
+                Similarity::Renamed(score) => ("rename", score),
+                Similarity::Copied(score) => ("copy", score),
+            };
+            writeln!(writer, "similarity index {}%", similarity_index(score))?;
+            writeln!(writer, "{kind} from {old_path}")?;
+            writeln!(writer, "{kind} to {new_path}")?;
+        }
+
+        let binary = self.is_binary(&new_path, &a, &b, attributes)?;
+        self.print_diff_content(writer, &a, &b, binary, options)
+    }
+
+    fn print_diff_mode(
+        &self,
+        writer: &mut impl WriteColor,
+        a: &DiffTarget,
+        b: &DiffTarget,
+    ) -> Result<()> {
         if a.is_removed() {
-            println!("added file mode {:o}", b.mode().unwrap());
+            writeln!(writer, "new file mode {:o}", b.mode().unwrap())?;
         } else if b.is_removed() {
-            println!("deleted file mode {:o}", a.mode().unwrap());
+            writeln!(writer, "deleted file mode {:o}", a.mode().unwrap())?;
         } else if a.mode() != b.mode() {
-            println!("old mode {:o}", a.mode().unwrap());
-            println!("new mode {:o}", b.mode().unwrap());
+            writeln!(writer, "old mode {:o}", a.mode().unwrap())?;
+            writeln!(writer, "new mode {:o}", b.mode().unwrap())?;
         }
+        Ok(())
     }
 
-    fn print_diff_content(&self, a: &DiffTarget, b: &DiffTarget) {
+    /// Whether to treat the change to `path` as binary. The `diff` attribute decides: unset (as
+    /// by `binary`) means binary, set means text, and a driver is binary if
+    /// `diff.<driver>.binary` says so. Otherwise, the change is binary if either side looks it.
+    fn is_binary(
+        &self,
+        path: &Utf8Path,
+        a: &DiffTarget,
+        b: &DiffTarget,
+        attributes: &mut Attributes,
+    ) -> Result<bool> {
+        let driver = match attributes.get(path, "diff") {
+            Some(AttrState::Unset) => return Ok(true),
+            Some(AttrState::Set) => return Ok(false),
+            Some(AttrState::Value(driver)) => {
+                self.config.get_bool(&format!("diff.{driver}.binary"))?
+            }
+            Some(AttrState::Unspecified) | None => None,
+        };
+
+        Ok(driver.unwrap_or_else(|| is_binary(a.data()) || is_binary(b.data())))
+    }
+
+    fn print_diff_content(
+        &self,
+        writer: &mut impl WriteColor,
+        a: &DiffTarget,
+        b: &DiffTarget,
+        binary: bool,
+        options: &DiffOptions,
+    ) -> Result<()> {
+        // Only the mode changed
+        if a.oid() == b.oid() {
+            return Ok(());
+        }
+
+        // Binary patches name objects in full, so they can be checked when applied
+        let (a_oid, b_oid) = match binary && options.binary {
+            true => (a.oid().to_hex(), b.oid().to_hex()),
+            false => (a.oid().short(), b.oid().short()),
+        };
         if a.mode() != b.mode() {
-            println!("index {}..{}", a.oid().short(), b.oid().short());
+            writeln!(writer, "index {a_oid}..{b_oid}")?;
         } else {
             assert!(a.mode().is_some());
-            println!(
-                "index {}..{} {:o}",
-                a.oid().short(),
-                b.oid().short(),
-                a.mode().unwrap()
-            );
+            writeln!(writer, "index {a_oid}..{b_oid} {:o}", a.mode().unwrap())?;
         }
-        println!("--- {}", a.path());
-        println!("+++ {}", b.path());
-
-        let a = std::str::from_utf8(a.data());
-        let b = std::str::from_utf8(b.data());
 
-        if a.is_err() || b.is_err() {
-            unimplemented!("invalid utf-8, assuming binary file, which is NYI")
+        if binary {
+            match options.binary {
+                true => write!(writer, "{}", binary_patch(a.data(), b.data())?)?,
+                false => writeln!(writer, "Binary files {} and {} differ", a.path(), b.path())?,
+            }
+            return Ok(());
         }
 
-        let a = a.unwrap().lines().collect::<Vec<_>>();
-        let b = b.unwrap().lines().collect::<Vec<_>>();
+        // Text that isn't UTF-8 is shown as well as it can be
+        let a_text = String::from_utf8_lossy(a.data());
+        let b_text = String::from_utf8_lossy(b.data());
+        let a_lines = a_text.lines().collect::<Vec<_>>();
+        let b_lines = b_text.lines().collect::<Vec<_>>();
 
-        let edits = crate::diff::diff(&a, &b);
+        // The new version comes first
+        let edits = options.algorithm.diff(&b_lines, &a_lines);
 
         let hunks = crate::diff::hunks(&edits);
 
-        let mut writer = StandardStream::stdout(ColorChoice::Auto);
+        // As when an empty file is created or deleted
+        if hunks.is_empty() {
+            return Ok(());
+        }
+        writeln!(writer, "--- {}", a.path())?;
+        writeln!(writer, "+++ {}", b.path())?;
 
         for hunk in hunks {
-            writer
-                .set_color(ColorSpec::new().set_fg(Some(Color::Ansi256(244))))
-                .unwrap();
-            println!("{}", hunk.header());
-            writer.reset().unwrap();
+            writer.set_color(ColorSpec::new().set_fg(Some(Color::Ansi256(244))))?;
+            writeln!(writer, "{}", hunk.header())?;
+            writer.reset()?;
             for edit in hunk.edits() {
                 match edit.kind() {
-                    EditKind::Insert => writer
-                        .set_color(ColorSpec::new().set_fg(Some(Color::Green)))
-                        .unwrap(),
-                    EditKind::Delete => writer
-                        .set_color(ColorSpec::new().set_fg(Some(Color::Red)))
-                        .unwrap(),
-                    EditKind::Equal => writer.reset().unwrap(),
+                    EditKind::Insert => {
+                        writer.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?
+                    }
+                    EditKind::Delete => {
+                        writer.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?
+                    }
+                    EditKind::Equal => writer.reset()?,
                 };
 
-                println!("{}", edit);
+                writeln!(writer, "{}", edit)?;
             }
         }
+        writer.reset()?;
+
+        Ok(())
     }
 }
 
+/// Pair up the files that appeared in `pairs` with files that went away, or with `copies`, that
+/// changed, and that they were renamed or copied from. The pairs stay in order of their new paths.
+///
+/// A file that went away is renamed to the last file that came from it, and copied to any others.
+/// Files that are still there are only copied.
+fn find_renames(pairs: Vec<FilePair>, options: &RenameOptions) -> Vec<FilePair> {
+    let sources = (0..pairs.len())
+        .filter(|&i| !pairs[i].a.is_removed() && (pairs[i].b.is_removed() || options.copies))
+        .collect::<Vec<_>>();
+    let destinations = (0..pairs.len())
+        .filter(|&i| pairs[i].a.is_removed())
+        .collect::<Vec<_>>();
+    if sources.is_empty() || destinations.is_empty() {
+        return pairs;
+    }
+
+    fn candidate<'a>(path: &'a Utf8Path, target: &'a DiffTarget) -> Candidate<'a> {
+        Candidate {
+            path,
+            oid: target.oid(),
+            data: target.data(),
+        }
+    }
+    let renames = rename::find_renames(
+        &sources
+            .iter()
+            .map(|&i| candidate(&pairs[i].old_path, &pairs[i].a))
+            .collect::<Vec<_>>(),
+        &destinations
+            .iter()
+            .map(|&i| candidate(&pairs[i].new_path, &pairs[i].b))
+            .collect::<Vec<_>>(),
+        options,
+    );
+
+    // Renames are in order of their destinations, so the last one for each source wins
+    let last_destination = renames
+        .iter()
+        .map(|x| (x.source, x.destination))
+        .collect::<HashMap<_, _>>();
+
+    let mut origins = HashMap::new();
+    let mut renamed = BTreeSet::new();
+    for x in renames {
+        let source = &pairs[sources[x.source]];
+        let similarity = if source.b.is_removed() && last_destination[&x.source] == x.destination {
+            renamed.insert(sources[x.source]);
+            Similarity::Renamed(x.score)
+        } else {
+            Similarity::Copied(x.score)
+        };
+        let origin = (source.old_path.clone(), source.a.clone(), similarity);
+        origins.insert(destinations[x.destination], origin);
+    }
+
+    pairs
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !renamed.contains(i))
+        .map(|(i, pair)| match origins.remove(&i) {
+            Some((old_path, a, similarity)) => FilePair {
+                old_path,
+                a,
+                similarity: Some(similarity),
+                ..pair
+            },
+            None => pair,
+        })
+        .collect()
+}
+
+#[derive(Clone)]
 enum DiffTarget {
     Removed,
     Modified {
@@ -148,57 +605,33 @@ enum DiffTarget {
 pub const NULL_PATH: &str = "/dev/null";
 
 impl DiffTarget {
-    fn from_file(path: &Utf8Path) -> Result<Self> {
-        if !path.exists() {
-            Ok(Self::Removed)
-        } else {
-            let bytes = std::fs::read(path)?;
-            let blob = Blob::new(bytes);
-            let formatted = blob.format();
-
-            let oid = blob.oid(&formatted);
-            let mode = FileMode::from(&Repo::stat_file(path)?.unwrap());
-            let path = Utf8Path::new("b").join(path);
-            Ok(Self::Modified {
-                oid,
-                mode,
-                path,
-                data: blob.into_data(),
-            })
-        }
-    }
-
-    fn from_index(path: &Utf8Path, repo: &Repo) -> Result<Self> {
-        let entry = match repo.index.get_entry_by_path(path) {
-            Some(x) => x,
+    /// Load `version` of `path`, labelled with `prefix`: `a` for the old version, and `b` for
+    /// the new one.
+    fn load(repo: &Repo, path: &Utf8Path, version: Option<Version>, prefix: &str) -> Result<Self> {
+        let Version {
+            oid,
+            mode,
+            in_workspace,
+        } = match version {
+            Some(version) => version,
             None => return Ok(Self::Removed),
         };
-        Self::from_entry(path, repo, entry)
-    }
 
-    fn from_head(path: &Utf8Path, repo: &Repo, tree: &Tree) -> Result<Self> {
-        let entry = match tree.get_entry(path.as_str()) {
-            Some(x) => x,
-            None => return Ok(Self::Removed),
+        let data = if in_workspace {
+            std::fs::read(repo.dir.join(path))?
+        } else {
+            repo.database
+                .load(&oid)?
+                .into_blob()
+                .expect("file oid should be blob")
+                .into_data()
         };
-        Self::from_entry(path, repo, entry)
-    }
-
-    fn from_entry(path: &Utf8Path, repo: &Repo, entry: &IndexEntry) -> Result<Self> {
-        let oid = entry.oid().clone();
-        let mode = entry.mode();
-        let path = Utf8Path::new("a").join(path);
-        let blob = repo
-            .database
-            .load(&oid)?
-            .into_blob()
-            .expect("file oid should be blob");
 
         Ok(Self::Modified {
             oid,
             mode,
-            path,
-            data: blob.into_data(),
+            path: Utf8Path::new(prefix).join(path),
+            data,
         })
     }
 
@@ -0,0 +1,55 @@
+use std::process::Command;
+
+use camino::Utf8Path;
+use color_eyre::eyre::{eyre, Context};
+use tracing::debug;
+
+use crate::Result;
+
+impl super::Repo {
+    /// The command used to edit files, from the first of `GIT_EDITOR`, `core.editor`, `VISUAL`
+    /// and `EDITOR` that is set, or `vi`. As in git, `VISUAL` is ignored on a dumb terminal, where
+    /// there is no default.
+    pub fn editor(&self) -> Result<String> {
+        let dumb = std::env::var("TERM").map_or(true, |term| term == "dumb");
+        let editor = std::env::var("GIT_EDITOR")
+            .ok()
+            .or_else(|| self.config.get("core.editor").map(str::to_owned))
+            .or_else(|| std::env::var("VISUAL").ok().filter(|_| !dumb))
+            .or_else(|| std::env::var("EDITOR").ok())
+            .filter(|editor| !editor.is_empty());
+
+        match editor {
+            Some(editor) => Ok(editor),
+            None if dumb => Err(eyre!("Terminal is dumb, but EDITOR unset")),
+            None => Ok("vi".to_owned()),
+        }
+    }
+
+    /// Open `path` in the user's [editor](Self::editor), and wait for them to finish.
+    ///
+    /// The editor is run by the shell, so it may include arguments, such as `code --wait`.
+    pub fn launch_editor(&self, path: &Utf8Path) -> Result<()> {
+        let editor = self.editor()?;
+        // `:` does nothing, so there is no need to start a shell for it
+        if editor == ":" {
+            return Ok(());
+        }
+
+        debug!(%editor, %path, "Launching editor");
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$@\""))
+            .arg(&editor)
+            .arg(path)
+            .current_dir(&self.dir)
+            .status()
+            .wrap_err_with(|| format!("Unable to start editor '{editor}'"))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(eyre!("There was a problem with the editor '{editor}'."))
+        }
+    }
+}
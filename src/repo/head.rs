@@ -12,18 +12,14 @@ impl super::Repo {
         }
         let contents = std::fs::read_to_string(&self.head_path)?;
         let contents = contents.trim();
-        if let Some(contents) = contents.strip_prefix("ref: ") {
-            let path = self.git_dir.join(contents);
-            if !path.exists() {
-                if !self.database.any(|item| item.is_commit())? {
+        if let Some(target) = contents.strip_prefix("ref: ") {
+            match self.read_full_ref(target)? {
+                Some(oid) => Ok(Some(oid)),
+                None if !self.database.any(|item| item.is_commit())? => {
                     // an empty repo can have a dangling HEAD and that is fine
-                    return Ok(None);
+                    Ok(None)
                 }
-                Err(eyre!("HEAD points to non-existstant ref: {}", path))
-            } else {
-                let contents = std::fs::read_to_string(path)?;
-                let contents = contents.trim();
-                Ok(Some(Digest::from_str(contents)?))
+                None => Err(eyre!("HEAD points to non-existstant ref: {}", target)),
             }
         } else {
             let digest = Digest::from_str(contents)
@@ -31,4 +27,17 @@ impl super::Repo {
             Ok(Some(digest))
         }
     }
+
+    /// Returns the name of the branch HEAD points to, or `None` if HEAD is detached. The branch
+    /// need not exist yet, as is the case in a repository with no commits.
+    pub fn current_branch(&self) -> Result<Option<String>> {
+        if !self.head_path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&self.head_path)?;
+        Ok(contents
+            .trim()
+            .strip_prefix("ref: refs/heads/")
+            .map(str::to_owned))
+    }
 }
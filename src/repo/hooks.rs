@@ -0,0 +1,49 @@
+use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+use camino::Utf8PathBuf;
+use color_eyre::eyre::{eyre, Context};
+use tracing::debug;
+
+use crate::Result;
+
+impl super::Repo {
+    /// Where hooks are installed: `core.hooksPath`, relative to the workspace, or `.git/hooks`.
+    fn hooks_dir(&self) -> Result<Utf8PathBuf> {
+        Ok(match self.config.get_path("core.hookspath")? {
+            Some(path) => self.dir.join(path),
+            None => self.git_dir.join("hooks"),
+        })
+    }
+
+    /// Run the hook `name` with `args` from the root of the workspace, if it is installed.
+    /// Returns whether the hook ran, or an error if it exited unsuccessfully.
+    ///
+    /// See: <https://git-scm.com/docs/githooks>
+    pub fn run_hook(&self, name: &str, args: &[&str]) -> Result<bool> {
+        let path = self.hooks_dir()?.join(name);
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return Ok(false),
+        };
+
+        if metadata.permissions().mode() & 0o111 == 0 {
+            eprintln!("hint: The '{name}' hook was ignored because it's not set as executable.");
+            return Ok(false);
+        }
+
+        debug!(%name, ?args, "Running hook");
+        let status = Command::new(&path)
+            .args(args)
+            .current_dir(&self.dir)
+            .env("GIT_INDEX_FILE", self.git_dir.join("index"))
+            .status()
+            .wrap_err_with(|| format!("Unable to run the '{name}' hook"))?;
+
+        if status.success() {
+            Ok(true)
+        } else {
+            Err(eyre!("The '{name}' hook failed ({status})"))
+        }
+    }
+}
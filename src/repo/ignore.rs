@@ -1,16 +1,33 @@
-use once_cell::sync::OnceCell;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::ignore::{Ignores, PatternList};
 
 impl super::Repo {
-    pub fn ignores(&self) -> &[String] {
-        static IGNORES: OnceCell<Vec<String>> = OnceCell::new();
+    /// Load the ignore rules for this repository. `.gitignore` files in the workspace are read
+    /// lazily, as paths in their directories are checked.
+    pub fn ignores(&self) -> Ignores {
+        let root = Utf8Path::new("");
+        let exclude = PatternList::from_file(&self.git_dir.join("info/exclude"), root);
+        let global = match self.excludes_file() {
+            Some(path) => PatternList::from_file(&path, root),
+            None => PatternList::default(),
+        };
+
+        Ignores::new(&self.dir, exclude, global)
+    }
 
-        IGNORES.get_or_init(|| {
-            let global_ignore = [".git".to_owned()];
-            let gitignore =
-                std::fs::read_to_string(self.dir.join(".gitignore")).unwrap_or_default();
-            let repo_ignore = gitignore.lines().map(|x| x.to_string());
+    /// The path of the user's global ignore file. This is `core.excludesFile` if it is set,
+    /// falling back to `$XDG_CONFIG_HOME/git/ignore`.
+    fn excludes_file(&self) -> Option<Utf8PathBuf> {
+        // An unexpandable path is treated as unset
+        if let Ok(Some(path)) = self.config.get_path("core.excludesfile") {
+            return Some(path);
+        }
 
-            global_ignore.into_iter().chain(repo_ignore).collect()
-        })
+        let home = std::env::var("HOME").ok().map(Utf8PathBuf::from);
+        match std::env::var("XDG_CONFIG_HOME") {
+            Ok(xdg) if !xdg.is_empty() => Some(Utf8PathBuf::from(xdg).join("git/ignore")),
+            _ => home.map(|home| home.join(".config/git/ignore")),
+        }
     }
 }
@@ -0,0 +1,330 @@
+use std::io::Write;
+
+use color_eyre::eyre::eyre;
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+use crate::commit::{Commit, CommitWalk, Signature};
+use crate::digest::Digest;
+use crate::revision::Rev;
+use crate::timestamp::DateFormat;
+use crate::Result;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The oid, author, and the subject, indented
+    Short,
+    /// git's default: the oid, author, date, and indented message
+    Medium,
+    /// The oid, author, committer, and indented message
+    Full,
+    /// Like [`LogFormat::Full`], with both dates
+    Fuller,
+    /// The abbreviated oid and subject, on one line
+    Oneline,
+    /// A user-specified format string. See [`format_commit`]. With `terminate`, every commit's
+    /// output ends with a newline, and otherwise newlines only go between commits.
+    Custom { format: String, terminate: bool },
+}
+
+impl LogFormat {
+    /// Parse the value of `--format` or `--pretty`: the name of a format, `format:<string>`,
+    /// `tformat:<string>`, or a string containing placeholders, which is treated as `tformat:`.
+    pub fn parse(value: &str) -> Result<Self> {
+        if let Some(format) = value.strip_prefix("format:") {
+            return Ok(Self::Custom {
+                format: format.to_owned(),
+                terminate: false,
+            });
+        }
+        if let Some(format) = value.strip_prefix("tformat:") {
+            return Ok(Self::Custom {
+                format: format.to_owned(),
+                terminate: true,
+            });
+        }
+
+        match value {
+            "oneline" => Ok(Self::Oneline),
+            "short" => Ok(Self::Short),
+            "medium" => Ok(Self::Medium),
+            "full" => Ok(Self::Full),
+            "fuller" => Ok(Self::Fuller),
+            _ if value.contains('%') => Ok(Self::Custom {
+                format: value.to_owned(),
+                terminate: true,
+            }),
+            _ => Err(eyre!("invalid --pretty format: {value}")),
+        }
+    }
+}
+
+pub struct LogOptions<'a> {
+    /// The revision to start from. Defaults to HEAD.
+    pub start: Option<&'a str>,
+    pub max_count: Option<usize>,
+    pub format: LogFormat,
+    /// Show abbreviated oids in the commit headers, rather than full ones
+    pub abbrev_commit: bool,
+    pub date_format: DateFormat,
+    /// Check the signatures of signed commits, and print what the signing program said
+    pub show_signature: bool,
+}
+
+impl super::Repo {
+    pub fn log(&self, options: LogOptions) -> Result<()> {
+        let start = match options.start {
+            Some(rev) => Rev::parse(rev)?
+                .resolve(self)?
+                .ok_or_else(|| eyre!("unknown revision: '{}'", rev))?,
+            None => self
+                .read_head()?
+                .ok_or_else(|| eyre!("your current branch does not have any commits yet"))?,
+        };
+
+        let walk = CommitWalk::new(&self.database, [start])?;
+        let walk = walk.take(options.max_count.unwrap_or(usize::MAX));
+
+        let mut writer = StandardStream::stdout(ColorChoice::Auto);
+        let mut first = true;
+        let abbrev = self.abbrev_len()?;
+
+        for item in walk {
+            let (oid, commit) = item?;
+            let signature = match commit.signature() {
+                Some(signature) if options.show_signature => Some(
+                    self.verify_signature(&commit.signed_payload(), signature)?
+                        .output,
+                ),
+                _ => None,
+            };
+
+            match &options.format {
+                LogFormat::Oneline => {
+                    write!(&mut writer, "{}", signature.unwrap_or_default())?;
+                    writer.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+                    let id = if options.abbrev_commit {
+                        self.database.abbreviate(&oid, abbrev)?
+                    } else {
+                        oid.to_hex()
+                    };
+                    write!(&mut writer, "{id}")?;
+                    writer.reset()?;
+                    writeln!(&mut writer, " {}", commit.subject())?;
+                }
+                LogFormat::Custom { format, terminate } => {
+                    if !first && !terminate {
+                        writeln!(&mut writer)?;
+                    }
+                    write!(&mut writer, "{}", signature.unwrap_or_default())?;
+                    let short = self.database.abbreviate(&oid, abbrev)?;
+                    write!(
+                        &mut writer,
+                        "{}",
+                        format_commit(format, &oid, &short, &commit, options.date_format)
+                    )?;
+                    if *terminate {
+                        writeln!(&mut writer)?;
+                    }
+                }
+                format => {
+                    if !first {
+                        writeln!(&mut writer)?;
+                    }
+                    // Parents are only shown for merges
+                    let parents = match commit.parents() {
+                        [_, _, ..] => commit
+                            .parents()
+                            .iter()
+                            .map(|parent| self.database.abbreviate(parent, abbrev))
+                            .collect::<Result<Vec<_>>>()?,
+                        _ => Vec::new(),
+                    };
+                    print_pretty(
+                        &mut writer,
+                        format,
+                        &oid,
+                        &parents,
+                        signature.as_deref(),
+                        &commit,
+                        options.date_format,
+                    )?;
+                }
+            }
+            first = false;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Print `commit` in one of the multi-line formats. `parents` are the abbreviated oids of the
+/// commit's parents, if it is a merge, and `signature` is what checking its signature printed, if
+/// it was asked for.
+fn print_pretty(
+    writer: &mut StandardStream,
+    format: &LogFormat,
+    oid: &Digest,
+    parents: &[String],
+    signature: Option<&str>,
+    commit: &Commit,
+    date_format: DateFormat,
+) -> std::io::Result<()> {
+    writer.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+    writeln!(writer, "commit {oid:x}")?;
+    writer.reset()?;
+    write!(writer, "{}", signature.unwrap_or_default())?;
+
+    if !parents.is_empty() {
+        writeln!(writer, "Merge: {}", parents.join(" "))?;
+    }
+
+    let (author, committer) = (commit.author(), commit.committer());
+    let identity = |signature: &Signature| format!("{} <{}>", signature.name(), signature.email());
+    match format {
+        LogFormat::Short => writeln!(writer, "Author: {}", identity(author))?,
+        LogFormat::Full => {
+            writeln!(writer, "Author: {}", identity(author))?;
+            writeln!(writer, "Commit: {}", identity(committer))?;
+        }
+        LogFormat::Fuller => {
+            writeln!(writer, "Author:     {}", identity(author))?;
+            writeln!(writer, "AuthorDate: {}", author.when().format(date_format))?;
+            writeln!(writer, "Commit:     {}", identity(committer))?;
+            writeln!(
+                writer,
+                "CommitDate: {}",
+                committer.when().format(date_format)
+            )?;
+        }
+        _ => {
+            writeln!(writer, "Author: {}", identity(author))?;
+            writeln!(writer, "Date:   {}", author.when().format(date_format))?;
+        }
+    }
+    writeln!(writer)?;
+
+    if *format == LogFormat::Short {
+        writeln!(writer, "    {}", commit.subject())?;
+    } else {
+        for line in commit.message().lines() {
+            writeln!(writer, "    {line}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand the placeholders in a `--format` string for `commit`, whose abbreviated oid is `short`.
+///
+/// Supported placeholders are:
+/// - `%H`: the commit oid
+/// - `%h`: the abbreviated commit oid
+/// - `%an`: the author name
+/// - `%ae`: the author email
+/// - `%ad`: the author date, respecting `--date`
+/// - `%s`: the subject
+/// - `%b`: the body
+/// - `%n`: a newline
+/// - `%%`: a literal `%`
+///
+/// Unknown placeholders are copied to the output unchanged, as git does.
+pub fn format_commit(
+    format: &str,
+    oid: &Digest,
+    short: &str,
+    commit: &Commit,
+    date_format: DateFormat,
+) -> String {
+    let mut out = String::with_capacity(format.len());
+    let mut rest = format;
+
+    while let Some(idx) = rest.find('%') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        let author = commit.author();
+        let (expansion, len) = match rest.as_bytes().get(1..3) {
+            Some(b"an") => (author.name().to_owned(), 3),
+            Some(b"ae") => (author.email().to_owned(), 3),
+            Some(b"ad") => (author.when().format(date_format), 3),
+            _ => match rest.as_bytes().get(1) {
+                Some(b'H') => (oid.to_hex(), 2),
+                Some(b'h') => (short.to_owned(), 2),
+                Some(b's') => (commit.subject(), 2),
+                Some(b'b') => (commit.body().to_owned(), 2),
+                Some(b'n') => ("\n".to_owned(), 2),
+                Some(b'%') => ("%".to_owned(), 2),
+                _ => ("%".to_owned(), 1),
+            },
+        };
+
+        out.push_str(&expansion);
+        rest = &rest[len..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_format_commit() {
+        let input = "tree 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5
+author Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
+committer Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
+
+Add a feature
+to rit
+
+This is the body.
+";
+        let commit = Commit::parse(input.as_bytes()).unwrap();
+        let oid = Digest::from_str("e6a49274aa0893ce2e2928589100387aee220c5b").unwrap();
+
+        let format = |f| format_commit(f, &oid, &oid.short(), &commit, DateFormat::Short);
+
+        assert_eq!(format("%h %s"), "e6a4927 Add a feature to rit");
+        assert_eq!(format("%H"), oid.to_hex());
+        assert_eq!(
+            format("%an <%ae> %ad"),
+            "Jamie Quigley <jamie@quigley.xyz> 2022-07-20"
+        );
+        assert_eq!(format("%b"), "This is the body.");
+        assert_eq!(format("100%% %x%n"), "100% %x\n");
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(LogFormat::parse("oneline").unwrap(), LogFormat::Oneline);
+        assert_eq!(LogFormat::parse("fuller").unwrap(), LogFormat::Fuller);
+        assert_eq!(
+            LogFormat::parse("%h %s").unwrap(),
+            LogFormat::Custom {
+                format: "%h %s".to_owned(),
+                terminate: true
+            }
+        );
+        assert_eq!(
+            LogFormat::parse("format:oneline").unwrap(),
+            LogFormat::Custom {
+                format: "oneline".to_owned(),
+                terminate: false
+            }
+        );
+        assert_eq!(
+            LogFormat::parse("tformat:%H").unwrap(),
+            LogFormat::Custom {
+                format: "%H".to_owned(),
+                terminate: true
+            }
+        );
+        assert!(LogFormat::parse("online").is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::str::FromStr;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::{debug, trace};
+
+use crate::digest::Digest;
+use crate::filemode::FileMode;
+use crate::merge::{self, MergedEntry};
+use crate::revision::Rev;
+use crate::tree::RawTreeEntry;
+use crate::Result;
+
+use super::status::{Change, Status};
+
+pub enum MergeOutcome {
+    /// The target is already reachable from HEAD
+    UpToDate,
+    /// HEAD was an ancestor of the target, and has been moved to it
+    FastForward(Digest),
+    /// The merge was clean, and a merge commit was created
+    Merged(Digest),
+    /// The merge stopped because of conflicts in these paths
+    Conflicted(Vec<Utf8PathBuf>),
+}
+
+impl super::Repo {
+    fn merge_head_path(&self) -> Utf8PathBuf {
+        self.git_dir.join("MERGE_HEAD")
+    }
+
+    fn merge_msg_path(&self) -> Utf8PathBuf {
+        self.git_dir.join("MERGE_MSG")
+    }
+
+    /// Returns the commit being merged into HEAD, if a merge is in progress.
+    pub fn read_merge_head(&self) -> Result<Option<Digest>> {
+        let path = self.merge_head_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        let oid = Digest::from_str(contents.trim())
+            .wrap_err_with(|| eyre!("Unexpected MERGE_HEAD contents: {contents}"))?;
+        Ok(Some(oid))
+    }
+
+    /// Forget about any merge in progress.
+    pub fn clear_merge_state(&mut self) -> Result<()> {
+        for path in [self.merge_head_path(), self.merge_msg_path()] {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the commit `rev` into HEAD.
+    ///
+    /// If the merge is clean, a merge commit with `message` (or a default message) is created.
+    /// Otherwise, conflicts are written to the workspace with conflict markers and recorded in the
+    /// index, and the merge can be concluded with [`merge_continue`] once they're resolved.
+    ///
+    /// [`merge_continue`]: Self::merge_continue
+    pub fn merge(&mut self, rev: &str, message: Option<&str>) -> Result<MergeOutcome> {
+        if self.read_merge_head()?.is_some() {
+            return Err(eyre!(
+                "You have not concluded your merge (MERGE_HEAD exists). Please, commit your changes before you merge."
+            ));
+        }
+
+        let head = self.read_head()?.ok_or_else(|| {
+            eyre!("Cannot merge: your current branch does not have any commits yet")
+        })?;
+        let target = Rev::parse(rev)?
+            .resolve(self)?
+            .ok_or_else(|| eyre!("merge: {rev} - not something we can merge"))?;
+
+        let base = merge::merge_base(&self.database, &head, &target)?;
+        debug!(?head, ?target, ?base, "Merging");
+
+        if base.as_ref() == Some(&target) {
+            return Ok(MergeOutcome::UpToDate);
+        }
+
+        self.ensure_clean_for_merge()?;
+
+        let base_tree = match &base {
+            Some(base) => Some(self.database.load_commit(base)?.tree_id().clone()),
+            None => None,
+        };
+        let head_tree = self.database.load_commit(&head)?.tree_id().clone();
+        let target_tree = self.database.load_commit(&target)?.tree_id().clone();
+
+        let changes = merge::merge_trees(
+            &self.database,
+            base_tree.as_ref(),
+            &head_tree,
+            &target_tree,
+            "HEAD",
+            rev,
+        )?;
+
+        self.check_untracked_overwrites(changes.keys(), "merge")?;
+        let conflicts = self.apply_merge(&changes)?;
+
+        if base.as_ref() == Some(&head) {
+            debug_assert!(conflicts.is_empty());
+            self.set_head(&target, &format!("merge {rev}: Fast-forward"))?;
+            return Ok(MergeOutcome::FastForward(target));
+        }
+
+        let message = match message {
+            Some(message) => message.to_owned(),
+            None if self.branch_exists(rev)? => format!("Merge branch '{rev}'"),
+            None => format!("Merge commit '{rev}'"),
+        };
+
+        std::fs::write(self.merge_head_path(), format!("{target:x}\n"))?;
+        std::fs::write(self.merge_msg_path(), &message)?;
+
+        if !conflicts.is_empty() {
+            return Ok(MergeOutcome::Conflicted(conflicts));
+        }
+
+        let oid = self.commit(&message)?;
+        Ok(MergeOutcome::Merged(oid))
+    }
+
+    /// Conclude a merge that stopped due to conflicts, once they have all been resolved.
+    pub fn merge_continue(&mut self) -> Result<Digest> {
+        if self.read_merge_head()?.is_none() {
+            return Err(eyre!("There is no merge in progress (MERGE_HEAD missing)"));
+        }
+
+        if self.index.has_conflicts() {
+            return Err(eyre!(
+                "You have not concluded your merge: fix conflicts and run 'rit add' on the files"
+            ));
+        }
+
+        let message = std::fs::read_to_string(self.merge_msg_path()).unwrap_or_default();
+        self.commit(message.trim())
+    }
+
+    /// Refuse to merge if there are uncommitted changes to tracked files.
+    fn ensure_clean_for_merge(&self) -> Result<()> {
+        let status = match Status::new(self)? {
+            Some(status) => status,
+            None => return Ok(()),
+        };
+
+        let dirty = status
+            .get_statuses()?
+            .into_iter()
+            .filter(|(_, change)| *change != Change::Untracked)
+            .map(|(path, _)| path.to_string())
+            .collect::<Vec<_>>();
+
+        if dirty.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "Your local changes to the following files would be overwritten by merge:\n\t{}\nPlease commit your changes before you merge.",
+                dirty.join("\n\t")
+            ))
+        }
+    }
+
+    /// Refuse to carry out `operation` if writing to `paths` would overwrite any untracked files.
+    pub(super) fn check_untracked_overwrites<'a>(
+        &self,
+        paths: impl Iterator<Item = &'a Utf8PathBuf>,
+        operation: &str,
+    ) -> Result<()> {
+        let untracked = paths
+            .filter(|path| {
+                self.index.get_entry_by_path(path).is_none() && self.dir.join(path).is_file()
+            })
+            .map(|path| path.to_string())
+            .collect::<Vec<_>>();
+
+        if untracked.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "The following untracked working tree files would be overwritten by {operation}:\n\t{}\nPlease move or remove them before you {operation}.",
+                untracked.join("\n\t")
+            ))
+        }
+    }
+
+    /// Write the result of a tree merge into the workspace and the index. Returns the paths that
+    /// were conflicted.
+    fn apply_merge(
+        &mut self,
+        changes: &std::collections::BTreeMap<Utf8PathBuf, MergedEntry>,
+    ) -> Result<Vec<Utf8PathBuf>> {
+        let mut conflicts = Vec::new();
+
+        for (path, change) in changes {
+            trace!(%path, ?change, "Applying merge result");
+            match change {
+                MergedEntry::Clean(entry) => {
+                    let (_, data) = self.database.read_raw(&entry.oid)?;
+                    self.write_workspace_file(path, &data, FileMode::from(entry.mode))?;
+                    self.add_to_index(path, &entry.oid)?;
+                }
+                MergedEntry::Deleted => {
+                    self.remove_workspace_file(path)?;
+                    self.index.remove(path);
+                }
+                MergedEntry::Conflict {
+                    base,
+                    ours,
+                    theirs,
+                    merged,
+                } => {
+                    let mode = ours
+                        .as_ref()
+                        .or(theirs.as_ref())
+                        .map(|x| FileMode::from(x.mode))
+                        .unwrap_or(FileMode::Regular);
+                    self.write_workspace_file(path, merged, mode)?;
+
+                    fn version(x: &Option<RawTreeEntry>) -> Option<(&Digest, FileMode)> {
+                        x.as_ref().map(|x| (&x.oid, FileMode::from(x.mode)))
+                    }
+                    self.index
+                        .add_conflict(path, version(base), version(ours), version(theirs));
+                    conflicts.push(path.clone());
+                }
+            }
+        }
+
+        self.index.flush()?;
+
+        Ok(conflicts)
+    }
+
+    /// Add the file at `path`, which has just been written with the contents of blob `oid`, to
+    /// the index.
+    pub(super) fn add_to_index(&mut self, path: &Utf8Path, oid: &Digest) -> Result<()> {
+        let stat = Self::stat_file(&self.dir.join(path))?
+            .ok_or_else(|| eyre!("File should exist: {path}"))?;
+        self.index.add(path, oid, stat);
+        Ok(())
+    }
+}
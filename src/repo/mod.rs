@@ -1,13 +1,25 @@
 mod add;
+mod attributes;
 mod branch;
-mod commit;
+pub mod checkout;
+pub mod commit;
+pub mod config;
 pub mod database;
 pub mod diff;
+mod editor;
 mod head;
+mod hooks;
 mod ignore;
+pub mod log;
+pub mod merge;
+mod reflog;
 mod refs;
+mod repack;
 mod show_head;
+pub mod signing;
 pub mod status;
+mod tag;
+pub mod transaction;
 mod workspace;
 
 use camino::{Utf8Path, Utf8PathBuf};
@@ -16,7 +28,9 @@ use std::fs::File;
 use std::io::Write;
 use tracing::*;
 
+use crate::config::{Config, ConfigFile, ConfigKey};
 use crate::index::IndexWrapper;
+use crate::packed_refs::PackedRefsCache;
 use crate::Result;
 
 use self::database::Database;
@@ -33,8 +47,11 @@ pub struct Repo {
     pub head_path: Utf8PathBuf,
     pub refs_path: Utf8PathBuf,
     pub heads_path: Utf8PathBuf,
+    pub tags_path: Utf8PathBuf,
     pub database: Database,
     pub index: IndexWrapper,
+    pub config: Config,
+    packed_refs: PackedRefsCache,
 }
 
 /// The default location to store the git information. This cannot (yet) be changed.
@@ -51,19 +68,24 @@ impl Repo {
         }
 
         trace!(path=?repo_root, "Opening repo");
-        let database = Database::new(&git_dir);
+        let config = Config::load(Some(&git_dir))?;
+        let database = Database::new(&git_dir)?;
         let index = IndexWrapper::open(&git_dir);
         let head_path = git_dir.join("HEAD");
         let refs_path = git_dir.join("refs");
         let heads_path = refs_path.join("heads");
+        let tags_path = refs_path.join("tags");
         Ok(Self {
             dir: repo_root,
             git_dir,
             head_path,
             refs_path,
             heads_path,
+            tags_path,
             database,
             index,
+            config,
+            packed_refs: PackedRefsCache::default(),
         })
     }
 
@@ -79,7 +101,7 @@ impl Repo {
             warn!("Repo already exists, init will do nothing");
             return Ok(());
         }
-        for d in ["objects", "refs", "refs/heads"] {
+        for d in ["objects", "refs", "refs/heads", "refs/tags"] {
             let dir = git_dir.join(d);
             trace!(path=?dir, "Creating directory");
             std::fs::create_dir_all(dir)?;
@@ -92,18 +114,19 @@ impl Repo {
             branch_name
         )?;
 
-        /// Needed for gitoxide's [`discover`] to work
-        /// TODO: actually generate this
-        ///
-        /// [`discover`]: https://docs.rs/git-repository/latest/git_repository/struct.ThreadSafeRepository.html#method.discover
-        const DEFAULT_CONFIG: &str = "[core]
-\trepositoryformatversion = 0
-\tfilemode = true
-\tbare = false
-\tlogallrefupdates = true";
-
-        let config_path = git_dir.join("config");
-        write!(File::create(config_path)?, "{}", DEFAULT_CONFIG)?;
+        // Needed for gitoxide's [`discover`] to work
+        //
+        // [`discover`]: https://docs.rs/git-repository/latest/git_repository/struct.ThreadSafeRepository.html#method.discover
+        let mut config = ConfigFile::open(&git_dir.join("config"))?;
+        for (key, value) in [
+            ("core.repositoryformatversion", "0"),
+            ("core.filemode", "true"),
+            ("core.bare", "false"),
+            ("core.logallrefupdates", "true"),
+        ] {
+            config.set(&key.parse::<ConfigKey>()?, value)?;
+        }
+        config.save()?;
         Ok(())
     }
 }
@@ -0,0 +1,212 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::trace;
+
+use crate::commit::{CommitWalk, Signature};
+use crate::config::parse_bool;
+use crate::digest::Digest;
+use crate::lockfile::Lockfile;
+use crate::reflog::{self, ReflogEntry};
+use crate::timestamp::Timestamp;
+use crate::Result;
+
+impl super::Repo {
+    pub(super) fn reflog_path(&self, name: &str) -> Utf8PathBuf {
+        self.git_dir.join("logs").join(name)
+    }
+
+    /// Whether updates to the ref `name` should be logged, according to `core.logAllRefUpdates`.
+    ///
+    /// When it is `true` (the default), only HEAD, branches, remote-tracking branches and notes
+    /// are logged. When `always`, every ref is. Refs that already have a reflog are always
+    /// logged.
+    fn should_log(&self, name: &str) -> Result<bool> {
+        if self.reflog_path(name).is_file() {
+            return Ok(true);
+        }
+
+        match self.config.get("core.logallrefupdates") {
+            Some(value) if value.eq_ignore_ascii_case("always") => Ok(true),
+            Some(value) => match parse_bool(value) {
+                Some(true) => Ok(is_logged_by_default(name)),
+                Some(false) => Ok(false),
+                None => Err(eyre!("bad core.logAllRefUpdates value: '{value}'")),
+            },
+            None => Ok(is_logged_by_default(name)),
+        }
+    }
+
+    /// The identity recorded in reflog entries. Unlike commits, a missing identity is not an
+    /// error, as it would stop checkouts from working.
+    fn reflog_identity(&self) -> Signature {
+        self.committer().unwrap_or_else(|_| {
+            let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_owned());
+            let email = format!("{user}@localhost");
+            Signature::new(user, email, Timestamp::now())
+        })
+    }
+
+    /// Record that the ref `name` changed from `old` to `new`, if it should be logged.
+    pub(super) fn append_reflog(
+        &self,
+        name: &str,
+        old: Option<&Digest>,
+        new: &Digest,
+        message: &str,
+    ) -> Result<()> {
+        if !self.should_log(name)? {
+            return Ok(());
+        }
+
+        let path = self.reflog_path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let entry = ReflogEntry::new(old, new, self.reflog_identity(), message);
+        trace!(%name, %entry, "Appending to reflog");
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(entry.to_string().as_bytes()))
+            .wrap_err_with(|| format!("Unable to append to {path}"))
+    }
+
+    /// Remove the reflog of the ref `name`, which is being deleted.
+    pub(super) fn delete_reflog(&self, name: &str) -> Result<()> {
+        let path = self.reflog_path(name);
+        if path.is_file() {
+            std::fs::remove_file(&path)?;
+            self.remove_empty_log_dirs(&path);
+        }
+        Ok(())
+    }
+
+    /// Move the reflog of `from` to be the reflog of `to`, replacing any it already has.
+    pub(super) fn rename_reflog(&self, from: &str, to: &str) -> Result<()> {
+        let from_path = self.reflog_path(from);
+        let to_path = self.reflog_path(to);
+        if let Some(parent) = to_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(&from_path, &to_path)
+            .wrap_err_with(|| format!("Unable to move reflog '{from_path}' to '{to_path}'"))?;
+        self.remove_empty_log_dirs(&from_path);
+        Ok(())
+    }
+
+    /// Remove the directories holding the (removed) reflog at `path`, up to the first that isn't
+    /// empty.
+    fn remove_empty_log_dirs(&self, path: &Utf8Path) {
+        for dir in path.ancestors().skip(1) {
+            if dir == self.git_dir.join("logs") || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Read the reflog of the ref with the full name `name`, oldest entry first.
+    pub fn read_reflog(&self, name: &str) -> Result<Vec<ReflogEntry>> {
+        reflog::read(&self.reflog_path(name))
+    }
+
+    /// The full names of every ref that has a reflog.
+    pub fn reflog_names(&self) -> Result<Vec<String>> {
+        let logs = self.git_dir.join("logs");
+        let mut names = Vec::new();
+
+        if !logs.is_dir() {
+            return Ok(names);
+        }
+
+        for entry in walkdir::WalkDir::new(&logs) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let path = entry.path().strip_prefix(&logs)?;
+                let name = path
+                    .to_str()
+                    .ok_or_else(|| eyre!("All paths must be valid unicode: {path:?}"))?;
+                names.push(name.to_owned());
+            }
+        }
+
+        names.sort_unstable();
+        Ok(names)
+    }
+
+    /// Remove entries from the reflog of `name` that are older than `expire`, or older than
+    /// `expire_unreachable` and no longer reachable from the ref. Both are unix timestamps, with
+    /// `None` meaning entries are never expired for that reason. Returns the number of entries
+    /// removed.
+    pub fn expire_reflog(
+        &mut self,
+        name: &str,
+        expire: Option<i64>,
+        expire_unreachable: Option<i64>,
+    ) -> Result<usize> {
+        let path = self.reflog_path(name);
+        if !path.is_file() {
+            return Ok(0);
+        }
+
+        // Hold the ref's lock, so it can't be updated (and logged) while we rewrite its log
+        let _ref_lock = Lockfile::acquire(&self.git_dir.join(name))?;
+        let mut lock = Lockfile::acquire(&path)?;
+
+        let entries = self.read_reflog(name)?;
+        let mut reachable: Option<HashSet<Digest>> = None;
+        let mut kept = Vec::new();
+
+        for entry in &entries {
+            let time = entry.committer.when().0.timestamp();
+            let expired = if expire.is_some_and(|cutoff| time < cutoff) {
+                true
+            } else if expire_unreachable.is_some_and(|cutoff| time < cutoff) {
+                let reachable = match &mut reachable {
+                    Some(reachable) => reachable,
+                    None => reachable.insert(self.reachable_from_ref(name)?),
+                };
+                !reachable.contains(&entry.new)
+            } else {
+                false
+            };
+
+            if !expired {
+                kept.push(entry);
+            }
+        }
+
+        let removed = entries.len() - kept.len();
+        trace!(%name, removed, "Expiring reflog");
+        for entry in kept {
+            lock.write_all(entry.to_string().as_bytes())?;
+        }
+        lock.commit()?;
+
+        Ok(removed)
+    }
+
+    /// Every commit reachable from the current value of the ref `name`.
+    fn reachable_from_ref(&self, name: &str) -> Result<HashSet<Digest>> {
+        let tip = match self.read_full_ref(name)? {
+            Some(tip) => self.database.peel(&tip)?,
+            None => return Ok(HashSet::new()),
+        };
+
+        CommitWalk::new(&self.database, [tip])?
+            .map(|x| x.map(|(oid, _)| oid))
+            .collect()
+    }
+}
+
+fn is_logged_by_default(name: &str) -> bool {
+    name == "HEAD"
+        || ["refs/heads/", "refs/remotes/", "refs/notes/"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
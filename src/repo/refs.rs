@@ -1,122 +1,294 @@
-use std::fs::File;
-use std::io::Write;
+use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use camino::{Utf8Path, Utf8PathBuf};
-use color_eyre::eyre::eyre;
-use tap::Tap;
+use camino::Utf8Path;
+use color_eyre::eyre::{eyre, Context};
 use tracing::trace;
+use walkdir::WalkDir;
 
-use crate::digest::Digest;
-use crate::repo::database::LoadedItem;
+use crate::digest::{Digest, MINIMUM_ABBREV};
+use crate::lockfile::Lockfile;
+use crate::packed_refs::PackedRefs;
 use crate::revision::is_valid_ref_name;
 use crate::Result;
 
+use super::transaction::{Expected, RefTransaction};
+
 impl super::Repo {
-    /// Updates the value of HEAD to oid
-    pub fn set_head(&mut self, oid: &Digest) -> Result<()> {
-        self.update_ref_file(&self.head_path, oid)
+    /// Updates the value of HEAD to oid. If HEAD is a symbolic ref, the branch it points to is
+    /// updated instead. `message` is recorded in the reflog.
+    pub fn set_head(&mut self, oid: &Digest, message: &str) -> Result<()> {
+        self.update_ref("HEAD", oid, Expected::Any, message)
+    }
+
+    /// Point HEAD at the branch `name`, making it a symbolic ref. The move is recorded in HEAD's
+    /// reflog with `message`.
+    pub fn attach_head(&mut self, name: &str, message: &str) -> Result<()> {
+        trace!(%name, "Attaching HEAD");
+        let old = self.read_head()?;
+        let mut lock = Lockfile::acquire(&self.head_path)?;
+        lock.write_all(format!("ref: refs/heads/{name}\n").as_bytes())?;
+        lock.commit()?;
+
+        if let Some(new) = self.read_head()? {
+            self.append_reflog("HEAD", old.as_ref(), &new, message)?;
+        }
+        Ok(())
+    }
+
+    /// Point HEAD directly at `oid`, detaching it from any branch. The move is recorded in HEAD's
+    /// reflog with `message`.
+    pub fn detach_head(&mut self, oid: &Digest, message: &str) -> Result<()> {
+        let mut transaction = RefTransaction::new();
+        transaction
+            .no_deref()
+            .message(message)
+            .update("HEAD", oid, Expected::Any);
+        self.commit_transaction(transaction)
     }
 
-    pub fn create_branch(&mut self, name: &str, target: &Digest) -> Result<()> {
+    /// Create the branch `name` pointing at `target`. `start_point` is how the target was named by
+    /// the user, and is recorded in the reflog.
+    pub fn create_branch(&mut self, name: &str, target: &Digest, start_point: &str) -> Result<()> {
         if !is_valid_ref_name(name) {
             return Err(eyre!("Invalid ref name: {}", name));
         }
 
-        let path = self.heads_path.join(name);
-
-        if path.exists() {
+        if self.branch_exists(name)? {
             return Err(eyre!("Branch already exists: {}", name));
         }
 
-        self.update_ref_file(&path, target)
+        self.update_ref(
+            &format!("refs/heads/{name}"),
+            target,
+            Expected::Absent,
+            &format!("branch: Created from {start_point}"),
+        )
     }
 
-    /// Set the value of a ref file to the specified oid.
-    ///
-    /// This function does not use git locks. This creates a possible issue when multiple processes
-    /// (realistically, git and rit) are contending a head file. The solution to this is to Just
-    /// Not run rit while a git process is running.
-    fn update_ref_file(&self, path: &Utf8Path, oid: &Digest) -> Result<()> {
-        trace!(%path, ?oid, "Updating ref");
-        let mut file = File::create(path)?;
-        writeln!(&mut file, "{oid:x}")?;
+    /// Follow the chain of symbolic refs starting at the full ref name `name`, returning the name
+    /// of the ref at the end of it. The final ref need not exist.
+    pub fn resolve_symbolic_ref(&self, name: &str) -> Result<String> {
+        let mut name = name.to_owned();
+        // Same limit as git, to avoid looping forever
+        for _ in 0..5 {
+            let contents = match std::fs::read_to_string(self.git_dir.join(&name)) {
+                Ok(contents) => contents,
+                // A missing (or packed) ref ends the chain
+                Err(_) => return Ok(name),
+            };
+            match contents.trim().strip_prefix("ref: ") {
+                Some(target) => name = target.to_owned(),
+                None => return Ok(name),
+            }
+        }
+        Err(eyre!("symbolic ref loop at '{name}'"))
+    }
+
+    /// Remove any directories left empty by deleting the ref file at `path`, up to (but not
+    /// including) `refs/` itself.
+    pub(super) fn remove_empty_ref_dirs(&self, path: &Utf8Path) -> Result<()> {
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.refs_path) || dir == self.refs_path {
+                break;
+            }
+            if std::fs::remove_dir(dir).is_err() {
+                // Not empty
+                break;
+            }
+        }
         Ok(())
     }
 
+    /// Read the ref `name`, which may be abbreviated as in `master` or `tags/v1.0`. A name that
+    /// isn't a ref but is at least 4 hex characters is looked up as an abbreviated oid, which is
+    /// an [`AmbiguousObject`](super::database::AmbiguousObject) error if it matches more than one
+    /// object.
     pub fn read_ref(&self, name: &str) -> Result<Option<Digest>> {
-        if let Some(path) = self.path_for_ref(name) {
-            let string = std::fs::read_to_string(path)?;
+        if let Some(full_name) = self.expand_ref_name(name)? {
+            return self.read_full_ref(&full_name);
+        }
+
+        if name.len() < MINIMUM_ABBREV || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+            // Definitely not an oid fragment
+            return Ok(None);
+        }
+
+        self.database.resolve_prefix(name, None)
+    }
+
+    /// Read the ref with the full name `name`, such as `HEAD` or `refs/heads/master`, following
+    /// symbolic refs. A loose ref file shadows a packed ref of the same name. Returns `None` if the
+    /// ref (or the target of a symbolic ref) does not exist.
+    pub fn read_full_ref(&self, name: &str) -> Result<Option<Digest>> {
+        let path = self.git_dir.join(name);
+        if path.is_file() {
+            let string = std::fs::read_to_string(&path)?;
             let string = string.trim();
 
-            if string.starts_with("ref: refs/") {
+            if let Some(target) = string.strip_prefix("ref: ") {
                 // This is a symbolic ref
                 // See: https://git-scm.com/docs/git-symbolic-ref
-                let target = string.trim_start_matches("ref: refs/");
-                return self.read_ref(target);
+                return self.read_full_ref(target);
             }
 
-            let oid = Digest::from_str(string)?;
+            let oid = Digest::from_str(string)
+                .wrap_err_with(|| format!("Bad ref file {path}: {string:?}"))?;
             return Ok(Some(oid));
         }
 
-        if !name.chars().all(|c| c.is_ascii_hexdigit()) {
-            // Definitely not an oid fragment
-            return Ok(None);
+        Ok(self.packed_refs()?.get(name).map(|r| r.oid.clone()))
+    }
+
+    /// Returns `true` if the ref with the full name `name` exists, either loose or packed.
+    pub fn ref_exists(&self, name: &str) -> Result<bool> {
+        Ok(self.git_dir.join(name).is_file() || self.packed_refs()?.contains(name))
+    }
+
+    /// Delete the ref with the full name `name`, both its loose file and any packed entry.
+    /// Returns the oid it pointed to.
+    pub fn delete_ref(&mut self, name: &str) -> Result<Digest> {
+        let oid = self
+            .read_full_ref(name)?
+            .ok_or_else(|| eyre!("ref '{name}' not found"))?;
+        trace!(%name, ?oid, "Deleting ref");
+
+        let mut transaction = RefTransaction::new();
+        transaction
+            .no_deref()
+            .delete(name, Expected::Value(oid.clone()));
+        self.commit_transaction(transaction)?;
+
+        Ok(oid)
+    }
+
+    /// The refs in `packed-refs`, which is only read again if it has changed since last time.
+    pub(super) fn packed_refs(&self) -> Result<Arc<PackedRefs>> {
+        self.packed_refs.get(&self.git_dir)
+    }
+
+    /// List every ref under `refs/`, loose or packed, as `(full name, oid)` pairs sorted by name.
+    /// Symbolic refs are resolved.
+    pub fn list_refs(&self) -> Result<Vec<(String, Digest)>> {
+        let mut refs = BTreeMap::new();
+
+        for (name, r) in self.packed_refs()?.iter() {
+            refs.insert(name.to_owned(), r.oid.clone());
         }
 
-        let candidates = self.database.prefix_match(name)?;
-
-        match &candidates[..] {
-            [] => Ok(None),
-            [oid] => Ok(Some(oid.clone())),
-            candidates => {
-                // TODO: this should not explode. Make it return a real error type that captures
-                // this information
-                eprintln!("Too many candidates for prefix {}:", name);
-
-                for candidate in candidates {
-                    eprint!("\t{candidate:x}");
-                    let loaded = self.database.load(candidate)?;
-                    let kind = loaded.kind();
-                    eprint!(" {kind}");
-                    if let LoadedItem::Commit(commit) = loaded {
-                        let date = commit.commit_date();
-                        let first_line = commit
-                            .message()
-                            .split_once('\n')
-                            .map(|x| x.0)
-                            .unwrap_or(commit.message());
-                        eprint!(" {date}: {first_line}");
-                    }
-                    eprintln!()
-                }
+        for name in self.loose_ref_names()? {
+            if let Some(oid) = self.read_full_ref(&name)? {
+                refs.insert(name, oid);
+            }
+        }
+
+        Ok(refs.into_iter().collect())
+    }
+
+    /// Like [`list_refs`](Self::list_refs), but with annotated tags peeled to the object they
+    /// point to. Packed refs are peeled using their `^` lines where possible, rather than reading
+    /// the tags.
+    pub fn list_peeled_refs(&self) -> Result<Vec<(String, Digest)>> {
+        let packed = self.packed_refs()?;
+        self.list_refs()?
+            .into_iter()
+            .map(|(name, oid)| {
+                // A loose ref takes precedence, so the packed value may be stale
+                let peeled = match packed.peeled(&name) {
+                    Some(peeled) if !self.git_dir.join(&name).is_file() => peeled.clone(),
+                    _ => self.database.peel(&oid)?,
+                };
+                Ok((name, peeled))
+            })
+            .collect()
+    }
+
+    /// The full names of every loose ref file under `refs/`.
+    fn loose_ref_names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
 
-                std::process::exit(1);
+        for entry in WalkDir::new(&self.refs_path) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
             }
+            let path = Utf8Path::from_path(entry.path())
+                .ok_or_else(|| eyre!("All paths must be valid unicode: {:?}", entry.path()))?;
+            names.push(path.strip_prefix(&self.git_dir)?.to_string());
         }
+
+        Ok(names)
     }
 
-    fn path_for_ref(&self, name: &str) -> Option<Utf8PathBuf> {
-        if name == "HEAD" {
-            return Some(self.head_path.clone());
+    /// Move loose refs into `packed-refs`. Only tags are packed unless `all` is set, as branches
+    /// are expected to change often. Unless `prune` is false, the loose files are then deleted.
+    ///
+    /// Annotated tags are stored along with the object they peel to.
+    pub fn pack_refs(&mut self, all: bool, prune: bool) -> Result<()> {
+        let mut packed = PackedRefs::lock(&self.git_dir)?;
+        let mut loose = Vec::new();
+
+        for name in self.loose_ref_names()? {
+            if !all && !name.starts_with("refs/tags/") {
+                continue;
+            }
+
+            // Symbolic refs are never packed
+            let contents = std::fs::read_to_string(self.git_dir.join(&name))?;
+            if contents.starts_with("ref: ") {
+                continue;
+            }
+
+            let oid = Digest::from_str(contents.trim())?;
+            let peeled = match self.database.peel(&oid)? {
+                peeled if peeled == oid => None,
+                peeled => Some(peeled),
+            };
+            trace!(%name, ?oid, ?peeled, "Packing ref");
+            packed.insert(name.clone(), oid.clone(), peeled);
+            loose.push((name, oid));
         }
 
-        let x = self.refs_path.join(name);
-        if x.exists() {
-            return Some(x);
+        packed.commit()?;
+
+        if prune {
+            for (name, oid) in loose {
+                // Only remove the loose ref if nothing has changed it since it was packed
+                let path = self.git_dir.join(&name);
+                let lock = match Lockfile::acquire(&path) {
+                    Ok(lock) => lock,
+                    Err(_) => continue,
+                };
+                if self.read_full_ref(&name)?.as_ref() == Some(&oid) {
+                    lock.delete()?;
+                    self.remove_empty_ref_dirs(&path)?;
+                }
+            }
         }
 
-        let x = x.tap_mut(|x| {
-            x.clear();
-            x.push(&self.heads_path);
-            x.push(name);
-        });
+        Ok(())
+    }
 
-        if x.exists() {
-            return Some(x);
+    /// Find the full name of the ref `name`, trying the same locations as git, in order: the full
+    /// name if it starts with `refs/`, `refs/<name>`, `refs/tags/<name>`, `refs/heads/<name>`, then
+    /// `refs/remotes/<name>`. The first that exists, either loose or packed, wins.
+    pub fn expand_ref_name(&self, name: &str) -> Result<Option<String>> {
+        if name == "HEAD" {
+            return Ok(Some(name.to_owned()));
         }
 
-        None
+        let packed = self.packed_refs()?;
+        let full = name.starts_with("refs/").then(|| name.to_owned());
+
+        Ok(full
+            .into_iter()
+            .chain([
+                format!("refs/{name}"),
+                format!("refs/tags/{name}"),
+                format!("refs/heads/{name}"),
+                format!("refs/remotes/{name}"),
+            ])
+            .find(|x| self.git_dir.join(x).is_file() || packed.contains(x)))
     }
 }
@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+
+use camino::Utf8PathBuf;
+use tracing::{debug, trace};
+
+use crate::commit::Commit;
+use crate::pack::{self, ObjectKind, PackObject};
+use crate::tag::Tag;
+use crate::tree::Tree;
+use crate::Result;
+
+impl super::Repo {
+    /// Write every object reachable from HEAD and the refs into a single new pack.
+    ///
+    /// If `prune` is set, loose objects and packs that are made redundant by the new pack are
+    /// deleted. Unreachable loose objects are never deleted.
+    ///
+    /// Returns the path of the new pack index, or `None` if there was nothing to pack.
+    pub fn repack(&mut self, prune: bool) -> Result<Option<Utf8PathBuf>> {
+        let objects = self.reachable_objects()?;
+
+        if objects.is_empty() {
+            debug!("No reachable objects, not writing a pack");
+            return Ok(None);
+        }
+
+        let pack_dir = self.database.database_root.join("pack");
+        let idx_path = pack::write_pack(&pack_dir, objects)?;
+        self.database.reload_packs()?;
+
+        if prune {
+            self.prune_packed(&idx_path)?;
+            self.database.reload_packs()?;
+        }
+
+        Ok(Some(idx_path))
+    }
+
+    /// Collect every object reachable from HEAD and the refs, along with the path each was
+    /// first seen at.
+    fn reachable_objects(&self) -> Result<Vec<PackObject>> {
+        let mut stack = self
+            .list_refs()?
+            .into_iter()
+            .map(|(_, oid)| oid)
+            .chain(self.read_head()?)
+            .map(|oid| (oid, String::new()))
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        let mut objects = Vec::new();
+
+        while let Some((oid, path)) = stack.pop() {
+            if !seen.insert(oid.clone()) {
+                continue;
+            }
+
+            trace!(?oid, %path, "Visiting object");
+            let (kind, data) = self.database.read_raw(&oid)?;
+
+            match kind {
+                ObjectKind::Commit => {
+                    let commit = Commit::parse(&data)?;
+                    stack.push((commit.tree_id().clone(), String::new()));
+                    stack.extend(commit.parents().iter().map(|x| (x.clone(), String::new())));
+                }
+                ObjectKind::Tree => {
+                    for entry in Tree::parse_raw(&data)? {
+                        // Submodule commits live in another repository
+                        if entry.is_gitlink() {
+                            continue;
+                        }
+                        let path = match path.as_str() {
+                            "" => entry.name,
+                            path => format!("{path}/{}", entry.name),
+                        };
+                        stack.push((entry.oid, path));
+                    }
+                }
+                ObjectKind::Tag => {
+                    let tag = Tag::parse(&data)?;
+                    stack.push((tag.object().clone(), String::new()));
+                }
+                ObjectKind::Blob => {}
+            }
+
+            objects.push(PackObject {
+                name_hash: pack::name_hash(&path),
+                oid,
+                kind,
+                data,
+            });
+        }
+
+        Ok(objects)
+    }
+
+    /// Delete loose objects and other packs whose contents are all present in the pack at
+    /// `idx_path`.
+    fn prune_packed(&self, idx_path: &Utf8PathBuf) -> Result<()> {
+        let new_pack = self
+            .database
+            .packs()
+            .iter()
+            .find(|pack| pack.pack_path() == idx_path.with_extension("pack"))
+            .expect("the new pack should have been loaded");
+        let index = new_pack.index();
+
+        for oid in self.database.loose_entries() {
+            if index.contains(&oid) {
+                let path = self.database.object_path(&oid);
+                trace!(%path, "Pruning loose object");
+                std::fs::remove_file(&path)?;
+                if let Some(dir) = path.parent() {
+                    // Only succeeds if this was the last object in the directory
+                    let _ = std::fs::remove_dir(dir);
+                }
+            }
+        }
+
+        for pack in self.database.packs() {
+            if pack.pack_path() == new_pack.pack_path() {
+                continue;
+            }
+            if pack.index().oids().iter().all(|oid| index.contains(oid)) {
+                debug!(path=%pack.pack_path(), "Removing redundant pack");
+                std::fs::remove_file(pack.pack_path().with_extension("idx"))?;
+                std::fs::remove_file(pack.pack_path())?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+//! Signing objects and checking their signatures, with `gpg` for OpenPGP, `gpgsm` for X.509, or
+//! `ssh-keygen` for SSH signatures.
+//!
+//! See: <https://git-scm.com/docs/git-config#Documentation/git-config.txt-gpgformat>
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{eyre, Context};
+use tracing::debug;
+
+use crate::digest::Digest;
+use crate::util;
+use crate::Result;
+
+/// The kinds of signature, as chosen by `gpg.format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFormat {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl SignatureFormat {
+    /// Recognise the format of `signature` by its armour.
+    pub fn detect(signature: &str) -> Option<Self> {
+        if signature.starts_with("-----BEGIN PGP SIGNATURE-----")
+            || signature.starts_with("-----BEGIN PGP MESSAGE-----")
+        {
+            Some(Self::OpenPgp)
+        } else if signature.starts_with("-----BEGIN SIGNED MESSAGE-----") {
+            Some(Self::X509)
+        } else if signature.starts_with("-----BEGIN SSH SIGNATURE-----") {
+            Some(Self::Ssh)
+        } else {
+            None
+        }
+    }
+
+    /// The name used in config, as in `gpg.<name>.program`.
+    fn name(self) -> &'static str {
+        match self {
+            Self::OpenPgp => "openpgp",
+            Self::X509 => "x509",
+            Self::Ssh => "ssh",
+        }
+    }
+
+    fn default_program(self) -> &'static str {
+        match self {
+            Self::OpenPgp => "gpg",
+            Self::X509 => "gpgsm",
+            Self::Ssh => "ssh-keygen",
+        }
+    }
+}
+
+/// The outcome of checking a signature.
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub format: SignatureFormat,
+    /// Whether the signature is valid, and was made by a known key
+    pub good: bool,
+    /// What the program printed for people to read
+    pub output: String,
+}
+
+impl super::Repo {
+    /// The format new signatures are made in, from `gpg.format`.
+    pub fn signature_format(&self) -> Result<SignatureFormat> {
+        match self.config.get("gpg.format") {
+            None | Some("openpgp") => Ok(SignatureFormat::OpenPgp),
+            Some("x509") => Ok(SignatureFormat::X509),
+            Some("ssh") => Ok(SignatureFormat::Ssh),
+            Some(other) => Err(eyre!("invalid value for 'gpg.format': '{other}'")),
+        }
+    }
+
+    /// The program that handles `format`: `gpg.<format>.program`, or for OpenPGP the older
+    /// `gpg.program`, or the usual tool for the format.
+    fn signing_program(&self, format: SignatureFormat) -> String {
+        let specific = format!("gpg.{}.program", format.name());
+        self.config
+            .get(&specific)
+            .or_else(|| match format {
+                SignatureFormat::OpenPgp => self.config.get("gpg.program"),
+                _ => None,
+            })
+            .unwrap_or(format.default_program())
+            .to_owned()
+    }
+
+    /// Sign `payload` with `key`, or `user.signingKey`, returning the armoured signature.
+    ///
+    /// OpenPGP and X.509 keys default to the committer's identity. SSH keys are either a path to
+    /// a key file, or a public key given literally (optionally prefixed with `key::`), whose
+    /// private key is in `ssh-agent`.
+    pub fn sign_buffer(&self, payload: &[u8], key: Option<&str>) -> Result<String> {
+        let format = self.signature_format()?;
+        let program = self.signing_program(format);
+        let key = key
+            .filter(|key| !key.is_empty())
+            .or_else(|| self.config.get("user.signingkey"))
+            .map(str::to_owned);
+
+        if format == SignatureFormat::Ssh {
+            let key =
+                key.ok_or_else(|| eyre!("user.signingKey needs to be set for ssh signing"))?;
+            return self.sign_ssh(&program, payload, &key);
+        }
+
+        let key = match key {
+            Some(key) => key,
+            None => {
+                let committer = self.committer()?;
+                format!("{} <{}>", committer.name(), committer.email())
+            }
+        };
+        let output = run(&program, &["--status-fd=2", "-bsau", &key], payload)?;
+        let status = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() || !has_status(&status, "SIG_CREATED") {
+            return Err(eyre!("gpg failed to sign the data:\n{status}"));
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn sign_ssh(&self, program: &str, payload: &[u8], key: &str) -> Result<String> {
+        let buffer = TempFile::new(&self.git_dir, payload)?;
+        // A literal public key means the private key is in the agent
+        let literal = key
+            .strip_prefix("key::")
+            .or_else(|| key.starts_with("ssh-").then_some(key));
+        let key_file = match literal {
+            Some(key) => Some(TempFile::new(&self.git_dir, key.as_bytes())?),
+            None => None,
+        };
+
+        let mut args = vec!["-Y", "sign", "-n", "git", "-f"];
+        match &key_file {
+            Some(file) => args.extend([file.path.as_str(), "-U"]),
+            None => args.push(key),
+        }
+        args.push(buffer.path.as_str());
+
+        let output = run(program, &args, &[])?;
+        let signature_path = Utf8PathBuf::from(format!("{}.sig", buffer.path));
+        let signature = std::fs::read_to_string(&signature_path);
+        let _ = std::fs::remove_file(&signature_path);
+        if !output.status.success() {
+            return Err(eyre!(
+                "ssh-keygen failed to sign the data:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        signature.wrap_err("ssh-keygen did not write a signature")
+    }
+
+    /// Check that `signature` is a valid signature of `payload`, with the program for its
+    /// format. SSH signatures are checked against `gpg.ssh.allowedSignersFile`.
+    pub fn verify_signature(&self, payload: &[u8], signature: &str) -> Result<Verification> {
+        let format = SignatureFormat::detect(signature)
+            .ok_or_else(|| eyre!("unrecognised signature format"))?;
+        let program = self.signing_program(format);
+        let signature_file = TempFile::new(&self.git_dir, signature.as_bytes())?;
+
+        if format == SignatureFormat::Ssh {
+            return self.verify_ssh(&program, payload, &signature_file.path);
+        }
+
+        let output = run(
+            &program,
+            &[
+                "--keyid-format=long",
+                "--status-fd=1",
+                "--verify",
+                signature_file.path.as_str(),
+                "-",
+            ],
+            payload,
+        )?;
+        let status = String::from_utf8_lossy(&output.stdout);
+        let good = has_status(&status, "GOODSIG") && !has_status(&status, "BADSIG");
+        Ok(Verification {
+            format,
+            good,
+            output: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    fn verify_ssh(
+        &self,
+        program: &str,
+        payload: &[u8],
+        signature: &Utf8Path,
+    ) -> Result<Verification> {
+        let allowed = self
+            .config
+            .get_path("gpg.ssh.allowedsignersfile")?
+            .filter(|path| path.is_file())
+            .ok_or_else(|| {
+                eyre!("gpg.ssh.allowedSignersFile needs to be configured and exist for ssh signature verification")
+            })?;
+        let signature = signature.as_str();
+
+        let principals = run(
+            program,
+            &[
+                "-Y",
+                "find-principals",
+                "-f",
+                allowed.as_str(),
+                "-s",
+                signature,
+            ],
+            &[],
+        )?;
+        let principals = String::from_utf8_lossy(&principals.stdout).into_owned();
+
+        let mut output = String::new();
+        for principal in principals.lines().filter(|x| !x.is_empty()) {
+            let verified = run(
+                program,
+                &[
+                    "-Y",
+                    "verify",
+                    "-n",
+                    "git",
+                    "-f",
+                    allowed.as_str(),
+                    "-I",
+                    principal,
+                    "-s",
+                    signature,
+                ],
+                payload,
+            )?;
+            output = String::from_utf8_lossy(&verified.stdout).into_owned();
+            if verified.status.success() {
+                return Ok(Verification {
+                    format: SignatureFormat::Ssh,
+                    good: true,
+                    output,
+                });
+            }
+            output.push_str(&String::from_utf8_lossy(&verified.stderr));
+        }
+
+        if output.is_empty() {
+            // Say what we can about a signature from an unknown key
+            let checked = run(
+                program,
+                &["-Y", "check-novalidate", "-n", "git", "-s", signature],
+                payload,
+            )?;
+            output = String::from_utf8_lossy(&checked.stdout).into_owned();
+            output.push_str(&String::from_utf8_lossy(&checked.stderr));
+            output.push_str("No principal matched.\n");
+        }
+        Ok(Verification {
+            format: SignatureFormat::Ssh,
+            good: false,
+            output,
+        })
+    }
+
+    /// Check the signature of the commit `oid`, if it has one.
+    pub fn verify_commit(&self, oid: &Digest) -> Result<Option<Verification>> {
+        let commit = self.database.load_commit(oid)?;
+        match commit.signature() {
+            Some(signature) => self
+                .verify_signature(&commit.signed_payload(), signature)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Whether gpg printed the status `keyword`, as `[GNUPG:] <keyword> ...`.
+fn has_status(status: &str, keyword: &str) -> bool {
+    status.lines().any(|line| {
+        line.strip_prefix("[GNUPG:] ")
+            .and_then(|line| line.strip_prefix(keyword))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    })
+}
+
+/// Run `program` with `args`, giving it `stdin`.
+fn run(program: &str, args: &[&str], stdin: &[u8]) -> Result<Output> {
+    debug!(%program, ?args, "Running signing program");
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("Unable to run '{program}'"))?;
+
+    // Write from another thread, so the child can't block on a full stdout while we wait for it
+    // to read all of its input
+    let mut input = child.stdin.take().unwrap();
+    let stdin = stdin.to_vec();
+    let writer = std::thread::spawn(move || input.write_all(&stdin));
+    let output = child.wait_with_output()?;
+    // The program may exit without reading its input, which is fine
+    let _ = writer.join();
+    Ok(output)
+}
+
+/// A file in the git directory that is removed when dropped, for passing data to programs that
+/// only read files.
+struct TempFile {
+    path: Utf8PathBuf,
+}
+
+impl TempFile {
+    fn new(dir: &Utf8Path, contents: &[u8]) -> Result<Self> {
+        let path = dir.join(util::tmp_file_name());
+        std::fs::write(&path, contents)?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -1,10 +1,16 @@
+use crate::diff::rename::{find_renames, Candidate};
+use crate::digest::Digest;
 use crate::index::IndexEntry;
 use crate::storable::DatabaseObject;
 use crate::tree::Tree;
 use crate::Result;
 use crate::{blob::Blob, tree::TreeEntry};
 
-use std::{collections::HashMap, fmt::Display, io::Write};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::Write,
+};
 
 use camino::{Utf8Path, Utf8PathBuf};
 use rayon::prelude::*;
@@ -25,20 +31,39 @@ impl super::Repo {
             None => return Ok(()),
         };
 
-        let statuses = status
-            .get_statuses()?
-            .tap_mut(|v| v.sort_unstable_by_key(|x| x.0));
+        let mut statuses = status.get_statuses()?;
+        let renames = status.detect_renames(&mut statuses)?;
+        statuses.sort_unstable_by_key(|x| x.0);
 
         match mode {
-            StatusOutputMode::Long => print_long_status(&statuses)?,
-            StatusOutputMode::Porcelain => print_porcelain_status(&statuses),
+            StatusOutputMode::Long => print_long_status(&statuses, &renames)?,
+            StatusOutputMode::Porcelain => print_porcelain_status(&statuses, &renames),
         }
 
         Ok(())
     }
 }
 
-fn print_long_status(statuses: &[(&Utf8Path, Change)]) -> std::io::Result<()> {
+/// The files `files` as candidates for renames.
+fn candidates<'a>(files: &'a [(&Utf8Path, &Digest, Vec<u8>)]) -> Vec<Candidate<'a>> {
+    files
+        .iter()
+        .map(|(path, oid, data)| Candidate { path, oid, data })
+        .collect()
+}
+
+/// Show `path`, or for a renamed file, `source -> path`.
+fn display_path(path: &Utf8Path, renames: &HashMap<&Utf8Path, &Utf8Path>) -> String {
+    match renames.get(path) {
+        Some(source) => format!("{source} -> {path}"),
+        None => path.to_string(),
+    }
+}
+
+fn print_long_status(
+    statuses: &[(&Utf8Path, Change)],
+    renames: &HashMap<&Utf8Path, &Utf8Path>,
+) -> std::io::Result<()> {
     let mut writer = StandardStream::stdout(ColorChoice::Auto);
 
     let mut it = statuses.iter().filter(|x| x.1.is_index()).peekable();
@@ -50,9 +75,10 @@ fn print_long_status(statuses: &[(&Utf8Path, Change)]) -> std::io::Result<()> {
                 Change::IndexAdded => "new file",
                 Change::IndexRemoved => "deleted",
                 Change::IndexModified => "modified",
+                Change::IndexRenamed => "renamed",
                 _ => unreachable!(),
             };
-            writeln!(&mut writer, "\t{word}: {path}")?;
+            writeln!(&mut writer, "\t{word}: {}", display_path(path, renames))?;
         }
         writer.reset()?;
         writeln!(&mut writer)?;
@@ -93,9 +119,12 @@ fn print_long_status(statuses: &[(&Utf8Path, Change)]) -> std::io::Result<()> {
     Ok(())
 }
 
-fn print_porcelain_status(statuses: &[(&Utf8Path, Change)]) {
+fn print_porcelain_status(
+    statuses: &[(&Utf8Path, Change)],
+    renames: &HashMap<&Utf8Path, &Utf8Path>,
+) {
     for (path, change) in statuses {
-        println!("{} {}", change, path);
+        println!("{} {}", change, display_path(path, renames));
     }
 }
 
@@ -114,13 +143,19 @@ pub enum Change {
     IndexAdded,
     IndexRemoved,
     IndexModified,
+    /// Added to the index, having been renamed from a file removed from it. See
+    /// [`Status::detect_renames`]
+    IndexRenamed,
 }
 
 impl Change {
     fn is_index(self) -> bool {
         matches!(
             self,
-            Change::IndexAdded | Change::IndexRemoved | Change::IndexModified
+            Change::IndexAdded
+                | Change::IndexRemoved
+                | Change::IndexModified
+                | Change::IndexRenamed
         )
     }
 }
@@ -134,6 +169,7 @@ impl Display for Change {
             Change::IndexAdded => write!(f, "A "),
             Change::IndexRemoved => write!(f, "D "),
             Change::IndexModified => write!(f, "M "),
+            Change::IndexRenamed => write!(f, "R "),
         }
     }
 }
@@ -227,6 +263,72 @@ impl<'r: 'i, 'i> Status<'r, 'i> {
             .tap_mut(|v| v.extend(del)))
     }
 
+    /// Find the files added to the index that were renamed from files removed from it, if
+    /// `status.renames` says to. Their changes become [`Change::IndexRenamed`], and the changes
+    /// for the files they came from are dropped. The map gives the file each came from.
+    pub fn detect_renames<'s>(
+        &'s self,
+        statuses: &mut Vec<(&'s Utf8Path, Change)>,
+    ) -> Result<HashMap<&'s Utf8Path, &'s Utf8Path>> {
+        let options = match self.repo.rename_options("status.renames")? {
+            // Only renames are shown, not copies
+            Some(options) => options.tap_mut(|x| x.copies = false),
+            None => return Ok(HashMap::new()),
+        };
+
+        let load = |path, oid| -> Result<_> {
+            let blob = self.repo.database.load(oid)?.into_blob();
+            Ok((
+                path,
+                oid,
+                blob.expect("file oid should be blob").into_data(),
+            ))
+        };
+
+        let mut destinations = Vec::new();
+        for &(path, change) in statuses.iter() {
+            if change == Change::IndexAdded {
+                destinations.push(load(path, self.index[path].oid())?);
+            }
+        }
+        let removed = statuses
+            .iter()
+            .filter(|(_, change)| *change == Change::IndexRemoved)
+            .map(|(path, _)| *path)
+            .collect::<HashSet<_>>();
+        let mut sources = Vec::new();
+        for entry in self.head_tree.iter() {
+            let (name, oid) = match entry {
+                TreeEntry::File(f) => (f.name(), f.oid()),
+                TreeEntry::IncompleteFile { name, oid, .. } => (name.as_str(), oid),
+                TreeEntry::Directory { .. } => continue,
+            };
+            if removed.contains(Utf8Path::new(name)) {
+                sources.push(load(Utf8Path::new(name), oid)?);
+            }
+        }
+        sources.sort_unstable_by_key(|x| x.0);
+        if sources.is_empty() || destinations.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let renames = find_renames(&candidates(&sources), &candidates(&destinations), &options)
+            .into_iter()
+            .map(|x| (destinations[x.destination].0, sources[x.source].0))
+            .collect::<HashMap<_, _>>();
+
+        let renamed = renames.values().copied().collect::<HashSet<_>>();
+        statuses
+            .retain(|(path, change)| *change != Change::IndexRemoved || !renamed.contains(path));
+        for (path, change) in statuses.iter_mut() {
+            if renames.contains_key(path) {
+                *change = Change::IndexRenamed;
+            }
+        }
+
+        Ok(renames)
+    }
+
     /// Checks whether an index entry has been modified.
     ///
     /// Returns `true` if a file has been modified, `false` otherwise.
@@ -237,7 +339,7 @@ impl<'r: 'i, 'i> Status<'r, 'i> {
             None => return Ok(false),
         };
 
-        if !entry.stat_matches(&stat) {
+        if !entry.stat_matches(&stat, self.repo.trust_filemode()?) {
             return Ok(true);
         }
         if entry.times_match(&stat) {
@@ -0,0 +1,131 @@
+use color_eyre::eyre::{eyre, WrapErr};
+use tracing::trace;
+
+use crate::commit::cleanup::{cleanup_message, Cleanup};
+use crate::digest::Digest;
+use crate::ignore::wildmatch;
+use crate::revision::{is_valid_ref_name, Rev};
+use crate::storable::DatabaseObject;
+use crate::tag::Tag;
+use crate::Result;
+
+use super::transaction::Expected;
+
+impl super::Repo {
+    /// Create the tag `name` pointing at `target`.
+    ///
+    /// If `message` is given, an annotated tag object is written and the ref points at that.
+    /// Otherwise, a lightweight tag is created, pointing directly at `target`. Returns the oid the
+    /// new ref points at.
+    pub fn create_tag(
+        &mut self,
+        name: &str,
+        target: &str,
+        message: Option<&str>,
+        force: bool,
+    ) -> Result<Digest> {
+        if !is_valid_ref_name(name) {
+            return Err(eyre!("'{name}' is not a valid tag name."));
+        }
+
+        let ref_name = format!("refs/tags/{name}");
+        if !force && self.ref_exists(&ref_name)? {
+            return Err(eyre!("tag '{name}' already exists"));
+        }
+
+        // A tag of a tag points at the tag object itself, so only peel when given a revision
+        // expression rather than a plain ref or oid
+        let target = match self.read_ref(target)? {
+            Some(oid) => oid,
+            None => Rev::parse(target)?
+                .resolve(self)?
+                .ok_or_else(|| eyre!("Failed to resolve '{target}' as a valid ref."))?,
+        };
+
+        let oid = match message {
+            Some(message) => {
+                let (kind, _) = self.database.read_raw(&target)?;
+                let tagger = self.committer()?;
+
+                let mut message = message.to_owned();
+                if !message.ends_with('\n') {
+                    message.push('\n');
+                }
+
+                let tag = Tag::new(target, kind, name.to_owned(), tagger, message);
+                let tag = DatabaseObject::new(&tag);
+                self.database.store(&tag)?;
+                tag.into_oid()
+            }
+            None => target,
+        };
+
+        trace!(%name, ?oid, "Creating tag");
+        let expected = if force {
+            Expected::Any
+        } else {
+            Expected::Absent
+        };
+        let message = format!("tag: tagging {}", oid.short());
+        self.update_ref(&ref_name, &oid, expected, &message)?;
+        Ok(oid)
+    }
+
+    /// Ask for the message of the annotated tag `name` in the editor, through `.git/TAG_EDITMSG`.
+    /// Comments are stripped from what is written, and an empty message is an error.
+    pub fn edit_tag_message(&self, name: &str) -> Result<String> {
+        let c = self.comment_char()?;
+        let path = self.git_dir.join("TAG_EDITMSG");
+        let instructions = format!(
+            "\n{c}\n{c} Write a message for tag:\n{c}   {name}\n\
+             {c} Lines starting with '{c}' will be ignored.\n{c}\n"
+        );
+        std::fs::write(&path, instructions).wrap_err_with(|| format!("Unable to write {path}"))?;
+
+        self.launch_editor(&path)?;
+
+        let contents = std::fs::read(&path).wrap_err_with(|| format!("Unable to read {path}"))?;
+        let contents = String::from_utf8(contents)
+            .map_err(|_| eyre!("The tag message in {path} is not valid UTF-8"))?;
+        let message = cleanup_message(&contents, Cleanup::Strip, c, true);
+        if message.is_empty() {
+            return Err(eyre!("no tag message?"));
+        }
+        Ok(message)
+    }
+
+    /// Delete the tag `name`, returning the oid it pointed to.
+    pub fn delete_tag(&mut self, name: &str) -> Result<Digest> {
+        let ref_name = format!("refs/tags/{name}");
+        if !self.ref_exists(&ref_name)? {
+            return Err(eyre!("tag '{name}' not found."));
+        }
+
+        self.delete_ref(&ref_name)
+    }
+
+    /// List the names of all tags, sorted. If any `patterns` are given, only tags matching at
+    /// least one of those globs are returned.
+    pub fn list_tags(&self, patterns: &[String]) -> Result<Vec<String>> {
+        let patterns = patterns
+            .iter()
+            .map(|x| x.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut tags = Vec::new();
+
+        for (name, _) in self.list_refs()? {
+            let name = match name.strip_prefix("refs/tags/") {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let chars = name.chars().collect::<Vec<_>>();
+            if !patterns.is_empty() && !patterns.iter().any(|x| wildmatch(x, &chars)) {
+                continue;
+            }
+            tags.push(name.to_owned());
+        }
+
+        Ok(tags)
+    }
+}
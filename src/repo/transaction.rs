@@ -0,0 +1,308 @@
+use std::collections::HashSet;
+
+use camino::Utf8Path;
+use color_eyre::eyre::{eyre, Context};
+use tracing::{debug, trace};
+use walkdir::WalkDir;
+
+use crate::digest::Digest;
+use crate::lockfile::Lockfile;
+use crate::packed_refs::PackedRefs;
+use crate::revision::is_valid_ref_name;
+use crate::Result;
+
+/// What a ref must point to before a transaction is allowed to change it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    /// The ref may have any value, or not exist
+    Any,
+    /// The ref must not exist
+    Absent,
+    /// The ref must point to this oid
+    Value(Digest),
+}
+
+impl Expected {
+    /// Build an expectation from an optional current value, as returned by
+    /// [`Repo::read_full_ref`](super::Repo::read_full_ref).
+    pub fn from_current(current: Option<Digest>) -> Self {
+        match current {
+            Some(oid) => Self::Value(oid),
+            None => Self::Absent,
+        }
+    }
+
+    fn check(&self, name: &str, current: Option<&Digest>) -> Result<()> {
+        match (self, current) {
+            (Expected::Any, _) | (Expected::Absent, None) => Ok(()),
+            (Expected::Value(expected), Some(current)) if expected == current => Ok(()),
+            (Expected::Absent, Some(_)) => {
+                Err(eyre!("cannot lock ref '{name}': reference already exists"))
+            }
+            (Expected::Value(expected), Some(current)) => Err(eyre!(
+                "cannot lock ref '{name}': is at {current:x} but expected {expected:x}"
+            )),
+            (Expected::Value(_), None) => Err(eyre!(
+                "cannot lock ref '{name}': unable to resolve reference"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Change {
+    Set(Digest),
+    Delete,
+    /// Only check the expected value
+    Verify,
+}
+
+#[derive(Debug, Clone)]
+struct RefUpdate {
+    name: String,
+    change: Change,
+    expected: Expected,
+}
+
+/// A set of ref updates that are applied together: either every ref is updated, or none are.
+///
+/// Each ref is locked and checked against its expected value before anything is written, so a
+/// concurrent change to any of them (by rit or git) aborts the whole transaction.
+#[derive(Debug, Clone)]
+pub struct RefTransaction {
+    updates: Vec<RefUpdate>,
+    deref: bool,
+    message: String,
+}
+
+impl Default for RefTransaction {
+    fn default() -> Self {
+        Self {
+            updates: Vec::new(),
+            deref: true,
+            message: String::new(),
+        }
+    }
+}
+
+impl RefTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update symbolic refs themselves, rather than the refs they point to. Without this,
+    /// updating `HEAD` updates the branch it is on.
+    pub fn no_deref(&mut self) -> &mut Self {
+        self.deref = false;
+        self
+    }
+
+    /// The reason for the update, recorded in the reflog of every ref that is changed.
+    pub fn message(&mut self, message: &str) -> &mut Self {
+        self.message = message.to_owned();
+        self
+    }
+
+    /// Point the ref `name` at `new`.
+    pub fn update(&mut self, name: &str, new: &Digest, expected: Expected) -> &mut Self {
+        self.push(name, Change::Set(new.clone()), expected)
+    }
+
+    /// Create the ref `name`, which must not already exist.
+    pub fn create(&mut self, name: &str, new: &Digest) -> &mut Self {
+        self.push(name, Change::Set(new.clone()), Expected::Absent)
+    }
+
+    /// Delete the ref `name`, loose or packed.
+    pub fn delete(&mut self, name: &str, expected: Expected) -> &mut Self {
+        self.push(name, Change::Delete, expected)
+    }
+
+    /// Check that the ref `name` has the expected value, without changing it.
+    pub fn verify(&mut self, name: &str, expected: Expected) -> &mut Self {
+        self.push(name, Change::Verify, expected)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    fn push(&mut self, name: &str, change: Change, expected: Expected) -> &mut Self {
+        self.updates.push(RefUpdate {
+            name: name.to_owned(),
+            change,
+            expected,
+        });
+        self
+    }
+}
+
+/// A ref update whose lock is held and whose expected value has been checked.
+struct Prepared {
+    name: String,
+    change: Change,
+    lock: Lockfile,
+    old: Option<Digest>,
+    /// HEAD points to this ref, so its reflog should record the change too
+    is_head: bool,
+}
+
+impl super::Repo {
+    /// Update the single ref `name` to `new`, if its current value is as expected. `message` is
+    /// recorded in the reflog.
+    pub fn update_ref(
+        &mut self,
+        name: &str,
+        new: &Digest,
+        expected: Expected,
+        message: &str,
+    ) -> Result<()> {
+        let mut transaction = RefTransaction::new();
+        transaction.message(message).update(name, new, expected);
+        self.commit_transaction(transaction)
+    }
+
+    /// Apply every update in `transaction`, or none of them.
+    ///
+    /// All refs are locked and checked first. Only once that has succeeded for every ref are the
+    /// new values moved into place, so a failed check or a ref locked by another process leaves
+    /// everything untouched. Each change is then recorded in the reflog.
+    pub fn commit_transaction(&mut self, transaction: RefTransaction) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut prepared = Vec::new();
+        let head_target = self.resolve_symbolic_ref("HEAD")?;
+        let packed = self.packed_refs()?;
+
+        // Any error while preparing drops the locks taken so far, releasing them
+        for update in transaction.updates {
+            let name = if transaction.deref {
+                self.resolve_symbolic_ref(&update.name)?
+            } else {
+                update.name.clone()
+            };
+
+            check_ref_name(&name)?;
+            if !seen.insert(name.clone()) {
+                return Err(eyre!("multiple updates for ref '{name}' not allowed"));
+            }
+
+            // Directories left behind by deleted refs are cleared away, but not ones still in use
+            let path = self.git_dir.join(&name);
+            if path.is_dir() {
+                if contains_refs(&path)? {
+                    return Err(eyre!(
+                        "cannot lock ref '{name}': there is a non-empty directory '{path}' \
+                         blocking reference '{name}'"
+                    ));
+                }
+                std::fs::remove_dir_all(&path)
+                    .wrap_err_with(|| format!("cannot lock ref '{name}'"))?;
+            }
+
+            // A ref can't be created where another ref needs its name as a directory, or the
+            // other way round
+            for (i, _) in name.match_indices('/').skip(1) {
+                let parent = &name[..i];
+                if self.ref_exists(parent)? {
+                    return Err(eyre!(
+                        "cannot lock ref '{name}': '{parent}' exists; cannot create '{name}'"
+                    ));
+                }
+            }
+
+            let mut lock =
+                Lockfile::acquire(&path).wrap_err_with(|| format!("cannot lock ref '{name}'"))?;
+
+            // The value must be read while holding the lock, so it can't change before we write
+            let current = self.read_full_ref(&name)?;
+            update.expected.check(&name, current.as_ref())?;
+
+            if current.is_none() && matches!(update.change, Change::Set(_)) {
+                let prefix = format!("{name}/");
+                if let Some((child, _)) = packed.iter().find(|(x, _)| x.starts_with(&prefix)) {
+                    return Err(eyre!(
+                        "cannot lock ref '{name}': '{child}' exists; cannot create '{name}'"
+                    ));
+                }
+            }
+
+            if let Change::Set(oid) = &update.change {
+                lock.write_all(format!("{oid:x}\n").as_bytes())?;
+            }
+
+            trace!(%name, change = ?update.change, "Prepared ref update");
+            prepared.push(Prepared {
+                is_head: name != "HEAD" && name == head_target,
+                name,
+                change: update.change,
+                lock,
+                old: current,
+            });
+        }
+
+        // Deleted refs are removed from packed-refs first, so they can never reappear once their
+        // loose files are gone
+        let deleted = prepared
+            .iter()
+            .filter(|x| x.change == Change::Delete)
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        if deleted.iter().any(|name| packed.contains(name)) {
+            let mut packed = PackedRefs::lock(&self.git_dir)?;
+            for name in deleted {
+                packed.remove(name);
+            }
+            packed.commit()?;
+        }
+
+        debug!(refs = prepared.len(), "Committing ref transaction");
+        for Prepared {
+            name,
+            change,
+            lock,
+            old,
+            is_head,
+        } in prepared
+        {
+            match change {
+                Change::Set(new) => {
+                    lock.commit()?;
+                    let message = &transaction.message;
+                    self.append_reflog(&name, old.as_ref(), &new, message)?;
+                    if is_head {
+                        self.append_reflog("HEAD", old.as_ref(), &new, message)?;
+                    }
+                }
+                Change::Delete => {
+                    let path = lock.path().to_owned();
+                    lock.delete()?;
+                    self.remove_empty_ref_dirs(&path)?;
+                    self.delete_reflog(&name)?;
+                }
+                Change::Verify => drop(lock),
+            }
+            trace!(%name, "Committed ref update");
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether there are any ref files, or anything other than directories, under `dir`.
+fn contains_refs(dir: &Utf8Path) -> Result<bool> {
+    for entry in WalkDir::new(dir) {
+        if !entry?.file_type().is_dir() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Refs written by a transaction must be `HEAD`, or a valid name under `refs/`.
+fn check_ref_name(name: &str) -> Result<()> {
+    match name.strip_prefix("refs/") {
+        _ if name == "HEAD" => Ok(()),
+        Some(rest) if is_valid_ref_name(rest) => Ok(()),
+        _ => Err(eyre!("refusing to update ref with bad name '{name}'")),
+    }
+}
@@ -1,24 +1,41 @@
 use std::ffi::CString;
+use std::fs::Permissions;
 use std::mem::MaybeUninit;
-use std::os::unix::prelude::OsStrExt;
+use std::os::unix::prelude::{OsStrExt, PermissionsExt};
 
 use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::eyre::{eyre, Context};
 use walkdir::WalkDir;
 
+use crate::filemode::FileMode;
 use crate::*;
 
 impl super::Repo {
+    /// List the files under `path` (relative to the root of the workspace) that are not ignored.
     pub fn list_files(&self, path: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
         let path = self.dir.join(path);
         if path.is_file() {
             Ok(vec![path])
         } else {
-            let ignores = self.ignores();
+            let mut ignores = self.ignores();
 
             let mut entries = Vec::new();
 
-            'outer: for entry in WalkDir::new(path) {
+            let walk = WalkDir::new(path).into_iter().filter_entry(|entry| {
+                let path = match Utf8Path::from_path(entry.path()) {
+                    Some(path) => path,
+                    // Reported as an error below
+                    None => return true,
+                };
+                match path.strip_prefix(&self.dir) {
+                    Ok(path) if !path.as_str().is_empty() => {
+                        !ignores.is_ignored(path, entry.file_type().is_dir())
+                    }
+                    _ => true,
+                }
+            });
+
+            for entry in walk {
                 let entry = entry?;
                 let path = entry.path();
                 let path = Utf8Path::from_path(path).ok_or_else(|| {
@@ -28,14 +45,8 @@ impl super::Repo {
                     )
                 })?;
 
-                for ignore in ignores {
-                    if path.as_str().contains(ignore) {
-                        continue 'outer;
-                    }
-                }
-
                 if path.is_dir() && !path.is_symlink() {
-                    continue 'outer;
+                    continue;
                 }
                 entries.push(path.strip_prefix(&self.dir)?.to_owned());
             }
@@ -43,6 +54,58 @@ impl super::Repo {
         }
     }
 
+    /// Write `data` to the file at `path` (relative to the root of the workspace), creating any
+    /// parent directories. Anything already at `path` is replaced, including a directory.
+    pub fn write_workspace_file(&self, path: &Utf8Path, data: &[u8], mode: FileMode) -> Result<()> {
+        let abs_path = self.dir.join(path);
+
+        // A file may be in the way of one of the parent directories
+        for parent in path.ancestors().skip(1) {
+            let abs_parent = self.dir.join(parent);
+            if abs_parent.is_file() || abs_parent.is_symlink() {
+                std::fs::remove_file(abs_parent)?;
+                break;
+            }
+        }
+        if abs_path.is_dir() && !abs_path.is_symlink() {
+            std::fs::remove_dir_all(&abs_path)?;
+        }
+        if let Some(parent) = abs_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(&abs_path, data)
+            .wrap_err_with(|| format!("Failed to write file: {abs_path}"))?;
+
+        let permissions = if mode.is_executable() { 0o755 } else { 0o644 };
+        std::fs::set_permissions(&abs_path, Permissions::from_mode(permissions))?;
+
+        Ok(())
+    }
+
+    /// Remove the file at `path` (relative to the root of the workspace), along with any parent
+    /// directories that are left empty.
+    pub fn remove_workspace_file(&self, path: &Utf8Path) -> Result<()> {
+        let abs_path = self.dir.join(path);
+        if abs_path.is_dir() && !abs_path.is_symlink() {
+            std::fs::remove_dir_all(&abs_path)?;
+        } else if abs_path.exists() || abs_path.is_symlink() {
+            std::fs::remove_file(&abs_path)?;
+        }
+
+        for parent in path.ancestors().skip(1) {
+            if parent.as_str().is_empty() {
+                break;
+            }
+            // Fails if the directory is not empty, which is the signal to stop
+            if std::fs::remove_dir(self.dir.join(parent)).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Get the libc::stat information for a file. Returns None if the file does not exist
     pub fn stat_file(path: &Utf8Path) -> Result<Option<libc::stat>> {
         if path.exists() {
@@ -0,0 +1,144 @@
+//! `rit rev-list`: list the commits in a [`RevRange`], and optionally the objects they refer to.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::commit::{topo_sort, Commit, CommitWalk};
+use crate::digest::Digest;
+use crate::interface::RevListArgs;
+use crate::pack::ObjectKind;
+use crate::repo::Repo;
+use crate::revision::RevRange;
+use crate::tree::{RawTreeEntry, Tree};
+use crate::Result;
+
+pub fn handle(repo: &Repo, args: &RevListArgs) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for line in rev_list(repo, args)? {
+        writeln!(stdout, "{line}")?;
+    }
+    Ok(())
+}
+
+/// The lines `rev-list` prints.
+pub fn rev_list(repo: &Repo, args: &RevListArgs) -> Result<Vec<String>> {
+    let mut revs = args.revs.clone();
+    if !args.not.is_empty() {
+        revs.push("--not".to_owned());
+        revs.extend(args.not.iter().cloned());
+    }
+    let (include, exclude) = RevRange::parse(&revs)?.resolve(repo)?;
+
+    let mut walk = CommitWalk::new(&repo.database, include)?.hide(exclude.iter().cloned())?;
+    if args.first_parent {
+        walk = walk.first_parent();
+    }
+
+    let max_count = args.max_count.unwrap_or(usize::MAX);
+    let mut commits = if args.topo_order {
+        let commits = walk.by_ref().collect::<Result<Vec<_>>>()?;
+        let mut commits = topo_sort(commits, args.first_parent);
+        commits.truncate(max_count);
+        commits
+    } else {
+        walk.by_ref().take(max_count).collect::<Result<Vec<_>>>()?
+    };
+
+    if args.reverse {
+        commits.reverse();
+    }
+
+    let objects = if args.objects {
+        // Objects are hidden if they are in an excluded commit, or in the tree of a hidden parent
+        // of a listed commit, which is where most unchanged files will be found
+        let listed = commits.iter().map(|(oid, _)| oid).collect::<HashSet<_>>();
+        let mut boundary = exclude;
+        for (_, commit) in &commits {
+            boundary.extend(
+                commit
+                    .parents()
+                    .iter()
+                    .filter(|x| !listed.contains(x) && walk.is_hidden(x))
+                    .cloned(),
+            );
+        }
+        list_objects(repo, &commits, &boundary)?
+    } else {
+        Vec::new()
+    };
+
+    if args.count {
+        return Ok(vec![(commits.len() + objects.len()).to_string()]);
+    }
+
+    Ok(commits
+        .iter()
+        .map(|(oid, _)| format!("{oid:x}"))
+        .chain(objects.iter().map(|(oid, path)| format!("{oid:x} {path}")))
+        .collect())
+}
+
+/// The trees and blobs reachable from the trees of `commits`, but not from those of `hidden`,
+/// each with the path it was first found at. The root tree of each commit has an empty path.
+fn list_objects(
+    repo: &Repo,
+    commits: &[(Digest, Commit)],
+    hidden: &[Digest],
+) -> Result<Vec<(Digest, String)>> {
+    let mut seen = HashSet::new();
+    for oid in hidden {
+        let tree = repo.database.load_commit(oid)?.tree_id().clone();
+        walk_tree(repo, tree, String::new(), &mut seen, &mut |_, _| {})?;
+    }
+
+    let mut objects = Vec::new();
+    for (_, commit) in commits {
+        walk_tree(
+            repo,
+            commit.tree_id().clone(),
+            String::new(),
+            &mut seen,
+            &mut |oid, path| objects.push((oid.clone(), path.to_owned())),
+        )?;
+    }
+
+    Ok(objects)
+}
+
+/// Call `f` on `tree` and everything under it that hasn't been `seen`, depth first in tree order.
+fn walk_tree(
+    repo: &Repo,
+    tree: Digest,
+    path: String,
+    seen: &mut HashSet<Digest>,
+    f: &mut impl FnMut(&Digest, &str),
+) -> Result<()> {
+    if !seen.insert(tree.clone()) {
+        return Ok(());
+    }
+    f(&tree, &path);
+
+    let (kind, data) = repo.database.read_raw(&tree)?;
+    debug_assert_eq!(kind, ObjectKind::Tree);
+
+    for RawTreeEntry { mode, name, oid } in Tree::parse_raw(&data)? {
+        let path = if path.is_empty() {
+            name
+        } else {
+            format!("{path}/{name}")
+        };
+
+        match mode {
+            RawTreeEntry::MODE_TREE => walk_tree(repo, oid, path, seen, f)?,
+            // Submodule commits live in another repository
+            RawTreeEntry::MODE_GITLINK => {}
+            _ => {
+                if seen.insert(oid.clone()) {
+                    f(&oid, &path);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
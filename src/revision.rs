@@ -1,20 +1,34 @@
 //! A revision is valid if it matches the following (informally defined) context-free grammar:
 //! `<rev>` = `<refname>`
-//! `<rev>` = `<rev>^`
-//! `<rev>` = `<rev>~<num>`
+//! `<rev>` = `<rev>^[<num>]`, the `<num>`th parent (the first by default). `^0` is the commit itself
+//! `<rev>` = `<rev>~<num>`, the `<num>`th generation ancestor, following first parents
+//! `<rev>` = `<rev>^{<type>}`, peeled to a `commit`, `tree`, `blob` or `tag`. `^{}` follows tags
+//! `<rev>` = `<rev>:<path>`, the blob or tree at `<path>` in the tree of `<rev>`
+//! `<rev>` = `:[<stage>:]<path>`, the blob at `<path>` in the index, at stage 0 by default
+//! `<rev>` = `:/<regex>`, the newest commit reachable from any ref whose message matches
 //! `<num>` = a natural number
-//! `<refname>` = a branch name | a sha1 hash | "HEAD" or '@'
+//! `<refname>` = a branch name | a sha1 hash, abbreviated to at least 4 characters | "HEAD" or '@'
+//! `<refname>` = `[<refname>]@{<num>}`, the `<num>`th prior value of a ref, from its reflog
+//! `<refname>` = `[<branch>]@{upstream}` or `[<branch>]@{u}`, the branch `<branch>` tracks
+//!
+//! See: <https://git-scm.com/docs/gitrevisions>
 
+use std::fmt;
 use std::str::FromStr;
 
+use regex::Regex;
+
 use crate::{
-    digest::Digest,
+    commit::{Commit, CommitWalk},
+    digest::{Digest, MINIMUM_ABBREV},
+    merge,
+    pack::ObjectKind,
     repo::{database::LoadedItem, Repo},
+    tag::Tag,
+    tree::Tree,
     Result,
 };
 
-use color_eyre::eyre::{eyre, Context};
-
 /// Contains all characters that cannot appear in a ref name.
 ///
 /// In git, the character `'*'` is allowed in ref names if the environment variable
@@ -55,78 +69,430 @@ pub fn is_valid_ref_name(name: &str) -> bool {
         || name.contains("@{"))
 }
 
+/// Why a revision couldn't be parsed or resolved.
+///
+/// A revision naming something that doesn't exist, such as a missing branch or `HEAD~5` in a
+/// repository with three commits, is not an error: [`Rev::resolve`] returns `None`. These
+/// errors can be recovered from a [`color_eyre::Report`] with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevisionError {
+    /// The revision is not valid syntax
+    InvalidSyntax { rev: String, reason: String },
+    /// The revision names an object that can't be peeled to the kind that was needed, such as
+    /// the parent of a tree
+    WrongKind {
+        rev: String,
+        expected: ObjectKind,
+        actual: ObjectKind,
+    },
+    /// `<rev>:<path>` named a path that isn't in the tree of `<rev>`
+    PathNotFound { rev: String, path: String },
+    /// `:<stage>:<path>` named a path that isn't in the index at that stage
+    NotInIndex { stage: u16, path: String },
+    /// `<branch>@{upstream}` was used on a branch that doesn't track anything
+    NoUpstream { branch: String },
+    /// `<ref>@{<n>}` asked for an entry older than the reflog's oldest
+    ReflogTooShort { name: String, entries: usize },
+    /// A revision that had to exist, such as either end of a range, names nothing
+    UnknownRevision { rev: String },
+    /// The current branch was needed, as in `@{upstream}`, but HEAD is detached
+    DetachedHead,
+    /// `<ref>@{<n>}` was used with a name that isn't a ref
+    UnknownRef { name: String },
+}
+
+impl fmt::Display for RevisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSyntax { rev, reason } => write!(f, "invalid revision '{rev}': {reason}"),
+            Self::WrongKind {
+                rev,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "'{rev}' is a {}, which can't be peeled to a {}",
+                actual.as_str(),
+                expected.as_str()
+            ),
+            Self::PathNotFound { rev, path } => {
+                write!(f, "path '{path}' does not exist in '{rev}'")
+            }
+            Self::NotInIndex { stage, path } => {
+                write!(f, "path '{path}' is not in the index at stage {stage}")
+            }
+            Self::NoUpstream { branch } => {
+                write!(f, "no upstream configured for branch '{branch}'")
+            }
+            Self::ReflogTooShort { name, entries } => {
+                write!(f, "log for '{name}' only has {entries} entries")
+            }
+            Self::UnknownRevision { rev } => write!(f, "bad revision '{rev}'"),
+            Self::DetachedHead => write!(f, "HEAD does not point to a branch"),
+            Self::UnknownRef { name } => write!(f, "unknown ref '{name}'"),
+        }
+    }
+}
+
+impl std::error::Error for RevisionError {}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Rev {
     refname: Refname,
-    distance: u64,
+    steps: Vec<Step>,
+    /// `<rev>:<path>`
+    path: Option<String>,
+}
+
+/// A suffix applied to a revision, in the order they were written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// `~<n>`, or `^` for a single generation
+    Ancestor(u64),
+    /// `^<n>`, for `n` other than 1. `^0` peels to a commit without moving
+    Parent(usize),
+    /// `^{<type>}`, or `^{}` to peel tags to whatever they point to
+    Peel(Option<ObjectKind>),
 }
 
 impl Rev {
-    pub fn parse(mut input: &str) -> Result<Self> {
-        let mut distance = 0;
-        loop {
-            if input.ends_with('^') {
-                distance += 1;
-                input = &input[..input.len() - 1];
-            } else if let Some(idx) = input.rfind('~') {
-                distance += input[idx + 1..]
-                    .parse::<u64>()
-                    .wrap_err(eyre!("A number is required after '~'"))?;
-                input = &input[..idx];
+    pub fn parse(input: &str) -> std::result::Result<Self, RevisionError> {
+        let syntax = |reason: &str| RevisionError::InvalidSyntax {
+            rev: input.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        // `:/<regex>` and `:[<stage>:]<path>` don't start from a revision
+        if let Some(rest) = input.strip_prefix(':') {
+            let refname = match rest.strip_prefix('/') {
+                Some(pattern) => {
+                    Regex::new(pattern).map_err(|e| syntax(&e.to_string()))?;
+                    Refname::Search(pattern.to_owned())
+                }
+                None => match rest.split_once(':') {
+                    Some((stage @ ("0" | "1" | "2" | "3"), path)) => Refname::Index {
+                        stage: stage.parse().expect("stage is a digit"),
+                        path: path.to_owned(),
+                    },
+                    _ => Refname::Index {
+                        stage: 0,
+                        path: rest.to_owned(),
+                    },
+                },
+            };
+
+            if matches!(&refname, Refname::Index { path, .. } if path.is_empty()) {
+                return Err(syntax("a path is required after ':'"));
+            }
+            return Ok(Rev {
+                refname,
+                steps: Vec::new(),
+                path: None,
+            });
+        }
+
+        let (input, path) = match input.split_once(':') {
+            Some((rev, path)) => (rev, Some(path.trim_start_matches("./").to_owned())),
+            None => (input, None),
+        };
+
+        let end = input.find(['^', '~']).unwrap_or(input.len());
+        let refname = Refname::parse(&input[..end])?;
+
+        let mut steps = Vec::new();
+        let mut rest = &input[end..];
+        while !rest.is_empty() {
+            let step = if let Some(after) = rest.strip_prefix("^{") {
+                let (kind, after) = after
+                    .split_once('}')
+                    .ok_or_else(|| syntax("missing '}' after '^{'"))?;
+                rest = after;
+                match kind {
+                    "" => Step::Peel(None),
+                    "commit" | "tree" | "blob" | "tag" => {
+                        Step::Peel(Some(kind.parse().expect("kind is valid")))
+                    }
+                    _ => return Err(syntax(&format!("unknown object type '{kind}'"))),
+                }
+            } else if let Some(after) = rest.strip_prefix('^') {
+                let (digits, after) = split_number(after);
+                rest = after;
+                match digits {
+                    "" => Step::Ancestor(1),
+                    digits => match digits.parse::<usize>() {
+                        Ok(1) => Step::Ancestor(1),
+                        Ok(n) => Step::Parent(n),
+                        Err(_) => return Err(syntax("parent number is too large")),
+                    },
+                }
+            } else if let Some(after) = rest.strip_prefix('~') {
+                let (digits, after) = split_number(after);
+                rest = after;
+                match digits {
+                    "" => Step::Ancestor(1),
+                    digits => digits
+                        .parse::<u64>()
+                        .map(Step::Ancestor)
+                        .map_err(|_| syntax("generation number is too large"))?,
+                }
             } else {
-                let refname = Refname::parse(input)?;
-                break Ok(Rev { refname, distance });
+                return Err(syntax(&format!("unexpected '{rest}'")));
+            };
+
+            // Runs of first parents are followed all at once
+            match (steps.last_mut(), &step) {
+                (Some(Step::Ancestor(a)), Step::Ancestor(b)) => {
+                    *a = a
+                        .checked_add(*b)
+                        .ok_or_else(|| syntax("too many generations"))?;
+                }
+                _ => steps.push(step),
             }
         }
+
+        Ok(Rev {
+            refname,
+            steps,
+            path,
+        })
     }
 
+    /// Find the object this revision names, or `None` if it names something that doesn't exist.
+    ///
+    /// Unless the revision explicitly asks for another kind of object, with `^{<type>}` or a
+    /// path, the result is always a commit.
     pub fn resolve(self, repo: &Repo) -> Result<Option<Digest>> {
         let Self {
             refname,
-            mut distance,
+            steps,
+            path,
         } = self;
 
-        if distance == 0 {
-            return refname.resolve(repo);
-        }
+        // An abbreviated oid is disambiguated by what the first step, or the path, needs it to be
+        let kind = match (steps.first(), &path) {
+            (Some(Step::Peel(kind)), _) => *kind,
+            (_, Some(_)) => Some(ObjectKind::Tree),
+            _ => Some(ObjectKind::Commit),
+        };
 
-        let intermediary = match refname.resolve(repo)? {
+        let name = refname.to_string();
+        let mut oid = match refname.lookup(repo, kind)? {
             Some(x) => x,
             None => return Ok(None),
         };
 
-        let mut commit = repo
-            .database
-            .load(&intermediary)?
-            .into_commit()
-            .ok_or_else(|| eyre!("Ref pointed to something other than a commit"))?;
+        if let Refname::Index { .. } = refname {
+            return Ok(Some(oid));
+        }
 
-        let mut parent = match commit.parents().first() {
-            Some(x) => x,
-            None => return Ok(None),
-        };
+        for step in &steps {
+            oid = match step {
+                Step::Ancestor(n) => {
+                    oid = peel_to(repo, &oid, ObjectKind::Commit, &name)?;
+                    for _ in 0..*n {
+                        oid = match repo.database.load_commit(&oid)?.parents().first() {
+                            Some(parent) => parent.clone(),
+                            None => return Ok(None),
+                        };
+                    }
+                    oid
+                }
+                Step::Parent(0) => peel_to(repo, &oid, ObjectKind::Commit, &name)?,
+                Step::Parent(n) => {
+                    let commit = peel_to(repo, &oid, ObjectKind::Commit, &name)?;
+                    match repo.database.load_commit(&commit)?.parents().get(n - 1) {
+                        Some(parent) => parent.clone(),
+                        None => return Ok(None),
+                    }
+                }
+                Step::Peel(Some(kind)) => peel_to(repo, &oid, *kind, &name)?,
+                Step::Peel(None) => repo.database.peel(&oid)?,
+            };
+        }
 
-        distance -= 1;
+        if let Some(path) = path {
+            let tree = peel_to(repo, &oid, ObjectKind::Tree, &name)?;
+            return lookup_path(repo, &tree, &path)?
+                .map(Some)
+                .ok_or_else(|| RevisionError::PathNotFound { rev: name, path }.into());
+        }
 
-        while distance > 0 {
-            commit = repo
-                .database
-                .load(parent)?
-                .into_commit()
-                .ok_or_else(|| eyre!("Ref pointed to something other than a commit"))?;
+        match steps.last() {
+            Some(Step::Peel(_)) => Ok(Some(oid)),
+            _ => peel_to(repo, &oid, ObjectKind::Commit, &name).map(Some),
+        }
+    }
 
-            parent = match commit.parents().first() {
-                Some(x) => x,
-                None => return Ok(None),
-            };
+    /// Find the tree this revision names, which may be a tree itself or a commit or tag that
+    /// leads to one.
+    pub fn resolve_tree(mut self, repo: &Repo) -> Result<Option<Digest>> {
+        let name = self.refname.to_string();
+        if self.path.is_none() {
+            self.steps.push(Step::Peel(Some(ObjectKind::Tree)));
+        }
+        match self.resolve(repo)? {
+            Some(oid) => peel_to(repo, &oid, ObjectKind::Tree, &name).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A set of commits named by several revisions, as taken by `rev-list`: every commit reachable
+/// from one of the included revisions, but from none of the excluded ones.
+///
+/// - `<rev>` includes `<rev>`, and `^<rev>` excludes it
+/// - `<a>..<b>` is the same as `^<a> <b>`, with either side defaulting to HEAD
+/// - `<a>...<b>` includes both sides, and excludes their merge bases, leaving the commits
+///   reachable from either but not both
+/// - `--not` flips whether each revision after it is included or excluded, until the next
+///   `--not`. It has no effect on `<a>...<b>`
+#[derive(Debug, Default)]
+pub struct RevRange {
+    include: Vec<NamedRev>,
+    exclude: Vec<NamedRev>,
+    symmetric: Vec<(NamedRev, NamedRev)>,
+}
+
+/// A revision, along with how it was written, for error messages.
+#[derive(Debug)]
+struct NamedRev {
+    name: String,
+    rev: Rev,
+}
+
+impl NamedRev {
+    fn parse(name: &str) -> std::result::Result<Self, RevisionError> {
+        // `A..` and `..B` leave out HEAD
+        let name = if name.is_empty() { "HEAD" } else { name };
+        Ok(Self {
+            name: name.to_owned(),
+            rev: Rev::parse(name)?,
+        })
+    }
+
+    fn resolve(self, repo: &Repo) -> Result<Digest> {
+        let Self { name, rev } = self;
+        rev.resolve(repo)?
+            .ok_or_else(|| RevisionError::UnknownRevision { rev: name }.into())
+    }
+}
+
+impl RevRange {
+    pub fn parse(args: &[impl AsRef<str>]) -> std::result::Result<Self, RevisionError> {
+        let mut range = Self::default();
+        let mut not = false;
+
+        for arg in args {
+            let arg = arg.as_ref();
+            if arg == "--not" {
+                not = !not;
+                continue;
+            }
+
+            // `:/<regex>` may contain anything, including dots
+            if arg.starts_with(':') {
+                range.push(NamedRev::parse(arg)?, !not);
+            } else if let Some((a, b)) = arg.split_once("...") {
+                range
+                    .symmetric
+                    .push((NamedRev::parse(a)?, NamedRev::parse(b)?));
+            } else if let Some((a, b)) = arg.split_once("..") {
+                range.push(NamedRev::parse(a)?, not);
+                range.push(NamedRev::parse(b)?, !not);
+            } else if let Some(rev) = arg.strip_prefix('^') {
+                range.push(NamedRev::parse(rev)?, not);
+            } else {
+                range.push(NamedRev::parse(arg)?, !not);
+            }
+        }
+
+        Ok(range)
+    }
 
-            distance -= 1;
+    fn push(&mut self, rev: NamedRev, include: bool) {
+        if include {
+            self.include.push(rev);
+        } else {
+            self.exclude.push(rev);
         }
+    }
 
-        assert_eq!(distance, 0);
+    /// Find the commits to start walking from, and the commits whose history should be hidden.
+    /// Every revision must exist.
+    pub fn resolve(self, repo: &Repo) -> Result<(Vec<Digest>, Vec<Digest>)> {
+        let Self {
+            include,
+            exclude,
+            symmetric,
+        } = self;
+
+        let mut include = include
+            .into_iter()
+            .map(|rev| rev.resolve(repo))
+            .collect::<Result<Vec<_>>>()?;
+        let mut exclude = exclude
+            .into_iter()
+            .map(|rev| rev.resolve(repo))
+            .collect::<Result<Vec<_>>>()?;
+
+        for (a, b) in symmetric {
+            let (a, b) = (a.resolve(repo)?, b.resolve(repo)?);
+            exclude.extend(merge::merge_bases(&repo.database, &a, &b)?);
+            include.extend([a, b]);
+        }
+
+        Ok((include, exclude))
+    }
+}
+
+/// Split the leading decimal digits off `input`.
+fn split_number(input: &str) -> (&str, &str) {
+    let end = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    input.split_at(end)
+}
+
+/// Follow `oid` through tags, and from a commit to its tree, until reaching an object of `kind`.
+fn peel_to(repo: &Repo, oid: &Digest, kind: ObjectKind, rev: &str) -> Result<Digest> {
+    let mut oid = oid.clone();
+    loop {
+        let (actual, data) = repo.database.read_raw(&oid)?;
+        oid = match actual {
+            _ if actual == kind => return Ok(oid),
+            ObjectKind::Tag => Tag::parse(&data)?.object().clone(),
+            ObjectKind::Commit if kind == ObjectKind::Tree => {
+                Commit::parse(&data)?.tree_id().clone()
+            }
+            _ => {
+                return Err(RevisionError::WrongKind {
+                    rev: rev.to_owned(),
+                    expected: kind,
+                    actual,
+                }
+                .into())
+            }
+        };
+    }
+}
 
-        Refname::Sha1(parent.clone()).resolve(repo)
+/// Find the entry at `path` under the tree `tree`, an empty path being the tree itself.
+fn lookup_path(repo: &Repo, tree: &Digest, path: &str) -> Result<Option<Digest>> {
+    let mut oid = tree.clone();
+    for component in path.split('/').filter(|x| !x.is_empty()) {
+        let (kind, data) = repo.database.read_raw(&oid)?;
+        if kind != ObjectKind::Tree {
+            return Ok(None);
+        }
+        oid = match Tree::parse_raw(&data)?
+            .into_iter()
+            .find(|entry| entry.name == component)
+        {
+            Some(entry) => entry.oid,
+            None => return Ok(None),
+        };
     }
+    Ok(Some(oid))
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -135,108 +501,204 @@ pub enum Refname {
     Sha1(Digest),
     PartialSha1(String),
     Head,
+    /// `<name>@{<n>}`. With no name, this is the branch HEAD is on
+    Reflog(Option<String>, usize),
+    /// `<name>@{upstream}`. With no name, this is the branch HEAD is on
+    Upstream(Option<String>),
+    /// `:<stage>:<path>`
+    Index {
+        stage: u16,
+        path: String,
+    },
+    /// `:/<regex>`
+    Search(String),
+}
+
+impl fmt::Display for Refname {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refname::BranchTag(name) | Refname::PartialSha1(name) => f.write_str(name),
+            Refname::Sha1(oid) => write!(f, "{oid:x}"),
+            Refname::Head => f.write_str("HEAD"),
+            Refname::Reflog(name, n) => write!(f, "{}@{{{n}}}", name.as_deref().unwrap_or("")),
+            Refname::Upstream(name) => write!(f, "{}@{{upstream}}", name.as_deref().unwrap_or("")),
+            Refname::Index { stage, path } => write!(f, ":{stage}:{path}"),
+            Refname::Search(pattern) => write!(f, ":/{pattern}"),
+        }
+    }
 }
 
 impl Refname {
-    fn parse(input: &str) -> Result<Self> {
+    fn parse(input: &str) -> std::result::Result<Self, RevisionError> {
+        let syntax = |reason: String| RevisionError::InvalidSyntax {
+            rev: input.to_owned(),
+            reason,
+        };
+
         if matches!(input, "HEAD" | "@") {
             return Ok(Self::Head);
         }
 
+        if input.is_empty() {
+            return Err(syntax("empty revision".to_owned()));
+        }
+
+        if let Some((name, selector)) = input
+            .strip_suffix('}')
+            .and_then(|input| input.rsplit_once("@{"))
+        {
+            if !name.is_empty() && name != "HEAD" && !is_valid_ref_name(name) {
+                return Err(syntax(format!("invalid ref name '{name}'")));
+            }
+
+            if selector.eq_ignore_ascii_case("upstream") || selector.eq_ignore_ascii_case("u") {
+                let name = (!name.is_empty() && name != "HEAD").then(|| name.to_owned());
+                return Ok(Self::Upstream(name));
+            }
+
+            let n = selector.parse::<usize>().map_err(|_| {
+                syntax(format!(
+                    "only numeric reflog entries and upstream are supported: '@{{{selector}}}'"
+                ))
+            })?;
+            let name = (!name.is_empty()).then(|| name.to_owned());
+            return Ok(Self::Reflog(name, n));
+        }
+
         if let Ok(digest) = Digest::from_str(input) {
             return Ok(Self::Sha1(digest));
         }
 
-        if input.chars().all(|c| c.is_ascii_hexdigit()) && input.len() <= 40 {
+        if input.chars().all(|c| c.is_ascii_hexdigit())
+            && (MINIMUM_ABBREV..=40).contains(&input.len())
+        {
             return Ok(Self::PartialSha1(input.to_owned()));
         }
 
         if !is_valid_ref_name(input) {
-            return Err(eyre!("Invalid ref name: {}", input));
+            return Err(syntax(format!("invalid ref name '{input}'")));
         }
 
         Ok(Self::BranchTag(input.to_owned()))
     }
 
+    /// Find the commit this names, following any tags.
     pub fn resolve(&self, repo: &Repo) -> Result<Option<Digest>> {
-        fn branchtag(name: &str, repo: &Repo) -> Result<Option<Digest>> {
-            let oid = match repo.read_ref(name)? {
-                Some(x) => x,
-                None => return Ok(None),
-            };
+        match self.lookup(repo, Some(ObjectKind::Commit))? {
+            Some(oid) => peel_to(repo, &oid, ObjectKind::Commit, &self.to_string()).map(Some),
+            None => Ok(None),
+        }
+    }
 
-            Ok(Some(oid))
+    /// Find the object this names, without peeling it. `kind` is the kind of object the result
+    /// will be peeled to, which is used to choose between objects an abbreviated oid matches.
+    fn lookup(&self, repo: &Repo, kind: Option<ObjectKind>) -> Result<Option<Digest>> {
+        /// The branch `name` refers to, with `None` meaning the current one.
+        fn branch_name(repo: &Repo, name: &Option<String>) -> Result<String> {
+            match name {
+                Some(name) => Ok(name.to_owned()),
+                None => repo
+                    .current_branch()?
+                    .ok_or_else(|| RevisionError::DetachedHead.into()),
+            }
         }
 
         match self {
-            Refname::Head => {
-                let oid = match repo.read_head()? {
-                    Some(x) => x,
-                    None => return Ok(None),
-                };
+            Refname::Head => repo.read_head(),
 
-                Ok(Some(oid))
-            }
+            Refname::Sha1(oid) => Ok(repo.database.contains(oid).then(|| oid.clone())),
+
+            // As in git, a ref whose name looks like an oid takes precedence
+            Refname::PartialSha1(prefix) => match repo.expand_ref_name(prefix)? {
+                Some(full_name) => repo.read_full_ref(&full_name),
+                None => repo.database.resolve_prefix(prefix, kind),
+            },
+
+            Refname::BranchTag(name) => repo.read_ref(name),
+
+            Refname::Reflog(name, n) => {
+                let full_name = match name {
+                    Some(name) => repo
+                        .expand_ref_name(name)?
+                        .ok_or_else(|| RevisionError::UnknownRef { name: name.clone() })?,
+                    // `@{n}` is the current branch, or HEAD itself if it is detached
+                    None => match repo.current_branch()? {
+                        Some(branch) => format!("refs/heads/{branch}"),
+                        None => "HEAD".to_owned(),
+                    },
+                };
 
-            Refname::Sha1(oid) => {
-                if repo.database.contains(oid) {
-                    match repo.database.load(oid)? {
-                        LoadedItem::Commit(_) => Ok(Some(oid.clone())),
-                        other => Err(eyre!(
-                            "Ref '{:x}' pointed to something other than a commit: {}",
-                            oid,
-                            other.kind()
-                        )),
+                let entries = repo.read_reflog(&full_name)?;
+                match entries.len().checked_sub(n + 1) {
+                    Some(i) => Ok(Some(entries[i].new.clone())),
+                    // With no reflog, `@{0}` is just the current value
+                    None if *n == 0 && entries.is_empty() => repo.read_full_ref(&full_name),
+                    None => Err(RevisionError::ReflogTooShort {
+                        name: name.clone().unwrap_or(full_name),
+                        entries: entries.len(),
                     }
-                } else {
-                    Ok(None)
+                    .into()),
                 }
             }
 
-            Refname::PartialSha1(candidate) => {
-                let entries = repo.database.entries();
+            Refname::Upstream(name) => {
+                let branch = branch_name(repo, name)?;
+                let no_upstream = || RevisionError::NoUpstream {
+                    branch: branch.clone(),
+                };
+
+                let remote = repo.config.get(&format!("branch.{branch}.remote"));
+                let merge = repo.config.get(&format!("branch.{branch}.merge"));
+                let (remote, merge) = remote.zip(merge).ok_or_else(no_upstream)?;
 
-                let candidates = entries
+                // A remote of `.` means the upstream is a local branch
+                let upstream = match (remote, merge.strip_prefix("refs/heads/")) {
+                    (".", _) => merge.to_owned(),
+                    (remote, Some(branch)) => format!("refs/remotes/{remote}/{branch}"),
+                    (_, None) => return Err(no_upstream().into()),
+                };
+                repo.read_full_ref(&upstream)
+            }
+
+            Refname::Index { stage, path } => repo
+                .index
+                .entries()
+                .iter()
+                .find(|entry| entry.stage() == *stage && entry.path() == path.as_str())
+                .map(|entry| Some(entry.oid().clone()))
+                .ok_or_else(|| {
+                    RevisionError::NotInIndex {
+                        stage: *stage,
+                        path: path.clone(),
+                    }
+                    .into()
+                }),
+
+            Refname::Search(pattern) => {
+                let regex = Regex::new(pattern)?;
+
+                let mut starts = Vec::new();
+                let head = match repo.read_head()? {
+                    Some(oid) => Some(repo.database.peel(&oid)?),
+                    None => None,
+                };
+                for oid in head
                     .into_iter()
-                    .filter_map(|entry| {
-                        if entry.to_hex().starts_with(candidate) {
-                            Some(entry)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<_>>();
-
-                match candidates.as_slice() {
-                    // No candidates - treat as a branch / tag name
-                    [] => branchtag(candidate, repo),
-
-                    // One candidate - Found it!
-                    [oid] => match repo.database.load(oid)? {
-                        LoadedItem::Commit(_) => Ok(Some(oid.clone())),
-                        other => Err(eyre!(
-                            "Ref '{:x}' pointed to something other than a commit: {}",
-                            oid,
-                            other.kind()
-                        )),
-                    },
+                    .chain(repo.list_peeled_refs()?.into_iter().map(|(_, oid)| oid))
+                {
+                    if let LoadedItem::Commit(_) = repo.database.load(&oid)? {
+                        starts.push(oid);
+                    }
+                }
 
-                    // Multiple candidates - Ambiguous, tell user to be more specific
-                    _ => {
-                        println!(
-                            "Ambiguous ref name: `{}` matches multiple candidates:",
-                            candidate
-                        );
-                        for candidate in candidates {
-                            let kind = repo.database.load(&candidate)?.kind();
-                            println!("  {:x} {}", candidate, kind);
-                        }
-                        Err(eyre!("Ambiguous ref name"))
+                for item in CommitWalk::new(&repo.database, starts)? {
+                    let (oid, commit) = item?;
+                    if regex.is_match(&commit.message()) {
+                        return Ok(Some(oid));
                     }
                 }
+                Ok(None)
             }
-
-            Refname::BranchTag(name) => branchtag(name, repo),
         }
     }
 }
@@ -245,6 +707,14 @@ impl Refname {
 mod parser_tests {
     use super::*;
 
+    fn ancestor(refname: Refname, n: u64) -> Rev {
+        Rev {
+            refname,
+            steps: vec![Step::Ancestor(n)],
+            path: None,
+        }
+    }
+
     #[test]
     fn head() {
         let rev = "HEAD";
@@ -273,16 +743,7 @@ mod parser_tests {
     #[test]
     fn parents() {
         let parents = ["HEAD^", "HEAD^^"];
-        let expected = [
-            Rev {
-                refname: Refname::Head,
-                distance: 1,
-            },
-            Rev {
-                refname: Refname::Head,
-                distance: 2,
-            },
-        ];
+        let expected = [ancestor(Refname::Head, 1), ancestor(Refname::Head, 2)];
 
         for (rev, expected) in parents.into_iter().zip(expected) {
             dbg!(&rev);
@@ -295,22 +756,10 @@ mod parser_tests {
     fn ancestors() {
         let ancestors = ["HEAD~1", "HEAD~2", "HEAD~3", "HEAD~1012123119"];
         let expected = [
-            Rev {
-                refname: Refname::Head,
-                distance: 1,
-            },
-            Rev {
-                refname: Refname::Head,
-                distance: 2,
-            },
-            Rev {
-                refname: Refname::Head,
-                distance: 3,
-            },
-            Rev {
-                refname: Refname::Head,
-                distance: 1012123119,
-            },
+            ancestor(Refname::Head, 1),
+            ancestor(Refname::Head, 2),
+            ancestor(Refname::Head, 3),
+            ancestor(Refname::Head, 1012123119),
         ];
         for (rev, expected) in ancestors.into_iter().zip(expected) {
             dbg!(&rev);
@@ -322,50 +771,139 @@ mod parser_tests {
     #[test]
     fn complex() {
         let complex = "HEAD~12^^~2";
-        let expected = Rev {
-            refname: Refname::Head,
-            distance: 16,
-        };
+        let expected = ancestor(Refname::Head, 16);
         let rev = Rev::parse(complex).unwrap();
         assert_eq!(rev, expected,);
     }
 
+    #[test]
+    fn extended() {
+        let cases = [
+            ("HEAD^2", Refname::Head, vec![Step::Parent(2)], None),
+            ("HEAD^0", Refname::Head, vec![Step::Parent(0)], None),
+            ("HEAD~", Refname::Head, vec![Step::Ancestor(1)], None),
+            ("HEAD~^", Refname::Head, vec![Step::Ancestor(2)], None),
+            (
+                "master~~",
+                Refname::BranchTag("master".to_owned()),
+                vec![Step::Ancestor(2)],
+                None,
+            ),
+            (
+                "v1^{}",
+                Refname::BranchTag("v1".to_owned()),
+                vec![Step::Peel(None)],
+                None,
+            ),
+            (
+                "HEAD~2^2^{tree}",
+                Refname::Head,
+                vec![
+                    Step::Ancestor(2),
+                    Step::Parent(2),
+                    Step::Peel(Some(ObjectKind::Tree)),
+                ],
+                None,
+            ),
+            (
+                "master:src/main.rs",
+                Refname::BranchTag("master".to_owned()),
+                vec![],
+                Some("src/main.rs"),
+            ),
+            (
+                "@{u}~1",
+                Refname::Upstream(None),
+                vec![Step::Ancestor(1)],
+                None,
+            ),
+            (
+                "topic@{upstream}",
+                Refname::Upstream(Some("topic".to_owned())),
+                vec![],
+                None,
+            ),
+            (
+                ":file",
+                Refname::Index {
+                    stage: 0,
+                    path: "file".to_owned(),
+                },
+                vec![],
+                None,
+            ),
+            (
+                ":2:dir/file",
+                Refname::Index {
+                    stage: 2,
+                    path: "dir/file".to_owned(),
+                },
+                vec![],
+                None,
+            ),
+            (
+                ":/fix: the ^bug",
+                Refname::Search("fix: the ^bug".to_owned()),
+                vec![],
+                None,
+            ),
+        ];
+
+        for (rev, refname, steps, path) in cases {
+            let expected = Rev {
+                refname,
+                steps,
+                path: path.map(str::to_owned),
+            };
+            assert_eq!(Rev::parse(rev).unwrap(), expected, "{rev}");
+        }
+    }
+
+    #[test]
+    fn range() {
+        let range = RevRange::parse(&["a..b", "^c", "..d", "--not", "e", "^f", "x...y"]).unwrap();
+        let names = |revs: &[NamedRev]| revs.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&range.include), ["b", "d", "f"]);
+        assert_eq!(names(&range.exclude), ["a", "c", "HEAD", "e"]);
+        assert_eq!(range.symmetric.len(), 1);
+
+        let range = RevRange::parse(&["a..b~"]).unwrap();
+        assert_eq!(names(&range.include), ["b~"]);
+        assert_eq!(range.include[0].rev.steps, [Step::Ancestor(1)]);
+        assert_eq!(names(&range.exclude), ["a"]);
+    }
+
     #[test]
     fn invalid() {
         let invalid = [
-            "HEAD~",
             "HEAD~-1",
-            "HEAD~^",
             "mast\0er",
-            "HEAD^2",
+            "HEAD^{",
+            "HEAD^{object}",
+            "HEAD^x",
+            ":",
+            ":/(",
+            "^HEAD",
+            "HEAD@{yesterday}",
             "HEAD~99999999999999999999999999999999999999999999999999",
         ];
 
         for rev in invalid {
             dbg!(rev);
-            assert!(Rev::parse(rev).is_err());
+            assert!(matches!(
+                Rev::parse(rev),
+                Err(RevisionError::InvalidSyntax { .. })
+            ));
         }
     }
     #[test]
     fn book() {
         let book_testcases = ["@^", "HEAD~42", "master^^", "abc123~3"];
         let expected = [
-            Rev {
-                refname: Refname::Head,
-                distance: 1,
-            },
-            Rev {
-                refname: Refname::Head,
-                distance: 42,
-            },
-            Rev {
-                refname: Refname::BranchTag("master".to_owned()),
-                distance: 2,
-            },
-            Rev {
-                refname: Refname::PartialSha1("abc123".to_owned()),
-                distance: 3,
-            },
+            ancestor(Refname::Head, 1),
+            ancestor(Refname::Head, 42),
+            ancestor(Refname::BranchTag("master".to_owned()), 2),
+            ancestor(Refname::PartialSha1("abc123".to_owned()), 3),
         ];
 
         for (rev, expected) in book_testcases.into_iter().zip(expected) {
@@ -407,9 +945,7 @@ mod evaluator_tests {
         repo.commit("three")?;
         crate::create_test_files!(dir, ["file4"]);
         repo.add_all()?;
-        let commit_id = dbg!(repo.commit("four")?);
-
-        repo.create_branch("master", &commit_id)?;
+        repo.commit("four")?;
 
         Ok(repo)
     }
@@ -506,4 +1042,147 @@ mod evaluator_tests {
 
         Ok(())
     }
+
+    /// The error from resolving `rev`, which must be a [`RevisionError`].
+    fn resolve_err(repo: &Repo, rev: &str) -> RevisionError {
+        let err = Rev::parse(rev).unwrap().resolve(repo).unwrap_err();
+        err.downcast_ref::<RevisionError>()
+            .unwrap_or_else(|| panic!("{rev}: {err}"))
+            .clone()
+    }
+
+    #[test]
+    fn extended_syntax() -> Result<()> {
+        let dir = TempDir::new("")?;
+        let dir = dir.path();
+        let dir = Utf8Path::from_path(dir).unwrap();
+
+        let mut repo = init_repo(dir)?;
+        let resolve = |repo: &Repo, rev: &str| Rev::parse(rev).unwrap().resolve(repo);
+
+        // Branch off before "four", and merge the branch back in
+        let three = resolve(&repo, "HEAD^")?.unwrap();
+        repo.create_branch("topic", &three, "HEAD^")?;
+        repo.checkout("topic", false)?;
+        crate::create_test_files!(dir, ["dir/nested"]);
+        repo.add_all()?;
+        let side = repo.commit("side")?;
+        repo.checkout("master", false)?;
+        let four = repo.read_head()?.unwrap();
+        repo.merge("topic", Some("merge topic"))?;
+        let merge = repo.read_head()?.unwrap();
+
+        assert_eq!(resolve(&repo, "HEAD^1")?, Some(four.clone()));
+        assert_eq!(resolve(&repo, "HEAD^2")?, Some(side.clone()));
+        assert_eq!(resolve(&repo, "HEAD^2~1")?, Some(three.clone()));
+        assert_eq!(resolve(&repo, "HEAD^0")?, Some(merge.clone()));
+        assert_eq!(resolve(&repo, "HEAD^3")?, None);
+
+        let commit = repo.database.load_commit(&merge)?;
+        assert_eq!(
+            resolve(&repo, "HEAD^{tree}")?,
+            Some(commit.tree_id().clone())
+        );
+        assert_eq!(resolve(&repo, "HEAD:")?, Some(commit.tree_id().clone()));
+        assert_eq!(resolve(&repo, "HEAD^{commit}")?, Some(merge.clone()));
+        assert!(matches!(
+            resolve_err(&repo, "HEAD^{tree}^"),
+            RevisionError::WrongKind {
+                expected: ObjectKind::Commit,
+                actual: ObjectKind::Tree,
+                ..
+            }
+        ));
+
+        // Paths in trees and the index
+        let nested = repo
+            .index
+            .entries()
+            .iter()
+            .find(|entry| entry.path() == "dir/nested")
+            .unwrap()
+            .oid()
+            .clone();
+        assert_eq!(resolve(&repo, "HEAD:dir/nested")?, Some(nested.clone()));
+        assert_eq!(resolve(&repo, "topic:./dir/nested")?, Some(nested.clone()));
+        assert_eq!(resolve(&repo, ":dir/nested")?, Some(nested.clone()));
+        assert_eq!(resolve(&repo, ":0:dir/nested")?, Some(nested));
+        assert!(repo
+            .database
+            .load(&resolve(&repo, "HEAD:dir")?.unwrap())?
+            .is_tree());
+        let tree = |rev: &str| Rev::parse(rev).unwrap().resolve_tree(&repo);
+        assert_eq!(tree("HEAD")?, Some(commit.tree_id().clone()));
+        assert_eq!(tree("HEAD^{tree}")?, Some(commit.tree_id().clone()));
+        assert_eq!(tree("HEAD:dir")?, resolve(&repo, "HEAD:dir")?);
+        assert!(tree("HEAD:dir/nested").is_err());
+        assert_eq!(
+            resolve_err(&repo, "HEAD~1:dir/nested"),
+            RevisionError::PathNotFound {
+                rev: "HEAD".to_owned(),
+                path: "dir/nested".to_owned()
+            }
+        );
+        assert!(matches!(
+            resolve_err(&repo, ":1:dir/nested"),
+            RevisionError::NotInIndex { stage: 1, .. }
+        ));
+
+        // Annotated tags are only peeled when asked, or when a commit is needed
+        let tag = repo.create_tag("v1", "HEAD~1", Some("tagged"), false)?;
+        assert_eq!(resolve(&repo, "v1")?, Some(four.clone()));
+        assert_eq!(resolve(&repo, "v1^{tag}")?, Some(tag));
+        assert_eq!(resolve(&repo, "v1^{}")?, Some(four.clone()));
+
+        // Message search finds the newest match reachable from any ref
+        assert_eq!(resolve(&repo, ":/^s.de$")?, Some(side));
+        assert_eq!(resolve(&repo, ":/t")?, Some(merge));
+        assert_eq!(resolve(&repo, ":/nothing matches")?, None);
+
+        // Upstreams come from the branch's config
+        assert_eq!(
+            resolve_err(&repo, "@{u}"),
+            RevisionError::NoUpstream {
+                branch: "master".to_owned()
+            }
+        );
+        std::process::Command::new("git")
+            .args(["config", "branch.master.remote", "."])
+            .current_dir(dir)
+            .status()?;
+        std::process::Command::new("git")
+            .args(["config", "branch.master.merge", "refs/heads/topic"])
+            .current_dir(dir)
+            .status()?;
+        let repo = Repo::open(dir.to_owned())?;
+        assert_eq!(resolve(&repo, "@{upstream}~1")?, Some(three.clone()));
+        assert_eq!(resolve(&repo, "master@{u}^")?, Some(three));
+        assert!(matches!(
+            resolve_err(&repo, "topic@{u}"),
+            RevisionError::NoUpstream { .. }
+        ));
+
+        // Names that must exist but don't
+        assert_eq!(
+            resolve_err(&repo, "missing@{1}"),
+            RevisionError::UnknownRef {
+                name: "missing".to_owned()
+            }
+        );
+        let err = RevRange::parse(&["missing..master"])?
+            .resolve(&repo)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RevisionError>(),
+            Some(&RevisionError::UnknownRevision {
+                rev: "missing".to_owned()
+            })
+        );
+
+        // A detached HEAD has no branch to find the upstream of
+        std::fs::write(dir.join(".git/HEAD"), format!("{four:x}\n"))?;
+        assert_eq!(resolve_err(&repo, "@{u}"), RevisionError::DetachedHead);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,73 @@
+mod parse;
+mod write;
+
+use crate::commit::Signature;
+use crate::digest::Digest;
+use crate::pack::ObjectKind;
+
+/// An annotated tag object.
+///
+/// Lightweight tags are just refs under `refs/tags` pointing straight at a commit, and have no
+/// object of their own.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    object: Digest,
+    kind: ObjectKind,
+    name: String,
+    tagger: Option<Signature>,
+    message: String,
+}
+
+impl Tag {
+    pub fn new(
+        object: Digest,
+        kind: ObjectKind,
+        name: String,
+        tagger: Signature,
+        message: String,
+    ) -> Self {
+        Self {
+            object,
+            kind,
+            name,
+            tagger: Some(tagger),
+            message,
+        }
+    }
+
+    /// The object this tag points to.
+    pub fn object(&self) -> &Digest {
+        &self.object
+    }
+
+    /// The type of the object this tag points to.
+    pub fn kind(&self) -> ObjectKind {
+        self.kind
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Who created the tag. Some very old tags don't record this.
+    pub fn tagger(&self) -> Option<&Signature> {
+        self.tagger.as_ref()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn pretty_print(&self) -> std::io::Result<()> {
+        println!("object {:x}", self.object);
+        println!("type {}", self.kind.as_str());
+        println!("tag {}", self.name);
+        if let Some(tagger) = &self.tagger {
+            println!("tagger {tagger}");
+        }
+        println!();
+        print!("{}", self.message);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use color_eyre::eyre::eyre;
+
+use crate::commit::Signature;
+use crate::digest::Digest;
+use crate::pack::ObjectKind;
+use crate::Result;
+
+use super::Tag;
+
+impl Tag {
+    /// Parse a decompressed tag.
+    ///
+    /// ## Example
+    /// ```text
+    /// object 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5
+    /// type commit
+    /// tag v1.0.0
+    /// tagger Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100
+    ///
+    /// Version 1.0.0
+    /// ```
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let text = std::str::from_utf8(bytes)?;
+        let (headers, message) = match text.split_once("\n\n") {
+            Some((headers, message)) => (headers, message),
+            None => (text.trim_end_matches('\n'), ""),
+        };
+
+        let mut object = None;
+        let mut kind = None;
+        let mut name = None;
+        let mut tagger = None;
+
+        for line in headers.lines() {
+            let (field, value) = line
+                .split_once(' ')
+                .ok_or_else(|| eyre!("Failed to parse tag: bad header line: {line:?}"))?;
+            match field {
+                "object" => object = Some(Digest::from_str(value)?),
+                "type" => kind = Some(ObjectKind::from_str(value)?),
+                "tag" => name = Some(value.to_owned()),
+                "tagger" => tagger = Some(Signature::parse(value.as_bytes())?),
+                // Unknown headers are allowed, and ignored
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            object: object.ok_or_else(|| eyre!("Failed to parse tag: missing object"))?,
+            kind: kind.ok_or_else(|| eyre!("Failed to parse tag: missing type"))?,
+            name: name.ok_or_else(|| eyre!("Failed to parse tag: missing tag name"))?,
+            tagger,
+            message: message.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use crate::storable::DatabaseObject;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let raw = "object 090c4c5dd61d2e84c832c4cd306b66bf2fabc1f5\n\
+                   type commit\n\
+                   tag v1.0.0\n\
+                   tagger Jamie Quigley <jamie@quigley.xyz> 1658312219 +0100\n\
+                   \n\
+                   Version 1.0.0\n";
+
+        let tag = Tag::parse(raw.as_bytes())?;
+        assert_eq!(tag.name(), "v1.0.0");
+        assert_eq!(tag.kind(), ObjectKind::Commit);
+        assert_eq!(tag.tagger().unwrap().name(), "Jamie Quigley");
+        assert_eq!(tag.message(), "Version 1.0.0\n");
+
+        let stored = DatabaseObject::new(&tag);
+        let expected = format!("tag {}\0{}", raw.len(), raw);
+        assert_eq!(stored.formatted(), expected.as_bytes());
+
+        Ok(())
+    }
+}
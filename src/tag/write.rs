@@ -0,0 +1,27 @@
+use crate::storable::Storable;
+
+impl Storable for super::Tag {
+    fn format(&self) -> Vec<u8> {
+        let mut data = format!(
+            "object {:x}\ntype {}\ntag {}\n",
+            self.object,
+            self.kind.as_str(),
+            self.name
+        )
+        .into_bytes();
+        if let Some(tagger) = &self.tagger {
+            data.extend_from_slice(b"tagger ");
+            data.extend_from_slice(&tagger.to_bytes());
+            data.push(b'\n');
+        }
+        data.push(b'\n');
+        data.extend_from_slice(self.message.as_bytes());
+
+        let mut formatted = Vec::new();
+        formatted.extend_from_slice(b"tag ");
+        formatted.extend_from_slice(format!("{}", data.len()).as_bytes());
+        formatted.push(b'\0');
+        formatted.extend_from_slice(&data);
+        formatted
+    }
+}
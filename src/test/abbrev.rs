@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::blob::Blob;
+use crate::commit::{Commit, Signature};
+use crate::digest::Digest;
+use crate::pack::ObjectKind;
+use crate::repo::database::AmbiguousObject;
+use crate::repo::Repo;
+use crate::revision::Rev;
+use crate::storable::DatabaseObject;
+use crate::test::{git, COMMIT_EMAIL, COMMIT_NAME};
+use crate::timestamp::Timestamp;
+use crate::Result;
+
+/// Store commits and blobs until one of each share the first 4 characters of their oids, with
+/// no other object doing so. Returns the prefix, the commit, and the blob.
+fn make_collision(repo: &Repo) -> Result<(String, Digest, Digest)> {
+    let tree = repo
+        .database
+        .load_commit(&repo.read_head()?.unwrap())?
+        .tree_id()
+        .clone();
+    let mut seen: HashMap<String, Vec<(ObjectKind, Digest)>> = HashMap::new();
+
+    for i in 0.. {
+        let signature = Signature::new(
+            COMMIT_NAME.to_owned(),
+            COMMIT_EMAIL.to_owned(),
+            Timestamp::now(),
+        );
+        let commit = Commit::new(
+            Vec::new(),
+            tree.clone(),
+            signature.clone(),
+            signature,
+            format!("commit {i}\n"),
+        );
+        let commit = DatabaseObject::new(&commit);
+        repo.database.store(&commit)?;
+
+        let blob = Blob::new(format!("blob {i}\n").into_bytes());
+        let blob = DatabaseObject::new(&blob);
+        repo.database.store(&blob)?;
+
+        for (kind, oid) in [
+            (ObjectKind::Commit, commit.oid()),
+            (ObjectKind::Blob, blob.oid()),
+        ] {
+            let prefix = oid.to_hex()[..4].to_owned();
+            let objects = seen.entry(prefix.clone()).or_default();
+            objects.push((kind, oid.clone()));
+
+            if let [(ObjectKind::Commit, commit), (ObjectKind::Blob, blob)]
+            | [(ObjectKind::Blob, blob), (ObjectKind::Commit, commit)] = &objects[..]
+            {
+                if repo.database.prefix_match(&prefix)?.len() == 2 {
+                    return Ok((prefix, commit.clone(), blob.clone()));
+                }
+            }
+        }
+    }
+    unreachable!()
+}
+
+#[test]
+/// An abbreviated oid matching a commit and a blob is an error, unless the context needs one of
+/// them in particular.
+fn test_ambiguous_object() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    repo.commit("first")?;
+
+    let (prefix, commit, blob) = make_collision(&repo)?;
+
+    let err = repo.read_ref(&prefix).unwrap_err();
+    let err = err.downcast_ref::<AmbiguousObject>().unwrap();
+    assert_eq!(err.prefix, prefix);
+    let kinds = err
+        .candidates
+        .iter()
+        .map(|x| (x.oid.clone(), x.kind))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(kinds[&commit], ObjectKind::Commit);
+    assert_eq!(kinds[&blob], ObjectKind::Blob);
+    let summary = err
+        .candidates
+        .iter()
+        .find_map(|x| x.summary.as_deref())
+        .unwrap();
+    assert!(summary.contains(" - commit "), "{summary}");
+    assert!(err
+        .to_string()
+        .starts_with(&format!("short object ID {prefix} is ambiguous\n")));
+
+    let resolve = |rev: &str| Rev::parse(rev).unwrap().resolve(&repo);
+
+    // A bare revision is peeled to a commit, so only the commit is considered
+    assert_eq!(resolve(&prefix)?, Some(commit.clone()));
+    assert_eq!(
+        resolve(&format!("{prefix}^{{commit}}"))?,
+        Some(commit.clone())
+    );
+    assert_eq!(resolve(&format!("{prefix}~0"))?, Some(commit.clone()));
+    assert_eq!(resolve(&format!("{prefix}^{{blob}}"))?, Some(blob.clone()));
+    assert_eq!(
+        resolve(&format!("{prefix}^{{commit}}"))?.unwrap().to_hex(),
+        git(dir, &["rev-parse", &format!("{prefix}^{{commit}}")])?.trim()
+    );
+
+    // Anything goes with `^{}`, so it stays ambiguous
+    let err = resolve(&format!("{prefix}^{{}}")).unwrap_err();
+    assert!(err.downcast_ref::<AmbiguousObject>().is_some());
+
+    // Too short to be an oid
+    assert_eq!(repo.read_ref(&prefix[..3])?, None);
+
+    // A ref with the same name wins
+    let head = repo.read_head()?.unwrap();
+    repo.create_branch(&prefix, &head, "HEAD")?;
+    assert_eq!(repo.read_ref(&prefix)?, Some(head.clone()));
+    assert_eq!(Rev::parse(&prefix)?.resolve(&repo)?, Some(head));
+
+    Ok(())
+}
+
+#[test]
+/// Abbreviated oids are lengthened until they are unique, starting from `core.abbrev`.
+fn test_abbreviate() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    repo.commit("first")?;
+
+    let (prefix, commit, blob) = make_collision(&repo)?;
+
+    let short = repo.abbreviate(&commit)?;
+    assert_eq!(short.len(), 7);
+    assert_eq!(
+        short,
+        git(dir, &["rev-parse", "--short", &commit.to_hex()])?.trim()
+    );
+
+    git(dir, &["config", "core.abbrev", "4"])?;
+    let repo = Repo::open(dir.to_owned())?;
+    assert_eq!(repo.abbrev_len()?, 4);
+    for oid in [&commit, &blob] {
+        let short = repo.abbreviate(oid)?;
+        assert!(short.len() > 4 && short.starts_with(&prefix), "{short}");
+        // The test helper's `core.abbrev` would override the repository's
+        let expected = git(
+            dir,
+            &["-c", "core.abbrev=4", "rev-parse", "--short", &oid.to_hex()],
+        )?;
+        assert_eq!(short, expected.trim());
+    }
+
+    git(dir, &["config", "core.abbrev", "no"])?;
+    let repo = Repo::open(dir.to_owned())?;
+    assert_eq!(repo.abbreviate(&commit)?, commit.to_hex());
+
+    git(dir, &["config", "core.abbrev", "3"])?;
+    let repo = Repo::open(dir.to_owned())?;
+    assert!(repo.abbreviate(&commit).is_err());
+
+    Ok(())
+}
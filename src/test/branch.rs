@@ -0,0 +1,152 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::digest::Digest;
+use crate::repo::Repo;
+use crate::test::{git, two_commits};
+use crate::Result;
+
+/// Create a repository with `master` at one commit, and `feature/x` at a second commit on top of
+/// it.
+fn setup(dir: &Utf8Path) -> Result<(Repo, Digest, Digest)> {
+    let (mut repo, first, second) = two_commits(dir, |repo, first| {
+        repo.create_branch("feature/x", first, "HEAD")?;
+        repo.checkout("feature/x", false)?;
+        Ok(())
+    })?;
+    repo.checkout("master", false)?;
+
+    Ok((repo, first, second))
+}
+
+#[test]
+fn test_list_branches() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = setup(dir)?;
+    repo.create_branch("other", &first, "HEAD")?;
+
+    assert_eq!(
+        repo.list_branches(&[])?,
+        [
+            ("feature/x".to_owned(), second),
+            ("master".to_owned(), first.clone()),
+            ("other".to_owned(), first.clone()),
+        ]
+    );
+    assert_eq!(
+        repo.list_branches(&["feature/*".to_owned()])?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+        ["feature/x"]
+    );
+    assert_eq!(
+        git(dir, &["branch", "--format=%(refname)"])?,
+        "refs/heads/feature/x\nrefs/heads/master\nrefs/heads/other\n"
+    );
+
+    // A branch can't be created where another branch is using its name as a directory
+    assert!(repo.create_branch("feature", &first, "HEAD").is_err());
+    assert!(repo.create_branch("master/y", &first, "HEAD").is_err());
+
+    // Nor can one be created over a packed ref
+    repo.pack_refs(true, true)?;
+    assert!(repo.create_branch("feature", &first, "HEAD").is_err());
+    assert!(repo.create_branch("other/y", &first, "HEAD").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_delete_branch() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = setup(dir)?;
+    repo.create_branch("merged", &first, "HEAD")?;
+
+    // feature/x has a commit that isn't on master
+    let err = repo.delete_branch("feature/x", false).unwrap_err();
+    assert!(err.to_string().contains("not fully merged"), "{err}");
+    assert!(repo.branch_exists("feature/x")?);
+
+    assert_eq!(repo.delete_branch("merged", false)?, first);
+    assert_eq!(repo.delete_branch("feature/x", true)?, second);
+    assert!(!dir.join(".git/refs/heads/feature").exists());
+    assert!(!dir.join(".git/logs/refs/heads/feature").exists());
+    assert_eq!(
+        git(dir, &["branch", "--format=%(refname)"])?,
+        "refs/heads/master\n"
+    );
+
+    assert!(repo.delete_branch("master", true).is_err());
+    assert!(repo.delete_branch("missing", true).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_rename_branch() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = setup(dir)?;
+
+    repo.rename_branch("feature/x", "topic", false)?;
+    assert!(!repo.branch_exists("feature/x")?);
+    assert_eq!(repo.read_ref("topic")?, Some(second.clone()));
+    assert!(!dir.join(".git/logs/refs/heads/feature").exists());
+    assert_eq!(
+        git(dir, &["reflog", "show", "--format=%gs", "topic"])?,
+        "Branch: renamed refs/heads/feature/x to refs/heads/topic\n\
+         commit: second\n\
+         branch: Created from HEAD\n"
+    );
+
+    // Renaming over an existing branch needs force
+    assert!(repo.rename_branch("topic", "master", false).is_err());
+
+    // Renaming the current branch moves HEAD with it
+    repo.rename_branch("master", "main", false)?;
+    assert_eq!(repo.current_branch()?.as_deref(), Some("main"));
+    assert_eq!(repo.read_head()?, Some(first.clone()));
+    assert_eq!(git(dir, &["symbolic-ref", "HEAD"])?, "refs/heads/main\n");
+
+    // ...unless it is the current branch, whose work tree would no longer match
+    assert!(repo.rename_branch("topic", "main", true).is_err());
+    repo.create_branch("other", &first, "HEAD")?;
+    repo.rename_branch("topic", "other", true)?;
+    assert_eq!(repo.read_ref("other")?, Some(second.clone()));
+    assert_eq!(
+        git(dir, &["branch", "--format=%(refname)"])?,
+        "refs/heads/main\nrefs/heads/other\n"
+    );
+
+    // A branch can be renamed to a name under itself, and back, keeping its reflog
+    repo.rename_branch("other", "other/x", false)?;
+    repo.rename_branch("other/x", "other", false)?;
+    assert_eq!(repo.read_ref("other")?, Some(second.clone()));
+    assert!(!dir.join(".git/logs/refs/.tmp-renamed-log").exists());
+    assert_eq!(
+        git(dir, &["reflog", "show", "--format=%gs", "other"])?,
+        "Branch: renamed refs/heads/other/x to refs/heads/other\n\
+         Branch: renamed refs/heads/other to refs/heads/other/x\n\
+         Branch: renamed refs/heads/topic to refs/heads/other\n\
+         Branch: renamed refs/heads/feature/x to refs/heads/topic\n\
+         commit: second\n\
+         branch: Created from HEAD\n"
+    );
+
+    // A failed rename leaves the branch as it was
+    repo.create_branch("blocked/y", &first, "HEAD")?;
+    assert!(repo.rename_branch("other", "blocked", true).is_err());
+    assert_eq!(repo.read_ref("other")?, Some(second));
+    assert_eq!(
+        git(dir, &["reflog", "show", "--format=%gs", "other"])?
+            .lines()
+            .count(),
+        6
+    );
+
+    Ok(())
+}
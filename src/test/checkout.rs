@@ -0,0 +1,132 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::repo::checkout::CheckoutOutcome;
+use crate::repo::Repo;
+use crate::test::{COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+/// Create a repository where `master` has `a.txt` and `dir/b.txt`, and the branch `topic` changes
+/// `a.txt`, removes `dir/b.txt`, and adds `c.txt`. HEAD is left on `master`.
+fn setup(dir: &Utf8Path) -> Result<Repo> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    std::fs::create_dir(dir.join("dir"))?;
+    std::fs::write(dir.join("a.txt"), "a\n")?;
+    std::fs::write(dir.join("dir/b.txt"), "b\n")?;
+    repo.add_all()?;
+    let master = repo.commit("master")?;
+
+    repo.create_branch("topic", &master, "HEAD")?;
+    repo.checkout("topic", false)?;
+    std::fs::write(dir.join("a.txt"), "topic\n")?;
+    std::fs::remove_file(dir.join("dir/b.txt"))?;
+    std::fs::write(dir.join("c.txt"), "c\n")?;
+    repo.index.remove(Utf8Path::new("dir/b.txt"));
+    repo.add_all()?;
+    repo.commit("topic")?;
+
+    repo.checkout("master", false)?;
+
+    Ok(repo)
+}
+
+fn index_paths(repo: &Repo) -> Vec<String> {
+    repo.index
+        .entries()
+        .iter()
+        .map(|e| e.name().to_owned())
+        .collect()
+}
+
+#[test]
+/// Switch between two branches, checking that the workspace, index and HEAD follow along.
+fn test_checkout_branch() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let mut repo = setup(dir)?;
+
+    assert_eq!(std::fs::read_to_string(dir.join("a.txt"))?, "a\n");
+    assert_eq!(std::fs::read_to_string(dir.join("dir/b.txt"))?, "b\n");
+    assert!(!dir.join("c.txt").exists());
+    assert_eq!(index_paths(&repo), ["a.txt", "dir/b.txt"]);
+    assert_eq!(
+        std::fs::read_to_string(dir.join(".git/HEAD"))?,
+        "ref: refs/heads/master\n"
+    );
+
+    assert!(matches!(
+        repo.checkout("topic", false)?,
+        CheckoutOutcome::Branch(name) if name == "topic"
+    ));
+    assert_eq!(std::fs::read_to_string(dir.join("a.txt"))?, "topic\n");
+    assert!(!dir.join("dir").exists());
+    assert_eq!(std::fs::read_to_string(dir.join("c.txt"))?, "c\n");
+    assert_eq!(index_paths(&repo), ["a.txt", "c.txt"]);
+    assert_eq!(repo.current_branch()?.as_deref(), Some("topic"));
+
+    // The workspace should be clean according to git as well
+    let output = std::process::Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(dir)
+        .output()?;
+    assert_eq!(String::from_utf8(output.stdout)?, "");
+
+    Ok(())
+}
+
+#[test]
+/// Check out a commit by oid, which should detach HEAD.
+fn test_checkout_detached() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let mut repo = setup(dir)?;
+
+    let topic = repo.read_ref("topic")?.unwrap();
+    assert!(matches!(
+        repo.checkout(&topic.to_hex(), false)?,
+        CheckoutOutcome::Detached(oid) if oid == topic
+    ));
+    assert_eq!(repo.current_branch()?, None);
+    assert_eq!(repo.read_head()?, Some(topic.clone()));
+
+    // Committing on a detached HEAD should not move any branch
+    std::fs::write(dir.join("d.txt"), "d\n")?;
+    repo.add_all()?;
+    let commit = repo.commit("detached")?;
+    assert_eq!(repo.read_head()?, Some(commit));
+    assert_eq!(repo.read_ref("topic")?, Some(topic));
+
+    Ok(())
+}
+
+#[test]
+/// Local changes to files that differ between the two commits should block the checkout, but
+/// changes to other files should be carried over.
+fn test_checkout_local_changes() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let mut repo = setup(dir)?;
+
+    std::fs::write(dir.join("a.txt"), "local\n")?;
+    assert!(repo.checkout("topic", false).is_err());
+    assert_eq!(repo.current_branch()?.as_deref(), Some("master"));
+    assert_eq!(std::fs::read_to_string(dir.join("a.txt"))?, "local\n");
+    std::fs::write(dir.join("a.txt"), "a\n")?;
+
+    std::fs::write(dir.join("c.txt"), "untracked\n")?;
+    assert!(repo.checkout("topic", false).is_err());
+    std::fs::remove_file(dir.join("c.txt"))?;
+
+    repo.create_branch("other", &repo.read_head()?.unwrap(), "HEAD")?;
+    std::fs::write(dir.join("dir/b.txt"), "local\n")?;
+    repo.checkout("other", false)?;
+    assert_eq!(std::fs::read_to_string(dir.join("dir/b.txt"))?, "local\n");
+
+    Ok(())
+}
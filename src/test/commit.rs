@@ -1,3 +1,7 @@
+use crate::commit::cleanup::Cleanup;
+use crate::repo::commit::CommitOptions;
+use crate::repo::config::Role;
+use crate::repo::transaction::Expected;
 use crate::repo::*;
 use crate::test::{COMMIT_EMAIL, COMMIT_NAME};
 use crate::*;
@@ -238,3 +242,444 @@ pub(super) fn commit_file_hierarchy() -> Result<()> {
     }
     Ok(())
 }
+
+/// Write an executable script to `path`.
+fn write_script(path: &Utf8Path, body: &str) -> Result<()> {
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, format!("#!/bin/sh\n{body}\n"))?;
+    std::fs::set_permissions(path, Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+/// The message of the commit `oid` exactly as stored, which [`Commit::message`] trims.
+fn raw_message(repo: &Repo, oid: &Digest) -> Result<String> {
+    let (_, data) = repo.database.read_raw(oid)?;
+    let data = String::from_utf8(data)?;
+    Ok(data.split_once("\n\n").unwrap().1.to_owned())
+}
+
+fn set_config(dir: &Utf8Path, key: &str, value: &str) -> Result<()> {
+    let status = Command::new("git")
+        .args(["config", key, value])
+        .current_dir(dir)
+        .status()?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+/// Without `-m`, the message is written in the editor, starting from `commit.template` and a
+/// summary of the changes, which is stripped from the result.
+fn commit_with_editor() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+    std::env::remove_var("GIT_EDITOR");
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let scripts = TempDir::new("")?;
+    let scripts = Utf8Path::from_path(scripts.path()).unwrap();
+
+    Repo::init_default(dir)?;
+    crate::create_test_files!(dir, ["file"]);
+
+    // The editor keeps a copy of what it was given, then adds a subject
+    let seen = scripts.join("seen");
+    let editor = scripts.join("editor");
+    write_script(
+        &editor,
+        &format!(
+            "cp \"$1\" {seen}\n{{ echo 'Subject  '; echo; echo; cat \"$1\"; }} > \"$1.new\"\nmv \"$1.new\" \"$1\""
+        ),
+    )?;
+    set_config(dir, "core.editor", editor.as_str())?;
+    std::fs::write(scripts.join("template"), "Template body\n")?;
+    set_config(dir, "commit.template", scripts.join("template").as_str())?;
+
+    let mut repo = Repo::open(dir.to_owned())?;
+    repo.add_all()?;
+    let oid = repo.commit_with(&Default::default())?;
+
+    let seen = std::fs::read_to_string(seen)?;
+    assert!(seen.starts_with("Template body\n\n# Please enter the commit message"));
+    assert!(seen.contains("# On branch master\n#\n# Initial commit\n"));
+    assert!(seen.contains("# Changes to be committed:\n#\tnew file:   file\n"));
+
+    assert_eq!(raw_message(&repo, &oid)?, "Subject\n\nTemplate body\n");
+
+    // Leaving the template as it was aborts the commit
+    set_config(dir, "core.editor", ":")?;
+    let repo = &mut Repo::open(dir.to_owned())?;
+    let err = repo
+        .commit_with(&CommitOptions {
+            allow_empty: true,
+            ..Default::default()
+        })
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Aborting commit; you did not edit the message."
+    );
+
+    // Editing a message given with `-m`, keeping comments
+    let options = CommitOptions {
+        allow_empty: true,
+        message: Some("\n\nGiven  \n# kept\n\n\n".to_owned()),
+        edit: true,
+        cleanup: Some(Cleanup::Whitespace),
+        ..Default::default()
+    };
+    let oid = repo.commit_with(&options)?;
+    let message = raw_message(repo, &oid)?;
+    assert!(
+        message.starts_with("Given\n# kept\n\n# Please enter"),
+        "{message}"
+    );
+
+    Ok(())
+}
+
+#[test]
+/// Messages from `-F` are cleaned up, and empty messages are refused unless allowed.
+fn commit_message_cleanup() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+
+    let file = dir.join(".git/message");
+    std::fs::write(&file, "\nFrom a file \n# not a comment without an editor\n")?;
+    let options = CommitOptions {
+        file: Some(file.clone()),
+        ..Default::default()
+    };
+    let oid = repo.commit_with(&options)?;
+    assert_eq!(
+        raw_message(&repo, &oid)?,
+        "From a file\n# not a comment without an editor\n"
+    );
+
+    let options = CommitOptions {
+        allow_empty: true,
+        file: Some(file),
+        cleanup: Some(Cleanup::Verbatim),
+        ..Default::default()
+    };
+    let oid = repo.commit_with(&options)?;
+    assert_eq!(
+        raw_message(&repo, &oid)?,
+        "\nFrom a file \n# not a comment without an editor\n"
+    );
+
+    let mut options = CommitOptions {
+        allow_empty: true,
+        message: Some(" \n\n".to_owned()),
+        ..Default::default()
+    };
+    let err = repo.commit_with(&options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Aborting commit due to empty commit message."
+    );
+    options.allow_empty_message = true;
+    let oid = repo.commit_with(&options)?;
+    assert_eq!(raw_message(&repo, &oid)?, "");
+
+    let output = Command::new("git")
+        .args(["fsck", "--strict"])
+        .current_dir(dir)
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+
+    Ok(())
+}
+
+#[test]
+/// `pre-commit` and `commit-msg` can stop a commit unless `--no-verify` is given, and
+/// `prepare-commit-msg` is told where the message came from.
+fn commit_hooks() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    crate::create_test_files!(dir, ["file"]);
+
+    let hooks = dir.join(".git/hooks");
+    let log = dir.join(".git/hook-log");
+    write_script(
+        &hooks.join("pre-commit"),
+        &format!("echo pre-commit >> {log}\ntest ! -e block"),
+    )?;
+    write_script(
+        &hooks.join("prepare-commit-msg"),
+        &format!("echo prepare-commit-msg \"$2\" >> {log}\necho 'Signed-off-by: hook' >> \"$1\""),
+    )?;
+    write_script(
+        &hooks.join("commit-msg"),
+        &format!("echo commit-msg >> {log}\n! grep -q WIP \"$1\""),
+    )?;
+
+    let mut repo = Repo::open(dir.to_owned())?;
+    repo.add_all()?;
+
+    let mut options = CommitOptions {
+        allow_empty: true,
+        message: Some("Subject".to_owned()),
+        ..Default::default()
+    };
+    let oid = repo.commit_with(&options)?;
+    assert_eq!(raw_message(&repo, &oid)?, "Subject\nSigned-off-by: hook\n");
+    assert_eq!(
+        std::fs::read_to_string(&log)?,
+        "pre-commit\nprepare-commit-msg message\ncommit-msg\n"
+    );
+
+    options.message = Some("WIP".to_owned());
+    assert!(repo.commit_with(&options).is_err());
+    assert_eq!(repo.read_head()?, Some(oid.clone()));
+
+    crate::create_test_files!(dir, ["block"]);
+    options.message = Some("Blocked".to_owned());
+    assert!(repo.commit_with(&options).is_err());
+    assert_eq!(repo.read_head()?, Some(oid));
+
+    // Without verification, only `prepare-commit-msg` runs
+    std::fs::remove_file(&log)?;
+    options.no_verify = true;
+    let oid = repo.commit_with(&options)?;
+    assert_eq!(raw_message(&repo, &oid)?, "Blocked\nSigned-off-by: hook\n");
+    assert_eq!(
+        std::fs::read_to_string(&log)?,
+        "prepare-commit-msg message\n"
+    );
+
+    // Hooks that aren't executable are ignored
+    std::fs::set_permissions(hooks.join("commit-msg"), Permissions::from_mode(0o644))?;
+    options.no_verify = false;
+    options.message = Some("WIP".to_owned());
+    std::fs::remove_file(dir.join("block"))?;
+    repo.commit_with(&options)?;
+
+    Ok(())
+}
+
+#[test]
+/// `--amend` replaces HEAD, keeping its parents and author, commits that change nothing are
+/// refused unless allowed, and the author can be overridden.
+fn commit_amend() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    let err = repo
+        .commit_with(&CommitOptions {
+            amend: true,
+            message: Some("Nothing".to_owned()),
+            ..Default::default()
+        })
+        .unwrap_err();
+    assert_eq!(err.to_string(), "You have nothing to amend.");
+
+    crate::create_test_files!(dir, ["file1"]);
+    repo.add_all()?;
+    let first = repo.commit("first")?;
+    crate::create_test_files!(dir, ["file2"]);
+    repo.add_all()?;
+    let second = repo.commit("second")?;
+    let original = repo.database.load_commit(&second)?;
+
+    // Nothing has changed since the last commit
+    let mut options = CommitOptions {
+        message: Some("empty".to_owned()),
+        ..Default::default()
+    };
+    let err = repo.commit_with(&options).unwrap_err();
+    assert!(err.to_string().starts_with("nothing to commit"), "{err}");
+    options.allow_empty = true;
+    let empty = repo.commit_with(&options)?;
+    assert_eq!(
+        repo.database.load_commit(&empty)?.parents(),
+        std::slice::from_ref(&second)
+    );
+
+    // Amending with a new file and the old message
+    repo.update_ref("HEAD", &second, Expected::Any, "reset: moving to second")?;
+    crate::create_test_files!(dir, ["file3"]);
+    repo.add_all()?;
+    let options = CommitOptions {
+        amend: true,
+        no_edit: true,
+        ..Default::default()
+    };
+    let amended = repo.commit_with(&options)?;
+    let commit = repo.database.load_commit(&amended)?;
+    assert_ne!(amended, second);
+    assert_eq!(commit.parents(), [first]);
+    let (author, original) = (commit.author(), original.author());
+    assert_eq!(
+        (author.name(), author.email(), author.when().to_string()),
+        (
+            original.name(),
+            original.email(),
+            original.when().to_string()
+        )
+    );
+    assert_eq!(raw_message(&repo, &amended)?, "second\n");
+    let reflog = repo.read_reflog("HEAD")?;
+    assert_eq!(reflog.last().unwrap().message, "commit (amend): second");
+
+    // Unstaging the amended changes would leave a commit that changes nothing
+    let status = Command::new("git")
+        .args(["rm", "--cached", "--quiet", "file2", "file3"])
+        .current_dir(dir)
+        .status()?;
+    assert!(status.success());
+    let mut repo = Repo::open(dir.to_owned())?;
+    let err = repo.commit_with(&options).unwrap_err();
+    assert!(err.to_string().starts_with("You asked to amend"), "{err}");
+
+    // An explicit author and date
+    let options = CommitOptions {
+        amend: true,
+        no_edit: true,
+        allow_empty: true,
+        author: Some("A U Thor <author@example.com>".to_owned()),
+        date: Some("2005-04-07T22:13:13+0200".to_owned()),
+        ..Default::default()
+    };
+    let oid = repo.commit_with(&options)?;
+    let output = Command::new("git")
+        .args(["cat-file", "-p", &oid.to_hex()])
+        .current_dir(dir)
+        .output()?;
+    let output = String::from_utf8(output.stdout)?;
+    assert!(
+        output.contains("\nauthor A U Thor <author@example.com> 1112904793 +0200\n"),
+        "{output}"
+    );
+
+    // An author found by searching existing commits
+    let options = CommitOptions {
+        author: Some("U Th".to_owned()),
+        ..options
+    };
+    let oid = repo.commit_with(&options)?;
+    let author = repo.database.load_commit(&oid)?.author().clone();
+    assert_eq!(
+        (author.name(), author.email()),
+        ("A U Thor", "author@example.com")
+    );
+
+    let options = CommitOptions {
+        author: Some("nobody at all".to_owned()),
+        ..options
+    };
+    let err = repo.commit_with(&options).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "--author 'nobody at all' is not 'Name <email>' and matches no existing author"
+    );
+
+    Ok(())
+}
+
+#[test]
+/// Authors and committers come from `GIT_AUTHOR_*` and `GIT_COMMITTER_*`, then config.
+fn commit_identity() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    set_config(dir, "user.name", "Config User")?;
+    set_config(dir, "user.email", "user@example.com")?;
+    set_config(dir, "committer.name", "Config Committer")?;
+    let repo = Repo::open(dir.to_owned())?;
+
+    let env = std::collections::HashMap::from([
+        ("GIT_AUTHOR_NAME", "Env Author"),
+        ("GIT_AUTHOR_DATE", "Thu, 07 Apr 2005 22:13:13 +0200"),
+        ("GIT_COMMITTER_EMAIL", "committer@example.com"),
+        ("GIT_COMMITTER_DATE", "@1112911993 -0130"),
+    ]);
+    let signature = |role, env: &std::collections::HashMap<&str, &str>| {
+        repo.signature(role, |var| env.get(var).map(|x| x.to_string()))
+    };
+
+    let author = signature(Role::Author, &env)?;
+    assert_eq!(
+        author.to_string(),
+        "Env Author <user@example.com> 1112904793 +0200"
+    );
+    let committer = signature(Role::Committer, &env)?;
+    assert_eq!(
+        committer.to_string(),
+        "Config Committer <committer@example.com> 1112911993 -0130"
+    );
+
+    let mut env = env;
+    env.insert("GIT_AUTHOR_DATE", "yesterday-ish");
+    let err = signature(Role::Author, &env).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid GIT_AUTHOR_DATE");
+    assert_eq!(
+        err.root_cause().to_string(),
+        "invalid date format: yesterday-ish"
+    );
+
+    env.insert("GIT_AUTHOR_NAME", "");
+    let err = signature(Role::Author, &env).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "empty ident name (for <user@example.com>) not allowed"
+    );
+
+    Ok(())
+}
+
+#[test]
+/// Commits written by git in another encoding are read without loss, so storing them again gives
+/// the same oid.
+fn commit_non_utf8() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    crate::create_test_files!(dir, ["file"]);
+
+    let message = dir.join(".git/message");
+    std::fs::write(&message, b"Gr\xfc\xdfe\n\nCaf\xe9\n")?;
+    let status = Command::new("git")
+        .args([
+            "-c",
+            "i18n.commitEncoding=ISO-8859-1",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(["commit", "-q", "--allow-empty", "--cleanup=verbatim", "-F"])
+        .arg(&message)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "J\u{f6}rg")
+        .env("GIT_AUTHOR_EMAIL", COMMIT_EMAIL)
+        .env("GIT_COMMITTER_NAME", COMMIT_NAME)
+        .env("GIT_COMMITTER_EMAIL", COMMIT_EMAIL)
+        .status()?;
+    assert!(status.success());
+
+    let repo = Repo::open(dir.to_owned())?;
+    let oid = repo.read_head()?.unwrap();
+    let commit = repo.database.load_commit(&oid)?;
+    assert_eq!(commit.encoding(), Some("ISO-8859-1"));
+    assert_eq!(commit.subject(), "Gr\u{fc}\u{df}e");
+    assert_eq!(commit.author().name(), "J\u{f6}rg");
+    assert_eq!(storable::DatabaseObject::new(&commit).oid(), &oid);
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::os::unix::prelude::PermissionsExt;
+use std::process::Command;
+
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::config::{ConfigFile, ConfigKey};
+use crate::repo::status::Status;
+use crate::repo::Repo;
+use crate::test::{COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+fn git_config(dir: &Utf8Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("config")
+        .args(args)
+        .current_dir(dir)
+        .output()?;
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[test]
+/// The config written by `init`, and any later edits, should be readable by git. Values written
+/// by git should be readable by rit.
+fn test_config_roundtrip_with_git() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    Repo::init_default(dir)?;
+    assert_eq!(
+        git_config(dir, &["--local", "--list"])?,
+        "core.repositoryformatversion=0\ncore.filemode=true\ncore.bare=false\ncore.logallrefupdates=true\n"
+    );
+
+    let mut file = ConfigFile::open(&dir.join(".git/config"))?;
+    file.set(
+        &"remote.origin.url".parse::<ConfigKey>()?,
+        "git@example.com:a b.git",
+    )?;
+    file.set(&"alias.x".parse::<ConfigKey>()?, "log # not a comment")?;
+    file.save()?;
+
+    assert_eq!(
+        git_config(dir, &["remote.origin.url"])?,
+        "git@example.com:a b.git\n"
+    );
+    assert_eq!(git_config(dir, &["alias.x"])?, "log # not a comment\n");
+
+    git_config(
+        dir,
+        &["--local", "branch.main.description", " padded\tvalue "],
+    )?;
+    let repo = Repo::open(dir.to_owned())?;
+    assert_eq!(
+        repo.config.get("branch.main.description"),
+        Some(" padded\tvalue ")
+    );
+    assert_eq!(repo.config.get_bool("core.bare")?, Some(false));
+
+    Ok(())
+}
+
+#[test]
+/// With `core.filemode` set to false, changing the executable bit of a file should not count as
+/// a modification, and adding it should keep the recorded mode.
+fn test_core_filemode() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    Repo::init_default(dir)?;
+    crate::create_test_files!(dir, ["file"]);
+    git_config(dir, &["core.filemode", "false"])?;
+
+    let mut repo = Repo::open(dir.to_owned())?;
+    repo.add_all()?;
+    repo.commit("initial")?;
+
+    std::fs::set_permissions(dir.join("file"), std::fs::Permissions::from_mode(0o755))?;
+
+    let status = Status::new(&repo)?.unwrap();
+    assert_eq!(status.get_statuses()?, []);
+
+    repo.add_all()?;
+    let entry = repo.index.get_entry_by_path(Utf8Path::new("file")).unwrap();
+    assert!(!entry.mode().is_executable());
+
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use std::os::unix::fs::PermissionsExt;
+
+use camino::Utf8Path;
+use clap::ValueEnum;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+use termcolor::NoColor;
+
+use crate::diff::DiffAlgorithm;
+use crate::pathspec::Pathspec;
+use crate::repo::diff::{DiffMode, DiffOptions};
+use crate::repo::Repo;
+use crate::test::{git, git_stdin, COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+fn init_repo(dir: &Utf8Path, files: &[(&str, &[u8])]) -> Result<Repo> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    for (path, contents) in files {
+        std::fs::write(dir.join(path), contents)?;
+    }
+
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+    repo.add_all()?;
+    repo.commit("test")?;
+    Ok(repo)
+}
+
+fn rit_diff(repo: &Repo, mode: DiffMode, options: &DiffOptions) -> Result<String> {
+    let mut out = NoColor::new(Vec::new());
+    repo.write_diff(&mut out, mode, options)?;
+    Ok(String::from_utf8(out.into_inner())?)
+}
+
+/// Run `rit diff` with the arguments `args`, as they would be given to `git diff`.
+fn rit_diff_args(repo: &Repo, args: &[&str]) -> Result<String> {
+    let (args, paths) = match args.iter().position(|arg| *arg == "--") {
+        Some(i) => (&args[..i], &args[i + 1..]),
+        None => (args, &[][..]),
+    };
+    let cached = args.contains(&"--cached");
+    let no_renames = args.contains(&"--no-renames");
+    let option = |name: &str| args.iter().find_map(|arg| arg.strip_prefix(name));
+    let args = args
+        .iter()
+        .filter(|arg| !arg.starts_with('-'))
+        .collect::<Vec<_>>();
+    let (revisions, mut pathspecs) = repo.split_diff_args(&args)?;
+    pathspecs.extend(paths.iter().map(|path| path.to_string()));
+
+    let mode = repo.diff_mode(cached, &revisions)?;
+    let options = DiffOptions {
+        pathspec: Pathspec::new(&pathspecs),
+        renames: repo.diff_renames(option("-M"), option("-C"), no_renames)?,
+        ..Default::default()
+    };
+    rit_diff(repo, mode, &options)
+}
+
+/// Contents that look binary, and are long enough for a delta to be worth making.
+fn binary_contents(seed: u8) -> Vec<u8> {
+    let mut contents = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    contents.extend((0..4000u32).map(|i| (i * 7 % 251) as u8 ^ seed));
+    contents
+}
+
+#[test]
+/// Changes to binary files, and files the attributes say are binary, are summarised as they are
+/// by git.
+fn diff_binary_files() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let repo = init_repo(
+        dir,
+        &[
+            (".gitattributes", b"*.dat -diff\n*.bin binary\n"),
+            ("image.png", &binary_contents(0)),
+            ("notes.dat", b"plain text\n"),
+            ("data.bin", b"more plain text\n"),
+        ],
+    )?;
+
+    std::fs::write(dir.join("image.png"), binary_contents(1))?;
+    std::fs::write(dir.join("notes.dat"), b"changed plain text\n")?;
+    std::fs::write(dir.join("data.bin"), b"changed more plain text\n")?;
+
+    let expected = git(dir, &["diff"])?;
+    assert!(expected.contains("Binary files a/notes.dat and b/notes.dat differ"));
+    assert_eq!(
+        rit_diff(&repo, DiffMode::WorktreeIndex, &DiffOptions::default())?,
+        expected
+    );
+
+    Ok(())
+}
+
+#[test]
+/// `diff --binary` writes patches that git can apply, in both directions.
+fn diff_binary_patch() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let repo = init_repo(
+        dir,
+        &[
+            ("image.png", &binary_contents(0)),
+            ("small.png", b"\0\x01\x02"),
+        ],
+    )?;
+
+    let mut changed = binary_contents(0);
+    changed[100..110].copy_from_slice(b"0123456789");
+    changed.extend_from_slice(b"trailer");
+    std::fs::write(dir.join("image.png"), &changed)?;
+    std::fs::remove_file(dir.join("small.png"))?;
+
+    let options = DiffOptions {
+        binary: true,
+        ..Default::default()
+    };
+    let patch = rit_diff(&repo, DiffMode::WorktreeIndex, &options)?;
+    assert!(patch.contains("GIT binary patch\ndelta "));
+    assert!(patch.contains("deleted file mode 100644"));
+
+    git_stdin(dir, &["apply", "-R"], patch.as_bytes())?;
+    assert_eq!(std::fs::read(dir.join("image.png"))?, binary_contents(0));
+    assert_eq!(std::fs::read(dir.join("small.png"))?, b"\0\x01\x02");
+
+    git_stdin(dir, &["apply"], patch.as_bytes())?;
+    assert_eq!(std::fs::read(dir.join("image.png"))?, changed);
+    assert!(!dir.join("small.png").exists());
+
+    Ok(())
+}
+
+#[test]
+/// Commits, trees, the index and the workspace can be compared with each other, limited by
+/// pathspecs, as git does.
+fn diff_revisions() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    // Lines that don't start with a letter, so git doesn't show them as function context
+    let lines = |n: usize| (1..=n).map(|i| format!("{i}\n")).collect::<String>();
+    let write = |path: &str, contents: &str| -> Result<()> {
+        std::fs::create_dir_all(dir.join(path).parent().unwrap())?;
+        Ok(std::fs::write(dir.join(path), contents)?)
+    };
+
+    git(dir, &["init", "-q"])?;
+    write("a.txt", &lines(20))?;
+    write("dir/b.txt", &lines(3))?;
+    write("dir/sub/c.txt", "c\n")?;
+    write("other/d.txt", &lines(5))?;
+    write("same/e.txt", "unchanged\n")?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-qm", "first"])?;
+
+    write("a.txt", &lines(20).replace("\n10\n", "\n10.0\n"))?;
+    std::fs::remove_file(dir.join("dir/b.txt"))?;
+    write("dir/new.txt", "new\n")?;
+    std::fs::set_permissions(
+        dir.join("dir/sub/c.txt"),
+        std::fs::Permissions::from_mode(0o755),
+    )?;
+    write("other/d.txt", &lines(7))?;
+    git(dir, &["add", "-A"])?;
+    git(dir, &["commit", "-qm", "second"])?;
+
+    // Staged and unstaged changes, and a file that isn't tracked
+    write("other/d.txt", &lines(8))?;
+    git(dir, &["add", "other/d.txt"])?;
+    write("a.txt", &lines(21).replace("\n2\n", "\n"))?;
+    write("dir/new.txt", "newer\n")?;
+    write("untracked.txt", "untracked\n")?;
+
+    let repo = Repo::open(dir.to_owned())?;
+    for args in [
+        &[][..],
+        &["--cached"],
+        &["HEAD~1"],
+        &["--cached", "HEAD~1"],
+        &["HEAD~1", "HEAD"],
+        &["HEAD", "HEAD~1"],
+        &["HEAD~1", "HEAD^{tree}"],
+        &["HEAD~1", "HEAD", "--", "dir"],
+        &["HEAD~1", "--", "*.txt", "other/"],
+        &["--cached", "HEAD~1", "--", "dir/sub"],
+        &["--", "a.txt"],
+        // Without `--`, arguments after the revisions that exist are paths
+        &["HEAD~1", "dir"],
+        &["--cached", "other"],
+        &["--cached", "HEAD~1", "dir/sub", "a.txt"],
+        &["dir/new.txt", "a.txt"],
+    ] {
+        let mut git_args = vec!["diff"];
+        git_args.extend(args);
+        let expected = git(dir, &git_args)?;
+        assert_eq!(rit_diff_args(&repo, args)?, expected, "diff {args:?}");
+    }
+
+    // Unchanged subtrees aren't read
+    let same = git(dir, &["rev-parse", "HEAD:same"])?;
+    let expected = git(dir, &["diff", "HEAD~1", "HEAD"])?;
+    let (prefix, rest) = same.trim().split_at(2);
+    std::fs::remove_file(dir.join(".git/objects").join(prefix).join(rest))?;
+    assert_eq!(rit_diff_args(&repo, &["HEAD~1", "HEAD"])?, expected);
+
+    assert!(rit_diff_args(&repo, &["HEAD~1", "HEAD", "HEAD"]).is_err());
+    assert!(rit_diff_args(&repo, &["--cached", "HEAD~1", "HEAD"]).is_err());
+    assert!(rit_diff_args(&repo, &["nonexistent"]).is_err());
+    assert!(rit_diff_args(&repo, &["HEAD~1", "dir/b.txt"]).is_err());
+    assert!(rit_diff_args(&repo, &["a.txt", "HEAD"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+/// Renames and copies are found as git finds them, and shown with the same similarity.
+fn diff_renames() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let lines = |range: std::ops::Range<usize>| range.map(|i| format!("{i}\n")).collect::<String>();
+    let write = |path: &str, contents: &str| -> Result<()> {
+        std::fs::create_dir_all(dir.join(path).parent().unwrap())?;
+        Ok(std::fs::write(dir.join(path), contents)?)
+    };
+
+    git(dir, &["init", "-q"])?;
+    write("exact", &lines(0..10))?;
+    write("edited", &lines(100..140))?;
+    write("halved", &lines(200..240))?;
+    write("twice", "0123456789\n".repeat(10).as_str())?;
+    write("empty", "")?;
+    write("source", &lines(300..330))?;
+    write("long", &format!("{}\n{}", "x".repeat(200), lines(400..410)))?;
+    write("dir/name", &lines(500..520))?;
+    write("unrelated", "gone\n")?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-qm", "first"])?;
+
+    for path in [
+        "exact",
+        "edited",
+        "halved",
+        "twice",
+        "empty",
+        "long",
+        "dir/name",
+        "unrelated",
+    ] {
+        std::fs::remove_file(dir.join(path))?;
+    }
+    write("moved/exact", &lines(0..10))?;
+    write(
+        "edited.txt",
+        &lines(100..140).replace("\n110\n", "\n110.5\n"),
+    )?;
+    write("half", &lines(200..220))?;
+    write("twice-1", "0123456789\n".repeat(10).as_str())?;
+    write("twice-2", "0123456789\n".repeat(9).as_str())?;
+    write("also-empty", "")?;
+    write("source", &lines(300..331))?;
+    write("source-copy", &lines(300..329))?;
+    write(
+        "long-lines",
+        &format!("{}\n{}", "x".repeat(199), lines(400..410)),
+    )?;
+    write("name", &lines(500..520))?;
+    write("other/name", &lines(500..520))?;
+    write("new", "unrelated\n")?;
+    std::fs::set_permissions(dir.join("name"), std::fs::Permissions::from_mode(0o755))?;
+    git(dir, &["add", "-A"])?;
+    git(dir, &["commit", "-qm", "second"])?;
+
+    let repo = Repo::open(dir.to_owned())?;
+    for args in [
+        &["HEAD~1", "HEAD"][..],
+        &["-M", "HEAD~1", "HEAD"],
+        &["-M90%", "HEAD~1", "HEAD"],
+        &["-M3", "HEAD~1", "HEAD"],
+        &["-C", "HEAD~1", "HEAD"],
+        &["-C", "HEAD~1", "HEAD", "--", "source*"],
+        &["--no-renames", "HEAD~1", "HEAD"],
+    ] {
+        let mut git_args = vec!["diff"];
+        git_args.extend(args);
+        let expected = git(dir, &git_args)?;
+        assert_eq!(rit_diff_args(&repo, args)?, expected, "diff {args:?}");
+    }
+
+    let diff = rit_diff_args(&repo, &["HEAD~1", "HEAD"])?;
+    assert!(diff.contains("similarity index 100%\nrename from exact\nrename to moved/exact\n"));
+    assert!(rit_diff_args(&repo, &["-Mx", "HEAD~1", "HEAD"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+/// Each diff algorithm, chosen with `--diff-algorithm` or `diff.algorithm`, gives the same diff as
+/// git's, for files whose blocks of lines have moved around.
+fn diff_algorithms() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    // Blocks with a line that repeats, but that each start and end with lines of their own, so
+    // the changes have only one place they can go. No line starts with a letter, so git doesn't
+    // show any as function context
+    let block = |i: usize| format!("{{ {i}\n  ({i})\n  {}\n}} {i}\n", i % 3);
+    let file = |order: &[usize]| order.iter().copied().map(block).collect::<String>();
+
+    git(dir, &["init", "-q"])?;
+    std::fs::write(dir.join("a"), file(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]))?;
+    std::fs::write(dir.join("b"), file(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]))?;
+    git(dir, &["add", "."])?;
+    git(dir, &["commit", "-qm", "first"])?;
+    std::fs::write(dir.join("a"), file(&[0, 1, 2, 4, 5, 6, 7, 9, 8, 3, 10]))?;
+    std::fs::write(dir.join("b"), file(&[0, 1, 2, 3, 6, 4, 9, 5, 8, 7]))?;
+
+    let repo = Repo::open(dir.to_owned())?;
+    for algorithm in ["myers", "minimal", "patience", "histogram"] {
+        let expected = git(dir, &["diff", "--diff-algorithm", algorithm])?;
+        let options = DiffOptions {
+            algorithm: repo
+                .diff_algorithm(DiffAlgorithm::from_str(algorithm, false).ok(), false)?,
+            ..Default::default()
+        };
+        let diff = rit_diff(&repo, DiffMode::WorktreeIndex, &options)?;
+        assert_eq!(diff, expected, "--diff-algorithm={algorithm}");
+    }
+
+    // The algorithms really do differ here
+    let myers = git(dir, &["diff", "--diff-algorithm=myers"])?;
+    let patience = git(dir, &["diff", "--diff-algorithm=patience"])?;
+    let histogram = git(dir, &["diff", "--diff-algorithm=histogram"])?;
+    assert!(myers != patience && patience != histogram && histogram != myers);
+
+    let expected = git(dir, &["-c", "diff.algorithm=histogram", "diff"])?;
+    git(dir, &["config", "diff.algorithm", "histogram"])?;
+    let repo = Repo::open(dir.to_owned())?;
+    let options = DiffOptions {
+        algorithm: repo.diff_algorithm(None, true)?,
+        ..Default::default()
+    };
+    assert_eq!(
+        rit_diff(&repo, DiffMode::WorktreeIndex, &options)?,
+        expected
+    );
+
+    git(dir, &["config", "diff.algorithm", "slow"])?;
+    let repo = Repo::open(dir.to_owned())?;
+    assert!(repo.diff_algorithm(None, false).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::repo::Repo;
+use crate::Result;
+
+#[test]
+/// Build a workspace with ignore rules from every source, and check that the files rit considers
+/// are exactly the ones git reports as untracked and not ignored.
+fn test_ignore_rules_match_git() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let global = TempDir::new("")?;
+    let global = Utf8Path::from_path(global.path()).unwrap().join("ignore");
+
+    Repo::init_default(dir)?;
+
+    crate::create_test_files!(
+        dir,
+        [
+            "a.txt",
+            "app.log",
+            "keep.log",
+            "build/out.o",
+            "src/build/nested.o",
+            "src/main.rs",
+            "src/gen.rs",
+            "src/sub/gen.rs",
+            "docs/guide.md",
+            "docs/notes/draft.md",
+            "vendor/lib/x.c",
+            "vendor/lib/keep.c",
+            "tmp/scratch",
+            "secret.key",
+            ".github/workflow.yml"
+        ]
+    );
+
+    std::fs::write(
+        dir.join(".gitignore"),
+        "*.log\n!keep.log\n/build/\ndocs/**/draft.md\nvendor/\n!vendor/lib/keep.c\n",
+    )?;
+    std::fs::write(dir.join("src/.gitignore"), "/gen.rs\nbuild\n")?;
+    std::fs::create_dir_all(dir.join(".git/info"))?;
+    std::fs::write(dir.join(".git/info/exclude"), "tmp/\n")?;
+    std::fs::write(&global, "*.key\n")?;
+
+    let mut config = std::fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join(".git/config"))?;
+    std::io::Write::write_all(
+        &mut config,
+        format!("\n[core]\n\texcludesFile = {global}\n").as_bytes(),
+    )?;
+
+    let repo = Repo::open(dir.to_owned())?;
+    let mut ours = repo
+        .list_files(Utf8Path::new("."))?
+        .into_iter()
+        .map(|path| path.to_string())
+        .collect::<Vec<_>>();
+    ours.sort();
+
+    let output = std::process::Command::new("git")
+        .args(["ls-files", "--others", "--exclude-standard"])
+        .current_dir(dir)
+        .output()?;
+    let mut theirs = String::from_utf8(output.stdout)?
+        .lines()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    theirs.sort();
+
+    assert_eq!(ours, theirs);
+    assert!(ours.contains(&"keep.log".to_owned()));
+    assert!(ours.contains(&"src/sub/gen.rs".to_owned()));
+    assert!(!ours.iter().any(|path| path.starts_with("vendor/")));
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::digest::Digest;
+use crate::repo::merge::MergeOutcome;
+use crate::repo::Repo;
+use crate::test::{COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+const BASE: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+
+/// Commit `contents` to `file`, with HEAD at `parent`.
+fn commit_on(
+    repo: &mut Repo,
+    parent: &Digest,
+    file: &str,
+    contents: &str,
+    message: &str,
+) -> Result<Digest> {
+    repo.set_head(parent, "reset: moving to parent")?;
+    std::fs::write(repo.dir.join(file), contents)?;
+    repo.add_all()?;
+    repo.commit(message)
+}
+
+/// Create a repository with a single commit containing `file.txt`. Then create two children of
+/// that commit: `ours`, which HEAD points to, and `theirs`, which the branch `topic` points to.
+fn diverge(dir: &Utf8Path, ours: &str, theirs: &str) -> Result<(Repo, Digest, Digest, Digest)> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    std::fs::write(dir.join("file.txt"), BASE)?;
+    repo.add_all()?;
+    let base = repo.commit("base")?;
+
+    let theirs = commit_on(&mut repo, &base, "file.txt", theirs, "theirs")?;
+    repo.create_branch("topic", &theirs, "HEAD")?;
+    let ours = commit_on(&mut repo, &base, "file.txt", ours, "ours")?;
+
+    Ok((repo, base, ours, theirs))
+}
+
+#[test]
+/// Change different parts of the same file on each side. The merge should be clean, and produce a
+/// commit with both sides as parents.
+fn test_clean_merge() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let ours = BASE.replace("1\n", "one\n");
+    let theirs = BASE.replace("10\n", "ten\n");
+    let (mut repo, base, ours_oid, theirs_oid) = diverge(dir, &ours, &theirs)?;
+
+    assert_eq!(
+        crate::merge::merge_base(&repo.database, &ours_oid, &theirs_oid)?,
+        Some(base)
+    );
+
+    let merge = match repo.merge("topic", None)? {
+        MergeOutcome::Merged(oid) => oid,
+        _ => panic!("merge should have been clean"),
+    };
+
+    let commit = repo.database.load_commit(&merge)?;
+    assert_eq!(commit.parents(), [ours_oid, theirs_oid]);
+    assert_eq!(commit.message(), "Merge branch 'topic'");
+
+    let merged = std::fs::read_to_string(dir.join("file.txt"))?;
+    assert_eq!(
+        merged,
+        BASE.replace("1\n", "one\n").replace("10\n", "ten\n")
+    );
+    assert!(repo.read_merge_head()?.is_none());
+
+    Ok(())
+}
+
+#[test]
+/// Change the same line on each side. The merge should stop with conflict markers in the file and
+/// stages 1-3 in the index, and can be concluded once the conflict is resolved.
+fn test_conflicted_merge() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let ours = BASE.replace("5\n", "five\n");
+    let theirs = BASE.replace("5\n", "FIVE\n");
+    let (mut repo, _, ours_oid, theirs_oid) = diverge(dir, &ours, &theirs)?;
+
+    match repo.merge("topic", None)? {
+        MergeOutcome::Conflicted(paths) => assert_eq!(paths, ["file.txt"]),
+        _ => panic!("merge should have conflicted"),
+    }
+
+    let merged = std::fs::read_to_string(dir.join("file.txt"))?;
+    assert_eq!(
+        merged,
+        BASE.replace("5\n", "<<<<<<< HEAD\nfive\n=======\nFIVE\n>>>>>>> topic\n")
+    );
+
+    let stages = repo
+        .index
+        .entries()
+        .iter()
+        .map(|e| (e.name(), e.stage()))
+        .collect::<Vec<_>>();
+    assert_eq!(stages, [("file.txt", 1), ("file.txt", 2), ("file.txt", 3)]);
+    assert!(repo.commit("too early").is_err());
+    assert!(repo.merge_continue().is_err());
+
+    std::fs::write(dir.join("file.txt"), BASE.replace("5\n", "Five\n"))?;
+    repo.add_all()?;
+    let merge = repo.merge_continue()?;
+
+    let commit = repo.database.load_commit(&merge)?;
+    assert_eq!(commit.parents(), [ours_oid, theirs_oid]);
+    assert!(repo.read_merge_head()?.is_none());
+
+    Ok(())
+}
+
+#[test]
+/// Merging a descendant of HEAD should just move HEAD forward, and merging an ancestor should do
+/// nothing.
+fn test_fast_forward() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let (mut repo, base, ours, _) = diverge(dir, BASE, BASE)?;
+    let ahead = commit_on(&mut repo, &ours, "other.txt", "new file\n", "ahead")?;
+    repo.create_branch("ahead", &ahead, "HEAD")?;
+    repo.set_head(&ours, "reset: moving to ours")?;
+    std::fs::remove_file(dir.join("other.txt"))?;
+    repo.index.remove(Utf8Path::new("other.txt"));
+    repo.index.flush()?;
+
+    assert!(matches!(
+        repo.merge(&base.to_hex(), None)?,
+        MergeOutcome::UpToDate
+    ));
+
+    match repo.merge("ahead", None)? {
+        MergeOutcome::FastForward(oid) => assert_eq!(oid, ahead),
+        _ => panic!("merge should have fast-forwarded"),
+    }
+    assert_eq!(repo.read_head()?, Some(ahead));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("other.txt"))?,
+        "new file\n"
+    );
+
+    Ok(())
+}
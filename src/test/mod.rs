@@ -1,11 +1,33 @@
 use crate::digest::Digest;
+use crate::repo::Repo;
+use crate::Result;
 
-use std::{io, path::Path, process::Command};
+use std::{
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
 
+use camino::Utf8Path;
+
+mod abbrev;
 mod add;
+mod branch;
+mod checkout;
 mod commit;
+mod config;
+mod diff;
+mod ignore;
 mod init;
+mod merge;
+mod pack;
+mod reflog;
+mod refs;
+mod rev_list;
+mod signing;
 mod status;
+mod tag;
+mod update_ref;
 
 pub const COMMIT_NAME: &str = "Jamie Quigley";
 pub const COMMIT_EMAIL: &str = "jamie@quigley.xyz";
@@ -41,3 +63,77 @@ pub fn git_cat_file(dir: &Path, oid: &Digest) -> io::Result<String> {
     )
     .unwrap())
 }
+
+/// Run git in `dir` as the test identity, with signing off and oids abbreviated to 7 characters,
+/// and return what it printed. Panics if git fails.
+pub fn git(dir: &Utf8Path, args: &[&str]) -> Result<String> {
+    run_git(git_command(dir, args), b"")
+}
+
+/// Like [`git`], with anything committed or tagged dated `date`.
+pub fn git_at(dir: &Utf8Path, args: &[&str], date: &str) -> Result<String> {
+    let mut command = git_command(dir, args);
+    command
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date);
+    run_git(command, b"")
+}
+
+/// Like [`git`], writing `stdin` to its input.
+pub fn git_stdin(dir: &Utf8Path, args: &[&str], stdin: &[u8]) -> Result<String> {
+    run_git(git_command(dir, args), stdin)
+}
+
+fn git_command(dir: &Utf8Path, args: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command
+        .args(["-c", "commit.gpgsign=false", "-c", "core.abbrev=7"])
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", COMMIT_NAME)
+        .env("GIT_AUTHOR_EMAIL", COMMIT_EMAIL)
+        .env("GIT_COMMITTER_NAME", COMMIT_NAME)
+        .env("GIT_COMMITTER_EMAIL", COMMIT_EMAIL);
+    command
+}
+
+fn run_git(mut command: Command, stdin: &[u8]) -> Result<String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(stdin)?;
+    let output = child.wait_with_output()?;
+    assert!(
+        output.status.success(),
+        "{command:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Create a repository at `dir` with two commits: "first", adding `file`, and "second", changing
+/// it. `between` is run on the repository after the first commit, and given its oid. Returns the
+/// repository and both commits.
+pub fn two_commits(
+    dir: &Utf8Path,
+    between: impl FnOnce(&mut Repo, &Digest) -> Result<()>,
+) -> Result<(Repo, Digest, Digest)> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    let first = repo.commit("first")?;
+    between(&mut repo, &first)?;
+
+    std::fs::write(dir.join("file"), "changed\n")?;
+    repo.add_all()?;
+    let second = repo.commit("second")?;
+
+    Ok((repo, first, second))
+}
@@ -0,0 +1,182 @@
+use std::io::Write;
+use std::process::Command;
+
+use camino::Utf8Path;
+use color_eyre::eyre::eyre;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::digest::Digest;
+use crate::pack::{write_pack, ObjectKind, Pack, PackObject};
+use crate::repo::Repo;
+use crate::test::{git, COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+#[test]
+/// Use git to create a repository with several revisions of the same file (so that `git gc` will
+/// produce deltas), then pack everything. Every object reachable in the repository should be
+/// readable by rit, and hash to its own oid.
+fn read_packed_objects() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    git(dir, &["init", "-q"])?;
+
+    let mut contents = String::new();
+    for i in 0..20 {
+        for j in 0..50 {
+            contents.push_str(&format!("line {j} of revision {i}\n"));
+        }
+        std::fs::write(dir.join("file"), &contents)?;
+        std::fs::create_dir_all(dir.join("sub"))?;
+        std::fs::write(dir.join("sub/other"), format!("{i}\n"))?;
+        git(dir, &["add", "--all"])?;
+        git(dir, &["commit", "-q", "-m", &format!("revision {i}")])?;
+    }
+
+    git(dir, &["gc", "-q", "--aggressive"])?;
+
+    let loose = std::fs::read_dir(dir.join(".git/objects"))?
+        .filter_map(|x| x.ok())
+        .filter(|x| x.file_name().len() == 2)
+        .count();
+    assert_eq!(loose, 0, "git gc should have packed every object");
+
+    let repo = Repo::open(dir.to_owned())?;
+
+    let objects = git(dir, &["rev-list", "--objects", "--all"])?;
+    let oids = objects
+        .lines()
+        .map(|line| line.split(' ').next().unwrap().parse::<Digest>().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(oids.len(), 20 * 5);
+    for oid in &oids {
+        assert!(repo.database.contains(oid));
+        let bytes = repo.database.read_uncompressed(oid)?;
+        assert_eq!(&Digest::new(&bytes), oid);
+    }
+
+    let head = git(dir, &["rev-parse", "HEAD"])?
+        .trim()
+        .parse::<Digest>()
+        .unwrap();
+    let commit = repo.database.load(&head)?.into_commit().unwrap();
+    assert_eq!(commit.message(), "revision 19");
+
+    let short = &head.to_hex()[..7];
+    assert_eq!(repo.database.prefix_match(short)?, vec![head.clone()]);
+    assert_eq!(repo.database.entries().len(), oids.len());
+
+    Ok(())
+}
+
+#[test]
+/// Make several commits with rit, then repack. Every loose object should be removed, and both git
+/// and rit should still be able to read every object out of the new pack.
+fn repack_loose_objects() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    let mut contents = String::new();
+    for i in 0..10 {
+        for j in 0..100 {
+            contents.push_str(&format!("line {j} of revision {i}\n"));
+        }
+        std::fs::write(dir.join("file"), &contents)?;
+        crate::create_test_files!(dir, ["a/b/c.txt"]);
+        repo.add_all()?;
+        repo.commit(&format!("revision {i}"))?;
+    }
+    let head = repo.read_head()?.unwrap();
+
+    let idx_path = repo.repack(true)?.unwrap();
+    assert!(repo.database.loose_entries().is_empty());
+
+    let verify = Command::new("git")
+        .arg("verify-pack")
+        .arg("-v")
+        .arg(&idx_path)
+        .current_dir(dir)
+        .output()?;
+    assert!(verify.status.success());
+    let verify = String::from_utf8(verify.stdout).unwrap();
+    assert!(
+        verify.contains("chain length = 1"),
+        "pack should contain deltas:\n{verify}"
+    );
+
+    git(dir, &["fsck", "--strict"])?;
+    let log = git(dir, &["log", "--format=%s"])?;
+    assert_eq!(log.lines().count(), 10);
+
+    let repo = Repo::open(dir.to_owned())?;
+    let commit = repo.database.load(&head)?.into_commit().unwrap();
+    assert_eq!(commit.message(), "revision 9");
+    for oid in repo.database.entries() {
+        let bytes = repo.database.read_uncompressed(&oid)?;
+        assert_eq!(Digest::new(&bytes), oid);
+    }
+
+    Ok(())
+}
+
+#[test]
+/// Deltas that are their own bases, directly or through other entries, should be errors rather
+/// than recursing forever.
+fn read_corrupt_deltas() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    let data = b"contents\n".to_vec();
+    let oid = Digest::new(b"blob 9\0contents\n");
+    let object = PackObject {
+        oid: oid.clone(),
+        kind: ObjectKind::Blob,
+        data: data.clone(),
+        name_hash: 0,
+    };
+    let idx_path = write_pack(dir, vec![object])?;
+    let pack_path = idx_path.with_extension("pack");
+
+    // A delta that inserts the whole of `data`
+    let mut delta = vec![data.len() as u8, data.len() as u8, data.len() as u8];
+    delta.extend_from_slice(&data);
+    // Small enough to fit in the entry header's first byte
+    let size = delta.len() as u8;
+    assert!(size < 0x10);
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&delta)?;
+    let delta = encoder.finish()?;
+
+    // The only entry is at offset 12, straight after the header
+    let corrupt = |entry: &[u8]| -> Result<String> {
+        let mut pack = std::fs::read(&pack_path)?;
+        pack.truncate(12);
+        pack.extend_from_slice(entry);
+        pack.extend_from_slice(&delta);
+        pack.extend_from_slice(&[0; 20]);
+        std::fs::write(&pack_path, pack)?;
+
+        let pack = Pack::open(&idx_path)?;
+        let err = pack
+            .read_object(&oid, |_| Err(eyre!("not in the pack")))
+            .unwrap_err();
+        Ok(err.to_string())
+    };
+
+    let ref_delta = [&[0x70 | size][..], &oid.0].concat();
+    assert!(corrupt(&ref_delta)?.contains("too long"));
+    let ofs_delta = [0x60 | size, 0];
+    assert!(corrupt(&ofs_delta)?.contains("its own base"));
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::digest::Digest;
+use crate::repo::Repo;
+use crate::revision::Rev;
+use crate::test::{git, COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+fn setup(dir: &Utf8Path) -> Result<Repo> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    Repo::init_default(dir)?;
+    Repo::open(dir.to_owned())
+}
+
+fn commit(repo: &mut Repo, dir: &Utf8Path, contents: &str) -> Result<Digest> {
+    std::fs::write(dir.join("file"), contents)?;
+    repo.add_all()?;
+    repo.commit(contents)
+}
+
+fn git_reflog(dir: &Utf8Path, name: &str) -> Result<String> {
+    git(dir, &["reflog", "show", "--format=%h %gd: %gs", name])
+}
+
+fn resolve(repo: &Repo, rev: &str) -> Result<Digest> {
+    Ok(Rev::parse(rev)?.resolve(repo)?.unwrap())
+}
+
+#[test]
+/// Commits, branches and checkouts are logged as git would log them.
+fn test_reflog_written() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let mut repo = setup(dir)?;
+
+    let first = commit(&mut repo, dir, "first")?;
+    let second = commit(&mut repo, dir, "second")?;
+    repo.create_branch("topic", &first, "HEAD~1")?;
+    repo.checkout("topic", false)?;
+    let third = commit(&mut repo, dir, "third")?;
+
+    assert_eq!(
+        git_reflog(dir, "HEAD")?,
+        format!(
+            "{} HEAD@{{0}}: commit: third\n\
+             {} HEAD@{{1}}: checkout: moving from master to topic\n\
+             {} HEAD@{{2}}: commit: second\n\
+             {} HEAD@{{3}}: commit (initial): first\n",
+            third.short(),
+            first.short(),
+            second.short(),
+            first.short(),
+        )
+    );
+    assert_eq!(
+        git_reflog(dir, "topic")?,
+        format!(
+            "{} topic@{{0}}: commit: third\n\
+             {} topic@{{1}}: branch: Created from HEAD~1\n",
+            third.short(),
+            first.short(),
+        )
+    );
+
+    let entries = repo.read_reflog("refs/heads/master")?;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].old.to_hex(), "0".repeat(40));
+    assert_eq!(entries[1].old, first);
+    assert_eq!(entries[1].new, second);
+
+    // @{n} counts back from the newest entry
+    assert_eq!(resolve(&repo, "HEAD@{0}")?, third);
+    assert_eq!(resolve(&repo, "HEAD@{2}")?, second);
+    assert_eq!(resolve(&repo, "master@{1}")?, first);
+    assert_eq!(resolve(&repo, "@{1}")?, first);
+    assert_eq!(resolve(&repo, "HEAD@{2}~1")?, first);
+    assert!(Rev::parse("topic@{2}")?.resolve(&repo).is_err());
+
+    // Deleting a branch deletes its log
+    repo.checkout("master", false)?;
+    repo.delete_branch("topic", true)?;
+    assert!(!dir.join(".git/logs/refs/heads/topic").exists());
+    assert!(repo.read_reflog("refs/heads/topic")?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_reflog_disabled() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    setup(dir)?;
+
+    git(dir, &["config", "core.logAllRefUpdates", "false"])?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    commit(&mut repo, dir, "first")?;
+    assert!(!dir.join(".git/logs").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_reflog_expire() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let mut repo = setup(dir)?;
+
+    commit(&mut repo, dir, "first")?;
+    commit(&mut repo, dir, "second")?;
+
+    assert_eq!(repo.expire_reflog("HEAD", None, None)?, 0);
+    assert_eq!(repo.read_reflog("HEAD")?.len(), 2);
+
+    // Both entries are reachable from HEAD, so only expire removes them
+    assert_eq!(repo.expire_reflog("HEAD", None, Some(i64::MAX))?, 0);
+    assert_eq!(repo.expire_reflog("HEAD", Some(i64::MAX), None)?, 2);
+    assert!(repo.read_reflog("HEAD")?.is_empty());
+
+    // The reflog stays, so later updates are still logged
+    assert!(dir.join(".git/logs/HEAD").is_file());
+    assert_eq!(repo.reflog_names()?, ["HEAD", "refs/heads/master"]);
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::digest::Digest;
+use crate::repo::Repo;
+use crate::revision::Rev;
+use crate::test::{git, two_commits};
+use crate::Result;
+
+/// Create a repository with two commits, the branch `topic` at the first, and an annotated tag
+/// `v1` and lightweight tag `light` at the second.
+fn setup(dir: &Utf8Path) -> Result<(Repo, Digest, Digest)> {
+    let (mut repo, first, second) = two_commits(dir, |repo, first| {
+        repo.create_branch("topic", first, "HEAD")?;
+        Ok(())
+    })?;
+
+    repo.create_tag("v1", "HEAD", Some("Version 1"), false)?;
+    repo.create_tag("light", "HEAD", None, false)?;
+
+    Ok((repo, first, second))
+}
+
+#[test]
+/// Refs packed by git should be found, and loose refs should take precedence over them.
+fn test_read_packed_refs() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = setup(dir)?;
+
+    git(dir, &["pack-refs", "--all"])?;
+    assert!(!dir.join(".git/refs/heads/master").exists());
+    assert!(!dir.join(".git/refs/tags/v1").exists());
+
+    assert_eq!(repo.read_head()?, Some(second.clone()));
+    assert_eq!(repo.read_ref("topic")?, Some(first.clone()));
+    assert!(repo.branch_exists("topic")?);
+    for name in ["v1", "light", "master", "refs/heads/master"] {
+        assert_eq!(Rev::parse(name)?.resolve(&repo)?, Some(second.clone()));
+    }
+    assert_eq!(repo.list_tags(&[])?, ["light", "v1"]);
+    assert_eq!(
+        repo.list_refs()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+        [
+            "refs/heads/master",
+            "refs/heads/topic",
+            "refs/tags/light",
+            "refs/tags/v1"
+        ]
+    );
+
+    // The annotated tag is peeled from its `^` line, which is read again once the file changes
+    let peeled = |repo: &Repo| -> Result<Vec<Digest>> {
+        Ok(repo
+            .list_peeled_refs()?
+            .into_iter()
+            .map(|(_, oid)| oid)
+            .collect())
+    };
+    assert_eq!(
+        peeled(&repo)?,
+        [
+            second.clone(),
+            first.clone(),
+            second.clone(),
+            second.clone()
+        ]
+    );
+    let packed = std::fs::read_to_string(dir.join(".git/packed-refs"))?;
+    let packed = packed.replace(&format!("^{second:x}"), &format!("^{first:x}"));
+    std::fs::write(dir.join(".git/packed-refs.new"), packed)?;
+    std::fs::rename(
+        dir.join(".git/packed-refs.new"),
+        dir.join(".git/packed-refs"),
+    )?;
+    assert_eq!(
+        peeled(&repo)?,
+        [second.clone(), first.clone(), second.clone(), first.clone()]
+    );
+
+    // A loose ref shadows the packed one
+    std::fs::write(dir.join(".git/refs/heads/topic"), format!("{second:x}\n"))?;
+    assert_eq!(repo.read_ref("topic")?, Some(second.clone()));
+
+    // Deleting removes both copies
+    repo.delete_branch("topic", false)?;
+    assert!(!repo.branch_exists("topic")?);
+    assert!(!dir.join(".git/refs/heads/topic").exists());
+    assert!(!git(dir, &["show-ref"])?.contains("refs/heads/topic"));
+    assert!(repo.delete_branch("master", false).is_err());
+
+    Ok(())
+}
+
+#[test]
+/// `pack-refs` should move refs into packed-refs without changing what git sees.
+fn test_pack_refs() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, _, second) = setup(dir)?;
+
+    let before = git(dir, &["show-ref", "-d"])?;
+
+    // Without `all`, only tags are packed
+    repo.pack_refs(false, true)?;
+    assert!(dir.join(".git/refs/heads/master").exists());
+    assert!(!dir.join(".git/refs/tags/v1").exists());
+
+    repo.pack_refs(true, true)?;
+    assert!(!dir.join(".git/refs/heads/master").exists());
+    assert_eq!(git(dir, &["show-ref", "-d"])?, before);
+    assert_eq!(git(dir, &["fsck", "--strict"])?, "");
+
+    let packed = std::fs::read_to_string(dir.join(".git/packed-refs"))?;
+    assert!(packed.starts_with("# pack-refs with: peeled fully-peeled sorted \n"));
+    assert!(packed.contains(&format!("^{second:x}\n")));
+
+    // Committing on a packed branch writes a loose ref, which shadows the packed one
+    std::fs::write(dir.join("file"), "third\n")?;
+    repo.add_all()?;
+    let third = repo.commit("third")?;
+    assert_eq!(repo.read_head()?, Some(third.clone()));
+    assert_eq!(git(dir, &["rev-parse", "master"])?.trim(), third.to_hex());
+
+    Ok(())
+}
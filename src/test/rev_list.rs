@@ -0,0 +1,92 @@
+use camino::Utf8Path;
+use clap::Parser;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::interface::RevListArgs;
+use crate::repo::Repo;
+use crate::rev_list::rev_list;
+use crate::test::{git, git_at};
+use crate::Result;
+
+#[derive(Parser)]
+struct Cli {
+    #[clap(flatten)]
+    args: RevListArgs,
+}
+
+/// Commit a new file `name` on the current branch at the given day of January 2020.
+fn commit(dir: &Utf8Path, name: &str, day: u32) -> Result<()> {
+    let date = format!("2020-01-{day:02}T00:00:00Z");
+    std::fs::create_dir_all(dir.join(name).parent().unwrap())?;
+    std::fs::write(dir.join(name), name)?;
+    git_at(dir, &["add", "."], &date)?;
+    git_at(dir, &["commit", "-m", name], &date)?;
+    Ok(())
+}
+
+fn merge(dir: &Utf8Path, branch: &str, day: u32) -> Result<()> {
+    let date = format!("2020-01-{day:02}T00:00:00Z");
+    git_at(dir, &["merge", "--no-ff", "--no-edit", branch], &date)?;
+    Ok(())
+}
+
+#[test]
+/// `rev-list` should agree with git's for ranges and every option, on a history with branches
+/// that were merged back in.
+fn test_rev_list_matches_git() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+    git(dir, &["init", "-q", "-b", "main"])?;
+    commit(dir, "one", 1)?;
+    commit(dir, "two", 2)?;
+    git(dir, &["checkout", "-q", "-b", "feature"])?;
+    commit(dir, "dir/three", 3)?;
+    commit(dir, "dir/four", 5)?;
+    git(dir, &["checkout", "-q", "main"])?;
+    commit(dir, "five", 4)?;
+    git(dir, &["checkout", "-q", "-b", "other", "main~1"])?;
+    commit(dir, "six", 6)?;
+    git(dir, &["checkout", "-q", "main"])?;
+    merge(dir, "feature", 7)?;
+    merge(dir, "other", 8)?;
+    git(dir, &["checkout", "-q", "feature"])?;
+    commit(dir, "dir/seven", 9)?;
+
+    let repo = Repo::open(dir.to_owned())?;
+
+    for args in [
+        "main",
+        "main..feature",
+        "feature..main",
+        "main...feature",
+        "other...feature",
+        "^main feature",
+        "feature --not main",
+        "main other --not feature other~1",
+        "--count main..feature",
+        "--max-count 3 main",
+        "-n 2 --reverse main",
+        "--reverse main",
+        "--first-parent main",
+        "--topo-order main",
+        "--topo-order feature main",
+        "--topo-order --first-parent main",
+        "--date-order main",
+        "--objects main",
+        "--objects main..feature",
+        "--objects --count feature...other",
+    ] {
+        let split = args.split_whitespace().collect::<Vec<_>>();
+        let expected = git(dir, &[&["rev-list"], &split[..]].concat())?;
+
+        let cli = Cli::try_parse_from(std::iter::once("rev-list").chain(split.iter().copied()))?;
+        let mut actual = rev_list(&repo, &cli.args)?.join("\n");
+        actual.push('\n');
+
+        assert_eq!(actual, expected, "rev-list {args}");
+    }
+
+    Ok(())
+}
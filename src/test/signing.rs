@@ -0,0 +1,216 @@
+use std::process::Command;
+
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::repo::commit::CommitOptions;
+use crate::repo::Repo;
+use crate::storable::DatabaseObject;
+use crate::test::{COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+fn run(dir: &Utf8Path, program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", COMMIT_NAME)
+        .env("GIT_AUTHOR_EMAIL", COMMIT_EMAIL)
+        .env("GIT_COMMITTER_NAME", COMMIT_NAME)
+        .env("GIT_COMMITTER_EMAIL", COMMIT_EMAIL)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "{program} {args:?} failed: {output:?}"
+    );
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// A repo with one commit, and `gpg.program` set to a `gpg` using a keyring of its own, with a key
+/// for the test identity.
+fn gpg_repo(dir: &Utf8Path, home: &Utf8Path) -> Result<Repo> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let gnupg = home.join("gnupg");
+    std::fs::create_dir(&gnupg)?;
+    std::fs::set_permissions(&gnupg, std::os::unix::fs::PermissionsExt::from_mode(0o700))?;
+    let identity = format!("{COMMIT_NAME} <{COMMIT_EMAIL}>");
+    run(
+        home,
+        "gpg",
+        &[
+            "--homedir",
+            gnupg.as_str(),
+            "--batch",
+            "--passphrase",
+            "",
+            "--quick-gen-key",
+            &identity,
+            "ed25519",
+            "sign",
+            "never",
+        ],
+    )?;
+
+    let program = home.join("gpg");
+    std::fs::write(
+        &program,
+        format!("#!/bin/sh\nexec gpg --homedir {gnupg} \"$@\"\n"),
+    )?;
+    std::fs::set_permissions(
+        &program,
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )?;
+
+    Repo::init_default(dir)?;
+    run(dir, "git", &["config", "gpg.program", program.as_str()])?;
+    let mut repo = Repo::open(dir.to_owned())?;
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    repo.commit("first")?;
+    Ok(repo)
+}
+
+#[test]
+/// Commits signed by rit can be verified by git, and the other way around, and keep their oid
+/// when they are written out again.
+fn test_gpg_signatures() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let home = TempDir::new("")?;
+    let home = Utf8Path::from_path(home.path()).unwrap();
+    let mut repo = gpg_repo(dir, home)?;
+
+    let unsigned = repo.read_head()?.unwrap();
+    assert!(repo.verify_commit(&unsigned)?.is_none());
+
+    crate::create_test_files!(dir, ["signed"]);
+    repo.add_all()?;
+    let oid = repo.commit_with(&CommitOptions {
+        message: Some("Signed by rit".to_owned()),
+        gpg_sign: Some(String::new()),
+        ..Default::default()
+    })?;
+    let commit = repo.database.load_commit(&oid)?;
+    assert!(commit
+        .signature()
+        .unwrap()
+        .starts_with("-----BEGIN PGP SIGNATURE-----"));
+    assert_eq!(DatabaseObject::new(&commit).oid(), &oid);
+
+    run(dir, "git", &["verify-commit", &oid.to_hex()])?;
+    let verification = repo.verify_commit(&oid)?.unwrap();
+    assert!(verification.good, "{}", verification.output);
+    assert!(verification.output.contains("Good signature from"));
+
+    // A commit signed by git, with `commit.gpgSign` turning signing on for rit too
+    run(
+        dir,
+        "git",
+        &["commit", "--allow-empty", "-S", "-m", "Signed by git"],
+    )?;
+    let oid = repo.read_head()?.unwrap();
+    let commit = repo.database.load_commit(&oid)?;
+    assert_eq!(DatabaseObject::new(&commit).oid(), &oid);
+    assert!(repo.verify_commit(&oid)?.unwrap().good);
+
+    run(dir, "git", &["config", "commit.gpgSign", "true"])?;
+    let mut repo = Repo::open(dir.to_owned())?;
+    let options = CommitOptions {
+        message: Some("Signed by config".to_owned()),
+        allow_empty: true,
+        ..Default::default()
+    };
+    let oid = repo.commit_with(&options)?;
+    assert!(repo.verify_commit(&oid)?.unwrap().good);
+    let oid = repo.commit_with(&CommitOptions {
+        no_gpg_sign: true,
+        ..options
+    })?;
+    assert!(repo.verify_commit(&oid)?.is_none());
+
+    // Changing anything the signature covers makes it bad
+    let tampered = format!(
+        "tree {}\nauthor {}\ncommitter {}\ngpgsig {}\n\nSigned by someone else\n",
+        commit.tree_id().to_hex(),
+        commit.author(),
+        commit.committer(),
+        commit.signature().unwrap().replace('\n', "\n "),
+    );
+    let tampered = crate::commit::Commit::parse(tampered.as_bytes())?;
+    let verification =
+        repo.verify_signature(&tampered.signed_payload(), tampered.signature().unwrap())?;
+    assert!(!verification.good);
+    assert!(verification.output.contains("BAD signature"));
+
+    let _ = Command::new("gpgconf")
+        .args([
+            "--homedir",
+            home.join("gnupg").as_str(),
+            "--kill",
+            "gpg-agent",
+        ])
+        .status();
+    Ok(())
+}
+
+#[test]
+/// With `gpg.format = ssh`, commits are signed with `ssh-keygen`, and checked against
+/// `gpg.ssh.allowedSignersFile`.
+fn test_ssh_signatures() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let home = TempDir::new("")?;
+    let home = Utf8Path::from_path(home.path()).unwrap();
+
+    let key = home.join("key");
+    run(
+        home,
+        "ssh-keygen",
+        &["-q", "-t", "ed25519", "-N", "", "-f", key.as_str()],
+    )?;
+    let public = std::fs::read_to_string(home.join("key.pub"))?;
+    let allowed = home.join("allowed_signers");
+    std::fs::write(&allowed, format!("{COMMIT_EMAIL} {public}"))?;
+
+    Repo::init_default(dir)?;
+    for (key, value) in [
+        ("gpg.format", "ssh"),
+        ("user.signingKey", key.as_str()),
+        ("gpg.ssh.allowedSignersFile", allowed.as_str()),
+    ] {
+        run(dir, "git", &["config", key, value])?;
+    }
+    let mut repo = Repo::open(dir.to_owned())?;
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    let oid = repo.commit_with(&CommitOptions {
+        message: Some("Signed with ssh".to_owned()),
+        gpg_sign: Some(String::new()),
+        ..Default::default()
+    })?;
+
+    let commit = repo.database.load_commit(&oid)?;
+    assert!(commit
+        .signature()
+        .unwrap()
+        .starts_with("-----BEGIN SSH SIGNATURE-----"));
+    run(dir, "git", &["verify-commit", &oid.to_hex()])?;
+    let verification = repo.verify_commit(&oid)?.unwrap();
+    assert!(verification.good, "{}", verification.output);
+    assert!(verification
+        .output
+        .contains(&format!("Good \"git\" signature for {COMMIT_EMAIL}")));
+
+    // A key nobody has allowed
+    std::fs::write(&allowed, "")?;
+    let verification = repo.verify_commit(&oid)?.unwrap();
+    assert!(!verification.good);
+    assert!(verification.output.contains("No principal matched."));
+
+    Ok(())
+}
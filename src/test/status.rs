@@ -328,3 +328,64 @@ fn test_index_remove() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+/// Files removed from the index and added back under another name are shown as renames, unless
+/// `status.renames` turns them off.
+fn test_index_rename() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = dir.path();
+    let dir = Utf8Path::from_path(dir).unwrap();
+
+    let lines = (0..20).map(|i| format!("line {i}\n")).collect::<String>();
+    std::fs::write(dir.join("moved"), &lines)?;
+    std::fs::write(dir.join("edited"), lines.replace("line 1\n", "line one\n"))?;
+    std::fs::write(dir.join("removed"), "something else entirely\n")?;
+    let repo = init_repo(dir)?;
+
+    std::fs::rename(dir.join("moved"), dir.join("file5"))?;
+    let edited = std::fs::read_to_string(dir.join("edited"))?.replace("line 2\n", "");
+    std::fs::remove_file(dir.join("edited"))?;
+    std::fs::write(dir.join("file6"), edited)?;
+    std::fs::remove_file(dir.join("removed"))?;
+
+    // rit doesn't have an `rm`, and `add` doesn't know how to add files that don't exist.
+    std::fs::remove_file(dir.join(".git/index"))?;
+    drop(repo);
+    let mut repo = Repo::open(dir.to_owned())?;
+    repo.add_all()?;
+
+    {
+        let status = Status::new(&repo)?.unwrap();
+        let mut files = status.get_statuses()?;
+        let renames = status.detect_renames(&mut files)?;
+        files.sort_unstable_by_key(|x| x.0);
+
+        assert_eq!(
+            files,
+            [
+                (Utf8Path::new("file5"), Change::IndexRenamed),
+                (Utf8Path::new("file6"), Change::IndexRenamed),
+                (Utf8Path::new("removed"), Change::IndexRemoved),
+            ]
+        );
+        assert_eq!(renames[Utf8Path::new("file5")], "moved");
+        assert_eq!(renames[Utf8Path::new("file6")], "edited");
+    }
+
+    let mut config = std::fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join(".git/config"))?;
+    writeln!(config, "[status]\n\trenames = false")?;
+    drop(repo);
+    let repo = Repo::open(dir.to_owned())?;
+
+    {
+        let status = Status::new(&repo)?.unwrap();
+        let mut files = status.get_statuses()?;
+        assert!(status.detect_renames(&mut files)?.is_empty());
+        assert_eq!(files.len(), 5);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::pack::ObjectKind;
+use crate::repo::Repo;
+use crate::revision::Rev;
+use crate::test::{git, COMMIT_EMAIL, COMMIT_NAME};
+use crate::Result;
+
+#[test]
+/// Create lightweight and annotated tags with rit, and check that git agrees with what was written.
+fn test_create_tags() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    let commit = repo.commit("first")?;
+
+    let light = repo.create_tag("light", "HEAD", None, false)?;
+    assert_eq!(light, commit);
+
+    let annotated = repo.create_tag("release/v1", "HEAD", Some("Version 1"), false)?;
+    let tag = repo.database.load(&annotated)?.into_tag().unwrap();
+    assert_eq!(tag.object(), &commit);
+    assert_eq!(tag.kind(), ObjectKind::Commit);
+    assert_eq!(tag.message(), "Version 1\n");
+
+    assert!(repo.create_tag("light", "HEAD", None, false).is_err());
+    repo.create_tag("light", "HEAD", None, true)?;
+
+    assert_eq!(git(dir, &["tag", "-l"])?, "light\nrelease/v1\n");
+    assert_eq!(git(dir, &["cat-file", "-t", "release/v1"])?, "tag\n");
+    assert_eq!(
+        git(dir, &["rev-parse", "release/v1^{commit}"])?.trim(),
+        commit.to_hex()
+    );
+    assert_eq!(git(dir, &["fsck", "--strict"])?, "");
+
+    assert_eq!(repo.list_tags(&[])?, ["light", "release/v1"]);
+    assert_eq!(repo.list_tags(&["rel*/*".to_owned()])?, ["release/v1"]);
+
+    assert_eq!(repo.delete_tag("release/v1")?, annotated);
+    assert!(!dir.join(".git/refs/tags/release").exists());
+    assert!(repo.delete_tag("release/v1").is_err());
+    assert_eq!(git(dir, &["tag", "-l"])?, "light\n");
+
+    Ok(())
+}
+
+#[test]
+/// Tags created by git should be readable, and should resolve to the commit they point at.
+fn test_read_git_tags() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    let mut repo = Repo::open(dir.to_owned())?;
+
+    crate::create_test_files!(dir, ["file"]);
+    repo.add_all()?;
+    let commit = repo.commit("first")?;
+
+    git(dir, &["tag", "-a", "v1", "-m", "Version 1\n\nWith a body"])?;
+    // A tag of a tag
+    git(dir, &["tag", "-a", "nested", "-m", "Nested", "v1"])?;
+
+    let oid = repo.read_ref("v1")?.unwrap();
+    let tag = repo.database.load(&oid)?.into_tag().unwrap();
+    assert_eq!(tag.name(), "v1");
+    assert_eq!(tag.object(), &commit);
+    assert_eq!(tag.tagger().unwrap().name(), COMMIT_NAME);
+    assert_eq!(tag.message(), "Version 1\n\nWith a body\n");
+
+    for name in ["v1", "nested", "tags/nested", "refs/tags/v1"] {
+        assert_eq!(Rev::parse(name)?.resolve(&repo)?, Some(commit.clone()));
+    }
+
+    Ok(())
+}
+
+#[test]
+/// Without `-m`, annotated tag messages are written in the editor, with comments stripped.
+fn test_tag_message_from_editor() -> Result<()> {
+    std::env::set_var("RIT_AUTHOR_NAME", COMMIT_NAME);
+    std::env::set_var("RIT_AUTHOR_EMAIL", COMMIT_EMAIL);
+    std::env::remove_var("GIT_EDITOR");
+
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    Repo::init_default(dir)?;
+    crate::create_test_files!(dir, ["file"]);
+
+    // Leaving only the instructions is an empty message
+    git(dir, &["config", "core.editor", ":"])?;
+    let mut repo = Repo::open(dir.to_owned())?;
+    repo.add_all()?;
+    repo.commit("first")?;
+    let err = repo.edit_tag_message("v1").unwrap_err();
+    assert_eq!(err.to_string(), "no tag message?");
+    let instructions = std::fs::read_to_string(dir.join(".git/TAG_EDITMSG"))?;
+    assert_eq!(
+        instructions,
+        "\n#\n# Write a message for tag:\n#   v1\n# Lines starting with '#' will be ignored.\n#\n"
+    );
+
+    git(
+        dir,
+        &["config", "core.editor", "sed -i.bak '1s/^/Version 1/'"],
+    )?;
+    let mut repo = Repo::open(dir.to_owned())?;
+    let message = repo.edit_tag_message("v1")?;
+    assert_eq!(message, "Version 1\n");
+
+    let oid = repo.create_tag("v1", "HEAD", Some(&message), false)?;
+    let tag = repo.database.load(&oid)?.into_tag().unwrap();
+    assert_eq!(tag.message(), "Version 1\n");
+    assert_eq!(
+        git(dir, &["tag", "-l", "--format=%(contents)", "v1"])?,
+        "Version 1\n\n"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use camino::Utf8Path;
+use pretty_assertions::assert_eq;
+use tempdir::TempDir;
+
+use crate::repo::transaction::{Expected, RefTransaction};
+use crate::test::{git, two_commits};
+use crate::update_ref::apply_stdin;
+use crate::Result;
+
+/// Check that no `.lock` files were left behind anywhere under `.git`.
+fn assert_no_locks(dir: &Utf8Path) {
+    for entry in walkdir::WalkDir::new(dir.join(".git")) {
+        let entry = entry.unwrap();
+        assert!(
+            !entry.path().to_string_lossy().ends_with(".lock"),
+            "left a lock behind: {:?}",
+            entry.path()
+        );
+    }
+}
+
+#[test]
+/// A transaction where any expected value doesn't match should change nothing.
+fn test_transaction_is_atomic() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = two_commits(dir, |_, _| Ok(()))?;
+
+    let before = git(dir, &["show-ref"])?;
+
+    let mut transaction = RefTransaction::new();
+    transaction.create("refs/heads/new", &first).update(
+        "refs/heads/master",
+        &first,
+        Expected::Value(first.clone()),
+    );
+    let err = repo.commit_transaction(transaction).unwrap_err();
+    assert!(err.to_string().contains("but expected"), "{err}");
+    assert_eq!(git(dir, &["show-ref"])?, before);
+    assert_no_locks(dir);
+
+    // Creating a ref that exists fails
+    let mut transaction = RefTransaction::new();
+    transaction.create("refs/heads/master", &first);
+    assert!(repo.commit_transaction(transaction).is_err());
+
+    // A ref locked by someone else blocks the whole transaction
+    std::fs::write(dir.join(".git/refs/heads/master.lock"), "")?;
+    let mut transaction = RefTransaction::new();
+    transaction
+        .create("refs/heads/new", &first)
+        .update("HEAD", &first, Expected::Any);
+    let err = repo.commit_transaction(transaction).unwrap_err();
+    assert!(format!("{err:?}").contains("File exists"), "{err:?}");
+    assert_eq!(git(dir, &["show-ref"])?, before);
+    std::fs::remove_file(dir.join(".git/refs/heads/master.lock"))?;
+
+    // Updating the same ref twice (here, via HEAD) is not allowed
+    let mut transaction = RefTransaction::new();
+    transaction.update("HEAD", &first, Expected::Any).update(
+        "refs/heads/master",
+        &first,
+        Expected::Any,
+    );
+    assert!(repo.commit_transaction(transaction).is_err());
+
+    // With every expectation met, everything is applied
+    let mut transaction = RefTransaction::new();
+    transaction
+        .create("refs/heads/new", &first)
+        .update("HEAD", &first, Expected::Value(second.clone()))
+        .verify("refs/heads/missing", Expected::Absent);
+    repo.commit_transaction(transaction)?;
+    assert_eq!(
+        git(dir, &["show-ref"])?,
+        format!("{first:x} refs/heads/master\n{first:x} refs/heads/new\n")
+    );
+    assert_eq!(repo.current_branch()?.as_deref(), Some("master"));
+    assert_no_locks(dir);
+
+    Ok(())
+}
+
+#[test]
+/// Commands read by `update-ref --stdin` should behave like git's.
+fn test_update_ref_stdin() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = two_commits(dir, |_, _| Ok(()))?;
+
+    let input = format!(
+        "create refs/heads/a {first:x}\n\
+         update refs/heads/b {second:x} 0000000000000000000000000000000000000000\n\
+         update refs/heads/master HEAD~1 {second:x}\n\
+         verify refs/tags/missing\n"
+    );
+    apply_stdin(&mut repo, input.as_bytes(), false, "")?;
+    assert_eq!(
+        git(dir, &["show-ref"])?,
+        format!("{first:x} refs/heads/a\n{second:x} refs/heads/b\n{first:x} refs/heads/master\n")
+    );
+
+    // A failed verify prevents the deletes
+    let input = format!(
+        "delete refs/heads/a\n\
+         delete refs/heads/b {second:x}\n\
+         verify refs/heads/master {second:x}\n"
+    );
+    let before = git(dir, &["show-ref"])?;
+    assert!(apply_stdin(&mut repo, input.as_bytes(), false, "").is_err());
+    assert_eq!(git(dir, &["show-ref"])?, before);
+
+    let input = format!("delete refs/heads/a\ndelete refs/heads/b {second:x}\n");
+    apply_stdin(&mut repo, input.as_bytes(), false, "")?;
+    assert_eq!(
+        git(dir, &["show-ref"])?,
+        format!("{first:x} refs/heads/master\n")
+    );
+
+    // With --no-deref, HEAD itself is detached rather than moving master
+    apply_stdin(
+        &mut repo,
+        format!("update HEAD {second:x}\n").as_bytes(),
+        true,
+        "",
+    )?;
+    assert_eq!(repo.current_branch()?, None);
+    assert_eq!(repo.read_head()?, Some(second));
+    assert_eq!(repo.read_ref("master")?, Some(first));
+
+    assert!(apply_stdin(&mut repo, "frobnicate refs/heads/x\n".as_bytes(), false, "").is_err());
+    assert!(apply_stdin(&mut repo, "update refs/heads/x\n".as_bytes(), false, "").is_err());
+    assert_no_locks(dir);
+
+    Ok(())
+}
+
+#[test]
+/// A failed update shouldn't leave directories behind that block other refs, and directories
+/// holding no refs shouldn't block refs of their name.
+fn test_failed_update_leaves_no_dirs() -> Result<()> {
+    let dir = TempDir::new("")?;
+    let dir = Utf8Path::from_path(dir.path()).unwrap();
+    let (mut repo, first, second) = two_commits(dir, |_, _| Ok(()))?;
+
+    let mut transaction = RefTransaction::new();
+    transaction.update("refs/heads/x/y", &second, Expected::Value(first.clone()));
+    assert!(repo.commit_transaction(transaction).is_err());
+    assert!(!dir.join(".git/refs/heads/x").exists());
+    assert!(dir.join(".git/refs/heads").is_dir());
+    assert_no_locks(dir);
+    repo.create_branch("x", &second, "HEAD")?;
+
+    std::fs::create_dir_all(dir.join(".git/refs/heads/empty/a/b"))?;
+    repo.create_branch("empty", &second, "HEAD")?;
+    assert_eq!(git(dir, &["rev-parse", "empty"])?.trim(), second.to_hex());
+
+    repo.create_branch("used/y", &second, "HEAD")?;
+    let err = repo.create_branch("used", &second, "HEAD").unwrap_err();
+    assert!(
+        format!("{err:?}").contains("non-empty directory"),
+        "{err:?}"
+    );
+
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use crate::Result;
 use std::fmt::Display;
 
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use color_eyre::eyre::eyre;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Timestamp(pub DateTime<FixedOffset>);
@@ -14,6 +15,92 @@ impl Timestamp {
     pub fn from_git(s: &str) -> Result<Self> {
         Ok(Self(DateTime::parse_from_str(s, "%s %z")?))
     }
+
+    /// Parse a date given by the user, as to `commit --date`. The formats git accepts are:
+    /// - its raw format, `<unix timestamp> <offset>`, where the timestamp may be prefixed with `@`
+    ///   and the offset defaults to UTC
+    /// - RFC 2822, as in `Wed, 20 Jul 2022 11:16:59 +0100`
+    /// - ISO 8601, as in `2022-07-20T11:16:59+01:00` or `2022-07-20 11:16:59 +0100`. Without an
+    ///   offset, the time is in the local timezone
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let invalid = || eyre!("invalid date format: {s}");
+
+        let (seconds, offset) = match s.split_once(' ') {
+            Some((seconds, offset)) => (seconds, Some(offset)),
+            None => (s, None),
+        };
+        let seconds = seconds.strip_prefix('@').unwrap_or(seconds);
+        if !seconds.is_empty() && seconds.chars().all(|c| c.is_ascii_digit()) {
+            return match offset {
+                Some(offset) => Self::from_git(&format!("{seconds} {offset}")),
+                None => Self::from_git(&format!("{seconds} +0000")),
+            }
+            .map_err(|_| invalid());
+        }
+
+        if let Ok(date) = DateTime::parse_from_rfc2822(s) {
+            return Ok(Self(date));
+        }
+
+        let s = s.replacen('T', " ", 1);
+        let s = s
+            .strip_suffix('Z')
+            .map_or(s.clone(), |s| format!("{s} +0000"));
+        for format in [
+            "%Y-%m-%d %H:%M:%S%.f %z",
+            "%Y-%m-%d %H:%M:%S%.f%z",
+            "%Y-%m-%d %H:%M:%S%.f%:z",
+        ] {
+            if let Ok(date) = DateTime::parse_from_str(&s, format) {
+                return Ok(Self(date));
+            }
+        }
+        let local =
+            NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S%.f").map_err(|_| invalid())?;
+        match Local.from_local_datetime(&local).earliest() {
+            Some(date) => Ok(Self(date.into())),
+            None => Err(invalid()),
+        }
+    }
+
+    /// Format the timestamp for display, in the style of git's `--date=<format>`.
+    pub fn format(&self, format: DateFormat) -> String {
+        match format {
+            DateFormat::Default => self.0.format("%a %b %-d %H:%M:%S %Y %z").to_string(),
+            DateFormat::Iso => self.0.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+            DateFormat::IsoStrict => self.0.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+            DateFormat::Rfc => self.0.format("%a, %-d %b %Y %H:%M:%S %z").to_string(),
+            DateFormat::Short => self.0.format("%Y-%m-%d").to_string(),
+            DateFormat::Raw => self.to_string(),
+            DateFormat::Unix => self.0.timestamp().to_string(),
+            DateFormat::Relative => self.relative_to(&Self::now()),
+        }
+    }
+
+    /// Describe how long before `now` this timestamp is, e.g. "3 days ago".
+    fn relative_to(&self, now: &Self) -> String {
+        let seconds = now.0.signed_duration_since(self.0).num_seconds();
+        if seconds < 0 {
+            return "in the future".to_owned();
+        }
+
+        let (n, unit) = match seconds {
+            s if s < 90 => (s, "second"),
+            s if s < 90 * 60 => ((s + 30) / 60, "minute"),
+            s if s < 36 * 60 * 60 => ((s + 30 * 60) / (60 * 60), "hour"),
+            s if s < 14 * 24 * 60 * 60 => ((s + 12 * 60 * 60) / (24 * 60 * 60), "day"),
+            s if s < 10 * 7 * 24 * 60 * 60 => ((s + 3 * 24 * 60 * 60) / (7 * 24 * 60 * 60), "week"),
+            s if s < 365 * 24 * 60 * 60 => ((s + 15 * 24 * 60 * 60) / (30 * 24 * 60 * 60), "month"),
+            s => ((s + 183 * 24 * 60 * 60) / (365 * 24 * 60 * 60), "year"),
+        };
+
+        if n == 1 {
+            format!("{n} {unit} ago")
+        } else {
+            format!("{n} {unit}s ago")
+        }
+    }
 }
 
 impl Display for Timestamp {
@@ -21,3 +108,90 @@ impl Display for Timestamp {
         write!(f, "{}", self.0.format("%s %z"))
     }
 }
+
+/// The ways in which a [`Timestamp`] can be displayed to the user.
+///
+/// See: <https://git-scm.com/docs/git-log#Documentation/git-log.txt---dateltformatgt>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DateFormat {
+    /// `Wed Jul 20 11:16:59 2022 +0100`
+    #[default]
+    Default,
+    /// `2022-07-20 11:16:59 +0100`
+    Iso,
+    /// `2022-07-20T11:16:59+01:00`
+    IsoStrict,
+    /// `Wed, 20 Jul 2022 11:16:59 +0100`
+    Rfc,
+    /// `2022-07-20`
+    Short,
+    /// `1658312219 +0100`
+    Raw,
+    /// `1658312219`
+    Unix,
+    /// `3 months ago`
+    Relative,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_formats() {
+        let ts = Timestamp::from_git("1658312219 +0100").unwrap();
+        assert_eq!(
+            ts.format(DateFormat::Default),
+            "Wed Jul 20 11:16:59 2022 +0100"
+        );
+        assert_eq!(ts.format(DateFormat::Iso), "2022-07-20 11:16:59 +0100");
+        assert_eq!(
+            ts.format(DateFormat::IsoStrict),
+            "2022-07-20T11:16:59+01:00"
+        );
+        assert_eq!(
+            ts.format(DateFormat::Rfc),
+            "Wed, 20 Jul 2022 11:16:59 +0100"
+        );
+        assert_eq!(ts.format(DateFormat::Short), "2022-07-20");
+        assert_eq!(ts.format(DateFormat::Raw), "1658312219 +0100");
+        assert_eq!(ts.format(DateFormat::Unix), "1658312219");
+
+        let later = Timestamp::from_git("1658571419 +0100").unwrap();
+        assert_eq!(ts.relative_to(&later), "3 days ago");
+    }
+
+    #[test]
+    fn test_parse() {
+        let expected = Timestamp::from_git("1658312219 +0100").unwrap();
+        for date in [
+            "1658312219 +0100",
+            "@1658312219 +0100",
+            "Wed, 20 Jul 2022 11:16:59 +0100",
+            "2022-07-20T11:16:59+01:00",
+            "2022-07-20 11:16:59 +0100",
+            "2022-07-20T11:16:59+0100",
+        ] {
+            let ts = Timestamp::parse(date).unwrap();
+            assert_eq!(ts.to_string(), expected.to_string(), "{date}");
+        }
+
+        assert_eq!(
+            Timestamp::parse("@1658312219").unwrap().to_string(),
+            "1658312219 +0000"
+        );
+        assert_eq!(
+            Timestamp::parse("2022-07-20T10:16:59Z")
+                .unwrap()
+                .to_string(),
+            "1658312219 +0000"
+        );
+        // Without an offset, the time is local
+        let local = Timestamp::parse("2022-07-20 11:16:59").unwrap();
+        assert_eq!(local.0.naive_local().to_string(), "2022-07-20 11:16:59");
+
+        for date in ["", "yesterday", "2022-07-20", "1658312219 +01"] {
+            assert!(Timestamp::parse(date).is_err(), "{date}");
+        }
+    }
+}
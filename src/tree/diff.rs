@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{digest::Digest, pathspec::Pathspec, repo::database::Database, Result};
+
+use super::RawTreeEntry;
+
+/// How a single path differs between two trees: its entry in the old tree, and in the new one.
+/// `None` means it isn't in that tree.
+pub type TreeChange = (Option<RawTreeEntry>, Option<RawTreeEntry>);
+
+impl super::Tree {
+    /// Find every path whose entry differs between the trees `old` and `new`, where `None` is the
+    /// empty tree. Only entries that aren't themselves trees are listed, keyed by their full path.
+    ///
+    /// Subtrees with the same oid on both sides are skipped without being read, as are
+    /// directories that nothing in `pathspec` could be inside. A path that is a file on one side
+    /// and a directory on the other is listed as the file going away, or appearing, alongside
+    /// the changes to the files in the directory.
+    pub fn diff(
+        database: &Database,
+        old: Option<&Digest>,
+        new: Option<&Digest>,
+        pathspec: &Pathspec,
+    ) -> Result<BTreeMap<Utf8PathBuf, TreeChange>> {
+        let mut changes = BTreeMap::new();
+        diff_trees(
+            database,
+            old,
+            new,
+            Utf8Path::new(""),
+            pathspec,
+            &mut changes,
+        )?;
+        Ok(changes)
+    }
+}
+
+fn diff_trees(
+    database: &Database,
+    old: Option<&Digest>,
+    new: Option<&Digest>,
+    prefix: &Utf8Path,
+    pathspec: &Pathspec,
+    changes: &mut BTreeMap<Utf8PathBuf, TreeChange>,
+) -> Result<()> {
+    if old == new {
+        return Ok(());
+    }
+
+    let mut entries = BTreeMap::<String, TreeChange>::new();
+    for entry in read_entries(database, old)? {
+        let name = entry.name.clone();
+        entries.entry(name).or_default().0 = Some(entry);
+    }
+    for entry in read_entries(database, new)? {
+        let name = entry.name.clone();
+        entries.entry(name).or_default().1 = Some(entry);
+    }
+
+    for (name, (old, new)) in entries {
+        if old == new {
+            continue;
+        }
+        let path = prefix.join(&name);
+
+        let old_tree = old.as_ref().filter(|x| x.is_tree()).map(|x| &x.oid);
+        let new_tree = new.as_ref().filter(|x| x.is_tree()).map(|x| &x.oid);
+        if (old_tree.is_some() || new_tree.is_some()) && pathspec.may_match_under(&path) {
+            diff_trees(database, old_tree, new_tree, &path, pathspec, changes)?;
+        }
+
+        let old = old.filter(|x| !x.is_tree());
+        let new = new.filter(|x| !x.is_tree());
+        if (old.is_some() || new.is_some()) && pathspec.matches(&path) {
+            changes.insert(path, (old, new));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_entries(database: &Database, tree: Option<&Digest>) -> Result<Vec<RawTreeEntry>> {
+    match tree {
+        Some(oid) => {
+            let (_, data) = database.read_raw(oid)?;
+            super::Tree::parse_raw(&data)
+        }
+        None => Ok(Vec::new()),
+    }
+}
@@ -1,14 +1,20 @@
+mod diff;
 mod parse;
 mod write;
 
+pub use self::parse::RawTreeEntry;
+
 use std::collections::BTreeMap;
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use color_eyre::Result;
 use once_cell::sync::OnceCell;
 use tracing::*;
 
-use crate::{filemode::FileMode, index::IndexEntry, storable::Storable, util::Descends, Digest};
+use crate::{
+    filemode::FileMode, index::IndexEntry, repo::database::Database, storable::Storable,
+    util::Descends, Digest,
+};
 
 #[derive(Debug)]
 pub enum TreeEntry {
@@ -126,6 +132,35 @@ impl Tree {
         Ok(())
     }
 
+    /// Recursively list every entry of the tree `oid` that isn't itself a tree, keyed by its full
+    /// path. Subtrees are read straight from the database, without looking at the workspace.
+    pub fn flatten(
+        database: &Database,
+        oid: &Digest,
+    ) -> Result<BTreeMap<Utf8PathBuf, RawTreeEntry>> {
+        fn recurse(
+            database: &Database,
+            oid: &Digest,
+            prefix: &Utf8Path,
+            out: &mut BTreeMap<Utf8PathBuf, RawTreeEntry>,
+        ) -> Result<()> {
+            let (_, data) = database.read_raw(oid)?;
+            for entry in Tree::parse_raw(&data)? {
+                let path = prefix.join(&entry.name);
+                if entry.is_tree() {
+                    recurse(database, &entry.oid, &path, out)?;
+                } else {
+                    out.insert(path, entry);
+                }
+            }
+            Ok(())
+        }
+
+        let mut out = BTreeMap::new();
+        recurse(database, oid, Utf8Path::new(""), &mut out)?;
+        Ok(out)
+    }
+
     pub fn entries(&self) -> &BTreeMap<String, TreeEntry> {
         &self.entries
     }
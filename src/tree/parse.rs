@@ -25,6 +25,14 @@ mod nom {
     /// Parses an entry from the tree. Lines are of the form
     /// `<mode> <name>\0<oid>`
     pub(super) fn parse_tree_entry(i: Input) -> Result<(FileMode, &str, Digest)> {
+        let (i, (mode, name, oid)) = parse_raw_tree_entry(i)?;
+        let mode = FileMode::from(mode);
+
+        Ok((i, (mode, name, oid)))
+    }
+
+    /// Parses an entry from the tree, without interpreting the mode.
+    pub(super) fn parse_raw_tree_entry(i: Input<'_>) -> Result<'_, (libc::mode_t, &str, Digest)> {
         let (i, mode) = nom::bytes::complete::take_until(" ").parse(i)?;
         let (i, _) = nom::bytes::complete::tag(" ").parse(i)?;
         let (i, name) = nom::bytes::complete::take_until("\0").parse(i)?;
@@ -32,15 +40,62 @@ mod nom {
         let (i, oid) = nom::bytes::complete::take(20usize).parse(i)?;
         let oid = Digest(oid.try_into().unwrap());
 
-        let mode = mode.to_str().unwrap().parse::<libc::mode_t>().unwrap();
-        let mode = FileMode::from(mode);
+        // Modes are stored in octal
+        let mode = libc::mode_t::from_str_radix(mode.to_str().unwrap(), 8).unwrap();
         let name = name.to_str().unwrap();
 
         Ok((i, (mode, name, oid)))
     }
 }
 
+/// An entry of a tree, straight from the database.
+///
+/// Unlike [`TreeEntry`], this does not load subtrees or look at the workspace, and it preserves
+/// modes (such as symlinks and submodules) that [`FileMode`] can't represent.
+///
+/// [`FileMode`]: crate::filemode::FileMode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawTreeEntry {
+    pub mode: libc::mode_t,
+    pub name: String,
+    pub oid: crate::digest::Digest,
+}
+
+impl RawTreeEntry {
+    pub const MODE_TREE: libc::mode_t = 0o040000;
+    pub const MODE_SYMLINK: libc::mode_t = 0o120000;
+    pub const MODE_GITLINK: libc::mode_t = 0o160000;
+
+    pub fn is_tree(&self) -> bool {
+        self.mode == Self::MODE_TREE
+    }
+
+    /// Returns `true` if this entry is a submodule, whose oid refers to a commit in another
+    /// repository.
+    pub fn is_gitlink(&self) -> bool {
+        self.mode == Self::MODE_GITLINK
+    }
+}
+
 impl super::Tree {
+    /// Parse the entries of a tree, without loading any subtrees.
+    pub fn parse_raw(mut bytes: &[u8]) -> Result<Vec<RawTreeEntry>> {
+        let mut entries = Vec::new();
+
+        while !bytes.is_empty() {
+            let (rest, (mode, name, oid)) = nom::parse_raw_tree_entry(bytes)
+                .map_err(|e| eyre!("Failed to parse tree entry: {:?}", e))?;
+            bytes = rest;
+            entries.push(RawTreeEntry {
+                mode,
+                name: name.to_owned(),
+                oid,
+            });
+        }
+
+        Ok(entries)
+    }
+
     pub fn parse(mut bytes: &[u8], root: &Utf8Path, database: &Database) -> Result<Self> {
         let mut entries: BTreeMap<String, TreeEntry> = Default::default();
 
@@ -84,7 +139,9 @@ impl super::TreeEntry {
                 },
             ))
         } else if path.exists() {
-            let stat = Repo::stat_file(&path)?.expect("File should exist");
+            let mut stat = Repo::stat_file(&path)?.expect("File should exist");
+            // The mode recorded in the tree is what matters, not the one on disk
+            stat.st_mode = mode.inner();
             Ok((
                 name.clone(),
                 Self::File(IndexEntry::new(Utf8Path::new(&name), &oid, stat)?),
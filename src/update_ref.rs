@@ -0,0 +1,176 @@
+//! `rit update-ref`, for changing refs directly.
+//!
+//! See: <https://git-scm.com/docs/git-update-ref>
+
+use std::io::BufRead;
+
+use color_eyre::eyre::{eyre, Context};
+use tracing::debug;
+
+use crate::digest::Digest;
+use crate::interface::UpdateRefArgs;
+use crate::repo::transaction::{Expected, RefTransaction};
+use crate::repo::Repo;
+use crate::revision::Rev;
+use crate::Result;
+
+/// The all-zero oid, which git uses to mean "no value": as a new value it deletes the ref, and as
+/// an old value it requires that the ref does not exist.
+const ZERO_OID: &str = "0000000000000000000000000000000000000000";
+
+pub fn handle(repo: &mut Repo, args: &UpdateRefArgs) -> Result<()> {
+    if args.stdin {
+        let stdin = std::io::stdin();
+        return apply_stdin(repo, stdin.lock(), args.no_deref, &args.message);
+    }
+
+    let mut transaction = RefTransaction::new();
+    transaction.message(&args.message);
+    if args.no_deref {
+        transaction.no_deref();
+    }
+
+    match (args.delete, args.args.as_slice()) {
+        (true, [name]) => {
+            transaction.delete(name, Expected::Any);
+        }
+        (true, [name, old]) => {
+            transaction.delete(name, old_value(repo, old)?);
+        }
+        (false, [name, new]) => new_value(repo, &mut transaction, name, new, Expected::Any)?,
+        (false, [name, new, old]) => {
+            let old = old_value(repo, old)?;
+            new_value(repo, &mut transaction, name, new, old)?
+        }
+        _ => {
+            return Err(eyre!(
+                "usage: rit update-ref [-d] <ref> [<new-value>] [<old-value>]"
+            ))
+        }
+    }
+
+    repo.commit_transaction(transaction)
+}
+
+/// Apply the commands read from `input`. Commands up to the end of input (or up to `commit`, if
+/// `start` was used) form a single transaction. `message` is recorded in the reflog.
+pub fn apply_stdin(
+    repo: &mut Repo,
+    input: impl BufRead,
+    no_deref: bool,
+    message: &str,
+) -> Result<()> {
+    let new_transaction = || {
+        let mut transaction = RefTransaction::new();
+        transaction.message(message);
+        if no_deref {
+            transaction.no_deref();
+        }
+        transaction
+    };
+    let mut transaction = new_transaction();
+
+    for line in input.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        debug!(%line, "update-ref command");
+
+        let (command, rest) = line.split_once(' ').unwrap_or((&line, ""));
+        let args = rest
+            .split(' ')
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>();
+
+        match (command, args.as_slice()) {
+            ("update", [name, new]) => {
+                new_value(repo, &mut transaction, name, new, Expected::Any)?;
+            }
+            ("update", [name, new, old]) => {
+                let old = old_value(repo, old)?;
+                new_value(repo, &mut transaction, name, new, old)?;
+            }
+            ("create", [name, new]) => {
+                let new =
+                    resolve(repo, new)?.ok_or_else(|| eyre!("create {name}: zero <new-oid>"))?;
+                transaction.create(name, &new);
+            }
+            ("delete", [name]) => {
+                transaction.delete(name, Expected::Any);
+            }
+            ("delete", [name, old]) => match old_value(repo, old)? {
+                Expected::Absent => return Err(eyre!("delete {name}: zero <old-oid>")),
+                old => {
+                    transaction.delete(name, old);
+                }
+            },
+            ("verify", [name]) => {
+                transaction.verify(name, Expected::Absent);
+            }
+            ("verify", [name, old]) => {
+                transaction.verify(name, old_value(repo, old)?);
+            }
+            ("start", []) => println!("start: ok"),
+            ("commit", []) => {
+                let transaction = std::mem::replace(&mut transaction, new_transaction());
+                repo.commit_transaction(transaction)?;
+                println!("commit: ok");
+            }
+            ("abort", []) => {
+                transaction = new_transaction();
+                println!("abort: ok");
+            }
+            ("update" | "create" | "delete" | "verify" | "start" | "commit" | "abort", _) => {
+                return Err(eyre!("{command}: wrong number of arguments: {line}"))
+            }
+            _ => return Err(eyre!("unknown command: {line}")),
+        }
+    }
+
+    if !transaction.is_empty() {
+        repo.commit_transaction(transaction)?;
+    }
+    Ok(())
+}
+
+/// Add an update of `name` to `new` to the transaction, where a zero `new` means deletion.
+fn new_value(
+    repo: &Repo,
+    transaction: &mut RefTransaction,
+    name: &str,
+    new: &str,
+    old: Expected,
+) -> Result<()> {
+    match resolve(repo, new)? {
+        Some(new) => transaction.update(name, &new, old),
+        None => transaction.delete(name, old),
+    };
+    Ok(())
+}
+
+fn old_value(repo: &Repo, old: &str) -> Result<Expected> {
+    Ok(match resolve(repo, old)? {
+        Some(oid) => Expected::Value(oid),
+        None => Expected::Absent,
+    })
+}
+
+/// Resolve a value given on the command line. The zero oid resolves to `None`. Refs and oids are
+/// used as-is, without peeling tags.
+fn resolve(repo: &Repo, value: &str) -> Result<Option<Digest>> {
+    if value == ZERO_OID {
+        return Ok(None);
+    }
+
+    if let Some(oid) = repo.read_ref(value)? {
+        return Ok(Some(oid));
+    }
+
+    Rev::parse(value)
+        .map_err(Into::into)
+        .and_then(|rev| rev.resolve(repo))
+        .wrap_err_with(|| format!("invalid value: {value}"))?
+        .map(Some)
+        .ok_or_else(|| eyre!("{value}: not a valid SHA1"))
+}
@@ -0,0 +1,39 @@
+//! `rit verify-commit`: check the signatures of commits.
+
+use std::io::Write;
+
+use color_eyre::eyre::eyre;
+
+use crate::repo::Repo;
+use crate::revision::Rev;
+use crate::Result;
+
+pub fn handle(repo: &Repo, commits: &[String], verbose: bool) -> Result<()> {
+    let mut failed = false;
+    for name in commits {
+        let oid = Rev::parse(name)?
+            .resolve(repo)?
+            .ok_or_else(|| eyre!("commit '{name}' not found."))?;
+        let commit = repo.database.load_commit(&oid)?;
+
+        if verbose {
+            std::io::stdout().write_all(&commit.signed_payload())?;
+        }
+        match commit.signature() {
+            Some(signature) => {
+                let verification = repo.verify_signature(&commit.signed_payload(), signature)?;
+                eprint!("{}", verification.output);
+                failed |= !verification.good;
+            }
+            None => {
+                eprintln!("error: no signature found");
+                failed = true;
+            }
+        }
+    }
+
+    match failed {
+        true => Err(eyre!("Some commits could not be verified")),
+        false => Ok(()),
+    }
+}